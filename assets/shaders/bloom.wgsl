@@ -0,0 +1,69 @@
+struct Bloom {
+    // Brightness above which colors bloom
+    threshold: f32,
+}
+
+// The texture the pass reads, a mip larger than its target when going
+// down the chain and a mip smaller when going back up
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> bloom: Bloom;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// A triangle covering the target, without a vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Averages the 4x4 source texels around the target texel, with four
+// bilinear samples between them
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    let a = textureSample(source, source_sampler, uv + texel * vec2<f32>(-1.0, -1.0)).rgb;
+    let b = textureSample(source, source_sampler, uv + texel * vec2<f32>(1.0, -1.0)).rgb;
+    let c = textureSample(source, source_sampler, uv + texel * vec2<f32>(-1.0, 1.0)).rgb;
+    let d = textureSample(source, source_sampler, uv + texel * vec2<f32>(1.0, 1.0)).rgb;
+    return (a + b + c + d) * 0.25;
+}
+
+// Keeps what is brighter than the threshold while halving the scene
+@fragment
+fn fs_threshold(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = downsample(in.uv);
+    let brightness = max(max(color.r, color.g), color.b);
+    let excess = max(brightness - bloom.threshold, 0.0);
+    return vec4<f32>(color * excess / max(brightness, 0.0001), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// Blurs the smaller mip with a 3x3 tent filter, added onto the target
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    var color = textureSample(source, source_sampler, in.uv).rgb * 4.0;
+    color += textureSample(source, source_sampler, in.uv + texel * vec2<f32>(-1.0, 0.0)).rgb * 2.0;
+    color += textureSample(source, source_sampler, in.uv + texel * vec2<f32>(1.0, 0.0)).rgb * 2.0;
+    color += textureSample(source, source_sampler, in.uv + texel * vec2<f32>(0.0, -1.0)).rgb * 2.0;
+    color += textureSample(source, source_sampler, in.uv + texel * vec2<f32>(0.0, 1.0)).rgb * 2.0;
+    color += textureSample(source, source_sampler, in.uv + texel * vec2<f32>(-1.0, -1.0)).rgb;
+    color += textureSample(source, source_sampler, in.uv + texel * vec2<f32>(1.0, -1.0)).rgb;
+    color += textureSample(source, source_sampler, in.uv + texel * vec2<f32>(-1.0, 1.0)).rgb;
+    color += textureSample(source, source_sampler, in.uv + texel * vec2<f32>(1.0, 1.0)).rgb;
+    return vec4<f32>(color / 16.0, 1.0);
+}
@@ -0,0 +1,59 @@
+struct VertexIn {
+    @location(0) vertex_pos: vec3<f32>,
+    // Distance along the wall and height, in blocks
+    @location(1) wall_pos: vec2<f32>,
+}
+
+struct VertexOut {
+    @builtin(position) vertex_pos: vec4<f32>,
+    @location(0) wall_pos: vec2<f32>,
+    @location(1) world_pos: vec3<f32>,
+}
+
+struct Camera {
+    transform: mat4x4<f32>,
+    // Seconds since the renderer started
+    time: f32,
+    // How bright the sky is, from 0 at night to 1 at noon
+    daylight: f32,
+    // How quickly the fog thickens with distance, 0 without fog
+    fog_density: f32,
+    eye: vec4<f32>,
+    fog_color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+const COLOR: vec3<f32> = vec3<f32>(0.3, 0.6, 1.0);
+// Width of a stripe and its gap, in blocks
+const STRIPE_PERIOD: f32 = 4.0;
+// Blocks the stripes move by every second
+const SCROLL_SPEED: f32 = 1.5;
+// The wall fades out between these distances from the eye
+const FADE_START: f32 = 8.0;
+const FADE_END: f32 = 48.0;
+
+@vertex
+fn vs_main(in: VertexIn) -> VertexOut {
+    var out: VertexOut;
+    out.vertex_pos = camera.transform * vec4<f32>(in.vertex_pos, 1.0);
+    out.wall_pos = in.wall_pos;
+    out.world_pos = in.vertex_pos;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    // Diagonal stripes scrolling up the wall
+    let stripe = fract((in.wall_pos.x + in.wall_pos.y - camera.time * SCROLL_SPEED) / STRIPE_PERIOD);
+    let alpha = select(0.08, 0.35, stripe < 0.5);
+    let distance = length(in.world_pos.xz - camera.eye.xz);
+    let fade = 1.0 - smoothstep(FADE_START, FADE_END, distance);
+    if fade <= 0.0 {
+        discard;
+    }
+    // Glows slightly at night
+    let brightness = max(camera.daylight, 0.5);
+    return vec4<f32>(COLOR * brightness, alpha * fade);
+}
@@ -1,34 +1,116 @@
 struct VertexIn {
     @location(0) vertex_pos: vec3<f32>,
     @location(1) texture_pos: vec2<f32>,
+    // Frame count and seconds per frame of animated tiles
+    @location(2) animation: vec2<f32>,
+    // Block and sky light
+    @location(3) light: vec2<f32>,
+    // Light emitted by the block itself
+    @location(4) emission: f32,
 }
 
 struct VertexData {
     @builtin(position) vertex_pos: vec4<f32>,
     @location(0) texture_pos: vec2<f32>,
+    // Block light and sky light dimmed by the time of day
+    @location(1) light: vec2<f32>,
+    @location(2) world_pos: vec3<f32>,
+    @location(3) emission: f32,
 }
 
 struct Camera {
     transform: mat4x4<f32>,
+    // Seconds since the renderer started
+    time: f32,
+    // How bright the sky is, from 0 at night to 1 at noon
+    daylight: f32,
+    // How quickly the fog thickens with distance, 0 without fog
+    fog_density: f32,
+    // Brightness added to emissive blocks so that they bloom, 0 without bloom
+    emission: f32,
+    eye: vec4<f32>,
+    fog_color: vec4<f32>,
 }
 
 @group(1) @binding(0)
 var<uniform> camera: Camera;
 
-@vertex
-fn vs_main(in: VertexIn) -> VertexData {
+// Where the chunk being drawn is, chunk meshes are relative to its origin
+struct Chunk {
+    offset: vec4<f32>,
+}
+
+@group(2) @binding(0)
+var<uniform> chunk: Chunk;
+
+fn vertex(in: VertexIn, world_pos: vec3<f32>) -> VertexData {
     var data: VertexData;
-    data.vertex_pos = camera.transform * vec4<f32>(in.vertex_pos, 1.0);
-    data.texture_pos = in.texture_pos;
+    data.vertex_pos = camera.transform * vec4<f32>(world_pos, 1.0);
+    data.world_pos = world_pos;
+    // Frames are stacked below each other, one tile (1/16 of the atlas) apart.
+    // Static tiles have a single frame so their offset is always zero.
+    let frame = floor(camera.time / in.animation.y) % in.animation.x;
+    data.texture_pos = in.texture_pos + vec2<f32>(0.0, frame / 16.0);
+    // Sky light dims at night while torches stay bright
+    data.light = vec2<f32>(in.light.x, in.light.y * camera.daylight);
+    data.emission = in.emission * camera.emission;
     return data;
 }
 
+// Meshes already placed by their transform, like the held block
+@vertex
+fn vs_main(in: VertexIn) -> VertexData {
+    return vertex(in, in.vertex_pos);
+}
+
+@vertex
+fn vs_chunk(in: VertexIn) -> VertexData {
+    return vertex(in, in.vertex_pos + chunk.offset.xyz);
+}
+
+// Model matrix of an entity, one per instance
+struct InstanceIn {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+}
+
+// Meshes of entities are around their origin and placed by their instance
+@vertex
+fn vs_entity(in: VertexIn, instance: InstanceIn) -> VertexData {
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    return vertex(in, (model * vec4<f32>(in.vertex_pos, 1.0)).xyz);
+}
+
 @group(0) @binding(0)
 var texture: texture_2d<f32>;
 @group(0) @binding(1)
 var tex_sampler: sampler;
 
+fn shade(data: VertexData) -> vec4<f32> {
+    let color = textureSample(texture, tex_sampler, data.texture_pos);
+    // Unlit faces keep some ambient light so caves aren't pitch black
+    // Glowing blocks go brighter than white, past the bloom threshold
+    let brightness = max(max(data.light.x, data.light.y), 0.05) + data.emission;
+    // Exponential fog, used underwater
+    let distance = length(data.world_pos - camera.eye.xyz);
+    let fog = 1.0 - exp(-camera.fog_density * distance);
+    return vec4<f32>(mix(color.rgb * brightness, camera.fog_color.rgb, fog), color.a);
+}
+
+// Opaque geometry, with holes where the texture is see-through like in plants
 @fragment
 fn fs_main(data: VertexData) -> @location(0) vec4<f32> {
-    return textureSample(texture, tex_sampler, data.texture_pos);
+    let color = shade(data);
+    if color.a < 0.5 {
+        discard;
+    }
+    return color;
+}
+
+// Blended geometry like water and glass
+@fragment
+fn fs_transparent(data: VertexData) -> @location(0) vec4<f32> {
+    return shade(data);
 }
\ No newline at end of file
@@ -0,0 +1,176 @@
+struct Fxaa {
+    // The size of a pixel of the frame in texture coordinates
+    inverse_size: vec2<f32>,
+}
+
+// The graded frame, before its edges are smoothed
+@group(0) @binding(0)
+var frame: texture_2d<f32>;
+@group(0) @binding(1)
+var frame_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> fxaa: Fxaa;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// A triangle covering the screen, without a vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// The default quality of FXAA 3.11
+// How much of the aliasing inside a pixel is removed
+const SUBPIX: f32 = 0.75;
+// Contrast needed to smooth an edge, relative to the brightest neighbor
+const EDGE_THRESHOLD: f32 = 0.166;
+// Contrast below which dark edges are left alone
+const EDGE_THRESHOLD_MIN: f32 = 0.0833;
+const SEARCH_STEPS: i32 = 5;
+
+fn color_at(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(frame, frame_sampler, uv, 0.0).rgb;
+}
+
+// FXAA is tuned for gamma encoded luma, while the frame is sampled as
+// linear colors
+fn luma_of(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn luma_at(uv: vec2<f32>, offset: vec2<f32>) -> f32 {
+    return luma_of(color_at(uv + offset * fxaa.inverse_size));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var pos = in.uv;
+    let color_m = color_at(pos);
+    let luma_m = luma_of(color_m);
+    var luma_n = luma_at(pos, vec2<f32>(0.0, -1.0));
+    var luma_s = luma_at(pos, vec2<f32>(0.0, 1.0));
+    let luma_e = luma_at(pos, vec2<f32>(1.0, 0.0));
+    let luma_w = luma_at(pos, vec2<f32>(-1.0, 0.0));
+
+    let range_max = max(max(luma_n, luma_w), max(luma_e, max(luma_s, luma_m)));
+    let range_min = min(min(luma_n, luma_w), min(luma_e, min(luma_s, luma_m)));
+    let range = range_max - range_min;
+    if range < max(EDGE_THRESHOLD_MIN, range_max * EDGE_THRESHOLD) {
+        return vec4<f32>(color_m, 1.0);
+    }
+
+    let luma_nw = luma_at(pos, vec2<f32>(-1.0, -1.0));
+    let luma_se = luma_at(pos, vec2<f32>(1.0, 1.0));
+    let luma_ne = luma_at(pos, vec2<f32>(1.0, -1.0));
+    let luma_sw = luma_at(pos, vec2<f32>(-1.0, 1.0));
+
+    // Whether the edge runs along x or y
+    let luma_ns = luma_n + luma_s;
+    let luma_we = luma_w + luma_e;
+    let luma_nese = luma_ne + luma_se;
+    let luma_nwne = luma_nw + luma_ne;
+    let luma_nwsw = luma_nw + luma_sw;
+    let luma_swse = luma_sw + luma_se;
+    let edge_horz = abs(-2.0 * luma_w + luma_nwsw) + abs(-2.0 * luma_m + luma_ns) * 2.0
+        + abs(-2.0 * luma_e + luma_nese);
+    let edge_vert = abs(-2.0 * luma_s + luma_swse) + abs(-2.0 * luma_m + luma_we) * 2.0
+        + abs(-2.0 * luma_n + luma_nwne);
+    let horz_span = edge_horz >= edge_vert;
+
+    // Blend of the pixel with its neighbors, for aliasing smaller than it
+    let subpix_a = (luma_ns + luma_we) * 2.0 + luma_nwsw + luma_nese;
+    let subpix_b = subpix_a * (1.0 / 12.0) - luma_m;
+    let subpix_c = clamp(abs(subpix_b) / range, 0.0, 1.0);
+    let subpix_f = (-2.0 * subpix_c + 3.0) * subpix_c * subpix_c;
+    let subpix_h = subpix_f * subpix_f * SUBPIX;
+
+    // Which side of the pixel the edge is on
+    var length_sign = fxaa.inverse_size.x;
+    if horz_span {
+        length_sign = fxaa.inverse_size.y;
+    } else {
+        luma_n = luma_w;
+        luma_s = luma_e;
+    }
+    let gradient_n = luma_n - luma_m;
+    let gradient_s = luma_s - luma_m;
+    let pair_n = abs(gradient_n) >= abs(gradient_s);
+    let gradient_scaled = max(abs(gradient_n), abs(gradient_s)) * 0.25;
+    var luma_nn = luma_s + luma_m;
+    if pair_n {
+        length_sign = -length_sign;
+        luma_nn = luma_n + luma_m;
+    }
+    let luma_mm = luma_m - luma_nn * 0.5;
+
+    // Walks along the edge both ways until its luma changes
+    var pos_b = pos;
+    var off_np = vec2<f32>(fxaa.inverse_size.x, 0.0);
+    if horz_span {
+        pos_b.y += length_sign * 0.5;
+    } else {
+        pos_b.x += length_sign * 0.5;
+        off_np = vec2<f32>(0.0, fxaa.inverse_size.y);
+    }
+    var steps = array<f32, 5>(1.0, 1.5, 2.0, 4.0, 12.0);
+    var pos_n = pos_b - off_np * steps[0];
+    var pos_p = pos_b + off_np * steps[0];
+    var luma_end_n = luma_of(color_at(pos_n)) - luma_nn * 0.5;
+    var luma_end_p = luma_of(color_at(pos_p)) - luma_nn * 0.5;
+    var done_n = abs(luma_end_n) >= gradient_scaled;
+    var done_p = abs(luma_end_p) >= gradient_scaled;
+    if !done_n {
+        pos_n -= off_np * steps[1];
+    }
+    if !done_p {
+        pos_p += off_np * steps[1];
+    }
+    for (var i = 2; i < SEARCH_STEPS && !(done_n && done_p); i++) {
+        if !done_n {
+            luma_end_n = luma_of(color_at(pos_n)) - luma_nn * 0.5;
+            done_n = abs(luma_end_n) >= gradient_scaled;
+            if !done_n {
+                pos_n -= off_np * steps[i];
+            }
+        }
+        if !done_p {
+            luma_end_p = luma_of(color_at(pos_p)) - luma_nn * 0.5;
+            done_p = abs(luma_end_p) >= gradient_scaled;
+            if !done_p {
+                pos_p += off_np * steps[i];
+            }
+        }
+    }
+
+    // Moves the sample across the edge by how close the pixel is to the
+    // nearer end of it
+    var dst_n = pos.x - pos_n.x;
+    var dst_p = pos_p.x - pos.x;
+    if !horz_span {
+        dst_n = pos.y - pos_n.y;
+        dst_p = pos_p.y - pos.y;
+    }
+    let luma_m_negative = luma_mm < 0.0;
+    var good_span = (luma_end_p < 0.0) != luma_m_negative;
+    if dst_n < dst_p {
+        good_span = (luma_end_n < 0.0) != luma_m_negative;
+    }
+    var pixel_offset = 0.0;
+    if good_span {
+        pixel_offset = 0.5 - min(dst_n, dst_p) / (dst_n + dst_p);
+    }
+    let offset = max(pixel_offset, subpix_h) * length_sign;
+    if horz_span {
+        pos.y += offset;
+    } else {
+        pos.x += offset;
+    }
+    return vec4<f32>(color_at(pos), 1.0);
+}
@@ -0,0 +1,57 @@
+struct Grading {
+    // Stops the scene is brightened by, negative to darken it
+    exposure: f32,
+    gamma: f32,
+    saturation: f32,
+    contrast: f32,
+    // How much of the bloom is added onto the scene, 0 without bloom
+    bloom: f32,
+}
+
+@group(0) @binding(0)
+var scene: texture_2d<f32>;
+// Upscales the scene when it is drawn below the frame resolution
+@group(0) @binding(1)
+var scene_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> grading: Grading;
+// The bright parts of the scene blurred, at half its size
+@group(0) @binding(3)
+var bloom: texture_2d<f32>;
+@group(0) @binding(4)
+var bloom_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// A triangle covering the screen, without a vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Middle gray in linear light, which contrast spreads the colors from
+const MIDDLE_GRAY: f32 = 0.18;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(scene, scene_sampler, in.uv).rgb;
+    let glow = textureSample(bloom, bloom_sampler, in.uv).rgb;
+    // Skipped without bloom, where the texture keeps an old frame
+    if grading.bloom > 0.0 {
+        color += glow * grading.bloom;
+    }
+    color = color * exp2(grading.exposure);
+    let luma = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    color = max(mix(vec3<f32>(luma), color, grading.saturation), vec3<f32>(0.0));
+    color = MIDDLE_GRAY * pow(color / MIDDLE_GRAY, vec3<f32>(grading.contrast));
+    color = pow(color, vec3<f32>(1.0 / grading.gamma));
+    // The sRGB target encodes the linear color
+    return vec4<f32>(color, 1.0);
+}
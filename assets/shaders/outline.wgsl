@@ -0,0 +1,139 @@
+struct Outline {
+    // Maps clip space back to positions relative to the render origin
+    inverse_transform: mat4x4<f32>,
+    // How different the normals of neighbors must be for an edge, from 0
+    // to 1. A tenth of it is how far off the plane of the pixel they must
+    // be, relative to their distance.
+    threshold: f32,
+    // Pixels between the compared neighbors and the pixel
+    thickness: f32,
+}
+
+// Read as plain floats, which every backend can load
+@group(0) @binding(0)
+var depth_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> outline: Outline;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// A triangle covering the scene, without a vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+// What the scene is multiplied by on edges
+const EDGE_DARKNESS: f32 = 0.2;
+// The depth of the sky with the reversed depth buffer
+const DEPTH_CLEAR: f32 = 0.0;
+// Fraction of the threshold a neighbor may be nearer or further than the
+// plane of the pixel
+const DEPTH_TOLERANCE: f32 = 0.1;
+
+// Neighbors outside the scene are moved onto its border
+fn clamp_pixel(pixel: vec2<i32>) -> vec2<i32> {
+    let size = vec2<i32>(textureDimensions(depth_texture));
+    return clamp(pixel, vec2<i32>(0), size - 1);
+}
+
+fn depth_at(pixel: vec2<i32>) -> f32 {
+    return textureLoad(depth_texture, clamp_pixel(pixel), 0).r;
+}
+
+// Position at a pixel and depth, relative to the render origin
+fn unproject(pixel: vec2<i32>, depth: f32) -> vec3<f32> {
+    let uv = (vec2<f32>(clamp_pixel(pixel)) + 0.5) / vec2<f32>(textureDimensions(depth_texture));
+    let clip = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let position = outline.inverse_transform * clip;
+    return position.xyz / position.w;
+}
+
+// Position of what was drawn at a pixel
+fn position_at(pixel: vec2<i32>) -> vec3<f32> {
+    return unproject(pixel, depth_at(pixel));
+}
+
+// The shorter of the steps to two opposite neighbors. Unless the pixel
+// is on an edge, the nearer neighbor is on the same face. A neighbor
+// clamped onto the pixel leaves no step.
+fn nearer_step(before: vec3<f32>, center: vec3<f32>, after: vec3<f32>) -> vec3<f32> {
+    let back = center - before;
+    let forward = after - center;
+    let back_length = dot(back, back);
+    let forward_length = dot(forward, forward);
+    if forward_length == 0.0 || (back_length > 0.0 && back_length < forward_length) {
+        return back;
+    }
+    return forward;
+}
+
+// Normal of the face drawn at a pixel, from the positions around it
+fn normal_at(pixel: vec2<i32>, center: vec3<f32>) -> vec3<f32> {
+    let dx = nearer_step(
+        position_at(pixel - vec2<i32>(1, 0)),
+        center,
+        position_at(pixel + vec2<i32>(1, 0)),
+    );
+    let dy = nearer_step(
+        position_at(pixel - vec2<i32>(0, 1)),
+        center,
+        position_at(pixel + vec2<i32>(0, 1)),
+    );
+    return normalize(cross(dx, dy));
+}
+
+// Whether a neighbor is on another face than the pixel, either turned
+// away from it or off its plane. Faces on the same plane are never
+// apart, even when they belong to different chunks, so chunk borders
+// stay seamless.
+fn is_edge(
+    center: vec3<f32>,
+    normal: vec3<f32>,
+    pixel: vec2<i32>,
+) -> bool {
+    let depth = depth_at(pixel);
+    if depth == DEPTH_CLEAR {
+        return true;
+    }
+    let position = unproject(pixel, depth);
+    if 1.0 - abs(dot(normal, normal_at(pixel, position))) > outline.threshold {
+        return true;
+    }
+    // Where the view ray through the neighbor meets the plane of the
+    // pixel, 1 if the neighbor is on it
+    let origin = unproject(pixel, 1.0);
+    let ray = position - origin;
+    let along = dot(center - origin, normal) / dot(ray, normal);
+    return !(abs(along - 1.0) <= outline.threshold * DEPTH_TOLERANCE);
+}
+
+// Darkens the pixels next to a neighbor on another face
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let depth = depth_at(pixel);
+    if depth == DEPTH_CLEAR {
+        return vec4<f32>(1.0);
+    }
+    let center = unproject(pixel, depth);
+    let normal = normal_at(pixel, center);
+    let reach = i32(outline.thickness);
+    var offsets = array<vec2<i32>, 4>(
+        vec2<i32>(reach, 0),
+        vec2<i32>(-reach, 0),
+        vec2<i32>(0, reach),
+        vec2<i32>(0, -reach),
+    );
+    for (var i = 0; i < 4; i++) {
+        if is_edge(center, normal, pixel + offsets[i]) {
+            return vec4<f32>(EDGE_DARKNESS);
+        }
+    }
+    return vec4<f32>(1.0);
+}
@@ -0,0 +1,70 @@
+struct InstanceIn {
+    @location(0) pos: vec3<f32>,
+    @location(1) size: f32,
+    // Top left and bottom right corners in the atlas
+    @location(2) uv: vec4<f32>,
+    @location(3) opacity: f32,
+}
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) texture_pos: vec2<f32>,
+    @location(1) opacity: f32,
+}
+
+struct Camera {
+    transform: mat4x4<f32>,
+    // Seconds since the renderer started
+    time: f32,
+    // How bright the sky is, from 0 at night to 1 at noon
+    daylight: f32,
+}
+
+// Directions of the screen in the world, the quads are spanned along them
+struct Billboard {
+    right: vec4<f32>,
+    up: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var texture: texture_2d<f32>;
+@group(0) @binding(1)
+var tex_sampler: sampler;
+
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+@group(2) @binding(0)
+var<uniform> billboard: Billboard;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, in: InstanceIn) -> VertexOut {
+    // Two triangles covering the quad
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, 0.5),
+    );
+    let corner = corners[index];
+    let offset = billboard.right.xyz * corner.x + billboard.up.xyz * corner.y;
+    var out: VertexOut;
+    out.pos = camera.transform * vec4<f32>(in.pos + offset * in.size, 1.0);
+    // Texture coordinates grow downwards
+    out.texture_pos = mix(in.uv.xy, in.uv.zw, vec2<f32>(corner.x + 0.5, 0.5 - corner.y));
+    out.opacity = in.opacity;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    let color = textureSample(texture, tex_sampler, in.texture_pos);
+    if color.a < 0.5 {
+        discard;
+    }
+    // Dimmed at night like the sky light, keeping the same ambient floor
+    let brightness = max(camera.daylight, 0.05);
+    return vec4<f32>(color.rgb * brightness, in.opacity);
+}
@@ -0,0 +1,24 @@
+struct Camera {
+    transform: mat4x4<f32>,
+}
+
+struct Selection {
+    // The center of the block and the scale of the outline in w
+    offset: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var<uniform> selection: Selection;
+
+@vertex
+fn vs_main(@location(0) vertex_pos: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return camera.transform * vec4<f32>(vertex_pos * selection.offset.w + selection.offset.xyz, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+}
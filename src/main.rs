@@ -1,8 +1,266 @@
+use std::{path::PathBuf, time::Instant};
+use voxgen::{
+    block::registry,
+    commands,
+    engine::{VoxelEngine, AUTOSAVE_INTERVAL},
+    input::Input,
+    logger,
+    renderer::{adapter::AdapterPreferences, Renderer},
+    scene::Scene,
+    settings::Settings,
+    ui::MenuAction,
+    window::{Window, WindowSettings},
+    world::export::{GLB_EXPORT_PATH, OBJ_EXPORT_PATH},
+};
+
 fn main() {
-    run();
+    run(WindowSettings::default());
+}
+
+pub fn run(settings: WindowSettings) {
+    std::env::set_var("RUST_LOG", "info, wgpu_core=error");
+    logger::init();
+    if let Err(e) = registry::init() {
+        exit_with_error("Failed to load the blocks", e);
+    }
+
+    let user_settings = Settings::load();
+    let args = match parse_args(user_settings.adapter_preferences()) {
+        Ok(args) => args,
+        Err(e) => exit_with_error("Invalid arguments", e),
+    };
+    if let Some(trace) = &args.trace {
+        if let Err(e) = std::fs::create_dir_all(trace) {
+            exit_with_error("Failed to create the trace directory", e);
+        }
+    }
+    let settings = WindowSettings {
+        fullscreen: user_settings.fullscreen,
+        monitor: user_settings.monitor,
+        ..settings
+    };
+    let event_loop = winit::event_loop::EventLoop::new();
+    let mut window = match Window::new(settings, &event_loop) {
+        Ok(window) => window,
+        Err(e) => exit_with_error("Failed to create the window", e),
+    };
+    let size = window.size();
+    let scale_factor = window.scale_factor() as f64;
+    let renderer = match pollster::block_on(Renderer::new(
+        &window,
+        size,
+        scale_factor,
+        args.preferences,
+        args.trace.as_deref(),
+    )) {
+        Ok(renderer) => renderer,
+        Err(e) => exit_with_error("Failed to initialize the renderer", e),
+    };
+    window.grab_cursor(true);
+
+    let mut engine = VoxelEngine::new(window, renderer, user_settings);
+    let mut scene = Scene::new(engine.renderer(), size.0 as f32, size.1 as f32);
+    let mut last_render_time = Instant::now();
+
+    event_loop.run(move |event, _, flow| {
+        engine.renderer_mut().gui.platform.handle_event(&event);
+        if !engine.locked_input {
+            scene.handle_input_events(&event);
+            engine.renderer_mut().input(&event);
+        }
+
+        match event {
+            winit::event::Event::MainEventsCleared => {
+                let background = !engine.window.is_focused() || engine.window.is_minimized();
+                engine
+                    .frame_limiter
+                    .configure(engine.settings.fps_cap, background);
+                // Woken up by an event before the next frame is due
+                if !engine.frame_limiter.is_due() {
+                    *flow = engine.frame_limiter.control_flow();
+                    return;
+                }
+                engine.frame_limiter.start_frame();
+                engine.window.apply_settings(&engine.settings);
+                engine.window.update();
+                let scale_factor = engine.window.scale_factor();
+                let dt = last_render_time.elapsed();
+                let update_start = Instant::now();
+                scene.apply_settings(&engine.settings);
+                engine.renderer.apply_settings(&engine.settings);
+                engine.update(&mut scene, dt);
+                if engine.last_save.elapsed() >= AUTOSAVE_INTERVAL {
+                    engine.save_world(&scene, true);
+                }
+                last_render_time = Instant::now();
+                let update_time = last_render_time - update_start;
+                match engine.renderer.render(
+                    &scene,
+                    &mut engine.bindings,
+                    &mut engine.settings,
+                    scale_factor,
+                    dt.as_secs_f32(),
+                ) {
+                    Ok(_) => (),
+                    Err(e) => log::error!("Rendering Error: {}", e),
+                }
+                let render_time = last_render_time.elapsed();
+                for command in engine.renderer.gui.state.console.take_submitted() {
+                    let (output, level) = match commands::execute(&command, &mut engine, &mut scene)
+                    {
+                        Ok(output) => (output, log::Level::Info),
+                        Err(error) => (error, log::Level::Error),
+                    };
+                    if !output.is_empty() {
+                        let state = &mut engine.renderer.gui.state;
+                        // Long outputs like the help only fit in the console
+                        let summary = match output.split_once('\n') {
+                            Some((first, _)) => format!("{} (see the console)", first),
+                            None => output.clone(),
+                        };
+                        state.hud.post(summary, level);
+                        state.console.print(output);
+                    }
+                }
+                engine
+                    .renderer
+                    .gui
+                    .state
+                    .frame_graph
+                    .record(update_time, render_time);
+                scene.camera_path.record_timings(
+                    update_time,
+                    render_time,
+                    engine.renderer.gpu_timings(),
+                );
+                engine.frame_limiter.wait();
+                *flow = engine.frame_limiter.control_flow();
+                if let Some((slot, block)) = engine.renderer.gui.state.inventory.take_dropped() {
+                    scene.hotbar.set(slot, block);
+                }
+                match engine.renderer.gui.state.menu_action.take() {
+                    Some(MenuAction::Resume) => engine.resume(),
+                    Some(MenuAction::Quit) => *flow = winit::event_loop::ControlFlow::Exit,
+                    Some(MenuAction::ExportObj) => {
+                        match engine.renderer().world().export_obj(OBJ_EXPORT_PATH) {
+                            Ok(()) => log::info!("Exported the world to {}", OBJ_EXPORT_PATH),
+                            Err(e) => log::error!("Failed to export the world: {}", e),
+                        }
+                    }
+                    Some(MenuAction::ExportGlb) => {
+                        engine.renderer().world().export_glb(GLB_EXPORT_PATH)
+                    }
+                    Some(MenuAction::BenchmarkUploads) => engine.renderer_mut().benchmark_uploads(),
+                    None => (),
+                }
+            }
+            winit::event::Event::LoopDestroyed => engine.save_world(&scene, false),
+            winit::event::Event::WindowEvent { event, .. } => match event {
+                winit::event::WindowEvent::KeyboardInput { .. }
+                | winit::event::WindowEvent::MouseInput { .. } => {
+                    // Clicking into the window grabs the cursor again
+                    // without triggering any action
+                    if let winit::event::WindowEvent::MouseInput {
+                        state: winit::event::ElementState::Pressed,
+                        ..
+                    } = event
+                    {
+                        if !engine.locked_input && !engine.window.is_cursor_grabbed() {
+                            engine.window.grab_cursor(true);
+                            return;
+                        }
+                    }
+                    // Waiting for a new binding from the controls menu
+                    if let Some((action, slot)) = engine.bindings.rebinding {
+                        if let Some((input, true)) = Input::from_event(&event) {
+                            engine.bindings.set(action, slot, input);
+                            engine.bindings.rebinding = None;
+                            engine.bindings.save();
+                        }
+                        return;
+                    }
+                    if let Some((action, pressed)) = engine.bindings.translate(&event) {
+                        if pressed {
+                            let locked = engine.locked_input;
+                            engine.on_action_pressed(action);
+                            if engine.locked_input != locked {
+                                scene.on_input_lock_changed();
+                            }
+                        } else {
+                            engine.on_action_released(action);
+                        }
+                        let context = engine.renderer().gui.platform.context();
+                        let over_ui = pressed
+                            && matches!(event, winit::event::WindowEvent::MouseInput { .. })
+                            && (context.wants_pointer_input() || context.is_pointer_over_area());
+                        // Releases are always processed so keys can't get stuck
+                        if (!engine.locked_input || !pressed) && !over_ui {
+                            scene.on_action(action, pressed, engine.renderer_mut().world_mut());
+                        }
+                    }
+                }
+                winit::event::WindowEvent::Focused(focused) => {
+                    engine.window.set_focused(focused);
+                    if !focused {
+                        scene.controller_mut().clear_pressed();
+                        engine.renderer_mut().set_comparing_filtering(false);
+                    }
+                }
+                winit::event::WindowEvent::CloseRequested => {
+                    *flow = winit::event_loop::ControlFlow::Exit
+                }
+                winit::event::WindowEvent::Resized(size) => {
+                    engine.window.on_resized();
+                    engine.renderer_mut().resize(&mut scene, size);
+                }
+                winit::event::WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    // macOS changes the scale factor in the middle of switching
+                    // in or out of fullscreen, suggesting the size from before
+                    // the switch. Keeping the current size lets the switch finish.
+                    if engine.window.is_transitioning() {
+                        *new_inner_size = engine.window.size().into();
+                    }
+                    engine.renderer_mut().resize(&mut scene, *new_inner_size);
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+    });
+}
+
+/// What the command line sets
+struct Args {
+    preferences: AdapterPreferences,
+    /// Directory wgpu records its API trace into
+    trace: Option<PathBuf>,
+}
+
+/// Overrides the adapter preferences from the settings with the
+/// `--backend <auto|vulkan|dx12|metal|gl>` and `--power <low|high>` flags,
+/// and records a wgpu trace with `--trace <dir>`
+fn parse_args(preferences: AdapterPreferences) -> Result<Args, String> {
+    let mut parsed = Args {
+        preferences,
+        trace: None,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing a value after {}", arg))?;
+        match arg.as_str() {
+            "--backend" => parsed.preferences.backend = value.parse()?,
+            "--power" => parsed.preferences.power = value.parse()?,
+            "--trace" => parsed.trace = Some(PathBuf::from(value)),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    Ok(parsed)
 }
 
-pub fn run() {
-    let settings = voxgen::window::WindowSettings::default();
-    voxgen::core::init(settings);
+/// Reports an error that prevents the engine from starting and exits
+fn exit_with_error(context: &str, error: impl std::fmt::Display) -> ! {
+    log::error!("{}: {}", context, error);
+    std::process::exit(1);
 }
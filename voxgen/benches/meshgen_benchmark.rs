@@ -1,14 +1,125 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use voxgen::renderer::WorldRenderer;
-use voxgen::world::chunk::{Chunk, ChunkPos};
+use voxgen::block::BlockId;
+use voxgen::world::chunk::{
+    compute_1d, Chunk, ChunkPos, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH, TOTAL_CHUNK_SIZE,
+};
+use voxgen::world::light::{LightMap, Lighting, MAX_LIGHT};
+use voxgen::world::palette::{BlockMeta, ChunkBlocks};
 
-fn meshgen_benchmark(c: &mut Criterion) {
-    c.bench_function("meshgen", |b| {
-        b.iter(|| {
-            let chunk = Chunk::generate(&ChunkPos::new(0, 0));
-        })
+/// Every block is solid, so only the outer faces are visible
+fn solid_chunk() -> Vec<BlockId> {
+    vec![BlockId::STONE; TOTAL_CHUNK_SIZE]
+}
+
+/// Rolling hills made of value noise, the typical case for terrain
+fn terrain_chunk() -> Vec<BlockId> {
+    let mut blocks = vec![BlockId::AIR; TOTAL_CHUNK_SIZE];
+    for x in 0..CHUNK_WIDTH {
+        for z in 0..CHUNK_DEPTH {
+            let height = 64.0 + value_noise(x as f32 / 8.0, z as f32 / 8.0) * 32.0;
+            for y in 0..height as usize {
+                blocks[compute_1d(x, y, z)] = if y + 1 == height as usize {
+                    BlockId::GRASS
+                } else {
+                    BlockId::DIRT
+                };
+            }
+        }
+    }
+    blocks
+}
+
+/// Alternating solid and air blocks, so every face of every block is visible
+fn checkerboard_chunk() -> Vec<BlockId> {
+    let mut blocks = vec![BlockId::AIR; TOTAL_CHUNK_SIZE];
+    for x in 0..CHUNK_WIDTH {
+        for y in 0..CHUNK_HEIGHT {
+            for z in 0..CHUNK_DEPTH {
+                if (x + y + z) % 2 == 0 {
+                    blocks[compute_1d(x, y, z)] = BlockId::STONE;
+                }
+            }
+        }
+    }
+    blocks
+}
+
+fn hash(x: i32, z: i32) -> f32 {
+    let mut n = (x.wrapping_mul(374761393) ^ z.wrapping_mul(668265263)) as u32;
+    n = (n ^ (n >> 13)).wrapping_mul(1274126177);
+    (n ^ (n >> 16)) as f32 / u32::MAX as f32
+}
+
+/// Smoothly interpolated random values on an integer grid, in `[0, 1]`
+fn value_noise(x: f32, z: f32) -> f32 {
+    let (x0, z0) = (x.floor() as i32, z.floor() as i32);
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, tz) = (smooth(x - x0 as f32), smooth(z - z0 as f32));
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    lerp(
+        lerp(hash(x0, z0), hash(x0 + 1, z0), tx),
+        lerp(hash(x0, z0 + 1), hash(x0 + 1, z0 + 1), tx),
+        tz,
+    )
+}
+
+fn generate_benchmark(c: &mut Criterion) {
+    c.bench_function("generate", |b| {
+        b.iter(|| Chunk::generate(black_box(ChunkPos::ORIGIN)))
     });
 }
 
-criterion_group!(benches, meshgen_benchmark);
+fn fixtures() -> [(&'static str, Vec<BlockId>); 3] {
+    [
+        ("solid", solid_chunk()),
+        ("terrain", terrain_chunk()),
+        ("checkerboard", checkerboard_chunk()),
+    ]
+}
+
+fn meshgen_benchmark(c: &mut Criterion) {
+    let fixtures = fixtures();
+    // No block of the fixtures has metadata
+    let meta = BlockMeta::default();
+    let mut group = c.benchmark_group("meshgen");
+    for (name, blocks) in &fixtures {
+        let light = LightMap::with_sky_columns(blocks);
+        for (lighting_name, lighting) in [("flat", Lighting::Flat), ("smooth", Lighting::Smooth)] {
+            group.bench_function(format!("{}/{}", name, lighting_name), |b| {
+                b.iter(|| {
+                    Chunk::build_mesh(
+                        black_box(blocks),
+                        &meta,
+                        &light,
+                        ChunkPos::ORIGIN,
+                        lighting,
+                        |_| (BlockId::AIR, [0, MAX_LIGHT]),
+                    )
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn storage_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("storage");
+    for (name, blocks) in &fixtures() {
+        let packed = ChunkBlocks::from_slice(blocks);
+        group.bench_function(format!("{}/pack", name), |b| {
+            b.iter(|| ChunkBlocks::from_slice(black_box(blocks)))
+        });
+        group.bench_function(format!("{}/unpack", name), |b| {
+            b.iter(|| black_box(&packed).to_vec())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    generate_benchmark,
+    meshgen_benchmark,
+    storage_benchmark
+);
 criterion_main!(benches);
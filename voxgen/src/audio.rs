@@ -0,0 +1,181 @@
+use std::{collections::HashMap, io::Cursor};
+
+use rodio::{
+    buffer::SamplesBuffer, decoder::DecoderError, Decoder, OutputStream, OutputStreamHandle, Sink,
+    Source,
+};
+
+use crate::{
+    block::SoundMaterial, scene::Scene, settings::Settings, world::chunk::SURFACE_HEIGHT,
+    world::events::WorldEvent,
+};
+
+const MATERIAL_SOUNDS: [(SoundMaterial, &[u8]); 6] = [
+    (
+        SoundMaterial::Dirt,
+        include_bytes!("../../assets/sounds/dirt.wav"),
+    ),
+    (
+        SoundMaterial::Grass,
+        include_bytes!("../../assets/sounds/grass.wav"),
+    ),
+    (
+        SoundMaterial::Stone,
+        include_bytes!("../../assets/sounds/stone.wav"),
+    ),
+    (
+        SoundMaterial::Sand,
+        include_bytes!("../../assets/sounds/sand.wav"),
+    ),
+    (
+        SoundMaterial::Glass,
+        include_bytes!("../../assets/sounds/glass.wav"),
+    ),
+    (
+        SoundMaterial::Water,
+        include_bytes!("../../assets/sounds/water.wav"),
+    ),
+];
+const FOOTSTEPS_WAV: &[u8] = include_bytes!("../../assets/sounds/footsteps.wav");
+const WIND_WAV: &[u8] = include_bytes!("../../assets/sounds/wind.wav");
+
+/// Most block sounds started in a frame, so that pasting a
+/// structure doesn't play hundreds of them at once
+const MAX_EFFECTS_PER_FRAME: usize = 4;
+/// Placing a block plays its breaking sound sped up
+const PLACE_SPEED: f32 = 1.3;
+/// Heights the wind goes from silent to full volume between
+const WIND_LOW: f32 = 64.0;
+const WIND_HIGH: f32 = SURFACE_HEIGHT as f32 + 64.0;
+
+/// A decoded sound, played as many times as needed without decoding it again
+struct Sound {
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<i16>,
+}
+
+impl Sound {
+    fn decode(bytes: &'static [u8]) -> Result<Self, DecoderError> {
+        let decoder = Decoder::new(Cursor::new(bytes))?;
+        Ok(Self {
+            channels: decoder.channels(),
+            sample_rate: decoder.sample_rate(),
+            samples: decoder.collect(),
+        })
+    }
+
+    fn source(&self) -> SamplesBuffer<i16> {
+        SamplesBuffer::new(self.channels, self.sample_rate, self.samples.clone())
+    }
+}
+
+struct Output {
+    /// Everything stops playing once the stream is dropped
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    materials: HashMap<SoundMaterial, Sound>,
+    footsteps: Sink,
+    wind: Sink,
+}
+
+impl Output {
+    fn open() -> Result<Self, Box<dyn std::error::Error>> {
+        let (stream, handle) = OutputStream::try_default()?;
+        let materials = MATERIAL_SOUNDS
+            .iter()
+            .map(|(material, bytes)| Ok((*material, Sound::decode(bytes)?)))
+            .collect::<Result<HashMap<_, _>, DecoderError>>()?;
+        let looped = |bytes| -> Result<Sink, Box<dyn std::error::Error>> {
+            let sink = Sink::try_new(&handle)?;
+            sink.set_volume(0.0);
+            sink.append(Sound::decode(bytes)?.source().repeat_infinite());
+            Ok(sink)
+        };
+        let footsteps = looped(FOOTSTEPS_WAV)?;
+        footsteps.pause();
+        let wind = looped(WIND_WAV)?;
+        Ok(Self {
+            _stream: stream,
+            handle,
+            materials,
+            footsteps,
+            wind,
+        })
+    }
+
+    fn play(&self, material: SoundMaterial, speed: f32, volume: f32) {
+        let source = self.materials[&material]
+            .source()
+            .speed(speed)
+            .amplify(volume)
+            .convert_samples();
+        if let Err(e) = self.handle.play_raw(source) {
+            log::debug!("Failed to play a sound: {}", e);
+        }
+    }
+}
+
+/// Plays the sounds of block changes, the footsteps of the player and
+/// the wind. Does nothing when there is no audio device.
+pub struct AudioSystem {
+    output: Option<Output>,
+}
+
+impl AudioSystem {
+    pub fn new() -> Self {
+        let output = match Output::open() {
+            Ok(output) => Some(output),
+            Err(e) => {
+                log::warn!("Audio is disabled: {}", e);
+                None
+            }
+        };
+        Self { output }
+    }
+
+    /// Plays the sounds of the block changes among `events` and adjusts
+    /// the loops to the player's movement and altitude
+    pub fn update(
+        &mut self,
+        scene: &Scene,
+        events: &[WorldEvent],
+        settings: &Settings,
+        paused: bool,
+    ) {
+        let output = match &self.output {
+            Some(output) => output,
+            None => return,
+        };
+        let effects_volume = settings.master_volume * settings.effects_volume;
+        let changes = events.iter().filter_map(|event| match event {
+            // Changes of only the metadata, like water flowing, are silent
+            WorldEvent::BlockChanged { old, new, .. } if old != new => Some((*old, *new)),
+            _ => None,
+        });
+        for (old, new) in changes.take(MAX_EFFECTS_PER_FRAME) {
+            if new.is_air() {
+                output.play(old.sound(), 1.0, effects_volume);
+            } else {
+                output.play(new.sound(), PLACE_SPEED, effects_volume);
+            }
+        }
+
+        if scene.is_walking() && !paused {
+            output.footsteps.set_volume(effects_volume);
+            output.footsteps.play();
+        } else {
+            output.footsteps.pause();
+        }
+        let altitude = (scene.camera_pos().y - WIND_LOW) / (WIND_HIGH - WIND_LOW);
+        output
+            .wind
+            .set_volume(settings.master_volume * altitude.clamp(0.0, 1.0));
+    }
+}
+
+impl Default for AudioSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,15 +1,205 @@
+pub mod registry;
+
+use registry::{registry, BlockProperties};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use vek::Vec3;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum BlockId {
-    AIR = 0,
-    DIRT = 1,
-    GRASS = 2,
-    STONE = 3,
+/// A block type, indexing its properties in the block registry.
+/// Saved as its `u8` value, which stays the same as long as blocks.ron
+/// only gets new blocks appended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct BlockId(u8);
+
+#[derive(Debug, Error)]
+#[error("unknown block id {0}")]
+pub struct UnknownBlock(pub u8);
+
+impl TryFrom<u8> for BlockId {
+    type Error = UnknownBlock;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let id = BlockId(value);
+        if registry().contains(id) {
+            Ok(id)
+        } else {
+            Err(UnknownBlock(value))
+        }
+    }
+}
+
+impl From<BlockId> for u8 {
+    fn from(id: BlockId) -> Self {
+        id.0
+    }
+}
+
+/// The set of sounds played when a block is broken or placed,
+/// one file of `assets/sounds` each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum SoundMaterial {
+    Dirt,
+    Grass,
+    Stone,
+    Sand,
+    Glass,
+    Water,
+}
+
+/// The geometry a block is meshed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BlockShape {
+    /// A cube filling the whole block
+    Full,
+    /// The lower half of a cube
+    Slab,
+    /// Two quads crossing diagonally, used by plants
+    Cross,
 }
+
+/// What a block does when one of its six neighbors changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum NeighborBehavior {
+    #[default]
+    Ignore,
+    /// Breaks when the block beneath it becomes air, like a torch
+    NeedsSupport,
+    /// Schedules a tick to flow into or recede from its neighbors, like water
+    Flows,
+}
+
+/// Level of the weakest flowing fluid. The level of a fluid block is its
+/// metadata: 0 for a source, otherwise how far it flowed from one.
+pub const MAX_FLUID_LEVEL: u8 = 7;
+
+/// Height of the surface of a fluid block at the given level above the
+/// bottom of the block, sources fill the whole block
+pub fn fluid_height(level: u8) -> f32 {
+    1.0 - level.min(MAX_FLUID_LEVEL) as f32 / (MAX_FLUID_LEVEL + 1) as f32
+}
+
 impl BlockId {
-    pub fn is_air(&self) -> bool {
-        self == &BlockId::AIR
+    pub const AIR: BlockId = BlockId(0);
+    pub const DIRT: BlockId = BlockId(1);
+    pub const GRASS: BlockId = BlockId(2);
+    pub const STONE: BlockId = BlockId(3);
+    pub const BEDROCK: BlockId = BlockId(4);
+    pub const WATER: BlockId = BlockId(5);
+    pub const GLASS: BlockId = BlockId(6);
+    pub const TORCH: BlockId = BlockId(7);
+    pub const GRASS_TUFT: BlockId = BlockId(8);
+    pub const STONE_SLAB: BlockId = BlockId(9);
+    pub const SAND: BlockId = BlockId(10);
+
+    /// Number of blocks the engine uses directly
+    pub const BUILTIN_COUNT: usize = 11;
+    /// The blocks the engine uses directly, which blocks.ron must define with these ids
+    pub const BUILTIN: [(BlockId, &'static str); Self::BUILTIN_COUNT] = [
+        (BlockId::AIR, "air"),
+        (BlockId::DIRT, "dirt"),
+        (BlockId::GRASS, "grass"),
+        (BlockId::STONE, "stone"),
+        (BlockId::BEDROCK, "bedrock"),
+        (BlockId::WATER, "water"),
+        (BlockId::GLASS, "glass"),
+        (BlockId::TORCH, "torch"),
+        (BlockId::GRASS_TUFT, "grass_tuft"),
+        (BlockId::STONE_SLAB, "stone_slab"),
+        (BlockId::SAND, "sand"),
+    ];
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// Any id, registered or not, for tests of storage that never looks
+    /// blocks up
+    #[cfg(test)]
+    pub fn from_raw(value: u8) -> BlockId {
+        BlockId(value)
+    }
+
+    pub fn properties(self) -> &'static BlockProperties {
+        registry().get(self)
+    }
+
+    pub fn name(self) -> &'static str {
+        &self.properties().name
+    }
+
+    pub fn is_air(self) -> bool {
+        self == BlockId::AIR
+    }
+
+    pub fn shape(self) -> BlockShape {
+        self.properties().shape
+    }
+
+    /// Whether entities collide with this block
+    pub fn is_solid(self) -> bool {
+        self.properties().solid
+    }
+
+    /// Whether blocks behind this one can be seen through it.
+    /// Transparent blocks are drawn in a separate, blended pass.
+    pub fn is_transparent(self) -> bool {
+        self.properties().transparent
+    }
+
+    /// Whether light can't pass through this block
+    pub fn is_opaque(self) -> bool {
+        self.properties().opaque
+    }
+
+    /// The block light level this block emits
+    pub fn light_emission(self) -> u8 {
+        self.properties().light_emission
+    }
+
+    /// Color of the block seen from above on the minimap
+    pub fn map_color(self) -> [u8; 3] {
+        self.properties().map_color
+    }
+
+    /// What the block sounds like when broken or placed
+    pub fn sound(self) -> SoundMaterial {
+        self.properties().sound
+    }
+
+    /// Whether the block falls down when there's air beneath it
+    pub fn falls(self) -> bool {
+        self.properties().falls
+    }
+
+    /// How the block reacts to its neighbors changing
+    pub fn neighbor_behavior(self) -> NeighborBehavior {
+        self.properties().on_neighbor_changed
+    }
+
+    /// Whether the block keeps state in its metadata. Other blocks always
+    /// have a metadata of 0.
+    pub fn has_metadata(self) -> bool {
+        self.properties().metadata
+    }
+
+    /// Whether the block is a fluid spreading to the air around it
+    pub fn flows(self) -> bool {
+        self.neighbor_behavior() == NeighborBehavior::Flows
+    }
+
+    /// Whether the face of this block towards `neighbor` has to be drawn.
+    /// Faces between two blocks of the same transparent type are hidden,
+    /// like the inside of a body of water. Only full blocks hide faces.
+    pub fn is_face_visible(self, neighbor: BlockId) -> bool {
+        neighbor.is_air()
+            || neighbor.shape() != BlockShape::Full
+            || (neighbor.is_transparent() && neighbor != self)
+    }
+
+    /// Whether the player is allowed to remove this block
+    pub fn is_breakable(self) -> bool {
+        !self.is_air() && self.properties().hardness.is_some()
     }
 }
 #[derive(Debug, Clone)]
@@ -30,3 +220,38 @@ impl Block {
         &self.pos
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_ids_round_trip() {
+        assert_eq!(BlockId::BUILTIN.len(), BlockId::BUILTIN_COUNT);
+        for (value, (id, name)) in BlockId::BUILTIN.iter().enumerate() {
+            assert_eq!(u8::from(*id), value as u8);
+            assert_eq!(BlockId::try_from(value as u8).unwrap(), *id);
+            assert_eq!(id.name(), *name);
+            let bytes = bincode::serialize(id).unwrap();
+            assert_eq!(bytes, [value as u8]);
+            assert_eq!(bincode::deserialize::<BlockId>(&bytes).unwrap(), *id);
+        }
+    }
+
+    #[test]
+    fn registered_ids_round_trip() {
+        for id in registry().ids() {
+            let bytes = bincode::serialize(&id).unwrap();
+            assert_eq!(bincode::deserialize::<BlockId>(&bytes).unwrap(), id);
+        }
+    }
+
+    #[test]
+    fn unregistered_ids_are_rejected() {
+        let unregistered = registry().len() as u8;
+        for value in [unregistered, u8::MAX] {
+            assert!(matches!(BlockId::try_from(value), Err(UnknownBlock(v)) if v == value));
+            assert!(bincode::deserialize::<BlockId>(&[value]).is_err());
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    collections::HashSet,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::{BlockId, BlockShape, NeighborBehavior, SoundMaterial};
+use crate::renderer::atlas::TextureId;
+
+pub const BLOCKS_RON: &str = include_str!("../../../assets/blocks.ron");
+/// Where blocks.ron is read from when the assets are reloaded
+pub const BLOCKS_PATH: &str = "assets/blocks.ron";
+
+/// The registry in use. Registries are leaked, so that the references
+/// handed out stay valid after a reload replaces them.
+static REGISTRY: AtomicPtr<BlockRegistry> = AtomicPtr::new(ptr::null_mut());
+
+fn current() -> Option<&'static BlockRegistry> {
+    // SAFETY: only ever set to leaked registries, which are never freed
+    unsafe { REGISTRY.load(Ordering::Acquire).as_ref() }
+}
+
+/// Uses the registry unless another one was set first, returning the one
+/// in use
+fn install_first(registry: BlockRegistry) -> &'static BlockRegistry {
+    let registry: &'static mut BlockRegistry = Box::leak(Box::new(registry));
+    match REGISTRY.compare_exchange(
+        ptr::null_mut(),
+        registry,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    ) {
+        Ok(_) => registry,
+        // SAFETY: only ever set to leaked registries, which are never freed
+        Err(existing) => unsafe { &*existing },
+    }
+}
+
+/// Parses the shipped block definitions. Called at startup so that
+/// mistakes in the file are reported before any block is used.
+pub fn init() -> Result<&'static BlockRegistry, RegistryError> {
+    if let Some(registry) = current() {
+        return Ok(registry);
+    }
+    let registry = BlockRegistry::from_ron(BLOCKS_RON)?;
+    Ok(install_first(registry))
+}
+
+/// The registry of every block, loaded on first use if `init` wasn't called
+pub fn registry() -> &'static BlockRegistry {
+    match current() {
+        Some(registry) => registry,
+        None => install_first(
+            BlockRegistry::from_ron(BLOCKS_RON)
+                .unwrap_or_else(|e| panic!("invalid block registry: {}", e)),
+        ),
+    }
+}
+
+/// Reads blocks.ron again, checking that it can replace the registry in
+/// use. The registry is only replaced by [`replace`].
+pub fn reload() -> Result<BlockRegistry, RegistryError> {
+    let source = std::fs::read_to_string(BLOCKS_PATH)?;
+    let reloaded = BlockRegistry::from_ron(&source)?;
+    // The loaded chunks may hold any of the current blocks
+    let len = registry().len();
+    if reloaded.len() < len {
+        return Err(RegistryError::RemovedBlocks {
+            expected: len,
+            found: reloaded.len(),
+        });
+    }
+    Ok(reloaded)
+}
+
+/// Uses a reloaded registry from now on. Blocks already looked up keep
+/// the properties they had.
+pub fn replace(registry: BlockRegistry) {
+    REGISTRY.store(Box::leak(Box::new(registry)), Ordering::Release);
+}
+
+#[derive(Debug, Error)]
+pub enum RegistryError {
+    #[error("failed to read blocks.ron: {0}")]
+    Read(#[from] std::io::Error),
+    #[error("failed to parse blocks.ron: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("block \"{block}\" uses the unknown texture \"{texture}\"")]
+    UnknownTexture { block: String, texture: String },
+    #[error("block \"{0}\" is defined more than once")]
+    DuplicateName(String),
+    #[error("expected block \"{name}\" at id {id}, found {found}")]
+    MissingBlock {
+        name: &'static str,
+        id: usize,
+        found: String,
+    },
+    #[error("there can't be more than 256 blocks")]
+    TooManyBlocks,
+    #[error(
+        "blocks.ron defines {found} of the {expected} blocks in use, blocks can only be appended"
+    )]
+    RemovedBlocks { expected: usize, found: usize },
+}
+
+/// Everything the game knows about a block
+#[derive(Debug, Clone)]
+pub struct BlockProperties {
+    pub name: String,
+    /// Indexed by `usize::from(Direction)`
+    pub textures: [TextureId; 6],
+    pub opaque: bool,
+    pub solid: bool,
+    pub transparent: bool,
+    pub shape: BlockShape,
+    /// How long the block takes to break, `None` if it can't be broken
+    pub hardness: Option<f32>,
+    pub light_emission: u8,
+    /// Color of the block seen from above on the minimap
+    pub map_color: [u8; 3],
+    pub sound: SoundMaterial,
+    /// Whether the block falls down when there's air beneath it
+    pub falls: bool,
+    pub on_neighbor_changed: NeighborBehavior,
+    /// Whether the block keeps state in its metadata, like the level of water
+    pub metadata: bool,
+}
+
+/// The properties of every block, indexed by `BlockId`
+#[derive(Debug)]
+pub struct BlockRegistry {
+    blocks: Vec<BlockProperties>,
+}
+
+impl BlockRegistry {
+    pub fn from_ron(source: &str) -> Result<Self, RegistryError> {
+        let definitions: Vec<BlockDefinition> = ron::from_str(source)?;
+        if definitions.len() > u8::MAX as usize + 1 {
+            return Err(RegistryError::TooManyBlocks);
+        }
+        let mut names = HashSet::new();
+        let mut blocks = Vec::with_capacity(definitions.len());
+        for definition in definitions {
+            if !names.insert(definition.name.clone()) {
+                return Err(RegistryError::DuplicateName(definition.name));
+            }
+            blocks.push(definition.resolve()?);
+        }
+        // The engine refers to these blocks directly
+        for (id, name) in BlockId::BUILTIN {
+            let found = blocks.get(id.index()).map(|block| &block.name);
+            if found.map(String::as_str) != Some(name) {
+                return Err(RegistryError::MissingBlock {
+                    name,
+                    id: id.index(),
+                    found: found.map_or("the end of the list".to_string(), |found| {
+                        format!("\"{}\"", found)
+                    }),
+                });
+            }
+        }
+        Ok(Self { blocks })
+    }
+
+    pub fn get(&self, id: BlockId) -> &BlockProperties {
+        &self.blocks[id.index()]
+    }
+
+    pub fn contains(&self, id: BlockId) -> bool {
+        id.index() < self.blocks.len()
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<BlockId> {
+        self.blocks
+            .iter()
+            .position(|block| block.name == name)
+            .map(|index| BlockId(index as u8))
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = BlockId> {
+        (0..self.blocks.len()).map(|index| BlockId(index as u8))
+    }
+}
+
+/// A block as written in blocks.ron
+#[derive(Deserialize)]
+struct BlockDefinition {
+    name: String,
+    textures: FaceTextures,
+    opaque: bool,
+    solid: bool,
+    #[serde(default)]
+    transparent: bool,
+    #[serde(default = "full_shape")]
+    shape: BlockShape,
+    hardness: Option<f32>,
+    #[serde(default)]
+    light: u8,
+    map_color: (u8, u8, u8),
+    sound: SoundMaterial,
+    #[serde(default)]
+    falls: bool,
+    #[serde(default)]
+    on_neighbor_changed: NeighborBehavior,
+    #[serde(default)]
+    metadata: bool,
+}
+
+fn full_shape() -> BlockShape {
+    BlockShape::Full
+}
+
+/// The texture names of the faces of a block
+#[derive(Deserialize)]
+enum FaceTextures {
+    All(String),
+    Column {
+        top: String,
+        bottom: String,
+        side: String,
+    },
+    Faces {
+        up: String,
+        down: String,
+        left: String,
+        right: String,
+        front: String,
+        back: String,
+    },
+}
+
+impl BlockDefinition {
+    fn resolve(self) -> Result<BlockProperties, RegistryError> {
+        // In the order of the `Direction` variants
+        let names = match &self.textures {
+            FaceTextures::All(all) => [all, all, all, all, all, all],
+            FaceTextures::Column { top, bottom, side } => [top, bottom, side, side, side, side],
+            FaceTextures::Faces {
+                up,
+                down,
+                left,
+                right,
+                front,
+                back,
+            } => [up, down, left, right, back, front],
+        };
+        let mut textures = [TextureId::Dirt; 6];
+        for (texture, name) in textures.iter_mut().zip(names) {
+            *texture = TextureId::from_name(name).ok_or_else(|| RegistryError::UnknownTexture {
+                block: self.name.clone(),
+                texture: name.clone(),
+            })?;
+        }
+        Ok(BlockProperties {
+            name: self.name,
+            textures,
+            opaque: self.opaque,
+            solid: self.solid,
+            transparent: self.transparent,
+            shape: self.shape,
+            hardness: self.hardness,
+            light_emission: self.light,
+            map_color: [self.map_color.0, self.map_color.1, self.map_color.2],
+            sound: self.sound,
+            falls: self.falls,
+            on_neighbor_changed: self.on_neighbor_changed,
+            metadata: self.metadata,
+        })
+    }
+}
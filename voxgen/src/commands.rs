@@ -0,0 +1,642 @@
+use std::str::FromStr;
+
+use vek::Vec3;
+
+use crate::{
+    block::{registry::registry, BlockId},
+    engine::VoxelEngine,
+    scene::{
+        camera_path::{CameraPath, CameraPathMode, FAR_FLYOVER_X, RECORDINGS_DIR},
+        diagnostics::RayDiagnostics,
+        inventory::GameMode,
+        Scene,
+    },
+    world::{
+        chunk_stats::CSV_PATH,
+        gen_hashes::{self, HASHES_PATH},
+        structure::{self, BlockMapping, Structure, STRUCTURES_DIR},
+    },
+};
+
+/// Most blocks the `copy` command copies at once
+const MAX_COPY_VOLUME: usize = 1 << 22;
+
+/// What an argument of a command is, which decides how it completes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// One of the given words, like a subcommand
+    Keyword(&'static [&'static str]),
+    /// The name of a block in the registry
+    Block,
+    /// A structure saved in `STRUCTURES_DIR`
+    Structure,
+    /// A camera path recorded in `RECORDINGS_DIR`
+    CameraPath,
+}
+
+impl ArgKind {
+    /// The values of this kind starting with `prefix`, sorted
+    pub fn complete(self, prefix: &str) -> Vec<String> {
+        let mut values = match self {
+            ArgKind::Keyword(words) => words.iter().map(|word| word.to_string()).collect(),
+            ArgKind::Block => registry()
+                .ids()
+                .filter(|block| !block.is_air())
+                .map(|block| block.name().to_owned())
+                .collect(),
+            ArgKind::Structure => saved_names(STRUCTURES_DIR, "bin"),
+            ArgKind::CameraPath => saved_names(RECORDINGS_DIR, "json"),
+        };
+        values.retain(|value: &String| value.starts_with(prefix));
+        values.sort();
+        values
+    }
+}
+
+/// A form of a console command, for the help and for completion
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// The arguments as shown in the help
+    pub usage: &'static str,
+    pub description: &'static str,
+    /// The leading arguments that can be completed. A command written in
+    /// several forms has a spec per form, told apart by their keywords.
+    pub args: &'static [ArgKind],
+}
+
+const fn spec(
+    name: &'static str,
+    usage: &'static str,
+    args: &'static [ArgKind],
+    description: &'static str,
+) -> CommandSpec {
+    CommandSpec {
+        name,
+        usage,
+        description,
+        args,
+    }
+}
+
+/// Every command in the order of the help
+pub const COMMANDS: &[CommandSpec] = &[
+    spec("help", "", &[], "Shows this message"),
+    spec(
+        "debug",
+        "ray <on|off>",
+        &[ArgKind::Keyword(&["ray"]), ArgKind::Keyword(&["on", "off"])],
+        "Draws the picking ray and the collision tests",
+    ),
+    spec(
+        "copy",
+        "<x y z> <x y z>",
+        &[],
+        "Copies the blocks between two corners to the clipboard",
+    ),
+    spec(
+        "paste",
+        "[x y z]",
+        &[],
+        "Pastes the clipboard at the position or the targeted block",
+    ),
+    spec(
+        "rotate",
+        "[turns]",
+        &[],
+        "Turns the clipboard clockwise by quarter turns",
+    ),
+    spec(
+        "import",
+        "<file>",
+        &[],
+        "Loads a MagicaVoxel model into the clipboard",
+    ),
+    spec(
+        "save-structure",
+        "<name>",
+        &[ArgKind::Structure],
+        "Saves the clipboard under structures/",
+    ),
+    spec(
+        "load-structure",
+        "<name>",
+        &[ArgKind::Structure],
+        "Loads a saved structure into the clipboard",
+    ),
+    spec(
+        "give",
+        "<block> [count]",
+        &[ArgKind::Block],
+        "Puts the block in the hotbar, adding count of it in survival",
+    ),
+    spec(
+        "tp",
+        "<x> <y> <z>",
+        &[],
+        "Teleports the player's feet to the position",
+    ),
+    spec("spawn", "", &[], "Teleports back to the world spawn"),
+    spec(
+        "spawnpoint",
+        "set [x y z]",
+        &[ArgKind::Keyword(&["set"])],
+        "Moves the world spawn to the position or the player",
+    ),
+    spec(
+        "back",
+        "",
+        &[],
+        "Returns to where the player was before the last teleport",
+    ),
+    spec(
+        "border",
+        "[radius|off]",
+        &[ArgKind::Keyword(&["off"])],
+        "Shows or sets the world border, in chunks from the origin",
+    ),
+    spec(
+        "entity",
+        "orbit [count]",
+        &[ArgKind::Keyword(&["orbit"])],
+        "Spawns blocks circling around the player",
+    ),
+    spec(
+        "entity",
+        "mob [count]",
+        &[ArgKind::Keyword(&["mob"])],
+        "Spawns test mobs on the targeted block that wander around",
+    ),
+    spec(
+        "entity",
+        "clear",
+        &[ArgKind::Keyword(&["clear"])],
+        "Despawns the orbiting blocks and the mobs",
+    ),
+    spec(
+        "record",
+        "start <name>",
+        &[ArgKind::Keyword(&["start"])],
+        "Starts recording the camera path",
+    ),
+    spec(
+        "record",
+        "stop",
+        &[ArgKind::Keyword(&["stop"])],
+        "Saves the recorded camera path",
+    ),
+    spec(
+        "record",
+        "far <name>",
+        &[ArgKind::Keyword(&["far"])],
+        "Saves a flyover 10 million blocks out, to check rendering precision with play",
+    ),
+    spec(
+        "play",
+        "<name>",
+        &[ArgKind::CameraPath],
+        "Moves the camera along a recorded path and saves the frame timings",
+    ),
+    spec(
+        "reload",
+        "assets",
+        &[ArgKind::Keyword(&["assets"])],
+        "Reloads atlas.png and blocks.ron from the assets directory",
+    ),
+    spec(
+        "stats",
+        "chunks [csv]",
+        &[ArgKind::Keyword(&["chunks"]), ArgKind::Keyword(&["csv"])],
+        "Shows the chunk timing percentiles, or saves the histograms as CSV",
+    ),
+    spec(
+        "worldgen",
+        "check",
+        &[ArgKind::Keyword(&["check"])],
+        "Compares the generated terrain with the expected hashes",
+    ),
+    spec(
+        "worldgen",
+        "update",
+        &[ArgKind::Keyword(&["update"])],
+        "Saves the hashes of the current terrain as the expected ones",
+    ),
+];
+
+/// Lists every command with its usage
+pub fn help() -> String {
+    let mut help = "Commands:".to_owned();
+    for command in COMMANDS {
+        let usage = format!("{} {}", command.name, command.usage);
+        help += &format!("\n  {:<22} {}", usage.trim_end(), command.description);
+    }
+    help
+}
+
+/// The candidates for the word under the end of a console line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    /// Where the completed word starts in the line
+    pub start: usize,
+    pub candidates: Vec<String>,
+}
+
+/// Completes the last word of the line, which is the command name or
+/// an argument of a kind its spec declares
+pub fn complete(line: &str) -> Completion {
+    let start = line.rfind(char::is_whitespace).map_or(0, |index| index + 1);
+    let prefix = &line[start..];
+    let mut words = line[..start].split_whitespace();
+    let candidates = match words.next() {
+        None => {
+            let mut names = COMMANDS
+                .iter()
+                .map(|command| command.name.to_owned())
+                .filter(|name| name.starts_with(prefix))
+                .collect::<Vec<_>>();
+            names.dedup();
+            names
+        }
+        Some(name) => {
+            let args = words.collect::<Vec<_>>();
+            let mut candidates = COMMANDS
+                .iter()
+                .filter(|command| command.name == name && command.args.len() > args.len())
+                // The keywords typed so far pick the form of the command
+                .filter(|command| {
+                    args.iter().zip(command.args).all(|(arg, kind)| match kind {
+                        ArgKind::Keyword(words) => words.contains(arg),
+                        _ => true,
+                    })
+                })
+                .flat_map(|command| command.args[args.len()].complete(prefix))
+                .collect::<Vec<_>>();
+            candidates.sort();
+            candidates.dedup();
+            candidates
+        }
+    };
+    Completion { start, candidates }
+}
+
+/// Runs a console command, returning the message to print
+pub fn execute(line: &str, engine: &mut VoxelEngine, scene: &mut Scene) -> Result<String, String> {
+    let mut args = line.split_whitespace();
+    match args.next() {
+        Some("help") => Ok(help()),
+        Some("copy") => {
+            const USAGE: &str = "Usage: copy <x y z> <x y z>";
+            let a = parse_position(&mut args)?.ok_or(USAGE)?;
+            let b = parse_position(&mut args)?.ok_or(USAGE)?;
+            let (min, max) = (Vec3::partial_min(a, b), Vec3::partial_max(a, b));
+            let volume = (max - min + 1).map(|v| v as usize).product();
+            if volume > MAX_COPY_VOLUME {
+                return Err(format!(
+                    "The region has {} blocks, at most {} can be copied",
+                    volume, MAX_COPY_VOLUME
+                ));
+            }
+            let world = engine.renderer().world();
+            let unloaded = structure::unloaded_chunks(world, min, max);
+            if !unloaded.is_empty() {
+                return Err(format!(
+                    "{} chunks of the region aren't loaded, move closer to copy it",
+                    unloaded.len()
+                ));
+            }
+            let copied = Structure::copy(world, min, max);
+            let message = format!(
+                "Copied {}x{}x{} blocks ({} solid)",
+                copied.size.x,
+                copied.size.y,
+                copied.size.z,
+                copied.block_count()
+            );
+            scene.clipboard = Some(copied);
+            Ok(message)
+        }
+        Some("paste") => {
+            let clipboard = scene
+                .clipboard
+                .as_ref()
+                .ok_or("The clipboard is empty, use copy, import or load-structure first")?;
+            let origin = match parse_position(args)? {
+                Some(origin) => origin,
+                None => scene
+                    .target_block(engine.renderer().world())
+                    .and_then(|hit| Some(hit.block_pos + hit.face?.normalized()))
+                    .ok_or("Look at a block or give a position to paste at")?,
+            };
+            let max = origin + clipboard.size.map(|v| v as i32) - 1;
+            let unloaded = structure::unloaded_chunks(engine.renderer().world(), origin, max);
+            if !unloaded.is_empty() {
+                return Err(format!(
+                    "{} chunks under the structure aren't loaded, move closer to paste it",
+                    unloaded.len()
+                ));
+            }
+            let edits = clipboard.paste(
+                engine.renderer_mut().world_mut().chunk_manager_mut(),
+                origin,
+            );
+            let count = edits.len();
+            for edit in edits {
+                scene.history.record(edit);
+            }
+            Ok(format!(
+                "Pasted {} blocks at {} {} {}",
+                count, origin.x, origin.y, origin.z
+            ))
+        }
+        Some("rotate") => {
+            let turns = match args.next() {
+                Some(turns) => turns
+                    .parse::<u32>()
+                    .map_err(|_| format!("Invalid number of turns: {}", turns))?,
+                None => 1,
+            };
+            let clipboard = scene.clipboard.as_mut().ok_or("The clipboard is empty")?;
+            *clipboard = clipboard.rotated(turns);
+            Ok(format!(
+                "Rotated the clipboard by {} degrees",
+                turns % 4 * 90
+            ))
+        }
+        Some("import") => {
+            let file = args.next().ok_or("Usage: import <file>")?;
+            let structure = Structure::load_vox(file, &BlockMapping::default())
+                .map_err(|e| format!("Failed to load {}: {}", file, e))?;
+            let message = format!("Imported {} blocks from {}", structure.block_count(), file);
+            scene.clipboard = Some(structure);
+            Ok(message)
+        }
+        Some("save-structure") => {
+            let name = args.next().ok_or("Usage: save-structure <name>")?;
+            let clipboard = scene.clipboard.as_ref().ok_or("The clipboard is empty")?;
+            clipboard
+                .save(name)
+                .map_err(|e| format!("Failed to save the structure {}: {}", name, e))?;
+            Ok(format!(
+                "Saved the clipboard to {}/{}",
+                STRUCTURES_DIR, name
+            ))
+        }
+        Some("load-structure") => {
+            let name = args.next().ok_or("Usage: load-structure <name>")?;
+            let structure = Structure::load(name)
+                .map_err(|e| format!("Failed to load the structure {}: {}", name, e))?;
+            let message = format!(
+                "Loaded {} blocks from {}/{}",
+                structure.block_count(),
+                STRUCTURES_DIR,
+                name
+            );
+            scene.clipboard = Some(structure);
+            Ok(message)
+        }
+        Some("give") => {
+            const USAGE: &str = "Usage: give <block> [count]";
+            let name = args.next().ok_or(USAGE)?;
+            let block = registry()
+                .by_name(name)
+                .filter(|block| !block.is_air())
+                .ok_or_else(|| format!("Unknown block: {}", name))?;
+            let count = match args.next() {
+                Some(count) => count
+                    .parse::<u32>()
+                    .map_err(|_| format!("Invalid count: {}", count))?,
+                None => 1,
+            };
+            scene.hotbar.pick(block);
+            match scene.inventory.mode {
+                GameMode::Creative => Ok(format!("Selected {}", name)),
+                GameMode::Survival => {
+                    scene.inventory.give(block, count);
+                    Ok(format!("Gave {} {}", count, name))
+                }
+            }
+        }
+        Some("tp") => {
+            let feet = parse_position(args)?.ok_or("Usage: tp <x> <y> <z>")?;
+            let feet = scene.teleport(feet);
+            engine.renderer_mut().stream_chunks(scene.camera_pos());
+            Ok(format!(
+                "Teleported to {:.1} {:.1} {:.1}",
+                feet.x, feet.y, feet.z
+            ))
+        }
+        Some("spawn") => {
+            scene.teleport(scene.spawn);
+            engine.renderer_mut().stream_chunks(scene.camera_pos());
+            Ok("Teleported to the world spawn".to_owned())
+        }
+        Some("spawnpoint") => match args.next() {
+            Some("set") => {
+                let spawn = match parse_position(args)? {
+                    Some(spawn) => spawn,
+                    None => scene.player_feet(),
+                };
+                scene.spawn = spawn;
+                let world = engine.renderer().world();
+                world.chunk_manager().saver().queue_meta(scene.world_meta());
+                Ok(format!(
+                    "Moved the world spawn to {:.1} {:.1} {:.1}",
+                    spawn.x, spawn.y, spawn.z
+                ))
+            }
+            _ => Err("Usage: spawnpoint set [x y z]".to_owned()),
+        },
+        Some("back") => {
+            let feet = scene.teleport_back().ok_or("No teleport to go back from")?;
+            engine.renderer_mut().stream_chunks(scene.camera_pos());
+            Ok(format!(
+                "Went back to {:.1} {:.1} {:.1}",
+                feet.x, feet.y, feet.z
+            ))
+        }
+        Some("border") => {
+            match args.next() {
+                None => {
+                    return Ok(match engine.settings.world_border().radius() {
+                        Some(radius) => format!("The world border is {} chunks away", radius),
+                        None => "There is no world border".to_owned(),
+                    })
+                }
+                Some("off") => engine.settings.world_border = 0,
+                Some(radius) => {
+                    engine.settings.world_border = radius
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|radius| *radius > 0)
+                        .ok_or_else(|| format!("Invalid radius: {}", radius))?;
+                }
+            }
+            engine.settings.save();
+            // Unloads the chunks outside and pushes the player back in now
+            // rather than on the next frame
+            scene.apply_settings(&engine.settings);
+            let settings = engine.settings.clone();
+            engine.renderer_mut().apply_settings(&settings);
+            engine.renderer_mut().stream_chunks(scene.camera_pos());
+            match settings.world_border().radius() {
+                Some(radius) => Ok(format!("Moved the world border to {} chunks", radius)),
+                None => Ok("Removed the world border".to_owned()),
+            }
+        }
+        Some("entity") => match (args.next(), args.next()) {
+            (Some("orbit"), count) => {
+                let count = match count {
+                    Some(count) => count
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid count: {}", count))?,
+                    None => 1,
+                };
+                let center = scene.camera_pos();
+                let block = scene.hotbar.selected_block();
+                let block = if block.is_air() {
+                    BlockId::STONE
+                } else {
+                    block
+                };
+                // Each on its own circle so they don't overlap
+                for i in 0..count {
+                    scene.spawn_orbiting_block(center, 3.0 + i as f32 * 1.5, block);
+                }
+                Ok(format!("Spawned {} orbiting blocks", count))
+            }
+            (Some("mob"), count) => {
+                let count = match count {
+                    Some(count) => count
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid count: {}", count))?,
+                    None => 1,
+                };
+                let hit = scene
+                    .target_block(engine.renderer().world())
+                    .ok_or("Look at a block to spawn the mobs on")?;
+                // Blocks are centered on integer coordinates
+                let feet = hit.block_pos.map(|v| v as f32) + Vec3::unit_y() * 0.5;
+                for _ in 0..count {
+                    scene.spawn_mob(feet);
+                }
+                Ok(format!("Spawned {} mobs", count))
+            }
+            (Some("clear"), _) => Ok(format!(
+                "Despawned {} orbiting blocks and {} mobs",
+                scene.despawn_orbiting_blocks(),
+                scene.despawn_mobs()
+            )),
+            _ => Err("Usage: entity orbit [count] | entity mob [count] | entity clear".to_owned()),
+        },
+        Some("record") => match (args.next(), args.next()) {
+            (Some("start"), Some(name)) => {
+                if !scene.camera_path.is_idle() {
+                    return Err("A camera path is already being recorded or played".to_owned());
+                }
+                scene.camera_path = CameraPathMode::record(name, scene.camera());
+                Ok(format!("Recording the camera path {}", name))
+            }
+            (Some("stop"), _) => scene.stop_camera_path(),
+            (Some("far"), Some(name)) => {
+                CameraPath::far_flyover()
+                    .save(name)
+                    .map_err(|e| format!("Failed to save {}: {}", name, e))?;
+                Ok(format!(
+                    "Saved a flyover at x = {} as {}, play it with `play {}`",
+                    FAR_FLYOVER_X, name, name
+                ))
+            }
+            _ => Err("Usage: record start <name> | record stop | record far <name>".to_owned()),
+        },
+        Some("play") => {
+            let name = args.next().ok_or("Usage: play <name>")?;
+            if !scene.camera_path.is_idle() {
+                return Err("A camera path is already being recorded or played".to_owned());
+            }
+            scene.play_camera_path(name)?;
+            Ok(format!("Playing the camera path {}", name))
+        }
+        Some("stats") => match (args.next(), args.next()) {
+            (Some("chunks"), None) => {
+                Ok(engine.renderer().world().chunk_manager().stats().summary())
+            }
+            (Some("chunks"), Some("csv")) => {
+                let stats = engine.renderer().world().chunk_manager().stats();
+                stats
+                    .save_csv()
+                    .map_err(|e| format!("Failed to save the chunk stats: {}", e))?;
+                Ok(format!("Saved the chunk stats to {}", CSV_PATH))
+            }
+            _ => Err("Usage: stats chunks [csv]".to_owned()),
+        },
+        Some("reload") => match args.next() {
+            Some("assets") => engine.reload_assets(),
+            _ => Err("Usage: reload assets".to_owned()),
+        },
+        Some("worldgen") => match args.next() {
+            Some("check") => match gen_hashes::check() {
+                Ok(()) => Ok(format!(
+                    "The {} sample chunks match their expected hashes",
+                    gen_hashes::SAMPLE_CHUNKS.len()
+                )),
+                Err(mismatches) => Err(mismatches.join("\n")),
+            },
+            Some("update") => {
+                gen_hashes::update()
+                    .map_err(|e| format!("Failed to save {}: {}", HASHES_PATH, e))?;
+                Ok(format!(
+                    "Saved the terrain hashes to {}, rebuild to check against them",
+                    HASHES_PATH
+                ))
+            }
+            _ => Err("Usage: worldgen <check|update>".to_owned()),
+        },
+        Some("debug") => match (args.next(), args.next()) {
+            (Some("ray"), Some(state @ ("on" | "off"))) => {
+                scene
+                    .world
+                    .resource_mut::<RayDiagnostics>()
+                    .set_enabled(state == "on");
+                Ok(format!("Raycast debugging {}", state))
+            }
+            _ => Err("Usage: debug ray <on|off>".to_owned()),
+        },
+        Some(command) => Err(format!("Unknown command: {}", command)),
+        None => Ok(String::new()),
+    }
+}
+
+/// Parses three coordinates, if any are given
+fn parse_position<'a, T: FromStr>(
+    mut args: impl Iterator<Item = &'a str>,
+) -> Result<Option<Vec3<T>>, String> {
+    let coords = [args.next(), args.next(), args.next()];
+    match coords {
+        [None, None, None] => Ok(None),
+        [Some(x), Some(y), Some(z)] => {
+            let parse = |v: &str| {
+                v.parse::<T>()
+                    .map_err(|_| format!("Invalid coordinate: {}", v))
+            };
+            Ok(Some(Vec3::new(parse(x)?, parse(y)?, parse(z)?)))
+        }
+        _ => Err("Expected x y z coordinates".to_owned()),
+    }
+}
+
+/// The names of the files with the extension in the directory, without it
+fn saved_names(dir: &str, extension: &str) -> Vec<String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != extension {
+                return None;
+            }
+            Some(path.file_stem()?.to_str()?.to_owned())
+        })
+        .collect()
+}
@@ -1,6 +1,7 @@
 use vek::Vec3;
 
-#[derive(Debug, Clone, Copy)]
+/// A face of a block, along one of the world axes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Direction {
     Up,
     Down,
@@ -11,14 +12,24 @@ pub enum Direction {
 }
 
 impl Direction {
+    /// Every direction in the order of the variants, which is the order
+    /// chunk meshes emit their faces in and the index of `usize::from`
     pub const ALL: [Direction; 6] = [
         Direction::Up,
         Direction::Down,
         Direction::Left,
         Direction::Right,
+        Direction::Back,
         Direction::Front,
+    ];
+    /// The directions along the ground, in the order of `ALL`
+    pub const HORIZONTAL: [Direction; 4] = [
+        Direction::Left,
+        Direction::Right,
         Direction::Back,
+        Direction::Front,
     ];
+
     pub fn normalized(self) -> Vec3<i32> {
         match self {
             Direction::Up => Vec3::unit_y(),
@@ -29,4 +40,135 @@ impl Direction {
             Direction::Back => -Vec3::unit_z(),
         }
     }
+
+    /// The direction of a unit vector along an axis, `None` for any other vector
+    pub fn from_normal(normal: Vec3<i32>) -> Option<Self> {
+        Self::ALL.into_iter().find(|dir| dir.normalized() == normal)
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::Back => Direction::Front,
+            Direction::Front => Direction::Back,
+        }
+    }
+
+    /// The index of the axis, 0 for x, 1 for y and 2 for z
+    pub fn axis(self) -> usize {
+        match self {
+            Direction::Left | Direction::Right => 0,
+            Direction::Up | Direction::Down => 1,
+            Direction::Back | Direction::Front => 2,
+        }
+    }
+
+    /// Whether the direction points towards increasing coordinates
+    pub fn is_positive(self) -> bool {
+        matches!(self, Direction::Up | Direction::Right | Direction::Front)
+    }
+
+    /// The two positive directions along the face, in the order of their axes.
+    /// Greedy meshing grows quads along them.
+    pub fn perpendiculars(self) -> [Direction; 2] {
+        match self.axis() {
+            0 => [Direction::Up, Direction::Front],
+            1 => [Direction::Right, Direction::Front],
+            _ => [Direction::Right, Direction::Up],
+        }
+    }
+}
+
+impl From<Direction> for usize {
+    fn from(dir: Direction) -> Self {
+        dir as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mappings_of_every_direction() {
+        // Direction, normal, opposite, axis, positive, perpendiculars
+        let table = [
+            (
+                Direction::Up,
+                Vec3::new(0, 1, 0),
+                Direction::Down,
+                1,
+                true,
+                [Direction::Right, Direction::Front],
+            ),
+            (
+                Direction::Down,
+                Vec3::new(0, -1, 0),
+                Direction::Up,
+                1,
+                false,
+                [Direction::Right, Direction::Front],
+            ),
+            (
+                Direction::Left,
+                Vec3::new(-1, 0, 0),
+                Direction::Right,
+                0,
+                false,
+                [Direction::Up, Direction::Front],
+            ),
+            (
+                Direction::Right,
+                Vec3::new(1, 0, 0),
+                Direction::Left,
+                0,
+                true,
+                [Direction::Up, Direction::Front],
+            ),
+            (
+                Direction::Back,
+                Vec3::new(0, 0, -1),
+                Direction::Front,
+                2,
+                false,
+                [Direction::Right, Direction::Up],
+            ),
+            (
+                Direction::Front,
+                Vec3::new(0, 0, 1),
+                Direction::Back,
+                2,
+                true,
+                [Direction::Right, Direction::Up],
+            ),
+        ];
+        for (index, (dir, normal, opposite, axis, positive, perpendiculars)) in
+            table.into_iter().enumerate()
+        {
+            assert_eq!(Direction::ALL[index], dir);
+            assert_eq!(usize::from(dir), index);
+            assert_eq!(dir.normalized(), normal);
+            assert_eq!(Direction::from_normal(normal), Some(dir));
+            assert_eq!(dir.opposite(), opposite);
+            assert_eq!(opposite.opposite(), dir);
+            assert_eq!(Direction::from_normal(-normal), Some(opposite));
+            assert_eq!(dir.axis(), axis);
+            assert_eq!(normal[axis].abs(), 1);
+            assert_eq!(dir.is_positive(), positive);
+            assert_eq!(dir.perpendiculars(), perpendiculars);
+            for perpendicular in perpendiculars {
+                assert!(perpendicular.is_positive());
+                assert_ne!(perpendicular.axis(), axis);
+            }
+        }
+        assert_eq!(Direction::from_normal(Vec3::zero()), None);
+        assert_eq!(Direction::from_normal(Vec3::new(1, 1, 0)), None);
+        assert_eq!(Direction::from_normal(Vec3::new(0, 2, 0)), None);
+        assert!(Direction::HORIZONTAL
+            .iter()
+            .all(|dir| dir.normalized().y == 0));
+    }
 }
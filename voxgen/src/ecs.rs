@@ -1,19 +1,356 @@
-use bevy_ecs::{prelude::Component, system::Query};
-use vek::{Vec3, Vec2};
+use bevy_ecs::{
+    prelude::{Component, Entity, IntoSystemConfigs, Query, Res, ResMut, Resource, With},
+    schedule::Schedule,
+};
+use vek::{Mat4, Quaternion, Vec3};
 
-#[derive(Component)]
-pub struct RigidBody;
+use crate::{
+    block::BlockId,
+    scene::{
+        camera::{self, Camera, CameraController, MovementMode, SPRINT_FOV_SCALE, TRANSITION_TIME},
+        diagnostics::RayDiagnostics,
+        physics::{CollisionMap, PlayerBody, CROUCH_EYE_HEIGHT, EYE_HEIGHT, PLAYER_WIDTH},
+    },
+    world::border::WorldBorder,
+};
+
+/// Length of a full day, in seconds
+pub const DAY_LENGTH: f32 = 1200.0;
 
+/// Marks the entity controlled by the local player
 #[derive(Component)]
-pub struct BoundingBox {
-    pub size: Vec2<i32>
+pub struct Player;
+
+/// Position of an entity in the world. For the player this is the eye position.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Transform {
+    pub pos: Vec3<f32>,
+    pub rotation: Quaternion<f32>,
+    /// Size of the mesh relative to its model
+    pub scale: f32,
+}
+
+impl Transform {
+    pub fn at(pos: Vec3<f32>) -> Self {
+        Self {
+            pos,
+            rotation: Quaternion::identity(),
+            scale: 1.0,
+        }
+    }
+
+    /// The transform `alpha` of the way from `from` to `self`
+    pub fn interpolate(&self, from: &Transform, alpha: f32) -> Self {
+        Self {
+            pos: Vec3::lerp(from.pos, self.pos, alpha),
+            rotation: Quaternion::slerp(from.rotation, self.rotation, alpha),
+            scale: from.scale + (self.scale - from.scale) * alpha,
+        }
+    }
+
+    pub fn model(&self) -> Mat4<f32> {
+        Mat4::<f32>::translation_3d(self.pos)
+            * Mat4::from(self.rotation)
+            * Mat4::scaling_3d(Vec3::broadcast(self.scale))
+    }
+}
+
+/// The transform of an entity at the previous tick, rendered frames
+/// fall in between the two
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PreviousTransform(pub Transform);
+
+/// The mesh an entity is drawn with, built by the renderer the first
+/// time an entity uses it
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeshHandle {
+    /// A unit cube with the textures of the block
+    Block(BlockId),
 }
 
+/// Moves an entity in a circle around a point, turning it along the way
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Orbit {
+    pub center: Vec3<f32>,
+    pub radius: f32,
+    /// Radians per second, counterclockwise seen from above
+    pub speed: f32,
+    /// Current angle around the center, in radians
+    pub angle: f32,
+}
+
+/// Keeps a camera at the position of another entity
 #[derive(Component)]
-pub struct Transform {
-    pub pos: Vec3<i32>,
+pub struct Follow(pub Entity);
+
+/// Seconds simulated by the current update
+#[derive(Resource, Default)]
+pub struct DeltaTime(pub f32);
+
+/// Progress through the current day, from 0 at midnight to 1
+#[derive(Resource)]
+pub struct TimeOfDay(pub f32);
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        // Start in the morning
+        Self(0.25)
+    }
 }
 
-fn update_physics(q: Query<(&RigidBody, &BoundingBox, &Transform)>, dt: f32) {
-    for (rbody, collider, transform) in q.iter() {}
+/// The systems run on every tick, in order. They read the
+/// [`DeltaTime`] and [`CollisionMap`] of the tick.
+pub fn schedule() -> Schedule {
+    let mut schedule = Schedule::new();
+    schedule.add_systems(
+        (
+            store_previous_transforms,
+            apply_input,
+            apply_physics,
+            orbit,
+            follow_camera,
+            advance_time,
+        )
+            .chain(),
+    );
+    schedule
+}
+
+/// How bright the sky is at the given time of day, from the
+/// moonlight at midnight to 1 at noon
+pub fn daylight(time_of_day: f32) -> f32 {
+    const MOONLIGHT: f32 = 0.15;
+    let sun = 0.5 - 0.5 * (time_of_day * std::f32::consts::TAU).cos();
+    MOONLIGHT + (1.0 - MOONLIGHT) * sun
+}
+
+/// Turns the movement inputs of the player into a velocity
+pub fn apply_input(
+    dt: Res<DeltaTime>,
+    mut players: Query<
+        (
+            Entity,
+            &mut CameraController,
+            &mut PlayerBody,
+            &mut Transform,
+        ),
+        With<Player>,
+    >,
+    mut cameras: Query<(&mut Camera, &Follow)>,
+) {
+    let dt = dt.0;
+    for (entity, controller, mut body, mut transform) in players.iter_mut() {
+        let mut camera = match cameras.iter_mut().find(|(_, follow)| follow.0 == entity) {
+            Some((camera, _)) => camera,
+            None => continue,
+        };
+        let target_fov_scale = if controller.is_sprinting() {
+            SPRINT_FOV_SCALE
+        } else {
+            1.0
+        };
+        camera.fov_scale = camera::approach(
+            camera.fov_scale,
+            target_fov_scale,
+            (SPRINT_FOV_SCALE - 1.0) * dt / TRANSITION_TIME,
+        );
+
+        let target_eye_height = if controller.is_crouching() {
+            CROUCH_EYE_HEIGHT
+        } else {
+            EYE_HEIGHT
+        };
+        // Keep the feet in place while the eyes move
+        let eye_height = camera::approach(
+            body.eye_height,
+            target_eye_height,
+            (EYE_HEIGHT - CROUCH_EYE_HEIGHT) * dt / TRANSITION_TIME,
+        );
+        if controller.mode == MovementMode::Walk {
+            transform.pos.y += eye_height - body.eye_height;
+        }
+        body.eye_height = eye_height;
+
+        let velocity = controller.velocity(&camera);
+        match controller.mode {
+            MovementMode::Fly => body.velocity = velocity,
+            MovementMode::Pan => {
+                camera.pan(controller.pan_direction(), dt);
+                body.velocity = Vec3::zero();
+            }
+            MovementMode::Walk => {
+                body.velocity.x = velocity.x;
+                body.velocity.z = velocity.z;
+                if controller.wants_jump() {
+                    body.jump();
+                }
+            }
+        }
+    }
+}
+
+/// Moves the player by its velocity, colliding with the world while walking.
+/// The world border stops the player in every mode.
+pub fn apply_physics(
+    dt: Res<DeltaTime>,
+    collisions: Res<CollisionMap>,
+    border: Res<WorldBorder>,
+    mut diagnostics: ResMut<RayDiagnostics>,
+    mut bodies: Query<(&CameraController, &mut PlayerBody, &mut Transform)>,
+) {
+    let diagnostics = &mut *diagnostics;
+    for (controller, mut body, mut transform) in bodies.iter_mut() {
+        // Nothing to stand on until the chunk is generated, after a teleport
+        if !collisions.chunk_loaded {
+            body.velocity = Vec3::zero();
+            continue;
+        }
+        match controller.mode {
+            MovementMode::Pan => (),
+            MovementMode::Fly => {
+                transform.pos += body.velocity * dt.0;
+                // Flying stops as soon as the keys are released
+                body.velocity = Vec3::zero();
+            }
+            MovementMode::Walk => {
+                let considered = diagnostics.enabled().then(|| {
+                    diagnostics.considered.clear();
+                    &mut diagnostics.considered
+                });
+                transform.pos = body.step(
+                    transform.pos,
+                    dt.0,
+                    controller.is_crouching(),
+                    &collisions,
+                    considered,
+                );
+            }
+        }
+        transform.pos = border.clamp(transform.pos, PLAYER_WIDTH / 2.0);
+        if diagnostics.enabled() {
+            // Overlapping sweeps test the same blocks
+            let considered = &mut diagnostics.considered;
+            considered.sort_unstable_by_key(|pos| (pos.x, pos.y, pos.z));
+            considered.dedup();
+            diagnostics.player = Some(body.aabb(transform.pos));
+        }
+    }
+}
+
+/// Remembers where entities were before they move this tick
+pub fn store_previous_transforms(mut entities: Query<(&Transform, &mut PreviousTransform)>) {
+    for (transform, mut previous) in entities.iter_mut() {
+        previous.0 = *transform;
+    }
+}
+
+pub fn orbit(dt: Res<DeltaTime>, mut entities: Query<(&mut Orbit, &mut Transform)>) {
+    for (mut orbit, mut transform) in entities.iter_mut() {
+        orbit.angle = (orbit.angle + orbit.speed * dt.0) % std::f32::consts::TAU;
+        let (sin, cos) = orbit.angle.sin_cos();
+        transform.pos = orbit.center + Vec3::new(cos, 0.0, sin) * orbit.radius;
+        transform.rotation = Quaternion::rotation_y(-orbit.angle);
+    }
+}
+
+pub fn follow_camera(targets: Query<&Transform>, mut cameras: Query<(&mut Camera, &Follow)>) {
+    for (mut camera, follow) in cameras.iter_mut() {
+        if let Ok(target) = targets.get(follow.0) {
+            camera.move_to(target.pos.map(|v| v as f64));
+        }
+    }
+}
+
+pub fn advance_time(dt: Res<DeltaTime>, mut time: ResMut<TimeOfDay>) {
+    time.0 = (time.0 + dt.0 / DAY_LENGTH).fract();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::Action, scene::physics::Aabb};
+    use bevy_ecs::prelude::World;
+
+    const STEP: f32 = 1.0 / 60.0;
+    const STEPS: usize = 120;
+    /// Eyes of a player standing on the floor, whose top is at y = 0.5
+    const START: Vec3<f32> = Vec3::new(0.0, 0.5 + EYE_HEIGHT, 0.0);
+
+    /// A player walking forward along +X on a flat floor, with a camera
+    /// following it, and no renderer or loaded chunks behind them
+    fn walking_world() -> (World, Entity, Entity) {
+        let mut world = World::new();
+        world.insert_resource(DeltaTime(STEP));
+        world.init_resource::<TimeOfDay>();
+        world.init_resource::<RayDiagnostics>();
+        world.init_resource::<WorldBorder>();
+        let area = Aabb::new(Vec3::new(-8.0, -2.0, -8.0), Vec3::new(32.0, 4.0, 8.0));
+        world.insert_resource(CollisionMap::from_fn(area, true, |pos| pos.y <= 0));
+
+        let mut controller = CameraController::new();
+        controller.handle_action(Action::MoveForward, true);
+        let player = world
+            .spawn((
+                Player,
+                Transform::at(START),
+                PreviousTransform(Transform::at(START)),
+                PlayerBody::new(),
+                controller,
+            ))
+            .id();
+        let camera = world.spawn((Camera::new(16.0, 9.0), Follow(player))).id();
+        (world, player, camera)
+    }
+
+    /// Runs the schedule for `STEPS` ticks, returning the eye positions
+    fn walk() -> (World, Entity, Entity, Vec<Vec3<f32>>) {
+        let (mut world, player, camera) = walking_world();
+        let mut schedule = schedule();
+        let path = (0..STEPS)
+            .map(|_| {
+                schedule.run(&mut world);
+                world.get::<Transform>(player).unwrap().pos
+            })
+            .collect();
+        (world, player, camera, path)
+    }
+
+    #[test]
+    fn player_walks_forward_on_the_floor() {
+        let (world, player, camera, path) = walk();
+        let speed = CameraController::new().speed;
+        let end = path[STEPS - 1];
+        assert!((end.x - speed * STEP * STEPS as f32).abs() < 1e-3);
+        assert_eq!(end.y, START.y);
+        assert_eq!(end.z, START.z);
+        assert!(path.windows(2).all(|pair| pair[1].x > pair[0].x));
+
+        let body = world.get::<PlayerBody>(player).unwrap();
+        assert!(body.on_ground);
+        let previous = world.get::<PreviousTransform>(player).unwrap();
+        assert_eq!(previous.0.pos, path[STEPS - 2]);
+        let camera = world.get::<Camera>(camera).unwrap();
+        assert_eq!(camera.pos, end.map(|v| v as f64));
+        let time = world.resource::<TimeOfDay>().0;
+        assert!((time - (0.25 + STEP * STEPS as f32 / DAY_LENGTH)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn steps_are_deterministic() {
+        let (_, _, _, first) = walk();
+        let (_, _, _, second) = walk();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn nothing_falls_until_the_chunk_is_loaded() {
+        let (mut world, player, _) = walking_world();
+        let area = Aabb::new(Vec3::broadcast(-8.0), Vec3::broadcast(8.0));
+        world.insert_resource(CollisionMap::from_fn(area, false, |_| false));
+        let mut schedule = schedule();
+        for _ in 0..STEPS {
+            schedule.run(&mut world);
+        }
+        assert_eq!(world.get::<Transform>(player).unwrap().pos.y, START.y);
+    }
 }
@@ -1,23 +1,233 @@
-use crate::{renderer::Renderer, window::Window};
+use std::time::{Duration, Instant};
+
+use vek::Vec3;
+
+use crate::{
+    audio::AudioSystem,
+    frame_limiter::FrameLimiter,
+    input::{Action, Bindings},
+    renderer::Renderer,
+    scene::Scene,
+    settings::Settings,
+    ui::PauseScreen,
+    window::Window,
+    world::{chunk::ChunkPos, saver::SHUTDOWN_TIMEOUT},
+};
+
+/// How often the world is saved while playing
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// Time simulated by a single step, 60 steps per second
+pub const TICK_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// Longest frame the simulation catches up with. Anything beyond is dropped
+/// so that a hitch doesn't cause more and more steps to run every frame.
+pub const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+/// Chunks on each side of the player's that are loaded before the game starts
+const STARTUP_RADIUS: i32 = 1;
 
 pub struct VoxelEngine {
-    pub(super) renderer: Renderer,
+    pub renderer: Renderer,
     pub window: Window,
     pub locked_input: bool,
+    /// Stops the scene from updating while the pause menu is open
+    pub paused: bool,
+    /// Shows the loading screen instead of playing until the chunks
+    /// around the player are loaded
+    pub loading: bool,
+    pub bindings: Bindings,
+    pub settings: Settings,
+    pub audio: AudioSystem,
+    pub frame_limiter: FrameLimiter,
+    pub last_save: Instant,
+    /// Frame time not yet consumed by simulation steps
+    accumulator: Duration,
 }
 
 impl VoxelEngine {
-    pub fn on_key_pressed(&mut self, input: Option<winit::event::VirtualKeyCode>) {
-        if let Some(key) = input {
-            match key {
-                winit::event::VirtualKeyCode::Escape => {
-                    self.locked_input = !self.locked_input;
-                    self.window.grab_cursor(!self.locked_input)
+    pub fn new(window: Window, renderer: Renderer, settings: Settings) -> Self {
+        Self {
+            renderer,
+            window,
+            locked_input: true,
+            paused: false,
+            loading: true,
+            bindings: Bindings::load(),
+            settings,
+            audio: AudioSystem::new(),
+            frame_limiter: FrameLimiter::new(),
+            last_save: Instant::now(),
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// Runs as many fixed simulation steps as fit in the elapsed frame time
+    /// and prepares the renderer for the frame.
+    pub fn update(&mut self, scene: &mut Scene, frame_time: Duration) {
+        let mut steps = 0;
+        if self.paused || self.loading {
+            self.accumulator = Duration::ZERO;
+        } else {
+            self.accumulator += frame_time.min(MAX_FRAME_TIME);
+            while self.accumulator >= TICK_DURATION {
+                scene.tick(TICK_DURATION, self.renderer.world_mut());
+                self.accumulator -= TICK_DURATION;
+                steps += 1;
+            }
+        }
+        scene.look();
+        scene.update_breaking(
+            self.renderer.world_mut(),
+            frame_time.as_secs_f32(),
+            !self.locked_input,
+        );
+        scene.update_camera_path(frame_time);
+        scene.draw_diagnostics(self.renderer.world());
+        scene.interpolation = self.accumulator.as_secs_f32() / TICK_DURATION.as_secs_f32();
+        scene.update_view(self.renderer.world());
+        scene.draw_mobs();
+        scene.update_medium(self.renderer.world());
+        self.renderer.update(scene);
+        if self.loading {
+            self.update_loading(scene);
+        }
+        scene.update_falling_blocks(self.renderer.world_mut());
+        scene.update_neighbors(self.renderer.world_mut());
+        scene.update_water(self.renderer.world_mut());
+        scene.update_mobs(self.renderer.world());
+        self.audio.update(
+            scene,
+            self.renderer.world().events(),
+            &self.settings,
+            self.paused,
+        );
+
+        let overlay = &mut self.renderer.gui.state.overlay;
+        if overlay.visible {
+            overlay.push("Performance", format!("Simulation steps: {}", steps));
+            overlay.push("Performance", self.frame_limiter.status());
+        }
+    }
+
+    /// Starts the game once the chunks around the player, including
+    /// the one under their feet, are loaded
+    fn update_loading(&mut self, scene: &Scene) {
+        let chunks = self.renderer.world().chunk_manager();
+        let progress =
+            chunks.area_progress(ChunkPos::from_world(scene.camera_pos()), STARTUP_RADIUS);
+        let ground = scene.player_feet() - Vec3::unit_y();
+        let ground_loaded = chunks.chunk(ChunkPos::from_world(ground)).is_some();
+        if progress.0 == progress.1 && ground_loaded {
+            self.loading = false;
+            self.locked_input = false;
+            self.renderer.gui.state.loading = None;
+        } else {
+            self.renderer.gui.state.loading = Some(progress);
+        }
+    }
+
+    pub fn on_action_pressed(&mut self, action: Action) {
+        // Only quitting, by closing the window, works while loading
+        if self.loading {
+            return;
+        }
+        match action {
+            Action::ToggleMenu => {
+                if self.paused {
+                    self.resume();
+                } else if self.renderer.gui.state.inventory.open {
+                    self.toggle_inventory();
+                } else {
+                    self.pause();
                 }
-                winit::event::VirtualKeyCode::F12 => self.renderer.toggle_wireframe(),
-                _ => (),
+            }
+            Action::ToggleInventory if !self.paused => self.toggle_inventory(),
+            Action::ToggleHud => {
+                let state = &mut self.renderer.gui.state;
+                state.hud_visible = !state.hud_visible;
+            }
+            Action::ToggleDebugOverlay => {
+                let overlay = &mut self.renderer.gui.state.overlay;
+                overlay.visible = !overlay.visible;
+            }
+            Action::ToggleWireframe => self.renderer.toggle_wireframe(),
+            // Applied to the window by the next frame
+            Action::ToggleFullscreen => {
+                self.settings.fullscreen = !self.settings.fullscreen;
+                self.settings.save();
+            }
+            Action::CompareFiltering => self.renderer.set_comparing_filtering(true),
+            Action::ReloadAssets => {
+                let (message, level) = match self.reload_assets() {
+                    Ok(message) => (message, log::Level::Info),
+                    Err(error) => (error, log::Level::Error),
+                };
+                self.renderer.gui.state.hud.post(message, level);
+            }
+            _ => (),
+        }
+    }
+
+    /// Ends the actions that last while their input is held
+    pub fn on_action_released(&mut self, action: Action) {
+        if action == Action::CompareFiltering {
+            self.renderer.set_comparing_filtering(false);
+        }
+    }
+
+    /// Opens the pause menu and releases the cursor
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.locked_input = true;
+        self.renderer.gui.state.pause_menu = Some(PauseScreen::Main);
+        // Monitors can be plugged in or out while playing
+        self.renderer.gui.state.monitors = self.window.monitors();
+        self.window.grab_cursor(false);
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.locked_input = false;
+        self.renderer.gui.state.pause_menu = None;
+        self.window.grab_cursor(true);
+    }
+
+    /// Opens or closes the inventory window. The game keeps running
+    /// while the cursor is released to drag blocks around.
+    fn toggle_inventory(&mut self) {
+        let inventory = &mut self.renderer.gui.state.inventory;
+        inventory.open = !inventory.open;
+        self.locked_input = inventory.open;
+        self.window.grab_cursor(!inventory.open);
+    }
+
+    /// Saves the edited chunks, the player and the spawn on the saving
+    /// thread. Without `background` this waits for every queued save to be
+    /// written, giving up after `SHUTDOWN_TIMEOUT`.
+    pub fn save_world(&mut self, scene: &Scene, background: bool) {
+        let hud = self.renderer.gui.state.hud.sender();
+        let world = self.renderer.world_mut();
+        world.save();
+        let saver = world.chunk_manager().saver();
+        saver.queue_meta(scene.world_meta());
+        if background {
+            saver.queue_notification(hud, "Saved the world");
+        } else {
+            if let Err(left) = saver.flush(SHUTDOWN_TIMEOUT) {
+                log::error!(
+                    "Gave up saving after {:?}, {} saves were not written",
+                    SHUTDOWN_TIMEOUT,
+                    left
+                );
             }
         }
+        self.last_save = Instant::now();
+    }
+
+    /// Reloads the block atlas and registry, keeping the old ones when
+    /// either is invalid
+    pub fn reload_assets(&mut self) -> Result<String, String> {
+        self.renderer
+            .reload_assets()
+            .map_err(|e| format!("Failed to reload the assets: {}", e))
     }
 
     pub fn renderer(&self) -> &Renderer {
@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+use winit::event_loop::ControlFlow;
+
+/// Frame rate while the window is unfocused or minimized
+pub const BACKGROUND_FPS: u32 = 10;
+/// Time left in the frame budget below which the limiter spins instead of
+/// sleeping, as sleeps can overshoot by about a scheduler tick
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+/// Keeps frames from starting more often than the frame rate cap allows
+pub struct FrameLimiter {
+    /// When the last frame started
+    frame_start: Instant,
+    /// Most frames per second in the foreground, `None` when uncapped
+    cap: Option<u32>,
+    /// Whether the window is unfocused or minimized
+    background: bool,
+}
+
+impl FrameLimiter {
+    pub fn new() -> Self {
+        Self {
+            frame_start: Instant::now(),
+            cap: None,
+            background: false,
+        }
+    }
+
+    /// Sets the cap, 0 for none, and whether the window is in the background
+    pub fn configure(&mut self, cap: u32, background: bool) {
+        self.cap = (cap > 0).then_some(cap);
+        self.background = background;
+    }
+
+    /// The frame rate frames are limited to, if any
+    pub fn fps(&self) -> Option<u32> {
+        match (self.cap, self.background) {
+            (Some(cap), true) => Some(cap.min(BACKGROUND_FPS)),
+            (None, true) => Some(BACKGROUND_FPS),
+            (cap, false) => cap,
+        }
+    }
+
+    /// When the next frame can start
+    fn next_frame(&self) -> Option<Instant> {
+        self.fps()
+            .map(|fps| self.frame_start + Duration::from_secs_f64(1.0 / fps as f64))
+    }
+
+    /// Whether enough time passed since the last frame to start a new one
+    pub fn is_due(&self) -> bool {
+        self.next_frame()
+            .is_none_or(|next_frame| Instant::now() >= next_frame)
+    }
+
+    pub fn start_frame(&mut self) {
+        self.frame_start = Instant::now();
+    }
+
+    /// Waits out the rest of the frame budget in the foreground. Most of it is
+    /// slept and the end is spun to start the next frame on time. In the
+    /// background the event loop waits instead, see `control_flow`.
+    pub fn wait(&self) {
+        let next_frame = match self.next_frame() {
+            Some(next_frame) if !self.background => next_frame,
+            _ => return,
+        };
+        loop {
+            let left = next_frame.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            if left > SPIN_THRESHOLD {
+                std::thread::sleep(left - SPIN_THRESHOLD);
+            } else {
+                std::hint::spin_loop();
+            }
+        }
+    }
+
+    /// How the event loop waits for the next frame. In the background it
+    /// sleeps until then, still waking up for window events.
+    pub fn control_flow(&self) -> ControlFlow {
+        match self.next_frame() {
+            Some(next_frame) if self.background => ControlFlow::WaitUntil(next_frame),
+            _ => ControlFlow::Poll,
+        }
+    }
+
+    /// Describes the current cap for the debug overlay
+    pub fn status(&self) -> String {
+        match (self.fps(), self.background) {
+            (Some(fps), true) => format!("Frame cap: {} FPS in the background", fps),
+            (Some(fps), false) => format!(
+                "Frame cap: {} FPS ({:.2} ms budget)",
+                fps,
+                1000.0 / fps as f32
+            ),
+            (None, _) => "Frame cap: none".to_owned(),
+        }
+    }
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
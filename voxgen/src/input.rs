@@ -0,0 +1,407 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+use winit::event::{ElementState, MouseButton, VirtualKeyCode, WindowEvent};
+
+use crate::settings;
+
+pub const BINDINGS_PATH: &str = "config/bindings.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    /// Crouches while walking and descends while flying
+    Crouch,
+    Sprint,
+    ToggleFly,
+    Break,
+    Place,
+    PickBlock,
+    /// Requires Ctrl to be held
+    Undo,
+    /// Requires Ctrl to be held
+    Redo,
+    Hotbar1,
+    Hotbar2,
+    Hotbar3,
+    Hotbar4,
+    Hotbar5,
+    Hotbar6,
+    Hotbar7,
+    Hotbar8,
+    Hotbar9,
+    ToggleMenu,
+    /// Opens the window listing the blocks the player has
+    ToggleInventory,
+    ToggleHud,
+    ToggleDebugOverlay,
+    ToggleWireframe,
+    /// Cycles between first person and the third person views
+    ToggleCameraMode,
+    /// Switches to an orthographic view looking down at the player
+    ToggleTopDownView,
+    ToggleFullscreen,
+    /// Uses the comparison texture filtering while held
+    CompareFiltering,
+    /// Reads the block atlas and registry from the assets directory again
+    ReloadAssets,
+}
+
+impl Action {
+    pub const ALL: [Action; 32] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Crouch,
+        Action::Sprint,
+        Action::ToggleFly,
+        Action::Break,
+        Action::Place,
+        Action::PickBlock,
+        Action::Undo,
+        Action::Redo,
+        Action::Hotbar1,
+        Action::Hotbar2,
+        Action::Hotbar3,
+        Action::Hotbar4,
+        Action::Hotbar5,
+        Action::Hotbar6,
+        Action::Hotbar7,
+        Action::Hotbar8,
+        Action::Hotbar9,
+        Action::ToggleMenu,
+        Action::ToggleInventory,
+        Action::ToggleHud,
+        Action::ToggleDebugOverlay,
+        Action::ToggleWireframe,
+        Action::ToggleCameraMode,
+        Action::ToggleTopDownView,
+        Action::ToggleFullscreen,
+        Action::CompareFiltering,
+        Action::ReloadAssets,
+    ];
+
+    /// The hotbar slot selected by this action
+    pub fn hotbar_slot(&self) -> Option<usize> {
+        match self {
+            Action::Hotbar1 => Some(0),
+            Action::Hotbar2 => Some(1),
+            Action::Hotbar3 => Some(2),
+            Action::Hotbar4 => Some(3),
+            Action::Hotbar5 => Some(4),
+            Action::Hotbar6 => Some(5),
+            Action::Hotbar7 => Some(6),
+            Action::Hotbar8 => Some(7),
+            Action::Hotbar9 => Some(8),
+            _ => None,
+        }
+    }
+
+    fn default_input(&self) -> Input {
+        match self {
+            Action::MoveForward => Input::Key(VirtualKeyCode::W),
+            Action::MoveBackward => Input::Key(VirtualKeyCode::S),
+            Action::MoveLeft => Input::Key(VirtualKeyCode::A),
+            Action::MoveRight => Input::Key(VirtualKeyCode::D),
+            Action::Jump => Input::Key(VirtualKeyCode::Space),
+            Action::Crouch => Input::Key(VirtualKeyCode::LShift),
+            Action::Sprint => Input::Key(VirtualKeyCode::LControl),
+            Action::ToggleFly => Input::Key(VirtualKeyCode::F),
+            Action::Break => Input::Mouse(MouseButton::Left),
+            Action::Place => Input::Mouse(MouseButton::Right),
+            Action::PickBlock => Input::Mouse(MouseButton::Middle),
+            Action::Undo => Input::Key(VirtualKeyCode::Z),
+            Action::Redo => Input::Key(VirtualKeyCode::Y),
+            Action::Hotbar1 => Input::Key(VirtualKeyCode::Key1),
+            Action::Hotbar2 => Input::Key(VirtualKeyCode::Key2),
+            Action::Hotbar3 => Input::Key(VirtualKeyCode::Key3),
+            Action::Hotbar4 => Input::Key(VirtualKeyCode::Key4),
+            Action::Hotbar5 => Input::Key(VirtualKeyCode::Key5),
+            Action::Hotbar6 => Input::Key(VirtualKeyCode::Key6),
+            Action::Hotbar7 => Input::Key(VirtualKeyCode::Key7),
+            Action::Hotbar8 => Input::Key(VirtualKeyCode::Key8),
+            Action::Hotbar9 => Input::Key(VirtualKeyCode::Key9),
+            Action::ToggleMenu => Input::Key(VirtualKeyCode::Escape),
+            Action::ToggleInventory => Input::Key(VirtualKeyCode::E),
+            Action::ToggleHud => Input::Key(VirtualKeyCode::F1),
+            Action::ToggleDebugOverlay => Input::Key(VirtualKeyCode::F3),
+            Action::ToggleWireframe => Input::Key(VirtualKeyCode::F12),
+            Action::ToggleCameraMode => Input::Key(VirtualKeyCode::F5),
+            Action::ToggleTopDownView => Input::Key(VirtualKeyCode::F6),
+            Action::ToggleFullscreen => Input::Key(VirtualKeyCode::F11),
+            Action::CompareFiltering => Input::Key(VirtualKeyCode::F7),
+            Action::ReloadAssets => Input::Key(VirtualKeyCode::F9),
+        }
+    }
+
+    /// The arrow keys move like WASD
+    fn default_secondary_input(&self) -> Option<Input> {
+        match self {
+            Action::MoveForward => Some(Input::Key(VirtualKeyCode::Up)),
+            Action::MoveBackward => Some(Input::Key(VirtualKeyCode::Down)),
+            Action::MoveLeft => Some(Input::Key(VirtualKeyCode::Left)),
+            Action::MoveRight => Some(Input::Key(VirtualKeyCode::Right)),
+            _ => None,
+        }
+    }
+}
+
+/// Which of the two inputs of an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    Primary,
+    Secondary,
+}
+
+/// A physical key or mouse button
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+impl Input {
+    /// Extracts the input and whether it was pressed from a window event
+    pub fn from_event(event: &WindowEvent) -> Option<(Input, bool)> {
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => input
+                .virtual_keycode
+                .map(|key| (Input::Key(key), input.state == ElementState::Pressed)),
+            WindowEvent::MouseInput { state, button, .. } => {
+                Some((Input::Mouse(*button), *state == ElementState::Pressed))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Input::Key(key) => write!(f, "{:?}", key),
+            Input::Mouse(MouseButton::Other(button)) => write!(f, "Mouse {}", button),
+            Input::Mouse(button) => write!(f, "Mouse {:?}", button),
+        }
+    }
+}
+
+/// Maps every [`Action`] to the input that triggers it, and some of them
+/// to a second input that triggers them as well
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bindings {
+    bindings: BTreeMap<Action, Input>,
+    /// Files saved before there were secondary inputs get the default ones.
+    /// Actions missing from it have none.
+    #[serde(default = "default_secondary")]
+    secondary: BTreeMap<Action, Input>,
+    /// The input waiting for a new key or button from the controls menu
+    #[serde(skip)]
+    pub rebinding: Option<(Action, Slot)>,
+}
+
+fn default_secondary() -> BTreeMap<Action, Input> {
+    Action::ALL
+        .iter()
+        .filter_map(|action| Some((*action, action.default_secondary_input()?)))
+        .collect()
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_input()))
+                .collect(),
+            secondary: default_secondary(),
+            rebinding: None,
+        }
+    }
+}
+
+impl Bindings {
+    /// Loads the bindings from disk, falling back to the defaults
+    /// for missing actions or if the file can't be read.
+    pub fn load() -> Self {
+        Self::load_from(BINDINGS_PATH)
+    }
+
+    fn load_from(path: &str) -> Self {
+        let mut bindings: Self = settings::load_toml(path).unwrap_or_default();
+        for action in Action::ALL {
+            bindings
+                .bindings
+                .entry(action)
+                .or_insert_with(|| action.default_input());
+        }
+        bindings
+    }
+
+    pub fn save(&self) {
+        settings::save_toml(BINDINGS_PATH, self);
+    }
+
+    pub fn get(&self, action: Action, slot: Slot) -> Option<Input> {
+        self.slot(slot).get(&action).copied()
+    }
+
+    pub fn set(&mut self, action: Action, slot: Slot, input: Input) {
+        match slot {
+            Slot::Primary => self.bindings.insert(action, input),
+            Slot::Secondary => self.secondary.insert(action, input),
+        };
+    }
+
+    fn slot(&self, slot: Slot) -> &BTreeMap<Action, Input> {
+        match slot {
+            Slot::Primary => &self.bindings,
+            Slot::Secondary => &self.secondary,
+        }
+    }
+
+    /// Every bound input along with its action
+    fn inputs(&self) -> impl Iterator<Item = (Action, Input)> + '_ {
+        self.bindings
+            .iter()
+            .chain(&self.secondary)
+            .map(|(action, input)| (*action, *input))
+    }
+
+    /// The action triggered by the input, primary inputs first
+    pub fn action_of(&self, input: Input) -> Option<Action> {
+        self.inputs()
+            .find(|(_, bound)| *bound == input)
+            .map(|(action, _)| action)
+    }
+
+    /// Translates a raw window event into the action bound to it
+    /// and whether it was pressed or released.
+    pub fn translate(&self, event: &WindowEvent) -> Option<(Action, bool)> {
+        let (input, pressed) = Input::from_event(event)?;
+        self.action_of(input).map(|action| (action, pressed))
+    }
+
+    /// Actions sharing one of their inputs with another action
+    pub fn conflicts(&self) -> Vec<Action> {
+        let mut conflicts = self
+            .inputs()
+            .filter(|(action, input)| {
+                self.inputs()
+                    .any(|(other, other_input)| other != *action && other_input == *input)
+            })
+            .map(|(action, _)| action)
+            .collect::<Vec<_>>();
+        conflicts.sort();
+        conflicts.dedup();
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: VirtualKeyCode) -> Input {
+        Input::Key(key)
+    }
+
+    #[test]
+    fn arrow_keys_move_by_default() {
+        let bindings = Bindings::default();
+        assert_eq!(
+            bindings.action_of(key(VirtualKeyCode::W)),
+            Some(Action::MoveForward)
+        );
+        assert_eq!(
+            bindings.action_of(key(VirtualKeyCode::Up)),
+            Some(Action::MoveForward)
+        );
+        assert_eq!(
+            bindings.action_of(key(VirtualKeyCode::Down)),
+            Some(Action::MoveBackward)
+        );
+        assert_eq!(
+            bindings.action_of(key(VirtualKeyCode::Left)),
+            Some(Action::MoveLeft)
+        );
+        assert_eq!(
+            bindings.action_of(key(VirtualKeyCode::Right)),
+            Some(Action::MoveRight)
+        );
+        assert_eq!(bindings.get(Action::Jump, Slot::Secondary), None);
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bindings.toml");
+        let path = path.to_str().unwrap();
+        let mut bindings = Bindings::default();
+        bindings.set(Action::Jump, Slot::Primary, key(VirtualKeyCode::J));
+        bindings.set(
+            Action::Jump,
+            Slot::Secondary,
+            Input::Mouse(MouseButton::Other(4)),
+        );
+        bindings.set(Action::MoveLeft, Slot::Secondary, key(VirtualKeyCode::Q));
+        settings::save_toml(path, &bindings);
+        assert_eq!(Bindings::load_from(path), bindings);
+    }
+
+    #[test]
+    fn missing_bindings_are_filled_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bindings.toml");
+        // Saved before there were secondary inputs
+        std::fs::write(&path, "[bindings]\nJump = { Key = \"J\" }\n").unwrap();
+        let bindings = Bindings::load_from(path.to_str().unwrap());
+        assert_eq!(
+            bindings.get(Action::Jump, Slot::Primary),
+            Some(key(VirtualKeyCode::J))
+        );
+        assert_eq!(
+            bindings.get(Action::Crouch, Slot::Primary),
+            Some(key(VirtualKeyCode::LShift))
+        );
+        assert_eq!(
+            bindings.get(Action::MoveForward, Slot::Secondary),
+            Some(key(VirtualKeyCode::Up))
+        );
+
+        // A secondary input left out on purpose stays unbound
+        std::fs::write(
+            &path,
+            "[bindings]\n\n[secondary]\nMoveLeft = { Key = \"Q\" }\n",
+        )
+        .unwrap();
+        let bindings = Bindings::load_from(path.to_str().unwrap());
+        assert_eq!(bindings.get(Action::MoveForward, Slot::Secondary), None);
+        assert_eq!(
+            bindings.get(Action::MoveLeft, Slot::Secondary),
+            Some(key(VirtualKeyCode::Q))
+        );
+    }
+
+    #[test]
+    fn conflicts_cover_both_slots() {
+        let mut bindings = Bindings::default();
+        assert_eq!(bindings.conflicts(), Vec::new());
+
+        bindings.set(Action::Jump, Slot::Primary, key(VirtualKeyCode::W));
+        assert_eq!(bindings.conflicts(), [Action::MoveForward, Action::Jump]);
+
+        bindings.set(Action::Jump, Slot::Primary, key(VirtualKeyCode::Space));
+        bindings.set(Action::Jump, Slot::Secondary, key(VirtualKeyCode::Up));
+        assert_eq!(bindings.conflicts(), [Action::MoveForward, Action::Jump]);
+
+        // Both inputs of one action being the same is harmless
+        bindings.set(Action::Jump, Slot::Secondary, key(VirtualKeyCode::Space));
+        assert_eq!(bindings.conflicts(), Vec::new());
+    }
+}
@@ -1,10 +1,15 @@
+pub mod audio;
 pub mod block;
-pub mod core;
+pub mod commands;
 pub mod direction;
 pub mod ecs;
 pub mod engine;
+pub mod frame_limiter;
+pub mod input;
+pub mod logger;
 pub mod renderer;
 pub mod scene;
+pub mod settings;
 pub mod ui;
 pub mod window;
 pub mod world;
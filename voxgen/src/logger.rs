@@ -0,0 +1,83 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use log::{Level, Log, Metadata, Record};
+
+use crate::ui::hud::HudSender;
+
+/// Maximum number of records kept for the log window
+pub const LOG_CAPACITY: usize = 500;
+
+static RECORDS: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+/// Where errors are posted so that they show up in the message feed
+static ERROR_FEED: Mutex<Option<HudSender>> = Mutex::new(None);
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub level: Level,
+    /// Module that emitted the record
+    pub target: String,
+    pub message: String,
+}
+
+/// Forwards records to env_logger and keeps the most recent ones in memory
+/// so they can be shown in the UI.
+struct EngineLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for EngineLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        self.inner.log(record);
+        // Chunks are generated on rayon workers, so this can be called from any thread
+        if let Ok(mut records) = RECORDS.lock() {
+            if records.len() == LOG_CAPACITY {
+                records.pop_front();
+            }
+            records.push_back(LogEntry {
+                level: record.level(),
+                target: record.target().to_owned(),
+                message: record.args().to_string(),
+            });
+        }
+        if record.level() == Level::Error {
+            if let Some(feed) = &*ERROR_FEED.lock().unwrap_or_else(|e| e.into_inner()) {
+                feed.post(record.args().to_string(), Level::Error);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Installs the logger, configured from the `RUST_LOG` environment variable
+pub fn init() {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max_level = inner.filter();
+    if log::set_boxed_logger(Box::new(EngineLogger { inner })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Posts every error logged from now on to the message feed
+pub fn post_errors_to(feed: HudSender) {
+    *ERROR_FEED.lock().unwrap_or_else(|e| e.into_inner()) = Some(feed);
+}
+
+/// Calls `f` with the records currently in the buffer, oldest first
+pub fn with_records<R>(f: impl FnOnce(&VecDeque<LogEntry>) -> R) -> R {
+    let records = RECORDS.lock().unwrap_or_else(|e| e.into_inner());
+    f(&records)
+}
+
+pub fn clear() {
+    RECORDS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
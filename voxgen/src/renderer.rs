@@ -1,26 +1,72 @@
+pub mod adapter;
 pub mod atlas;
+pub mod bloom;
+pub mod border;
 pub mod buffer;
+pub mod chunk_uniforms;
 pub mod debug;
+pub mod entities;
+pub mod error;
+pub mod fxaa;
+pub mod gizmo;
+pub mod held_block;
+pub mod instance;
+pub mod lod;
+pub mod markers;
 pub mod mesh;
+pub mod occlusion;
+pub mod outline;
+pub mod particles;
 pub mod pipelines;
+pub mod pool;
+pub mod post_process;
+pub mod screen_element;
+pub mod selection;
 pub mod texture;
+pub mod timing;
 pub mod ui;
+pub mod upload;
 pub mod world;
 
-use vek::Vec3;
+pub use error::{ReloadError, RendererError};
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+use vek::{Mat4, Vec3};
 pub use world::WorldRenderer;
 
-use std::time::Duration;
+use std::{path::Path, time::Instant};
 
 use crate::{
+    block, ecs,
+    input::Bindings,
+    logger,
     scene::{
-        camera::{Camera, CameraUniform},
+        camera::{self, CameraUniform, Projection, ViewMode},
+        medium::CameraMedium,
+        particles::MAX_PARTICLES,
         Scene,
     },
-    ui::EguiInstance,
+    settings::Settings,
+    ui::{overlay::FrameStats, EguiInstance},
+    world::{chunk::ChunkPos, events::WorldEvent},
 };
 
-use self::{buffer::Buffer, debug::DebugRenderer, texture::Texture, ui::UIRenderer};
+use self::{
+    adapter::{AdapterPreferences, SelectedAdapter},
+    atlas::ATLAS_PATH,
+    border::BorderRenderer,
+    buffer::Buffer,
+    debug::DebugRenderer,
+    entities::EntityRenderer,
+    gizmo::GizmoRenderer,
+    held_block::HeldBlockRenderer,
+    particles::ParticleRenderer,
+    post_process::PostProcess,
+    selection::SelectionRenderer,
+    texture::{SamplerSettings, Texture},
+    timing::{GpuTimer, TIMED_PASSES},
+    ui::UIRenderer,
+    upload::Uploader,
+};
 
 trait Renderable {
     fn render<'a>(
@@ -30,50 +76,113 @@ trait Renderable {
     );
 }
 
+/// Where frames are drawn to
+pub enum RenderTarget {
+    /// The surface of a window
+    Surface(wgpu::Surface),
+    /// A texture that can be read back, for rendering without a window
+    Offscreen {
+        texture: wgpu::Texture,
+        size: (u32, u32),
+    },
+}
+
+/// Color format of offscreen render targets
+pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+fn create_offscreen_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen Target"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &[],
+    })
+}
+
 pub struct Renderer {
-    surface: wgpu::Surface,
+    target: RenderTarget,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
     world_renderer: WorldRenderer,
     debug_renderer: DebugRenderer,
-    depth: Texture,
+    selection_renderer: SelectionRenderer,
+    gizmo_renderer: GizmoRenderer,
+    particle_renderer: ParticleRenderer,
+    entity_renderer: EntityRenderer,
+    held_block_renderer: HeldBlockRenderer,
+    border_renderer: BorderRenderer,
+    /// The texture the scene is drawn into and the passes that take it
+    /// to the frame
+    post_process: PostProcess,
     camera_uniform: CameraUniform,
     camera_bind_group: wgpu::BindGroup,
     camera_buffer: Buffer<CameraUniform>,
+    /// Stages the writes to the camera, chunk and debug line buffers
+    /// into the encoder of the next frame
+    uploader: Uploader,
     egui_render_pass: egui_wgpu_backend::RenderPass,
+    /// The block atlas registered as an egui texture for UI icons
+    atlas_ui_texture: egui::TextureId,
+    adapter_info: wgpu::AdapterInfo,
+    frame_stats: FrameStats,
+    /// Missing when the adapter doesn't support timestamp queries
+    gpu_timer: Option<GpuTimer>,
+    /// When the renderer was created, the origin of the animation clock
+    start_time: Instant,
+    /// Color of the water tint and fog
+    underwater_tint: [f32; 3],
+    underwater_fog_density: f32,
+    /// Multiplier of the OS scale factor for the UI and screen elements
+    ui_scale: f32,
+    /// Whether the adapter can filter textures anisotropically
+    anisotropic_filtering: bool,
+    /// How the block textures are sampled, and how while comparing
+    texture_filtering: SamplerSettings,
+    compare_filtering: SamplerSettings,
+    /// Whether the comparison filtering is used, while its key is held
+    comparing_filtering: bool,
     pub gui: EguiInstance,
 }
 
 impl Renderer {
-    pub async fn new(winit_impl: &winit::window::Window) -> Self {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            dx12_shader_compiler: Default::default(),
-        });
-        let surface = unsafe { instance.create_surface(&winit_impl) }.unwrap();
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::POLYGON_MODE_LINE,
-                    limits: wgpu::Limits::default(),
-                    label: None,
-                },
-                None, // Trace path
-            )
-            .await
-            .unwrap();
+    /// Creates a renderer drawing to the surface of the given window.
+    /// `size` is the physical size of the window. With a `trace` directory,
+    /// wgpu records every call into it, when built with the `trace` feature.
+    pub async fn new<W>(
+        window: &W,
+        size: (u32, u32),
+        scale_factor: f64,
+        preferences: AdapterPreferences,
+        trace: Option<&Path>,
+    ) -> Result<Self, RendererError>
+    where
+        W: HasRawWindowHandle + HasRawDisplayHandle,
+    {
+        adapter::log_adapters();
+        let SelectedAdapter { adapter, surface } =
+            adapter::request_for_window(window, preferences).await?;
+        let surface = surface.expect("Adapters for a window come with a surface");
+        let info = adapter.get_info();
+        log::info!(
+            "Using adapter {} ({:?}, {:?})",
+            info.name,
+            info.device_type,
+            info.backend
+        );
+        let (device, queue) = Self::request_device(&adapter, trace).await?;
         let surface_caps = surface.get_capabilities(&adapter);
         // Shader code in this tutorial assumes an sRGB surface texture. Using a different
         // one will result all the colors coming out darker. If you want to support non
@@ -82,30 +191,120 @@ impl Renderer {
             .formats
             .iter()
             .copied()
-            .filter(|f| f.is_srgb())
-            .next()
-            .unwrap_or(surface_caps.formats[0]);
-
-        let size = winit_impl.inner_size();
+            .find(|f| f.is_srgb())
+            .or_else(|| surface_caps.formats.first().copied())
+            .ok_or(RendererError::UnsupportedSurface)?;
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
-            width: size.width,
-            height: size.height,
+            width: size.0,
+            height: size.1,
             present_mode: surface_caps.present_modes[0],
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
 
         surface.configure(&device, &config);
+        let gui = EguiInstance::new(size.0, size.1, scale_factor);
+        Self::with_target(
+            device,
+            queue,
+            &adapter,
+            RenderTarget::Surface(surface),
+            config,
+            gui,
+        )
+        .await
+    }
+
+    /// Creates a renderer drawing into a texture instead of a window,
+    /// preferring the software fallback adapter when there is one.
+    pub async fn new_offscreen(width: u32, height: u32) -> Result<Self, RendererError> {
+        let adapter = adapter::request_offscreen().await?.adapter;
+        let (device, queue) = Self::request_device(&adapter, None).await?;
 
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: OFFSCREEN_FORMAT,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+        let texture = create_offscreen_texture(&device, &config);
+        let gui = EguiInstance::new(width, height, 1.0);
+        Self::with_target(
+            device,
+            queue,
+            &adapter,
+            RenderTarget::Offscreen {
+                texture,
+                size: (width, height),
+            },
+            config,
+            gui,
+        )
+        .await
+    }
+
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+        trace: Option<&Path>,
+    ) -> Result<(wgpu::Device, wgpu::Queue), RendererError> {
+        let trace = match trace {
+            Some(dir) if cfg!(feature = "trace") => {
+                log::info!("Recording a wgpu trace into {}", dir.display());
+                Some(dir)
+            }
+            Some(_) => {
+                log::warn!("Not recording a trace, the engine was built without the trace feature");
+                None
+            }
+            None => None,
+        };
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    // Wireframe mode and pass timings are optional
+                    features: adapter.features()
+                        & (wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::TIMESTAMP_QUERY),
+                    limits: wgpu::Limits::default(),
+                    label: Some("Device"),
+                },
+                trace,
+            )
+            .await?;
+        // Report validation errors in the log instead of panicking
+        device.on_uncaptured_error(Box::new(|error| log::error!("wgpu error: {}", error)));
+        Ok((device, queue))
+    }
+
+    async fn with_target(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        adapter: &wgpu::Adapter,
+        target: RenderTarget,
+        config: wgpu::SurfaceConfiguration,
+        gui: EguiInstance,
+    ) -> Result<Self, RendererError> {
+        let adapter_info = adapter.get_info();
+        // Not a feature to request, the sampler settings only use it when enabled
+        let anisotropic_filtering = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING);
+        let size = winit::dpi::PhysicalSize::new(config.width, config.height);
+        // Catch invalid shaders and pipelines instead of only logging them
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
         let transform_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: None,
+                label: Some("Camera bind group layout"),
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    // The fragment shader of the world reads the fog
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -114,18 +313,21 @@ impl Renderer {
                     count: None,
                 }],
             });
-        let depth = Texture::with_depth(&config, &device);
+        // The scene is drawn into the targets of the post process
+        let post_process = PostProcess::new(&device, &config);
+        let scene_config = post_process.scene_config(&config);
 
         let camera_uniform = CameraUniform::empty();
 
         let transform_buffer = Buffer::new(
             &device,
+            "Camera uniform buffer",
             wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             &[camera_uniform],
         );
 
         let transform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
+            label: Some("Camera bind group"),
             layout: &transform_bind_group_layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
@@ -133,26 +335,194 @@ impl Renderer {
             }],
         });
         let world_renderer =
-            WorldRenderer::new(&device, &queue, &config, &transform_bind_group_layout);
-        let debug_renderer = DebugRenderer::new(&device, &config, &transform_bind_group_layout);
-        let egui_render_pass = egui_wgpu_backend::RenderPass::new(&device, surface_format, 1);
-        let gui = EguiInstance::new(&winit_impl);
+            WorldRenderer::new(&device, &queue, &scene_config, &transform_bind_group_layout)?;
+        let debug_renderer =
+            DebugRenderer::new(&device, &scene_config, &transform_bind_group_layout);
+        let selection_renderer =
+            SelectionRenderer::new(&device, &scene_config, &transform_bind_group_layout);
+        let gizmo_renderer =
+            GizmoRenderer::new(&device, &scene_config, &transform_bind_group_layout);
+        let particle_renderer = ParticleRenderer::new(
+            &device,
+            &scene_config,
+            &world_renderer.atlas,
+            &transform_bind_group_layout,
+        );
+        let entity_renderer = EntityRenderer::new(
+            &device,
+            &scene_config,
+            &world_renderer.atlas,
+            &transform_bind_group_layout,
+        );
+        let held_block_renderer = HeldBlockRenderer::new(
+            &device,
+            &scene_config,
+            &world_renderer.atlas,
+            &transform_bind_group_layout,
+        );
+        let border_renderer =
+            BorderRenderer::new(&device, &scene_config, &transform_bind_group_layout);
+        let mut egui_render_pass = egui_wgpu_backend::RenderPass::new(&device, config.format, 1);
+        let atlas_ui_texture = egui_render_pass.egui_texture_from_wgpu_texture(
+            &device,
+            &world_renderer.atlas.texture.view,
+            wgpu::FilterMode::Nearest,
+        );
+        let gpu_timer = GpuTimer::new(&device, &queue);
+        if let Some(error) = device.pop_error_scope().await {
+            return Err(RendererError::Shader(error.to_string()));
+        }
+        logger::post_errors_to(gui.state.hud.sender());
 
-        Self {
-            surface,
+        Ok(Self {
+            target,
             device,
             queue,
             config,
             size,
             world_renderer,
-            depth,
+            post_process,
             camera_bind_group: transform_bind_group,
             camera_buffer: transform_buffer,
             camera_uniform,
+            uploader: Uploader::new(),
             egui_render_pass,
+            atlas_ui_texture,
+            adapter_info,
+            frame_stats: FrameStats::new(),
+            gpu_timer,
+            start_time: Instant::now(),
+            underwater_tint: [0.0; 3],
+            underwater_fog_density: 0.0,
+            ui_scale: 1.0,
+            anisotropic_filtering,
+            texture_filtering: SamplerSettings::default(),
+            compare_filtering: SamplerSettings::UNFILTERED,
+            comparing_filtering: false,
             gui,
             debug_renderer,
+            selection_renderer,
+            gizmo_renderer,
+            particle_renderer,
+            entity_renderer,
+            held_block_renderer,
+            border_renderer,
+        })
+    }
+
+    /// Milliseconds spent in each of the timed passes, if available
+    pub fn gpu_timings(&self) -> Option<[f32; TIMED_PASSES.len()]> {
+        self.gpu_timer.as_ref().and_then(|timer| timer.timings())
+    }
+
+    pub fn world(&self) -> &WorldRenderer {
+        &self.world_renderer
+    }
+
+    pub fn world_mut(&mut self) -> &mut WorldRenderer {
+        &mut self.world_renderer
+    }
+
+    /// Times uploading the loaded chunk meshes with `Queue::write_buffer`
+    /// and through the staging belt, shown in the chunk stats window
+    pub fn benchmark_uploads(&mut self) {
+        let benchmark = self
+            .world_renderer
+            .benchmark_uploads(&self.device, &self.queue);
+        match benchmark {
+            Some(benchmark) => log::info!(
+                "Uploaded {} bytes in {:.2} ms with write_buffer, {:.2} ms with the staging belt",
+                benchmark.bytes,
+                benchmark.write_buffer_ms,
+                benchmark.staging_belt_ms
+            ),
+            None => log::warn!("No chunk meshes to benchmark uploads with"),
         }
+        self.gui.state.upload_benchmark = benchmark;
+    }
+
+    /// Streams the chunks around the position right away
+    /// instead of waiting for the next frame
+    pub fn stream_chunks(&mut self, pos: Vec3<f32>) {
+        self.world_renderer
+            .tick(pos, &self.device, &mut self.uploader);
+    }
+
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.world_renderer.set_lighting(settings.lighting());
+        self.world_renderer.set_border(settings.world_border());
+        self.world_renderer
+            .set_occlusion_culling(settings.occlusion_culling);
+        self.world_renderer.set_lod_distance(settings.lod_distance);
+        self.underwater_tint = settings.underwater_tint;
+        self.underwater_fog_density = settings.underwater_fog_density;
+        self.ui_scale = settings.ui_scale;
+        self.gui.state.hud.enabled = settings.message_feed;
+        self.texture_filtering = settings.texture_filtering;
+        self.compare_filtering = settings.compare_filtering;
+        self.update_filtering();
+        self.post_process
+            .set_settings(&self.queue, settings.color_grading, settings.bloom);
+        self.post_process
+            .set_render_scale(&self.device, &self.config, settings.render_scale);
+        self.post_process
+            .set_upscale_filter(&self.device, settings.upscale_filter);
+        self.post_process
+            .set_fxaa(&self.device, &self.config, settings.fxaa);
+        self.post_process.set_outline(settings.outline);
+    }
+
+    /// Switches to the comparison filtering while its key is held
+    pub fn set_comparing_filtering(&mut self, comparing: bool) {
+        self.comparing_filtering = comparing;
+        self.update_filtering();
+    }
+
+    /// Samples the atlas with the filtering in use when it changed
+    fn update_filtering(&mut self) {
+        let filtering = if self.comparing_filtering {
+            self.compare_filtering
+        } else {
+            self.texture_filtering
+        };
+        if filtering == self.world_renderer.atlas.filtering() {
+            return;
+        }
+        if filtering.is_anisotropic() && !self.anisotropic_filtering {
+            log::warn!("The adapter doesn't support anisotropic filtering");
+        }
+        self.world_renderer.atlas.set_filtering(
+            &self.device,
+            filtering,
+            self.anisotropic_filtering,
+        );
+    }
+
+    /// Reads atlas.png and blocks.ron from the assets directory again and
+    /// remeshes the world with them. Nothing changes unless both are valid.
+    pub fn reload_assets(&mut self) -> Result<String, ReloadError> {
+        let registry = block::registry::reload()?;
+        let atlas = std::fs::read(ATLAS_PATH)?;
+        self.world_renderer
+            .atlas
+            .reload(&self.device, &self.queue, &atlas)?;
+        let blocks = registry.len();
+        block::registry::replace(registry);
+        if let Err(e) = self.egui_render_pass.update_egui_texture_from_wgpu_texture(
+            &self.device,
+            &self.world_renderer.atlas.texture.view,
+            wgpu::FilterMode::Nearest,
+            self.atlas_ui_texture,
+        ) {
+            log::warn!("The UI keeps the old atlas: {:?}", e);
+        }
+        self.held_block_renderer.invalidate();
+        self.world_renderer.remesh_all();
+        Ok(format!(
+            "Reloaded {} blocks and the atlas, remeshing {} chunks",
+            blocks,
+            self.world_renderer.chunk_manager().chunks().len()
+        ))
     }
 
     pub fn toggle_wireframe(&mut self) {
@@ -164,8 +534,14 @@ impl Renderer {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
-            self.depth = Texture::with_depth(&self.config, &self.device);
+            match &mut self.target {
+                RenderTarget::Surface(surface) => surface.configure(&self.device, &self.config),
+                RenderTarget::Offscreen { texture, size } => {
+                    *texture = create_offscreen_texture(&self.device, &self.config);
+                    *size = (new_size.width, new_size.height);
+                }
+            }
+            self.post_process.resize(&self.device, &self.config);
             scene.resize(new_size.width as f32, new_size.height as f32);
         }
     }
@@ -173,58 +549,838 @@ impl Renderer {
     pub fn input(&mut self, _: &winit::event::Event<()>) {}
 
     pub fn update(&mut self, scene: &Scene) {
-        self.camera_uniform.update(&scene.camera);
+        self.camera_uniform
+            .update(scene.camera(), scene.interpolation);
+        // Wall clock time keeps animations at the same speed at any frame rate.
+        // It wraps every hour so the shader doesn't lose float precision.
+        self.camera_uniform.time = self.start_time.elapsed().as_secs_f32() % 3600.0;
+        self.camera_uniform.daylight = ecs::daylight(scene.time_of_day());
+        self.camera_uniform.emission = self.post_process.emission();
+        let daylight = self.camera_uniform.daylight;
+        if scene.camera_medium == CameraMedium::Water {
+            self.camera_uniform.fog_density = self.underwater_fog_density;
+            let [r, g, b] = self.underwater_tint.map(|v| v * daylight);
+            self.camera_uniform.fog_color = [r, g, b, 1.0];
+        } else {
+            self.camera_uniform.fog_density = 0.0;
+        }
+        self.gui.state.camera_in_block = scene.camera_medium == CameraMedium::Solid;
         self.camera_buffer
-            .update(&self.queue, &[self.camera_uniform], 0);
-        self.world_renderer.tick(Vec3::zero(), &self.device);
+            .stage(&self.device, &mut self.uploader, &[self.camera_uniform], 0)
+            .expect("The uniform buffer holds one uniform");
+        self.post_process.update_outline(
+            &self.device,
+            &mut self.uploader,
+            Mat4::from_col_arrays(self.camera_uniform.transform),
+        );
+        self.world_renderer
+            .tick(scene.camera_pos(), &self.device, &mut self.uploader);
+        self.gui.state.pending_chunks = self.world_renderer.chunk_manager().pending();
+        self.gui.state.queued_saves = self.world_renderer.chunk_manager().saver().queued();
+        let camera = scene.camera();
+        let origin = camera.render_origin(scene.interpolation);
+        self.world_renderer
+            .update_chunk_uniforms(&self.device, &mut self.uploader, origin);
+        self.world_renderer.sort_for_drawing(
+            camera.eye_pos(scene.interpolation).map(|v| v as f32),
+            &camera.frustum(scene.interpolation),
+        );
+        let target = scene.target_block(&self.world_renderer);
+        let target_pos = target.map(|hit| hit.block_pos);
+        let progress = scene
+            .breaking
+            .progress()
+            .filter(|breaking| Some(breaking.pos) == target_pos)
+            .map_or(0.0, |breaking| breaking.progress);
+        self.selection_renderer
+            .update(&self.queue, target_pos, progress, origin);
+        self.border_renderer.update(
+            &self.device,
+            self.world_renderer.chunk_manager().border(),
+            origin,
+        );
+        self.debug_renderer
+            .update(&self.device, &mut self.uploader, origin);
+        self.gizmo_renderer.update(&self.queue, scene.camera());
+        self.entity_renderer.update(
+            &self.device,
+            &self.queue,
+            &scene.world,
+            camera,
+            scene.interpolation,
+        );
+        self.particle_renderer.update(
+            &self.device,
+            &self.queue,
+            &scene.particles,
+            scene.camera(),
+            scene.interpolation,
+        );
+        // Held in the player's hand, so only seen from the eyes
+        let held_block_visible = self.gui.state.hud_visible
+            && !self.gui.state.camera_in_block
+            && camera.view_mode == ViewMode::FirstPerson
+            && camera.projection == Projection::Perspective;
+        self.held_block_renderer.update(
+            &self.device,
+            &self.queue,
+            scene,
+            daylight,
+            held_block_visible,
+        );
+        self.gui.state.minimap.update(
+            &self.gui.platform.context(),
+            self.world_renderer.chunk_manager(),
+            self.world_renderer.events(),
+            scene.camera(),
+        );
+
+        let overlay = &mut self.gui.state.overlay;
+        if !overlay.visible {
+            return;
+        }
+        let frame = &self.frame_stats;
+        overlay.push(
+            "Performance",
+            format!(
+                "{:.0} FPS ({:.2} ms)",
+                frame.fps(),
+                frame.avg_frame_time * 1000.0
+            ),
+        );
+        overlay.push(
+            "Performance",
+            format!(
+                "Min {:.2} ms, max {:.2} ms",
+                frame.min_frame_time * 1000.0,
+                frame.max_frame_time * 1000.0
+            ),
+        );
+
+        match self.gpu_timer.as_ref().map(|timer| timer.timings()) {
+            Some(Some(timings)) => {
+                let passes = TIMED_PASSES
+                    .iter()
+                    .zip(timings)
+                    .map(|(pass, ms)| format!("{} {:.2} ms", pass, ms))
+                    .collect::<Vec<_>>();
+                overlay.push("Performance", format!("GPU: {}", passes.join(", ")));
+            }
+            Some(None) => overlay.push("Performance", "GPU: waiting for timings"),
+            None => overlay.push("Performance", "GPU: unavailable"),
+        }
+
+        let camera = scene.camera();
+        let pos = camera.pos;
+        let chunk_pos = ChunkPos::from_world(scene.camera_pos());
+        overlay.push(
+            "Player",
+            format!("XYZ: {:.2} {:.2} {:.2}", pos.x, pos.y, pos.z),
+        );
+        overlay.push(
+            "Player",
+            format!("Yaw/Pitch: {:.1} {:.1}", camera.yaw(), camera.pitch()),
+        );
+        let heading = camera.heading();
+        overlay.push(
+            "Player",
+            format!(
+                "Facing: {} ({:.1}°)",
+                camera::compass_point(heading),
+                heading
+            ),
+        );
+        overlay.push(
+            "Player",
+            format!("Chunk: {} {} {}", chunk_pos.x, chunk_pos.y, chunk_pos.z),
+        );
+        match target {
+            Some(hit) => overlay.push(
+                "Player",
+                format!(
+                    "Target: {} {} {} ({:?})",
+                    hit.block_pos.x, hit.block_pos.y, hit.block_pos.z, hit.block
+                ),
+            ),
+            None => overlay.push("Player", "Target: none"),
+        }
+
+        let hours = scene.time_of_day() * 24.0;
+        overlay.push(
+            "World",
+            format!(
+                "Time: {:02}:{:02}",
+                hours as u32,
+                (hours.fract() * 60.0) as u32
+            ),
+        );
+        let stats = self.world_renderer.chunk_manager().totals();
+        overlay.push("World", format!("Loaded chunks: {}", stats.loaded));
+        let (block_bytes, with_meta) = self.world_renderer.chunk_manager().block_memory();
+        overlay.push(
+            "World",
+            format!(
+                "Block storage: {} KiB, metadata in {} chunks",
+                block_bytes / 1024,
+                with_meta
+            ),
+        );
+        match self.world_renderer.chunk_manager().border().radius() {
+            Some(radius) => overlay.push("World", format!("Border: {} chunks", radius)),
+            None => overlay.push("World", "Border: none"),
+        }
+        let (mut changed, mut notified, mut loaded, mut unloaded) = (0, 0, 0, 0);
+        for event in self.world_renderer.events() {
+            match event {
+                WorldEvent::BlockChanged { .. } => changed += 1,
+                WorldEvent::NeighborChanged { .. } => notified += 1,
+                WorldEvent::ChunkLoaded(_) => loaded += 1,
+                WorldEvent::ChunkUnloaded(_) => unloaded += 1,
+            }
+        }
+        overlay.push(
+            "World",
+            format!(
+                "Events: {} blocks, {} neighbors, {} loads, {} unloads",
+                changed, notified, loaded, unloaded
+            ),
+        );
+        let ticks = &scene.block_ticks;
+        overlay.push(
+            "World",
+            format!(
+                "Block ticks: {} scheduled, {} waiting, {} dropped",
+                ticks.len() - ticks.waiting(),
+                ticks.waiting(),
+                ticks.dropped()
+            ),
+        );
+        if self.world_renderer.occlusion_culling() {
+            overlay.push(
+                "World",
+                format!(
+                    "Occlusion culled: {} chunks",
+                    self.world_renderer.occluded_chunks()
+                ),
+            );
+        } else {
+            overlay.push("World", "Occlusion culled: off");
+        }
+        overlay.push("World", format!("Vertices: {}", stats.vertices));
+        overlay.push(
+            "World",
+            format!("Particles: {}/{}", scene.particles.len(), MAX_PARTICLES),
+        );
+        let entities = &self.entity_renderer;
+        overlay.push(
+            "World",
+            format!(
+                "Entities: {} drawn of {} (room for {})",
+                entities.drawn(),
+                entities.total(),
+                entities.capacity()
+            ),
+        );
+        overlay.push(
+            "World",
+            format!("Mesh memory: {:.2} MiB", stats.gpu_bytes as f64 / 1048576.0),
+        );
+        let pool = self.world_renderer.chunk_manager().pool_stats();
+        overlay.push(
+            "World",
+            format!(
+                "Mesh pool: {} pages, {:.2} MiB free, largest block {:.2} MiB",
+                pool.pages,
+                pool.free_bytes as f64 / 1048576.0,
+                pool.largest_free_bytes as f64 / 1048576.0
+            ),
+        );
+
+        let adapter = &self.adapter_info;
+        overlay.push(
+            "Renderer",
+            format!(
+                "{} ({:?}, {:?})",
+                adapter.name, adapter.device_type, adapter.backend
+            ),
+        );
+        let (scene_width, scene_height) = self.post_process.scene_size();
+        overlay.push(
+            "Renderer",
+            format!(
+                "Resolution: {}x{} internal, {}x{} window",
+                scene_width, scene_height, self.config.width, self.config.height
+            ),
+        );
     }
 
-    pub fn render(&mut self, scale_factor: f32, dt: f32) -> Result<(), wgpu::SurfaceError> {
-        let surface_texture = self.surface.get_current_texture()?;
-        let view = surface_texture
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render enconder"),
-            });
+    pub fn render(
+        &mut self,
+        scene: &Scene,
+        bindings: &mut Bindings,
+        settings: &mut Settings,
+        scale_factor: f32,
+        dt: f32,
+    ) -> Result<(), RendererError> {
+        self.frame_stats.record(dt);
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.collect(&self.device);
+        }
+        let scale_factor = scale_factor * self.ui_scale;
+        let (frame, view) = match &self.target {
+            RenderTarget::Surface(surface) => {
+                let frame = match surface.get_current_texture() {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        // The staged chunk meshes would be missing from later frames
+                        self.uploader.submit(&self.queue);
+                        return Err(e.into());
+                    }
+                };
+                let view = frame
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(frame), view)
+            }
+            RenderTarget::Offscreen { texture, .. } => (
+                None,
+                texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            ),
+        };
+        // Recorded after the staged writes, so the frame sees them
+        let mut encoder = self.uploader.take_encoder(&self.device);
+        markers::insert(&mut encoder, "Staged uploads end");
+        if let Some(timer) = &self.gpu_timer {
+            timer.write(&mut encoder, None);
+        }
+
+        self.draw_scene(&mut encoder, &view, scale_factor);
+
+        // Drawn over the graded frame, so the UI keeps its colors
+        markers::push(&mut encoder, "UI");
+        let mut ui_renderer = UIRenderer::new(&mut encoder, self, dt, scene, bindings, settings);
+        // Submitted even if the UI fails, for the staged writes
+        let ui = ui_renderer.draw_egui(&view, scale_factor);
+        markers::pop(&mut encoder);
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.write(&mut encoder, Some(4));
+            timer.resolve(&mut encoder);
+        }
 
+        self.uploader.finish();
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.uploader.recall();
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.after_submit();
+        }
+        ui?;
+        if let Some(frame) = frame {
+            frame.present();
+        }
+        Ok(())
+    }
+
+    /// Records the passes drawing the world and everything in it, up to
+    /// the post processed frame the UI is drawn over
+    fn draw_scene(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        scale_factor: f32,
+    ) {
+        // Elements of the scene sized in pixels shrink along with it, so
+        // they cover as much of the window at any render scale
+        let scene_size = self.post_process.scene_size();
+        let scene_scale_factor = scale_factor * self.post_process.render_scale();
+        // The sky darkens at night along with the sky light. Underwater it
+        // takes the color of the fog so that distant faces blend into it.
+        let daylight = self.camera_uniform.daylight as f64;
+        let sky = if self.camera_uniform.fog_density > 0.0 {
+            self.camera_uniform.fog_color.map(|v| v as f64)
+        } else {
+            [0.1 * daylight, 0.3 * daylight, 0.6 * daylight, 1.0]
+        };
+        markers::push(encoder, "World");
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
+                label: Some("World Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view: self.post_process.scene_view(),
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.3,
-                            b: 0.6,
+                            r: sky[0],
+                            g: sky[1],
+                            b: sky[2],
                             a: 1.0,
                         }),
                         store: true,
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth.view,
+                    view: self.post_process.depth_view(),
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: wgpu::LoadOp::Clear(Texture::DEPTH_CLEAR),
                         store: true,
                     }),
                     stencil_ops: None,
                 }),
             });
+            markers::push(&mut render_pass, "Opaque chunks");
+            self.world_renderer
+                .render_opaque(&mut render_pass, &self.camera_bind_group);
+            markers::pop(&mut render_pass);
+            markers::push(&mut render_pass, "Entities");
+            self.entity_renderer.render(
+                &mut render_pass,
+                &self.world_renderer.atlas,
+                &self.camera_bind_group,
+            );
+            markers::pop(&mut render_pass);
+            // Over the opaque chunks, but behind water and glass
+            markers::push(&mut render_pass, "Particles");
+            self.particle_renderer.render(
+                &mut render_pass,
+                &self.world_renderer.atlas,
+                &self.camera_bind_group,
+            );
+            markers::pop(&mut render_pass);
+            markers::push(&mut render_pass, "Transparent chunks");
             self.world_renderer
+                .render_transparent(&mut render_pass, &self.camera_bind_group);
+            markers::pop(&mut render_pass);
+            markers::push(&mut render_pass, "World border");
+            self.border_renderer
                 .render(&mut render_pass, &self.camera_bind_group);
+            markers::pop(&mut render_pass);
+        }
+        // Before the debug lines, which would be outlined as well
+        self.post_process.render_outline(encoder);
+        markers::pop(encoder);
+        if let Some(timer) = &self.gpu_timer {
+            timer.write(encoder, Some(0));
+        }
+        markers::push(encoder, "Debug");
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Debug Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.post_process.scene_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: self.post_process.depth_view(),
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
             self.debug_renderer
                 .render(&mut render_pass, &self.camera_bind_group);
+            // The outline is redundant when every edge is already drawn
+            if !self.world_renderer.wireframe {
+                self.selection_renderer
+                    .render(&mut render_pass, &self.camera_bind_group);
+            }
+            self.gizmo_renderer
+                .render(&mut render_pass, scene_size, scene_scale_factor);
         }
-        let mut ui_renderer = UIRenderer::new(&mut encoder, self, dt, Vec3::zero());
-        ui_renderer.draw_egui(&surface_texture, scale_factor);
+        markers::pop(encoder);
+        markers::push(encoder, "Held Block");
+        {
+            // Cleared depth so the held block never goes into the terrain
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Held Block Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.post_process.scene_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: self.post_process.depth_view(),
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(Texture::DEPTH_CLEAR),
+                        store: false,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            self.held_block_renderer.render(
+                &mut render_pass,
+                &self.world_renderer.atlas,
+                scene_size,
+                scene_scale_factor,
+            );
+        }
+        markers::pop(encoder);
+        if let Some(timer) = &self.gpu_timer {
+            timer.write(encoder, Some(1));
+        }
+        markers::push(encoder, "Post Process");
+        self.post_process.render(encoder, view);
+        if let Some(timer) = &self.gpu_timer {
+            timer.write(encoder, Some(2));
+        }
+        self.post_process.render_fxaa(encoder, view);
+        markers::pop(encoder);
+        if let Some(timer) = &self.gpu_timer {
+            timer.write(encoder, Some(3));
+        }
+    }
+
+    /// Ticks the world until every chunk in range of `pos` is loaded
+    #[cfg(test)]
+    pub(crate) fn load_chunks_around(&mut self, pos: Vec3<f32>) {
+        for _ in 0..64 {
+            self.world_renderer
+                .tick(pos, &self.device, &mut self.uploader);
+            if self.world_renderer.chunk_manager().pending() == 0 {
+                return;
+            }
+        }
+        panic!("The chunks around {pos} are still loading");
+    }
 
+    /// Copies the last frame of an offscreen target into tightly packed
+    /// RGBA bytes. Returns `None` when drawing to a window.
+    pub fn read_pixels(&self) -> Option<Vec<u8>> {
+        let (texture, (width, height)) = match &self.target {
+            RenderTarget::Offscreen { texture, size } => (texture, *size),
+            RenderTarget::Surface(_) => return None,
+        };
+        // Rows of the copy must be aligned to 256 bytes
+        let unpadded_row = width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row = unpadded_row.div_ceil(align) * align;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
         self.queue.submit(std::iter::once(encoder.finish()));
-        surface_texture.present();
-        Ok(())
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().ok()?.ok()?;
+
+        let data = slice.get_mapped_range();
+        let pixels = data
+            .chunks(padded_row as usize)
+            .flat_map(|row| &row[..unpadded_row as usize])
+            .copied()
+            .collect();
+        drop(data);
+        buffer.unmap();
+        Some(pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::world::World;
+
+    use super::*;
+    use crate::{
+        block::BlockId,
+        ecs::{MeshHandle, Transform},
+        renderer::{bloom::BloomSettings, post_process::ColorGrading},
+        scene::{camera::Camera, camera_path::FAR_FLYOVER_X},
+        world::{border::WorldBorder, chunk::SURFACE_HEIGHT},
+    };
+
+    const WIDTH: u32 = 128;
+    const HEIGHT: u32 = 96;
+    /// Average hash of the frame below, with the bits of the
+    /// 8x8 downsampled luminance brighter than its mean set
+    const GOLDEN_HASH: u64 = 0xc3c3_c3c3_c1e7_ffff;
+    /// Bits allowed to differ, as drivers round and filter differently
+    const TOLERANCE: u32 = 4;
+
+    /// Where the frames below are drawn from, above the corner of the
+    /// origin chunk
+    const EYE: Vec3<f64> = Vec3::new(-6.0, 262.0, -6.0);
+
+    /// Draws the column of chunks at the origin from above its corner,
+    /// with the border keeping every other chunk from loading. `edit`
+    /// changes the renderer once the chunks are loaded.
+    fn render_origin_chunk(edit: impl FnOnce(&mut Renderer)) -> Vec<u8> {
+        let mut renderer = loaded_renderer(EYE, WorldBorder::new(0));
+        edit(&mut renderer);
+        // Remeshes what was edited
+        renderer.load_chunks_around(EYE.map(|v| v as f32));
+        draw_from(&mut renderer, EYE)
+    }
+
+    /// An offscreen renderer with the chunks around `eye` inside the border loaded
+    fn loaded_renderer(eye: Vec3<f64>, border: WorldBorder) -> Renderer {
+        let mut renderer = pollster::block_on(Renderer::new_offscreen(WIDTH, HEIGHT))
+            .expect("no adapter to render with");
+        renderer.world_renderer.set_border(border);
+        renderer.load_chunks_around(eye.map(|v| v as f32));
+        renderer
+    }
+
+    /// The camera of the frames below, looking down from `eye`
+    fn camera_at(eye: Vec3<f64>) -> Camera {
+        let mut camera = Camera::new(WIDTH as f32, HEIGHT as f32);
+        camera.teleport(eye);
+        camera.set_rotation(-45.0, -30.0);
+        camera
+    }
+
+    /// Draws the loaded world looking down from `eye`
+    fn draw_from(renderer: &mut Renderer, eye: Vec3<f64>) -> Vec<u8> {
+        let camera = camera_at(eye);
+        let pos = camera.pos.map(|v| v as f32);
+
+        // The animation clock stays at 0, so every frame is the same
+        renderer.camera_uniform.update(&camera, 1.0);
+        renderer.camera_uniform.emission = renderer.post_process.emission();
+        renderer
+            .camera_buffer
+            .stage(
+                &renderer.device,
+                &mut renderer.uploader,
+                &[renderer.camera_uniform],
+                0,
+            )
+            .unwrap();
+        let origin = camera.render_origin(1.0);
+        renderer.world_renderer.update_chunk_uniforms(
+            &renderer.device,
+            &mut renderer.uploader,
+            origin,
+        );
+        renderer
+            .world_renderer
+            .sort_for_drawing(pos, &camera.frustum(1.0));
+        renderer.gizmo_renderer.update(&renderer.queue, &camera);
+
+        let view = match &renderer.target {
+            RenderTarget::Offscreen { texture, .. } => {
+                texture.create_view(&wgpu::TextureViewDescriptor::default())
+            }
+            RenderTarget::Surface(_) => unreachable!(),
+        };
+        let mut encoder = renderer.uploader.take_encoder(&renderer.device);
+        renderer.draw_scene(&mut encoder, &view, 1.0);
+        renderer.uploader.finish();
+        renderer.queue.submit(std::iter::once(encoder.finish()));
+        renderer.uploader.recall();
+        renderer
+            .read_pixels()
+            .expect("offscreen targets can be read")
+    }
+
+    fn average_hash(pixels: &[u8]) -> u64 {
+        let mut cells = [0.0f32; 64];
+        for (index, pixel) in pixels.chunks_exact(4).enumerate() {
+            let x = index as u32 % WIDTH * 8 / WIDTH;
+            let y = index as u32 / WIDTH * 8 / HEIGHT;
+            let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|v| v as f32);
+            cells[(y * 8 + x) as usize] += 0.299 * r + 0.587 * g + 0.114 * b;
+        }
+        let mean = cells.iter().sum::<f32>() / 64.0;
+        cells
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| **cell > mean)
+            .fold(0, |hash, (bit, _)| hash | 1 << bit)
+    }
+
+    #[test]
+    fn origin_chunk_matches_the_golden_image() {
+        let pixels = render_origin_chunk(|_| {});
+        assert_eq!(pixels.len(), (WIDTH * HEIGHT * 4) as usize);
+        let hash = average_hash(&pixels);
+        let distance = (hash ^ GOLDEN_HASH).count_ones();
+        assert!(
+            distance <= TOLERANCE,
+            "frame hash {:#018x} is {} bits from {:#018x}",
+            hash,
+            distance,
+            GOLDEN_HASH
+        );
+    }
+
+    /// Hash of every byte of the frame below with a torch in view, as drawn
+    /// by the pipeline before bloom was added. Unlike the average hash it
+    /// is exact, so it only holds on the fallback adapter.
+    const PRE_BLOOM_PIXELS: u64 = 0x9001_aa3d_9567_8e6f;
+
+    /// FNV-1a over the bytes of the pixels
+    fn hash_pixels(pixels: &[u8]) -> u64 {
+        pixels.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// The frame below with a torch on the surface in front of the camera
+    fn render_torch(bloom: BloomSettings) -> Vec<u8> {
+        render_origin_chunk(|renderer| {
+            renderer
+                .post_process
+                .set_settings(&renderer.queue, ColorGrading::NEUTRAL, bloom);
+            renderer
+                .world_renderer
+                .set_block_world(Vec3::new(3, SURFACE_HEIGHT + 1, 3), BlockId::TORCH)
+                .expect("the origin chunks are loaded");
+        })
+    }
+
+    #[test]
+    fn frame_without_bloom_is_the_same_as_before_bloom() {
+        let off = BloomSettings {
+            enabled: false,
+            ..BloomSettings::default()
+        };
+        let pixels = render_torch(off);
+        assert_eq!(hash_pixels(&pixels), PRE_BLOOM_PIXELS);
+        // The torch glows with bloom on
+        assert_ne!(render_torch(BloomSettings::default()), pixels);
+    }
+
+    fn differing_pixels(a: &[u8], b: &[u8]) -> usize {
+        a.chunks_exact(4)
+            .zip(b.chunks_exact(4))
+            .filter(|(a, b)| a != b)
+            .count()
+    }
+
+    #[test]
+    fn far_frames_are_the_same_as_at_the_origin() {
+        // The terrain is the same everywhere, only the precision changes
+        let far = EYE + Vec3::unit_x() * FAR_FLYOVER_X;
+        let step = Vec3::new(0.3, 0.0, 0.3);
+        // Dropped before the next one, the GL fallback fails to make a
+        // second display while the first is alive
+        let near = {
+            let mut renderer = loaded_renderer(EYE, WorldBorder::NONE);
+            [EYE, EYE + step].map(|eye| draw_from(&mut renderer, eye))
+        };
+        let mut renderer = loaded_renderer(far, WorldBorder::NONE);
+        let far_frame = draw_from(&mut renderer, far);
+        assert_eq!(differing_pixels(&near[0], &far_frame), 0);
+        // Single precision would round the step away that far out
+        let far_stepped = draw_from(&mut renderer, far + step);
+        assert_eq!(differing_pixels(&near[1], &far_stepped), 0);
+        assert_ne!(differing_pixels(&far_frame, &far_stepped), 0);
+    }
+
+    #[test]
+    fn nearer_of_two_close_faces_wins_far_away() {
+        // 60 blocks away, depth that isn't reversed has steps of about 0.002
+        // blocks and can't tell the faces of the cubes apart. No chunks are
+        // loaded, so the cubes are drawn over the sky.
+        let eye = Vec3::zero();
+        let camera = camera_at(eye);
+        let center = camera.pos.map(|v| v as f32) + camera.direction() * 60.0;
+        let near = center - camera.direction() * 0.001;
+        let render = |cubes: &[(BlockId, Vec3<f32>)]| {
+            let mut world = World::new();
+            for (block, pos) in cubes {
+                // Large enough for their faces to cover many pixels
+                let transform = Transform {
+                    scale: 16.0,
+                    ..Transform::at(*pos)
+                };
+                world.spawn((MeshHandle::Block(*block), transform));
+            }
+            let mut renderer = pollster::block_on(Renderer::new_offscreen(WIDTH, HEIGHT))
+                .expect("no adapter to render with");
+            renderer.entity_renderer.update(
+                &renderer.device,
+                &renderer.queue,
+                &world,
+                &camera,
+                1.0,
+            );
+            draw_from(&mut renderer, eye)
+        };
+        // Either block in front, as one of them is drawn first where depth ties
+        for (front, back) in [
+            (BlockId::DIRT, BlockId::STONE),
+            (BlockId::STONE, BlockId::DIRT),
+        ] {
+            let alone = render(&[(front, near)]);
+            assert_ne!(differing_pixels(&alone, &render(&[(back, center)])), 0);
+            let both = render(&[(front, near), (back, center)]);
+            assert_eq!(differing_pixels(&both, &alone), 0);
+        }
+    }
+
+    #[test]
+    fn read_pixels_drops_the_row_padding() {
+        // 100 pixels make rows of 400 bytes, padded to 512 in the copy
+        let renderer = pollster::block_on(Renderer::new_offscreen(100, 3)).unwrap();
+        let texture = match &renderer.target {
+            RenderTarget::Offscreen { texture, .. } => texture,
+            RenderTarget::Surface(_) => unreachable!(),
+        };
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = renderer
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 1.0,
+                        g: 0.0,
+                        b: 1.0,
+                        a: 1.0,
+                    }),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        renderer.queue.submit(std::iter::once(encoder.finish()));
+
+        let pixels = renderer
+            .read_pixels()
+            .expect("offscreen targets can be read");
+        assert_eq!(pixels.len(), 100 * 3 * 4);
+        assert!(pixels
+            .chunks_exact(4)
+            .all(|pixel| pixel == [255, 0, 255, 255]));
     }
 }
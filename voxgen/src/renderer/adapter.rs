@@ -0,0 +1,292 @@
+use std::{fmt::Display, str::FromStr};
+
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+use serde::{Deserialize, Serialize};
+
+use super::RendererError;
+
+/// Graphics API the renderer runs on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Whichever the platform supports best
+    #[default]
+    Auto,
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 5] = [
+        Backend::Auto,
+        Backend::Vulkan,
+        Backend::Dx12,
+        Backend::Metal,
+        Backend::Gl,
+    ];
+
+    fn backends(&self) -> wgpu::Backends {
+        match self {
+            Backend::Auto => wgpu::Backends::all(),
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Backend::Auto => "auto",
+            Backend::Vulkan => "vulkan",
+            Backend::Dx12 => "dx12",
+            Backend::Metal => "metal",
+            Backend::Gl => "gl",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Backend::ALL
+            .into_iter()
+            .find(|backend| backend.to_string() == s.to_lowercase())
+            .ok_or_else(|| {
+                format!(
+                    "unknown backend '{}', expected auto, vulkan, dx12, metal or gl",
+                    s
+                )
+            })
+    }
+}
+
+/// Whether to pick the integrated or the discrete GPU when there are both
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerPreference {
+    #[default]
+    Low,
+    High,
+}
+
+impl PowerPreference {
+    pub const ALL: [PowerPreference; 2] = [PowerPreference::Low, PowerPreference::High];
+}
+
+impl From<PowerPreference> for wgpu::PowerPreference {
+    fn from(preference: PowerPreference) -> Self {
+        match preference {
+            PowerPreference::Low => wgpu::PowerPreference::LowPower,
+            PowerPreference::High => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+impl Display for PowerPreference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PowerPreference::Low => f.write_str("low"),
+            PowerPreference::High => f.write_str("high"),
+        }
+    }
+}
+
+impl FromStr for PowerPreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PowerPreference::ALL
+            .into_iter()
+            .find(|preference| preference.to_string() == s.to_lowercase())
+            .ok_or_else(|| format!("unknown power preference '{}', expected low or high", s))
+    }
+}
+
+/// Which graphics adapter the renderer asks for first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AdapterPreferences {
+    pub backend: Backend,
+    pub power: PowerPreference,
+}
+
+/// One way of asking for an adapter
+#[derive(Debug, Clone, Copy)]
+struct Attempt {
+    backends: wgpu::Backends,
+    force_fallback_adapter: bool,
+}
+
+/// An adapter along with the surface of the window created for it
+pub struct SelectedAdapter {
+    pub adapter: wgpu::Adapter,
+    pub surface: Option<wgpu::Surface>,
+}
+
+/// Logs every adapter found on the machine, to help choosing one
+pub fn log_adapters() {
+    let instance = create_instance(wgpu::Backends::all());
+    for adapter in instance.enumerate_adapters(wgpu::Backends::all()) {
+        let info = adapter.get_info();
+        log::info!(
+            "Found adapter {} ({:?}, {:?})",
+            info.name,
+            info.device_type,
+            info.backend
+        );
+    }
+}
+
+/// Requests the preferred adapter for the window. If there is none, any
+/// backend is tried and then the software fallback adapter.
+pub async fn request_for_window<W>(
+    window: &W,
+    preferences: AdapterPreferences,
+) -> Result<SelectedAdapter, RendererError>
+where
+    W: HasRawWindowHandle + HasRawDisplayHandle,
+{
+    let mut attempts = vec![Attempt {
+        backends: preferences.backend.backends(),
+        force_fallback_adapter: false,
+    }];
+    if preferences.backend != Backend::Auto {
+        attempts.push(Attempt {
+            backends: wgpu::Backends::all(),
+            force_fallback_adapter: false,
+        });
+    }
+    attempts.push(Attempt {
+        backends: wgpu::Backends::all(),
+        force_fallback_adapter: true,
+    });
+    request(Some(window), &attempts, preferences.power).await
+}
+
+/// Requests the software fallback adapter, or a hardware one if there is none
+pub async fn request_offscreen() -> Result<SelectedAdapter, RendererError> {
+    let attempts = [true, false].map(|force_fallback_adapter| Attempt {
+        backends: wgpu::Backends::all(),
+        force_fallback_adapter,
+    });
+    // Without a window there is nothing to create a surface for
+    request::<winit::window::Window>(None, &attempts, PowerPreference::default()).await
+}
+
+/// A device for tests that need the GPU, on the fallback adapter when there
+/// is one. Tests using it fail on machines without any adapter.
+#[cfg(test)]
+pub fn test_device() -> (wgpu::Device, wgpu::Queue) {
+    pollster::block_on(async {
+        let adapter = request_offscreen()
+            .await
+            .expect("no adapter to test with")
+            .adapter;
+        adapter
+            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .await
+            .expect("no device to test with")
+    })
+}
+
+async fn request<W>(
+    window: Option<&W>,
+    attempts: &[Attempt],
+    power: PowerPreference,
+) -> Result<SelectedAdapter, RendererError>
+where
+    W: HasRawWindowHandle + HasRawDisplayHandle,
+{
+    let mut surface_error = None;
+    for (i, attempt) in attempts.iter().enumerate() {
+        // Surfaces belong to the instance, which is limited to the backends tried
+        let instance = create_instance(attempt.backends);
+        let surface = match window.map(|window| unsafe { instance.create_surface(window) }) {
+            Some(Ok(surface)) => Some(surface),
+            Some(Err(e)) => {
+                log::warn!("Failed to create a surface with {:?}: {}", attempt, e);
+                surface_error = Some(e);
+                continue;
+            }
+            None => None,
+        };
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: power.into(),
+                compatible_surface: surface.as_ref(),
+                force_fallback_adapter: attempt.force_fallback_adapter,
+            })
+            .await;
+        match adapter {
+            Some(adapter) => {
+                if i > 0 {
+                    log::warn!("No adapter matched the preferences, using {:?}", attempt);
+                }
+                return Ok(SelectedAdapter { adapter, surface });
+            }
+            None => log::warn!("No adapter found with {:?}", attempt),
+        }
+    }
+    Err(surface_error.map_or(RendererError::NoAdapter, RendererError::Surface))
+}
+
+fn create_instance(backends: wgpu::Backends) -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        dx12_shader_compiler: Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asks for adapters without a window, like the offscreen renderer
+    fn request_with(attempts: &[Attempt]) -> Result<SelectedAdapter, RendererError> {
+        pollster::block_on(request::<winit::window::Window>(
+            None,
+            attempts,
+            PowerPreference::default(),
+        ))
+    }
+
+    const NO_BACKEND: Attempt = Attempt {
+        backends: wgpu::Backends::empty(),
+        force_fallback_adapter: false,
+    };
+    const FALLBACK: Attempt = Attempt {
+        backends: wgpu::Backends::all(),
+        force_fallback_adapter: true,
+    };
+
+    #[test]
+    fn falls_back_after_failed_attempts() {
+        let selected = request_with(&[NO_BACKEND, FALLBACK]).unwrap();
+        assert!(selected.surface.is_none());
+        assert_eq!(
+            selected.adapter.get_info().device_type,
+            wgpu::DeviceType::Cpu
+        );
+    }
+
+    #[test]
+    fn no_adapter_without_attempts_left() {
+        assert!(matches!(
+            request_with(&[NO_BACKEND]),
+            Err(RendererError::NoAdapter)
+        ));
+        assert!(matches!(request_with(&[]), Err(RendererError::NoAdapter)));
+    }
+
+    #[test]
+    fn offscreen_renderer_uses_the_fallback_adapter() {
+        let renderer = pollster::block_on(crate::renderer::Renderer::new_offscreen(4, 4)).unwrap();
+        assert!(renderer.read_pixels().is_some());
+    }
+}
@@ -1,17 +1,34 @@
 use crate::{block::BlockId, direction::Direction};
 
-use super::texture::Texture;
+use super::{
+    error::ReloadError,
+    texture::{SamplerSettings, Texture},
+    RendererError,
+};
 
 type Uv = [f32; 2];
 
+pub const ATLAS_PNG: &[u8] = include_bytes!("../../../assets/atlas.png");
+/// Where atlas.png is read from when the assets are reloaded
+pub const ATLAS_PATH: &str = "assets/atlas.png";
+/// The 16 texel tiles are halved down to a single texel, smaller mipmaps
+/// would blend tiles together
+const MIP_LEVELS: u32 = 5;
+
 pub struct Atlas {
     pub texture: Texture,
     pub bind_group: wgpu::BindGroup,
     pub bind_group_layout: wgpu::BindGroupLayout,
+    /// How `texture` is sampled
+    filtering: SamplerSettings,
 }
 
 impl Atlas {
-    pub fn new(texture: &[u8], device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+    pub fn new(
+        texture: &[u8],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Self, RendererError> {
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Atlas bind group layout"),
             entries: &[
@@ -35,11 +52,31 @@ impl Atlas {
                 },
             ],
         });
-        let texture: Texture = Texture::from_bytes(device, queue, texture, "atlas.png");
+        let mut texture = Texture::from_bytes(device, queue, texture, "atlas.png", MIP_LEVELS)
+            .map_err(|source| RendererError::Texture {
+                label: "atlas.png".to_string(),
+                source,
+            })?;
+        let filtering = SamplerSettings::default();
+        texture.sampler = filtering.create_sampler(device, false);
+
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &texture);
+        Ok(Self {
+            texture,
+            bind_group,
+            bind_group_layout,
+            filtering,
+        })
+    }
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        texture: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Atlas bind group"),
-            layout: &bind_group_layout,
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -50,17 +87,52 @@ impl Atlas {
                     resource: wgpu::BindingResource::Sampler(&texture.sampler),
                 },
             ],
-        });
-        Self {
-            texture,
-            bind_group,
-            bind_group_layout,
+        })
+    }
+
+    pub fn filtering(&self) -> SamplerSettings {
+        self.filtering
+    }
+
+    /// Samples the atlas with a new sampler. Only the bind group is
+    /// recreated, the pipelines using its layout are kept.
+    pub fn set_filtering(
+        &mut self,
+        device: &wgpu::Device,
+        filtering: SamplerSettings,
+        anisotropic_filtering: bool,
+    ) {
+        self.texture.sampler = filtering.create_sampler(device, anisotropic_filtering);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.texture);
+        self.filtering = filtering;
+    }
+
+    /// Replaces the texture with a new version of atlas.png, sampled the
+    /// same way. The tiles are laid out by [`TextureId`], so the image
+    /// must stay the same size.
+    pub fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+    ) -> Result<(), ReloadError> {
+        let image = image::load_from_memory(bytes)?;
+        let size = ATLAS_SIZE as u32;
+        if image.width() != size || image.height() != size {
+            return Err(ReloadError::AtlasSize(image.width(), image.height()));
         }
+        let mut texture = Texture::from_image(device, queue, &image, Some("atlas.png"), MIP_LEVELS);
+        std::mem::swap(&mut texture.sampler, &mut self.texture.sampler);
+        self.texture = texture;
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.texture);
+        Ok(())
     }
 }
 
 const ATLAS_SIZE: f32 = 256.0;
 const SUB_TEXTURE_SIZE: f32 = 16.0;
+/// Size of a tile in texture coordinates
+pub const TILE_UV_SIZE: f32 = SUB_TEXTURE_SIZE / ATLAS_SIZE;
 
 pub fn atlas_uv_mapping(texture_id: &TextureId, x: u8, y: u8) -> Uv {
     let mut offset_x = (*texture_id as u8 % 16) as f32 * SUB_TEXTURE_SIZE;
@@ -74,30 +146,88 @@ pub fn atlas_uv_mapping(texture_id: &TextureId, x: u8, y: u8) -> Uv {
         offset_y += 16.0;
     }
 
-    return [offset_x / ATLAS_SIZE, offset_y / ATLAS_SIZE];
+    [offset_x / ATLAS_SIZE, offset_y / ATLAS_SIZE]
 }
 impl BlockId {
-    pub fn map_texture(&self, corner: [u8; 2], dir: &Direction) -> Uv {
-        let id = match self {
-            BlockId::AIR => TextureId::Dirt,
-            BlockId::DIRT => TextureId::Dirt,
-            BlockId::GRASS => match dir {
-                Direction::Up => TextureId::GrassFull,
-                Direction::Down => TextureId::Dirt,
-                Direction::Left => TextureId::Grass,
-                Direction::Right => TextureId::Grass,
-                Direction::Back => TextureId::Grass,
-                Direction::Front => TextureId::Grass,
-            },
-            BlockId::STONE => TextureId::Stone,
-        };
-        atlas_uv_mapping(&id, corner[0], corner[1])
+    pub fn map_texture(self, corner: [u8; 2], dir: Direction) -> Uv {
+        atlas_uv_mapping(&self.texture_id(dir), corner[0], corner[1])
+    }
+
+    /// The atlas tile shown on the given face of the block
+    pub fn texture_id(self, dir: Direction) -> TextureId {
+        self.properties().textures[usize::from(dir)]
     }
 }
-#[derive(Copy, Clone)]
+/// The tiles of the atlas, in the order they are laid out
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TextureId {
     Dirt,
     Grass,
     GrassFull,
     Stone,
+    Bedrock,
+    Water,
+    Glass,
+    Torch,
+    GrassTuft,
+    Sand,
+    Glowstone,
+}
+
+/// An animated tile, whose frames are stacked vertically
+/// below its first frame in the atlas
+#[derive(Debug, Clone, Copy)]
+pub struct Animation {
+    pub frames: u32,
+    /// Seconds each frame is shown for
+    pub frame_time: f32,
+}
+
+impl TextureId {
+    pub const ALL: [TextureId; 11] = [
+        TextureId::Dirt,
+        TextureId::Grass,
+        TextureId::GrassFull,
+        TextureId::Stone,
+        TextureId::Bedrock,
+        TextureId::Water,
+        TextureId::Glass,
+        TextureId::Torch,
+        TextureId::GrassTuft,
+        TextureId::Sand,
+        TextureId::Glowstone,
+    ];
+
+    /// The name blocks.ron refers to the tile by
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextureId::Dirt => "dirt",
+            TextureId::Grass => "grass_side",
+            TextureId::GrassFull => "grass_top",
+            TextureId::Stone => "stone",
+            TextureId::Bedrock => "bedrock",
+            TextureId::Water => "water",
+            TextureId::Glass => "glass",
+            TextureId::Torch => "torch",
+            TextureId::GrassTuft => "grass_tuft",
+            TextureId::Sand => "sand",
+            TextureId::Glowstone => "glowstone",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TextureId> {
+        TextureId::ALL
+            .into_iter()
+            .find(|texture| texture.name() == name)
+    }
+
+    pub fn animation(&self) -> Option<Animation> {
+        match self {
+            TextureId::Water => Some(Animation {
+                frames: 4,
+                frame_time: 0.25,
+            }),
+            _ => None,
+        }
+    }
 }
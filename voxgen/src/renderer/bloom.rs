@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+
+use super::{buffer::Buffer, pipelines::bloom::BloomPipelines, texture::Texture};
+
+/// Mips of the bloom chain, each half the size of the previous one
+/// starting from half the size of the frame. More mips spread the glow
+/// further.
+pub const BLOOM_MIPS: usize = 5;
+/// Brightness emissive blocks add on top of their light while bloom is
+/// on, which takes them past the threshold
+pub const EMISSIVE_BRIGHTNESS: f32 = 1.0;
+
+/// How bright parts of the scene bleed light around them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    /// Brightness above which colors bloom, 1 for white
+    pub threshold: f32,
+    /// How much of the bloom is added onto the scene
+    pub intensity: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 1.0,
+            intensity: 0.3,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomUniform {
+    threshold: f32,
+    _padding: [f32; 3],
+}
+
+/// Spreads the light of the brightest parts of the scene: they are
+/// halved down a chain of mips, then each mip is blurred and added onto
+/// the larger one, ending in the first mip
+pub struct Bloom {
+    mips: Vec<Texture>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// Reading the scene, then each of the mips
+    bind_groups: Vec<wgpu::BindGroup>,
+    pipelines: BloomPipelines,
+    uniform: Buffer<BloomUniform>,
+    threshold: f32,
+}
+
+impl Bloom {
+    /// `cfg` is the configuration the scene is drawn with
+    pub fn new(device: &wgpu::Device, cfg: &wgpu::SurfaceConfiguration, scene: &Texture) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bloom bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let threshold = BloomSettings::default().threshold;
+        let uniform = Buffer::new(
+            device,
+            "Bloom uniform buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            &[BloomUniform {
+                threshold,
+                _padding: [0.0; 3],
+            }],
+        );
+        let pipelines = BloomPipelines::new(device, cfg, &[&bind_group_layout]);
+        let mut bloom = Self {
+            mips: Vec::new(),
+            bind_group_layout,
+            bind_groups: Vec::new(),
+            pipelines,
+            uniform,
+            threshold,
+        };
+        bloom.resize(device, cfg, scene);
+        bloom
+    }
+
+    /// Recreates the mips at the new size of the scene, which was
+    /// recreated as well
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        cfg: &wgpu::SurfaceConfiguration,
+        scene: &Texture,
+    ) {
+        self.mips = (1..=BLOOM_MIPS)
+            .map(|mip| {
+                let size = ((cfg.width >> mip).max(1), (cfg.height >> mip).max(1));
+                Texture::render_target(device, size, cfg.format, "Bloom mip")
+            })
+            .collect();
+        self.bind_groups = std::iter::once(scene)
+            .chain(&self.mips)
+            .map(|source| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Bloom bind group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&source.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&source.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: self.uniform.buf.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+    }
+
+    pub fn set_threshold(&mut self, queue: &wgpu::Queue, threshold: f32) {
+        if threshold != self.threshold {
+            self.threshold = threshold;
+            self.uniform
+                .update(
+                    queue,
+                    &[BloomUniform {
+                        threshold,
+                        _padding: [0.0; 3],
+                    }],
+                    0,
+                )
+                .expect("The uniform buffer holds one uniform");
+        }
+    }
+
+    /// The bloom of the last frame, half the size of the scene
+    pub fn output(&self) -> &Texture {
+        &self.mips[0]
+    }
+
+    /// Blooms the scene into the first mip
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder) {
+        // The scene, then each mip, is halved into the next mip. The bind
+        // group of a mip comes after the one of the scene.
+        for (mip, target) in self.mips.iter().enumerate() {
+            let pipeline = if mip == 0 {
+                &self.pipelines.threshold
+            } else {
+                &self.pipelines.downsample
+            };
+            self.pass(
+                encoder,
+                target,
+                &self.bind_groups[mip],
+                pipeline,
+                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            );
+        }
+        // Then each mip is blurred back up onto the larger one
+        for mip in (0..BLOOM_MIPS - 1).rev() {
+            self.pass(
+                encoder,
+                &self.mips[mip],
+                &self.bind_groups[mip + 2],
+                &self.pipelines.upsample,
+                wgpu::LoadOp::Load,
+            );
+        }
+    }
+
+    fn pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &Texture,
+        source: &wgpu::BindGroup,
+        pipeline: &wgpu::RenderPipeline,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Bloom Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: wgpu::Operations { load, store: true },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, source, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
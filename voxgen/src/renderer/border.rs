@@ -0,0 +1,136 @@
+use vek::{Vec2, Vec3};
+
+use crate::world::{border::WorldBorder, chunk::CHUNK_WIDTH};
+
+use super::{
+    buffer::Buffer, mesh::border::BorderVertex, pipelines::border::BorderPipeline, Renderable,
+};
+
+/// Draws the world border as a translucent wall with scrolling stripes
+pub struct BorderRenderer {
+    pipeline: BorderPipeline,
+    /// Missing without a border
+    vertices: Option<Buffer<BorderVertex>>,
+    num_vertices: u32,
+    /// The border and render origin the vertices were built for
+    border: WorldBorder,
+    origin: Vec3<i32>,
+}
+
+impl Renderable for BorderRenderer {
+    fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        global_uniforms: &'a wgpu::BindGroup,
+    ) {
+        let vertices = match &self.vertices {
+            Some(vertices) => vertices,
+            None => return,
+        };
+        render_pass.set_pipeline(&self.pipeline.pipeline);
+        render_pass.set_bind_group(0, global_uniforms, &[]);
+        render_pass.set_vertex_buffer(0, vertices.buf.slice(..));
+        render_pass.draw(0..self.num_vertices, 0..1);
+    }
+}
+
+impl BorderRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        sfc: &wgpu::SurfaceConfiguration,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self {
+            pipeline: BorderPipeline::new(device, sfc, &[transform_bind_group_layout]),
+            vertices: None,
+            num_vertices: 0,
+            border: WorldBorder::NONE,
+            origin: Vec3::zero(),
+        }
+    }
+
+    /// Rebuilds the wall when the border or the render origin moved
+    pub fn update(&mut self, device: &wgpu::Device, border: WorldBorder, origin: Vec3<i32>) {
+        if border == self.border && origin == self.origin {
+            return;
+        }
+        self.border = border;
+        self.origin = origin;
+        let vertices = wall(border, origin);
+        self.num_vertices = vertices.len() as u32;
+        self.vertices = (!vertices.is_empty()).then(|| {
+            Buffer::new(
+                device,
+                "Border vertex buffer",
+                wgpu::BufferUsages::VERTEX,
+                &vertices,
+            )
+        });
+    }
+}
+
+/// Blocks the wall reaches above and below the render origin. The world has
+/// no height limit, so the wall follows the camera up and down.
+const WALL_REACH: f32 = 1024.0;
+
+/// The four sides of the border as a strip of chunk wide quads spanning
+/// `WALL_REACH` around the height of `origin`, two triangles each, relative to `origin`
+fn wall(border: WorldBorder, origin: Vec3<i32>) -> Vec<BorderVertex> {
+    let (min, max) = match border.bounds() {
+        Some(bounds) => bounds,
+        None => return Vec::new(),
+    };
+    let corners = [
+        Vec2::new(min.x, min.y),
+        Vec2::new(max.x, min.y),
+        Vec2::new(max.x, max.y),
+        Vec2::new(min.x, max.y),
+    ];
+    let offset = origin.map(|v| v as f32);
+    // Blocks are centered on integer coordinates
+    let (bottom, top) = (offset.y - WALL_REACH - 0.5, offset.y + WALL_REACH + 0.5);
+    let mut vertices = Vec::new();
+    // Distance from the first corner, the stripes continue around the corners
+    let mut along = 0.0;
+    for side in 0..4 {
+        let (from, to) = (corners[side], corners[(side + 1) % 4]);
+        let length = from.distance(to);
+        let segments = (length / CHUNK_WIDTH as f32).round() as usize;
+        for segment in 0..segments {
+            let start = segment as f32 / segments as f32;
+            let end = (segment + 1) as f32 / segments as f32;
+            let (a, b) = (Vec2::lerp(from, to, start), Vec2::lerp(from, to, end));
+            let (u0, u1) = (along + length * start, along + length * end);
+            let corner = |pos: Vec2<f32>, u: f32, y: f32| BorderVertex {
+                pos: [pos.x - offset.x, y - offset.y, pos.y - offset.z],
+                wall_pos: [u, y],
+            };
+            vertices.extend([
+                corner(a, u0, bottom),
+                corner(b, u1, bottom),
+                corner(b, u1, top),
+                corner(a, u0, bottom),
+                corner(b, u1, top),
+                corner(a, u0, top),
+            ]);
+        }
+        along += length;
+    }
+    vertices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wall_follows_the_origin_up_and_down() {
+        for y in [-4096, 0, 256, 4096] {
+            let vertices = wall(WorldBorder::new(1), Vec3::new(16, y, -16));
+            let heights = vertices.iter().map(|vertex| vertex.pos[1]);
+            let bottom = heights.clone().fold(f32::MAX, f32::min);
+            let top = heights.fold(f32::MIN, f32::max);
+            assert_eq!((bottom, top), (-WALL_REACH - 0.5, WALL_REACH + 0.5));
+        }
+    }
+}
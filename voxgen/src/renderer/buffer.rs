@@ -1,75 +1,189 @@
 use bytemuck::Pod;
 use wgpu::util::DeviceExt;
 
-use crate::world::chunk::ChunkMesh;
-
-use super::mesh::vertex::Vertex;
+use super::{error::CapacityError, mesh::vertex::Vertex, pool::Allocation, upload::Uploader};
 
+/// Where the meshes of a chunk are in the [`ChunkPool`](super::pool::ChunkPool),
+/// `None` for empty meshes
+#[derive(Debug, Default)]
 pub struct ChunkBuffer {
-    pub vertex_buf: Buffer<Vertex>,
-    pub index_buf: Buffer<u32>,
+    pub vertices: Option<Allocation>,
+    pub indices: Option<Allocation>,
     pub indices_len: u32,
 }
 
 impl ChunkBuffer {
-    pub fn new(
-        device: &wgpu::Device,
-        vertices: &Vec<Vertex>,
-        indices: &Vec<u32>,
-        num_elements: u32,
-    ) -> Self {
-        Self {
-            vertex_buf: Buffer::new(
-                device,
-                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                &vertices,
-            ),
-            index_buf: Buffer::new(
-                device,
-                wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
-                &indices,
-            ),
-            indices_len: num_elements,
-        }
+    pub fn vertices_len(&self) -> usize {
+        self.vertices
+            .as_ref()
+            .map_or(0, |vertices| vertices.range.len())
     }
-    pub fn update(&mut self, queue: &wgpu::Queue, mesh: &ChunkMesh) {
-        self.vertex_buf.update(queue, &mesh.vertices, 0);
-        self.index_buf.update(queue, &mesh.indices, 0);
-        self.indices_len = mesh.num_elements;
+
+    /// Size of the vertex and index allocations in bytes
+    pub fn size(&self) -> u64 {
+        let indices = self
+            .indices
+            .as_ref()
+            .map_or(0, |indices| indices.range.len());
+        (self.vertices_len() * std::mem::size_of::<Vertex>() + indices * std::mem::size_of::<u32>())
+            as u64
     }
 }
 
 pub struct Buffer<T: Copy + bytemuck::Pod> {
     pub buf: wgpu::Buffer,
+    /// Name of the buffer in frame captures, kept when it's recreated
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    /// Elements written by `new` or the last `set`
     len: usize,
+    /// Elements the buffer has room for
+    capacity: usize,
     phantom_data: std::marker::PhantomData<T>,
 }
 
 impl<T: Copy + Pod> Buffer<T> {
-    pub fn new(device: &wgpu::Device, usage: wgpu::BufferUsages, data: &[T]) -> Self {
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        data: &[T],
+    ) -> Self {
         Self {
             buf: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: None,
+                label: Some(label),
                 contents: bytemuck::cast_slice(data),
                 usage,
             }),
+            label,
+            usage,
             len: data.len(),
+            capacity: data.len(),
             phantom_data: std::marker::PhantomData,
         }
     }
-    pub fn update(&self, queue: &wgpu::Queue, data: &[T], offset: usize) {
-        if data.is_empty() {
-            return;
+    /// Creates an empty buffer with room for `capacity` elements, for
+    /// contents that change size like debug lines and instances
+    pub fn with_capacity(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        capacity: usize,
+    ) -> Self {
+        Self {
+            buf: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: (capacity * std::mem::size_of::<T>()) as wgpu::BufferAddress,
+                usage,
+                mapped_at_creation: false,
+            }),
+            label,
+            usage,
+            len: 0,
+            capacity,
+            phantom_data: std::marker::PhantomData,
         }
-        queue.write_buffer(
-            &self.buf,
-            offset as u64 * std::mem::size_of::<T>() as u64,
-            bytemuck::cast_slice(data),
-        )
+    }
+    /// Whether `data` fits at `offset`
+    fn fits(&self, data: &[T], offset: usize) -> Result<(), CapacityError> {
+        if offset + data.len() <= self.capacity {
+            Ok(())
+        } else {
+            Err(CapacityError {
+                offset,
+                len: data.len(),
+                capacity: self.capacity,
+            })
+        }
+    }
+    /// Overwrites elements starting at `offset`. Data that doesn't fit
+    /// isn't written at all, use `set` for contents that can grow.
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        data: &[T],
+        offset: usize,
+    ) -> Result<(), CapacityError> {
+        self.fits(data, offset)?;
+        self.write(queue, data, offset);
+        Ok(())
+    }
+    /// Like `update`, but staged to be copied when the next frame is submitted
+    pub fn stage(
+        &self,
+        device: &wgpu::Device,
+        uploader: &mut Uploader,
+        data: &[T],
+        offset: usize,
+    ) -> Result<(), CapacityError> {
+        self.fits(data, offset)?;
+        self.write_staged(device, uploader, data, offset);
+        Ok(())
+    }
+    fn write(&self, queue: &wgpu::Queue, data: &[T], offset: usize) {
+        if !data.is_empty() {
+            queue.write_buffer(
+                &self.buf,
+                offset as u64 * std::mem::size_of::<T>() as u64,
+                bytemuck::cast_slice(data),
+            );
+        }
+    }
+    fn write_staged(
+        &self,
+        device: &wgpu::Device,
+        uploader: &mut Uploader,
+        data: &[T],
+        offset: usize,
+    ) {
+        if !data.is_empty() {
+            uploader.write(
+                device,
+                &self.buf,
+                offset as u64 * std::mem::size_of::<T>() as u64,
+                bytemuck::cast_slice(data),
+            );
+        }
+    }
+    /// Recreates the buffer with room for at least `len` elements, twice
+    /// what is needed, if it has less. Returns whether it was recreated.
+    fn reserve(&mut self, device: &wgpu::Device, len: usize) -> bool {
+        let grown = len > self.capacity;
+        if grown {
+            *self = Self::with_capacity(device, self.label, self.usage, len.next_power_of_two());
+        }
+        grown
+    }
+    /// Replaces the contents, recreating the buffer with twice the room
+    /// when they don't fit. Returns whether it was recreated, in which
+    /// case bind groups referring to it must be created again.
+    pub fn set(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[T]) -> bool {
+        let grown = self.reserve(device, data.len());
+        self.write(queue, data, 0);
+        self.len = data.len();
+        grown
+    }
+    /// Like `set`, but staged to be copied when the next frame is submitted
+    pub fn set_staged(
+        &mut self,
+        device: &wgpu::Device,
+        uploader: &mut Uploader,
+        data: &[T],
+    ) -> bool {
+        let grown = self.reserve(device, data.len());
+        self.write_staged(device, uploader, data, 0);
+        self.len = data.len();
+        grown
     }
     pub fn len(&self) -> usize {
         self.len
     }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
 }
 
 pub fn compute_cube_indices(vertices: usize) -> Vec<u32> {
@@ -86,5 +200,93 @@ pub fn compute_cube_indices(vertices: usize) -> Vec<u32> {
 pub fn create_cube_index_buffer(device: &wgpu::Device) -> Buffer<u32> {
     let vertices = 24;
     let indices = compute_cube_indices(vertices);
-    Buffer::new(&device, wgpu::BufferUsages::INDEX, &indices)
+    Buffer::new(
+        device,
+        "Cube index buffer",
+        wgpu::BufferUsages::INDEX,
+        &indices,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::adapter::test_device;
+
+    const USAGE: wgpu::BufferUsages =
+        wgpu::BufferUsages::COPY_SRC.union(wgpu::BufferUsages::COPY_DST);
+
+    /// Copies the whole buffer back, up to its capacity
+    fn read(device: &wgpu::Device, queue: &wgpu::Queue, buffer: &Buffer<u32>) -> Vec<u32> {
+        let size = (buffer.capacity() * std::mem::size_of::<u32>()) as wgpu::BufferAddress;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Test readback buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Test readback encoder"),
+        });
+        encoder.copy_buffer_to_buffer(&buffer.buf, 0, &readback, 0, size);
+        queue.submit(std::iter::once(encoder.finish()));
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::Maintain::Wait);
+        let data = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        data
+    }
+
+    #[test]
+    fn set_exact_fit_and_shrink_keep_the_buffer() {
+        let (device, queue) = test_device();
+        let mut buffer = Buffer::<u32>::with_capacity(&device, "Test buffer", USAGE, 4);
+        assert!(buffer.is_empty());
+        assert!(!buffer.set(&device, &queue, &[1, 2, 3, 4]));
+        assert_eq!((buffer.len(), buffer.capacity()), (4, 4));
+        assert_eq!(read(&device, &queue, &buffer), [1, 2, 3, 4]);
+        assert!(!buffer.set(&device, &queue, &[5, 6]));
+        assert_eq!((buffer.len(), buffer.capacity()), (2, 4));
+        // Elements past the length are left as they were
+        assert_eq!(read(&device, &queue, &buffer), [5, 6, 3, 4]);
+    }
+
+    #[test]
+    fn set_grows_the_buffer() {
+        let (device, queue) = test_device();
+        let mut buffer = Buffer::new(&device, "Test buffer", USAGE, &[1u32, 2, 3]);
+        assert_eq!((buffer.len(), buffer.capacity()), (3, 3));
+        assert!(buffer.set(&device, &queue, &[1, 2, 3, 4, 5]));
+        assert_eq!((buffer.len(), buffer.capacity()), (5, 8));
+        assert_eq!(read(&device, &queue, &buffer)[..5], [1, 2, 3, 4, 5]);
+        assert!(!buffer.set(&device, &queue, &[9; 8]));
+        assert_eq!(buffer.capacity(), 8);
+    }
+
+    #[test]
+    fn update_never_grows() {
+        let (device, queue) = test_device();
+        let buffer = Buffer::new(&device, "Test buffer", USAGE, &[0u32; 4]);
+        assert_eq!(buffer.update(&queue, &[7, 8], 2), Ok(()));
+        assert_eq!(read(&device, &queue, &buffer), [0, 0, 7, 8]);
+        // Writes that don't fit fail without writing anything
+        assert_eq!(
+            buffer.update(&queue, &[1, 2], 3),
+            Err(CapacityError {
+                offset: 3,
+                len: 2,
+                capacity: 4
+            })
+        );
+        assert_eq!(
+            buffer.update(&queue, &[1; 5], 0),
+            Err(CapacityError {
+                offset: 0,
+                len: 5,
+                capacity: 4
+            })
+        );
+        assert_eq!(read(&device, &queue, &buffer), [0, 0, 7, 8]);
+        assert_eq!(buffer.capacity(), 4);
+    }
 }
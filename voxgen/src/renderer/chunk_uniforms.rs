@@ -0,0 +1,179 @@
+use std::collections::{HashMap, HashSet};
+
+use vek::Vec3;
+
+use crate::world::chunk::{Chunk, ChunkPos};
+
+use super::{buffer::Buffer, upload::Uploader};
+
+/// Slots the buffer has room for at first, it doubles when full
+const INITIAL_SLOTS: usize = 128;
+
+/// What the chunk shader knows about the chunk being drawn. Padded to
+/// 256 bytes, the largest alignment adapters require of dynamic offsets.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ChunkUniform {
+    /// Position of the chunk origin relative to the render origin,
+    /// meshes are relative to it
+    offset: [f32; 4],
+    _padding: [[f32; 4]; 15],
+}
+
+impl ChunkUniform {
+    fn new(pos: ChunkPos, render_origin: Vec3<i32>) -> Self {
+        // Subtracted as integers, so the offset is exact however far out the chunk is
+        let origin = (pos.to_world() - render_origin).map(|v| v as f32);
+        Self {
+            offset: [origin.x, origin.y, origin.z, 0.0],
+            _padding: [[0.0; 4]; 15],
+        }
+    }
+}
+
+const SLOT_SIZE: usize = std::mem::size_of::<ChunkUniform>();
+
+/// One uniform buffer with a slot per loaded chunk, bound at the offset
+/// of the chunk before drawing it. Slots of unloaded chunks are reused.
+pub struct ChunkUniforms {
+    layout: wgpu::BindGroupLayout,
+    buffer: Buffer<ChunkUniform>,
+    bind_group: wgpu::BindGroup,
+    /// Contents of every slot, uploaded again when the buffer grows
+    slots: Vec<ChunkUniform>,
+    allocated: HashMap<ChunkPos, usize>,
+    free: Vec<usize>,
+    /// The render origin the offsets in the slots are relative to
+    origin: Vec3<i32>,
+}
+
+impl ChunkUniforms {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Chunk uniform layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(SLOT_SIZE as u64),
+                },
+                count: None,
+            }],
+        });
+        let buffer = Buffer::with_capacity(
+            device,
+            "Chunk uniform buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            INITIAL_SLOTS,
+        );
+        let bind_group = Self::create_bind_group(device, &layout, &buffer);
+        Self {
+            layout,
+            buffer,
+            bind_group,
+            slots: Vec::new(),
+            allocated: HashMap::new(),
+            free: Vec::new(),
+            origin: Vec3::zero(),
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &Buffer<ChunkUniform>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Chunk uniform bind group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                // A single slot, moved over the buffer by the dynamic offset
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer.buf,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(SLOT_SIZE as u64),
+                }),
+            }],
+        })
+    }
+
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Frees the slots of unloaded chunks and fills slots for the newly
+    /// loaded ones, growing the buffer when they don't fit. Every slot is
+    /// filled again when the render origin moved to another chunk.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        uploader: &mut Uploader,
+        chunks: &[Chunk],
+        origin: Vec3<i32>,
+    ) {
+        let loaded = chunks.iter().map(|chunk| chunk.pos).collect::<HashSet<_>>();
+        let free = &mut self.free;
+        self.allocated.retain(|pos, slot| {
+            let keep = loaded.contains(pos);
+            if !keep {
+                free.push(*slot);
+            }
+            keep
+        });
+
+        let mut added = Vec::new();
+        for chunk in chunks {
+            if self.allocated.contains_key(&chunk.pos) {
+                continue;
+            }
+            let uniform = ChunkUniform::new(chunk.pos, origin);
+            let slot = match self.free.pop() {
+                Some(slot) => {
+                    self.slots[slot] = uniform;
+                    slot
+                }
+                None => {
+                    self.slots.push(uniform);
+                    self.slots.len() - 1
+                }
+            };
+            self.allocated.insert(chunk.pos, slot);
+            added.push(slot);
+        }
+
+        let moved = origin != self.origin;
+        if moved {
+            self.origin = origin;
+            for (pos, slot) in &self.allocated {
+                self.slots[*slot] = ChunkUniform::new(*pos, origin);
+            }
+        }
+
+        if moved || self.slots.len() > self.buffer.capacity() {
+            if self.buffer.set_staged(device, uploader, &self.slots) {
+                self.bind_group = Self::create_bind_group(device, &self.layout, &self.buffer);
+            }
+        } else {
+            for slot in added {
+                self.buffer
+                    .stage(device, uploader, &self.slots[slot..slot + 1], slot)
+                    .expect("The buffer has room for every slot");
+            }
+        }
+    }
+
+    /// The dynamic offset of the slot of the chunk, `None` until `update`
+    /// saw it loaded
+    pub fn offset(&self, pos: ChunkPos) -> Option<u32> {
+        self.allocated
+            .get(&pos)
+            .map(|slot| (slot * SLOT_SIZE) as u32)
+    }
+}
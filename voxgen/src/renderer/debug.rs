@@ -1,28 +1,179 @@
+use std::{
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
 use vek::Vec3;
 
 use super::{
-    buffer::Buffer,
-    mesh::line::{LineVertex, make_cube_mesh, make_line_mesh},
-    pipelines::debug::DebugPipeline,
+    buffer::Buffer, mesh::line::LineVertex, pipelines::debug::DebugPipeline, upload::Uploader,
     Renderable,
 };
 
+/// Most debug line vertices drawn in a single frame, the rest are dropped
+pub const MAX_DEBUG_VERTICES: usize = 1 << 16;
+/// Segments of each of the three circles a sphere is drawn with
+const SPHERE_SEGMENTS: usize = 24;
+
+static DEBUG_DRAW: Mutex<DebugDraw> = Mutex::new(DebugDraw::new());
+
+/// The shapes queued for the next frames. Lock it from anywhere,
+/// for example `debug::draw().line(a, b, [1.0, 0.0, 0.0])`.
+pub fn draw() -> MutexGuard<'static, DebugDraw> {
+    DEBUG_DRAW.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Lines drawn over the world for debugging. Shapes are drawn in the next
+/// frame only, the `*_for` variants keep them until the duration elapsed.
+pub struct DebugDraw {
+    frame: Vec<LineVertex>,
+    timed: Vec<(Instant, [LineVertex; 2])>,
+    /// Whether dropped vertices were already reported
+    warned: bool,
+}
+
+impl DebugDraw {
+    const fn new() -> Self {
+        Self {
+            frame: Vec::new(),
+            timed: Vec::new(),
+            warned: false,
+        }
+    }
+
+    pub fn line(&mut self, a: Vec3<f32>, b: Vec3<f32>, color: [f32; 3]) {
+        self.push([(a, b)], color, None);
+    }
+
+    pub fn line_for(&mut self, a: Vec3<f32>, b: Vec3<f32>, color: [f32; 3], duration: Duration) {
+        self.push([(a, b)], color, Some(duration));
+    }
+
+    /// The edges of the box between the two corners
+    pub fn aabb(&mut self, min: Vec3<f32>, max: Vec3<f32>, color: [f32; 3]) {
+        self.push(aabb_edges(min, max), color, None);
+    }
+
+    pub fn aabb_for(
+        &mut self,
+        min: Vec3<f32>,
+        max: Vec3<f32>,
+        color: [f32; 3],
+        duration: Duration,
+    ) {
+        self.push(aabb_edges(min, max), color, Some(duration));
+    }
+
+    /// Three circles around the center, one in each axis plane
+    pub fn sphere(&mut self, center: Vec3<f32>, radius: f32, color: [f32; 3]) {
+        self.push(sphere_edges(center, radius), color, None);
+    }
+
+    pub fn sphere_for(
+        &mut self,
+        center: Vec3<f32>,
+        radius: f32,
+        color: [f32; 3],
+        duration: Duration,
+    ) {
+        self.push(sphere_edges(center, radius), color, Some(duration));
+    }
+
+    fn push(
+        &mut self,
+        lines: impl IntoIterator<Item = (Vec3<f32>, Vec3<f32>)>,
+        color: [f32; 3],
+        duration: Option<Duration>,
+    ) {
+        let lines = lines
+            .into_iter()
+            .map(|(a, b)| [LineVertex::new(a, color), LineVertex::new(b, color)]);
+        match duration {
+            Some(duration) => {
+                let expires = Instant::now() + duration;
+                self.timed.extend(lines.map(|line| (expires, line)));
+            }
+            None => self.frame.extend(lines.flatten()),
+        }
+    }
+
+    /// Returns the vertices of the lines to draw this frame
+    /// and forgets the lines that were only drawn once.
+    fn take_vertices(&mut self) -> Vec<LineVertex> {
+        let now = Instant::now();
+        self.timed.retain(|(expires, _)| *expires > now);
+        let mut vertices = std::mem::take(&mut self.frame);
+        vertices.extend(self.timed.iter().flat_map(|(_, line)| *line));
+        if vertices.len() > MAX_DEBUG_VERTICES {
+            if !self.warned {
+                log::warn!(
+                    "Dropped {} debug line vertices over the limit of {}",
+                    vertices.len() - MAX_DEBUG_VERTICES,
+                    MAX_DEBUG_VERTICES
+                );
+                self.warned = true;
+            }
+            vertices.truncate(MAX_DEBUG_VERTICES);
+        } else {
+            self.warned = false;
+        }
+        vertices
+    }
+}
+
+fn aabb_edges(min: Vec3<f32>, max: Vec3<f32>) -> Vec<(Vec3<f32>, Vec3<f32>)> {
+    let corner = |i: usize| {
+        Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        )
+    };
+    // Pairs of corners differing in a single axis
+    (0..8)
+        .flat_map(|i| [1, 2, 4].map(|axis| (i, i | axis)))
+        .filter(|(i, j)| i != j)
+        .map(|(i, j)| (corner(i), corner(j)))
+        .collect()
+}
+
+fn sphere_edges(center: Vec3<f32>, radius: f32) -> Vec<(Vec3<f32>, Vec3<f32>)> {
+    let point = |plane: usize, segment: usize| {
+        let angle = segment as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+        let (sin, cos) = (angle.sin() * radius, angle.cos() * radius);
+        center
+            + match plane {
+                0 => Vec3::new(cos, sin, 0.0),
+                1 => Vec3::new(cos, 0.0, sin),
+                _ => Vec3::new(0.0, cos, sin),
+            }
+    };
+    (0..3)
+        .flat_map(|plane| (0..SPHERE_SEGMENTS).map(move |segment| (plane, segment, segment + 1)))
+        .map(|(plane, from, to)| (point(plane, from), point(plane, to)))
+        .collect()
+}
+
 impl Renderable for DebugRenderer {
     fn render<'a>(
         &'a self,
         render_pass: &mut wgpu::RenderPass<'a>,
         global_uniforms: &'a wgpu::BindGroup,
     ) {
+        if self.num_vertices == 0 {
+            return;
+        }
         render_pass.set_pipeline(&self.pipeline.pipeline);
         render_pass.set_bind_group(0, global_uniforms, &[]);
-        self.line.render(render_pass, global_uniforms);
-        self.cube.render(render_pass, global_uniforms);
+        render_pass.set_vertex_buffer(0, self.buffer.buf.slice(..));
+        render_pass.draw(0..self.num_vertices, 0..1);
     }
 }
 
+/// Draws the lines submitted through `debug::draw()`
 pub struct DebugRenderer {
-    line: LineRenderer,
-    cube: LineRenderer,
+    buffer: Buffer<LineVertex>,
+    num_vertices: u32,
     pipeline: DebugPipeline,
 }
 
@@ -32,46 +183,29 @@ impl DebugRenderer {
         sfc: &wgpu::SurfaceConfiguration,
         transform_bind_group_layout: &wgpu::BindGroupLayout,
     ) -> Self {
-        let cube = make_cube_mesh(Vec3::zero());
-        let line = make_line_mesh(Vec3::new(0, 2, 2));
-        let pipeline = DebugPipeline::new(device, &sfc, &[transform_bind_group_layout]);
-
+        let pipeline = DebugPipeline::new(device, sfc, &[transform_bind_group_layout], true);
         Self {
-            line: LineRenderer::new(device, &line.0, &line.1),
-            cube: LineRenderer::new(device, &cube.0, &cube.1),
+            buffer: Buffer::with_capacity(
+                device,
+                "Debug vertex buffer",
+                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                1024,
+            ),
+            num_vertices: 0,
             pipeline,
         }
     }
-}
-pub struct LineRenderer {
-    buffer: Buffer<LineVertex>,
-    indices: Buffer<u16>,
-    num_indices: u32,
-}
 
-impl Renderable for LineRenderer {
-    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, _: &'a wgpu::BindGroup) {
-        render_pass.set_index_buffer(self.indices.buf.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.set_vertex_buffer(0, self.buffer.buf.slice(..));
-        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
-    }
-}
-impl LineRenderer {
-    pub fn new(device: &wgpu::Device, vertices: &[LineVertex], indices: &[u16]) -> Self {
-        let line_vertex_buffer = Buffer::new(
-            device,
-            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            vertices,
-        );
-        let line_index_buffer = Buffer::new(
-            device,
-            wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
-            indices,
-        );
-        Self {
-            buffer: line_vertex_buffer,
-            indices: line_index_buffer,
-            num_indices: indices.len() as u32,
-        }
+    /// Uploads the lines to draw this frame relative to the render origin,
+    /// growing the buffer if they don't fit
+    pub fn update(&mut self, device: &wgpu::Device, uploader: &mut Uploader, origin: Vec3<i32>) {
+        let origin = origin.map(|v| v as f32);
+        let vertices = draw()
+            .take_vertices()
+            .into_iter()
+            .map(|vertex| vertex.relative_to(origin))
+            .collect::<Vec<_>>();
+        self.buffer.set_staged(device, uploader, &vertices);
+        self.num_vertices = vertices.len() as u32;
     }
 }
@@ -0,0 +1,186 @@
+use std::{collections::HashMap, ops::Range};
+
+use bevy_ecs::world::World;
+
+use crate::{
+    ecs::{MeshHandle, PreviousTransform, Transform},
+    scene::camera::Camera,
+    world::chunk::CHUNK_WIDTH,
+};
+
+use super::{
+    atlas::Atlas,
+    buffer::{compute_cube_indices, Buffer},
+    instance::InstanceBuffer,
+    mesh::{entity::EntityInstance, quad::block_model, vertex::Vertex},
+    pipelines::entity::EntityPipeline,
+    world::RENDER_DISTANCE,
+};
+
+/// Instances the buffer has room for at first, it doubles when full
+const INITIAL_CAPACITY: usize = 64;
+/// Entities further away than the loaded chunks aren't drawn
+const MAX_DISTANCE: f32 = (RENDER_DISTANCE as usize * CHUNK_WIDTH) as f32;
+/// Distance from the origin of a mesh to its furthest corner, in any rotation
+const BOUNDING_RADIUS: f32 = 0.87;
+
+struct EntityMesh {
+    vertices: Buffer<Vertex>,
+    indices: Buffer<u32>,
+}
+
+impl EntityMesh {
+    fn build(device: &wgpu::Device, handle: MeshHandle) -> Self {
+        let vertices = match handle {
+            MeshHandle::Block(block) => block_model(block),
+        };
+        let indices = compute_cube_indices(vertices.len());
+        Self {
+            vertices: Buffer::new(
+                device,
+                "Entity vertex buffer",
+                wgpu::BufferUsages::VERTEX,
+                &vertices,
+            ),
+            indices: Buffer::new(
+                device,
+                "Entity index buffer",
+                wgpu::BufferUsages::INDEX,
+                &indices,
+            ),
+        }
+    }
+}
+
+/// Draws the entities that have a mesh and a transform, one instanced
+/// draw call per mesh
+pub struct EntityRenderer {
+    pipeline: EntityPipeline,
+    meshes: HashMap<MeshHandle, EntityMesh>,
+    instances: InstanceBuffer<EntityInstance>,
+    /// The instances drawn with each mesh this frame
+    batches: Vec<(MeshHandle, Range<u32>)>,
+    /// Entities with a mesh, whether drawn or culled
+    total: usize,
+}
+
+impl EntityRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        sfc: &wgpu::SurfaceConfiguration,
+        atlas: &Atlas,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self {
+            pipeline: EntityPipeline::new(
+                device,
+                sfc,
+                &[&atlas.bind_group_layout, transform_bind_group_layout],
+            ),
+            meshes: HashMap::new(),
+            instances: InstanceBuffer::new(device, "Entity instance buffer", INITIAL_CAPACITY),
+            batches: Vec::new(),
+            total: 0,
+        }
+    }
+
+    /// Uploads the transforms of the visible entities `alpha` of the way
+    /// between the last two ticks, relative to the render origin
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        world: &World,
+        camera: &Camera,
+        alpha: f32,
+    ) {
+        let eye = camera.eye_pos(alpha).map(|v| v as f32);
+        let frustum = camera.frustum(alpha);
+        let origin = camera.render_origin(alpha).map(|v| v as f32);
+        self.total = 0;
+        let mut visible = Vec::new();
+        for entity in world.iter_entities() {
+            let (handle, transform) = match (entity.get::<MeshHandle>(), entity.get::<Transform>())
+            {
+                (Some(handle), Some(transform)) => (*handle, *transform),
+                _ => continue,
+            };
+            self.total += 1;
+            let transform = match entity.get::<PreviousTransform>() {
+                Some(previous) => transform.interpolate(&previous.0, alpha),
+                None => transform,
+            };
+            if transform.pos.distance_squared(eye) > MAX_DISTANCE * MAX_DISTANCE
+                || !frustum.intersects_aabb(
+                    transform.pos - BOUNDING_RADIUS,
+                    transform.pos + BOUNDING_RADIUS,
+                )
+            {
+                continue;
+            }
+            let relative = Transform {
+                pos: transform.pos - origin,
+                ..transform
+            };
+            visible.push((handle, relative.model().into_col_arrays()));
+        }
+
+        // Instances of the same mesh are drawn together
+        visible.sort_unstable_by_key(|(handle, _)| match handle {
+            MeshHandle::Block(block) => block.index(),
+        });
+        self.batches.clear();
+        for (i, (handle, _)) in visible.iter().enumerate() {
+            let i = i as u32;
+            match self.batches.last_mut() {
+                Some((last, range)) if last == handle => range.end = i + 1,
+                _ => self.batches.push((*handle, i..i + 1)),
+            }
+            self.meshes
+                .entry(*handle)
+                .or_insert_with(|| EntityMesh::build(device, *handle));
+        }
+
+        let instances = visible
+            .into_iter()
+            .map(|(_, model)| EntityInstance { model })
+            .collect::<Vec<_>>();
+        self.instances.set(device, queue, &instances);
+    }
+
+    /// Entities drawn on the last frame
+    pub fn drawn(&self) -> usize {
+        self.batches.iter().map(|(_, range)| range.len()).sum()
+    }
+
+    /// Entities with a mesh, including the culled ones
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Instances the buffer has room for before growing
+    pub fn capacity(&self) -> usize {
+        self.instances.capacity()
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        atlas: &'a Atlas,
+        global_uniforms: &'a wgpu::BindGroup,
+    ) {
+        if self.batches.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline.pipeline);
+        render_pass.set_bind_group(0, &atlas.bind_group, &[]);
+        render_pass.set_bind_group(1, global_uniforms, &[]);
+        render_pass.set_vertex_buffer(1, self.instances.slice());
+        for (handle, range) in &self.batches {
+            let mesh = &self.meshes[handle];
+            render_pass.set_vertex_buffer(0, mesh.vertices.buf.slice(..));
+            render_pass.set_index_buffer(mesh.indices.buf.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..mesh.indices.len() as u32, 0, range.clone());
+        }
+    }
+}
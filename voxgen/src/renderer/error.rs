@@ -0,0 +1,148 @@
+use thiserror::Error;
+
+use crate::block::registry::RegistryError;
+
+/// Failures while setting up the renderer or drawing a frame
+#[derive(Debug, Error)]
+pub enum RendererError {
+    #[error("failed to create a surface for the window: {0}")]
+    Surface(#[from] wgpu::CreateSurfaceError),
+    #[error("the window surface is not supported by the graphics adapter")]
+    UnsupportedSurface,
+    #[error("no compatible graphics adapter was found")]
+    NoAdapter,
+    #[error("failed to open the graphics device: {0}")]
+    Device(#[from] wgpu::RequestDeviceError),
+    #[error("failed to create the render pipelines: {0}")]
+    Shader(String),
+    #[error("failed to load texture {label}: {source}")]
+    Texture {
+        label: String,
+        source: image::ImageError,
+    },
+    #[error("failed to acquire the next frame: {0}")]
+    Frame(#[from] wgpu::SurfaceError),
+    #[error("failed to draw the UI: {0}")]
+    Ui(#[from] egui_wgpu_backend::BackendError),
+}
+
+/// Failures while reloading the assets, which leave the old ones in use
+#[derive(Debug, Error)]
+pub enum ReloadError {
+    #[error(transparent)]
+    Registry(#[from] RegistryError),
+    #[error("failed to read atlas.png: {0}")]
+    Read(#[from] std::io::Error),
+    #[error("failed to decode atlas.png: {0}")]
+    Decode(#[from] image::ImageError),
+    #[error("atlas.png is {0}x{1}, it must stay 256x256")]
+    AtlasSize(u32, u32),
+}
+
+/// A write past the end of a buffer. Only `Buffer::set` grows buffers,
+/// so nothing of the write was done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("{len} elements at {offset} don't fit in a buffer of {capacity}")]
+pub struct CapacityError {
+    pub offset: usize,
+    pub len: usize,
+    pub capacity: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{
+        adapter::{self, test_device},
+        atlas::Atlas,
+    };
+
+    /// Opens a device the adapter can't provide, converting the error with `?`
+    async fn open_oversized_device(adapter: &wgpu::Adapter) -> Result<(), RendererError> {
+        let limits = wgpu::Limits {
+            max_texture_dimension_2d: u32::MAX,
+            ..wgpu::Limits::default()
+        };
+        adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    limits,
+                    ..Default::default()
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    fn encode_png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::new_rgba8(width, height)
+            .write_to(&mut bytes, image::ImageOutputFormat::Png)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn device_errors_convert() {
+        let error = pollster::block_on(async {
+            let adapter = adapter::request_offscreen().await.unwrap().adapter;
+            open_oversized_device(&adapter).await
+        })
+        .unwrap_err();
+        assert!(matches!(error, RendererError::Device(_)));
+        assert!(error
+            .to_string()
+            .starts_with("failed to open the graphics device: "));
+    }
+
+    #[test]
+    fn frame_and_ui_errors_convert() {
+        let error = RendererError::from(wgpu::SurfaceError::Lost);
+        assert!(matches!(
+            error,
+            RendererError::Frame(wgpu::SurfaceError::Lost)
+        ));
+        assert!(error
+            .to_string()
+            .starts_with("failed to acquire the next frame: "));
+
+        let error = RendererError::from(egui_wgpu_backend::BackendError::Internal(
+            "lost texture".to_string(),
+        ));
+        assert!(matches!(error, RendererError::Ui(_)));
+        assert!(error.to_string().starts_with("failed to draw the UI: "));
+    }
+
+    #[test]
+    fn invalid_atlas_is_a_texture_error() {
+        let (device, queue) = test_device();
+        match Atlas::new(b"not a png", &device, &queue) {
+            Err(RendererError::Texture { label, .. }) => assert_eq!(label, "atlas.png"),
+            Err(error) => panic!("expected a texture error, got {}", error),
+            Ok(_) => panic!("an invalid atlas was loaded"),
+        }
+    }
+
+    #[test]
+    fn reload_errors_convert() {
+        let error = ReloadError::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(matches!(error, ReloadError::Read(_)));
+
+        let (device, queue) = test_device();
+        let mut atlas = Atlas::new(&encode_png(256, 256), &device, &queue).unwrap();
+        let error = atlas.reload(&device, &queue, b"not a png").unwrap_err();
+        assert!(matches!(error, ReloadError::Decode(_)));
+        let error = atlas
+            .reload(&device, &queue, &encode_png(16, 32))
+            .unwrap_err();
+        assert!(matches!(error, ReloadError::AtlasSize(16, 32)));
+        assert_eq!(
+            error.to_string(),
+            "atlas.png is 16x32, it must stay 256x256"
+        );
+        atlas
+            .reload(&device, &queue, &encode_png(256, 256))
+            .unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use super::{buffer::Buffer, pipelines::fxaa::FxaaPipeline, texture::Texture};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FxaaUniform {
+    inverse_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+impl FxaaUniform {
+    fn new(cfg: &wgpu::SurfaceConfiguration) -> Self {
+        Self {
+            inverse_size: [1.0 / cfg.width as f32, 1.0 / cfg.height as f32],
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Smooths the jagged edges of the graded frame with FXAA 3.11, which
+/// finds edges by their contrast and blends the pixels along them in a
+/// single pass
+pub struct Fxaa {
+    /// What the frame is graded into before its edges are smoothed, the
+    /// size and format of the frame
+    input: Texture,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: FxaaPipeline,
+    uniform: Buffer<FxaaUniform>,
+}
+
+impl Fxaa {
+    /// `cfg` is the configuration of the frame
+    pub fn new(device: &wgpu::Device, cfg: &wgpu::SurfaceConfiguration) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("FXAA bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let input = Self::create_input(device, cfg);
+        let uniform = Self::create_uniform(device, cfg);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &input, &uniform);
+        let pipeline = FxaaPipeline::new(device, cfg, &[&bind_group_layout]);
+        Self {
+            input,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+            uniform,
+        }
+    }
+
+    fn create_input(device: &wgpu::Device, cfg: &wgpu::SurfaceConfiguration) -> Texture {
+        Texture::render_target(device, (cfg.width, cfg.height), cfg.format, "FXAA input")
+    }
+
+    fn create_uniform(
+        device: &wgpu::Device,
+        cfg: &wgpu::SurfaceConfiguration,
+    ) -> Buffer<FxaaUniform> {
+        Buffer::new(
+            device,
+            "FXAA uniform buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            &[FxaaUniform::new(cfg)],
+        )
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        input: &Texture,
+        uniform: &Buffer<FxaaUniform>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("FXAA bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&input.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&input.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform.buf.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Recreates the input at the new size of the frame
+    pub fn resize(&mut self, device: &wgpu::Device, cfg: &wgpu::SurfaceConfiguration) {
+        self.input = Self::create_input(device, cfg);
+        self.uniform = Self::create_uniform(device, cfg);
+        self.bind_group =
+            Self::create_bind_group(device, &self.bind_group_layout, &self.input, &self.uniform);
+    }
+
+    /// The texture the frame is graded into
+    pub fn input_view(&self) -> &wgpu::TextureView {
+        &self.input.view
+    }
+
+    /// Smooths the graded frame into the frame, replacing what it had
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, frame: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("FXAA Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: frame,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,99 @@
+use vek::{FrustumPlanes, Mat4, Vec3};
+
+use crate::scene::camera::{Camera, CameraUniform};
+
+use super::{
+    buffer::Buffer,
+    mesh::line::LineVertex,
+    pipelines::debug::DebugPipeline,
+    screen_element::{Corner, ScreenElement},
+};
+
+/// Side of the square the gizmo is drawn in, in logical pixels
+const GIZMO_SIZE: f32 = 80.0;
+/// Distance from the bottom left corner of the window, in logical pixels
+const GIZMO_MARGIN: f32 = 10.0;
+/// Length of the axes, the gizmo spans from -1 to 1
+const AXIS_LENGTH: f32 = 0.8;
+
+/// Draws the world axes in the bottom left corner, rotated like the
+/// camera so that X, Y and Z point where they do in the world.
+pub struct GizmoRenderer {
+    pipeline: DebugPipeline,
+    vertices: Buffer<LineVertex>,
+    element: ScreenElement,
+}
+
+impl GizmoRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        sfc: &wgpu::SurfaceConfiguration,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let axes = [
+            (Vec3::unit_x(), [1.0, 0.0, 0.0]),
+            (Vec3::unit_y(), [0.0, 1.0, 0.0]),
+            (Vec3::unit_z(), [0.0, 0.0, 1.0]),
+        ]
+        .into_iter()
+        .flat_map(|(axis, color)| {
+            [
+                LineVertex::new(Vec3::zero(), color),
+                LineVertex::new(axis * AXIS_LENGTH, color),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+        Self {
+            pipeline: DebugPipeline::new(device, sfc, &[transform_bind_group_layout], false),
+            vertices: Buffer::new(
+                device,
+                "Gizmo vertex buffer",
+                wgpu::BufferUsages::VERTEX,
+                &axes,
+            ),
+            element: ScreenElement::new(
+                device,
+                transform_bind_group_layout,
+                "Gizmo bind group",
+                Corner::BottomLeft,
+                GIZMO_SIZE,
+                GIZMO_MARGIN,
+            ),
+        }
+    }
+
+    /// Rotates the axes by the view rotation of the camera
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera) {
+        let view = Mat4::look_at_lh(Vec3::zero(), camera.view_direction(), camera.view_up());
+        let proj = Mat4::orthographic_lh_zo(FrustumPlanes {
+            left: -1.0,
+            right: 1.0,
+            bottom: -1.0,
+            top: 1.0,
+            near: -1.0,
+            far: 1.0,
+        });
+        self.element.update(queue, CameraUniform::new(proj * view));
+    }
+
+    /// Draws into the corner of a target of the given physical size.
+    /// This changes the viewport, so it must be the last draw of the pass.
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        scale_factor: f32,
+    ) {
+        if !self
+            .element
+            .set_viewport(render_pass, target_size, scale_factor)
+        {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline.pipeline);
+        render_pass.set_bind_group(0, &self.element.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertices.buf.slice(..));
+        render_pass.draw(0..self.vertices.len() as u32, 0..1);
+    }
+}
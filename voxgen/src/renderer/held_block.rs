@@ -0,0 +1,209 @@
+use std::{f32::consts::PI, time::Instant};
+
+use vek::{Mat4, Vec3};
+
+use crate::{
+    block::BlockId,
+    scene::{
+        camera::{reverse_z, CameraUniform},
+        Scene,
+    },
+};
+
+use super::{
+    atlas::Atlas,
+    buffer::{compute_cube_indices, Buffer},
+    mesh::{quad::block_model, vertex::Vertex},
+    pipelines::voxel::VoxelPipeline,
+    screen_element::{Corner, ScreenElement},
+};
+
+/// Side of the square the block is drawn in, in logical pixels
+const HELD_BLOCK_SIZE: f32 = 240.0;
+/// Field of view of the block, independent of the one of the camera
+const HELD_BLOCK_FOV: f32 = 50.0;
+/// Distance from the eye to the center of the block
+const HELD_BLOCK_DISTANCE: f32 = 3.0;
+/// Seconds a swing lasts
+const SWING_DURATION: f32 = 0.25;
+/// Bobbing cycles per second while walking
+const BOB_FREQUENCY: f32 = 1.8;
+/// Furthest the block bobs from its rest position, in blocks
+const BOB_AMPLITUDE: f32 = 0.08;
+/// How quickly the bobbing fades in and out when starting or stopping, per second
+const BOB_FADE: f32 = 5.0;
+
+/// Draws the block selected in the hotbar in the bottom right corner,
+/// as if held by the player
+pub struct HeldBlockRenderer {
+    pipeline: VoxelPipeline,
+    pipeline_transparent: VoxelPipeline,
+    element: ScreenElement,
+    /// The block the mesh was built for
+    block: BlockId,
+    vertices: Option<Buffer<Vertex>>,
+    indices: Option<Buffer<u32>>,
+    /// Whether the block is drawn this frame
+    visible: bool,
+    /// Progress through the bobbing cycle, in radians
+    bob_phase: f32,
+    /// How much of the bobbing is applied, from 0 standing to 1 walking
+    bob_amount: f32,
+    last_update: Instant,
+}
+
+impl HeldBlockRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        sfc: &wgpu::SurfaceConfiguration,
+        atlas: &Atlas,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../../../assets/shaders/cube.wgsl"));
+        let layouts = [&atlas.bind_group_layout, transform_bind_group_layout];
+        let pipeline = |transparent| {
+            VoxelPipeline::new(
+                device,
+                &shader,
+                "vs_main",
+                sfc,
+                &layouts,
+                wgpu::PolygonMode::Fill,
+                transparent,
+            )
+        };
+        Self {
+            pipeline: pipeline(false),
+            pipeline_transparent: pipeline(true),
+            element: ScreenElement::new(
+                device,
+                transform_bind_group_layout,
+                "Held block bind group",
+                Corner::BottomRight,
+                HELD_BLOCK_SIZE,
+                0.0,
+            ),
+            block: BlockId::AIR,
+            vertices: None,
+            indices: None,
+            visible: false,
+            bob_phase: 0.0,
+            bob_amount: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Rebuilds the mesh when the selection changed and animates the block.
+    /// `visible` is whether the block should be drawn at all.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &Scene,
+        daylight: f32,
+        visible: bool,
+    ) {
+        let dt = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+
+        let block = scene.hotbar.selected_block();
+        if block != self.block {
+            self.block = block;
+            self.rebuild_mesh(device);
+        }
+        self.visible = visible && self.vertices.is_some();
+
+        let target = if scene.is_walking() { 1.0 } else { 0.0 };
+        self.bob_amount += (target - self.bob_amount) * (BOB_FADE * dt).min(1.0);
+        if self.bob_amount > 0.01 {
+            self.bob_phase = (self.bob_phase + 2.0 * PI * BOB_FREQUENCY * dt) % (2.0 * PI);
+        } else {
+            self.bob_phase = 0.0;
+        }
+        let swing = scene
+            .last_swing
+            .map(|start| start.elapsed().as_secs_f32() / SWING_DURATION)
+            .filter(|progress| *progress < 1.0)
+            .map_or(0.0, |progress| (progress * PI).sin());
+
+        // The eye looks down +Z, the block rests towards the corner of the
+        // square and turns towards the center of the screen when swinging
+        let bob = Vec3::new(
+            self.bob_phase.sin(),
+            -(self.bob_phase * 2.0).sin().abs(),
+            0.0,
+        ) * BOB_AMPLITUDE
+            * self.bob_amount;
+        let model = Mat4::<f32>::translation_3d(
+            Vec3::new(
+                0.3 - 0.5 * swing,
+                -0.2 + 0.3 * swing,
+                HELD_BLOCK_DISTANCE - 0.5 * swing,
+            ) + bob,
+        ) * Mat4::rotation_x(-(25.0 + 40.0 * swing).to_radians())
+            * Mat4::rotation_y((45.0 + 20.0 * swing).to_radians());
+        let proj = Mat4::perspective_fov_lh_zo(HELD_BLOCK_FOV.to_radians(), 1.0, 1.0, 0.1, 10.0);
+        let mut uniform = CameraUniform::new(reverse_z() * proj * model);
+        uniform.daylight = daylight;
+        self.element.update(queue, uniform);
+    }
+
+    /// Rebuilds the mesh on the next update, after the blocks changed
+    pub fn invalidate(&mut self) {
+        self.block = BlockId::AIR;
+    }
+
+    fn rebuild_mesh(&mut self, device: &wgpu::Device) {
+        if self.block.is_air() {
+            self.vertices = None;
+            self.indices = None;
+            return;
+        }
+        let vertices = block_model(self.block);
+        let indices = compute_cube_indices(vertices.len());
+        self.vertices = Some(Buffer::new(
+            device,
+            "Held block vertex buffer",
+            wgpu::BufferUsages::VERTEX,
+            &vertices,
+        ));
+        self.indices = Some(Buffer::new(
+            device,
+            "Held block index buffer",
+            wgpu::BufferUsages::INDEX,
+            &indices,
+        ));
+    }
+
+    /// Draws into the corner of a target of the given physical size
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        atlas: &'a Atlas,
+        target_size: (u32, u32),
+        scale_factor: f32,
+    ) {
+        let (vertices, indices) = match (&self.vertices, &self.indices) {
+            (Some(vertices), Some(indices)) if self.visible => (vertices, indices),
+            _ => return,
+        };
+        if !self
+            .element
+            .set_viewport(render_pass, target_size, scale_factor)
+        {
+            return;
+        }
+        let pipeline = if self.block.is_transparent() {
+            &self.pipeline_transparent
+        } else {
+            &self.pipeline
+        };
+        render_pass.set_pipeline(&pipeline.pipeline);
+        render_pass.set_bind_group(0, &atlas.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.element.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertices.buf.slice(..));
+        render_pass.set_index_buffer(indices.buf.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+    }
+}
@@ -0,0 +1,88 @@
+use bytemuck::Pod;
+
+use super::buffer::Buffer;
+
+/// Per-instance data for instanced draws. The buffer doubles when more
+/// instances are set than it has room for, and only the instances of
+/// the last `set` are drawn, whatever its capacity.
+pub struct InstanceBuffer<T: Copy + Pod> {
+    buffer: Buffer<T>,
+}
+
+impl<T: Copy + Pod> InstanceBuffer<T> {
+    pub fn new(device: &wgpu::Device, label: &'static str, capacity: usize) -> Self {
+        Self {
+            buffer: Buffer::with_capacity(
+                device,
+                label,
+                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                capacity.max(1),
+            ),
+        }
+    }
+
+    /// Replaces the instances, growing the buffer if they don't fit
+    pub fn set(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, instances: &[T]) {
+        self.buffer.set(device, queue, instances);
+    }
+
+    /// Instances set last, the ones drawn
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Instances the buffer has room for before growing
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// The part of the buffer holding the instances set last,
+    /// which must not be empty
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        let size = (self.len() * std::mem::size_of::<T>()) as wgpu::BufferAddress;
+        self.buffer.buf.slice(..size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::adapter::test_device;
+
+    #[test]
+    fn grows_past_its_capacity() {
+        let (device, queue) = test_device();
+        let mut instances = InstanceBuffer::<[f32; 4]>::new(&device, "Test instances", 4);
+        assert!(instances.is_empty());
+        assert_eq!(instances.capacity(), 4);
+        // Up to the capacity, then one past it
+        instances.set(&device, &queue, &[[1.0; 4]; 4]);
+        assert_eq!((instances.len(), instances.capacity()), (4, 4));
+        instances.set(&device, &queue, &[[2.0; 4]; 5]);
+        assert_eq!((instances.len(), instances.capacity()), (5, 8));
+        instances.set(&device, &queue, &[[3.0; 4]; 100]);
+        assert_eq!((instances.len(), instances.capacity()), (100, 128));
+    }
+
+    #[test]
+    fn draws_only_the_last_instances() {
+        let (device, queue) = test_device();
+        let mut instances = InstanceBuffer::<[f32; 4]>::new(&device, "Test instances", 0);
+        // Room for one even when created empty
+        assert_eq!(instances.capacity(), 1);
+        instances.set(&device, &queue, &[[1.0; 4]; 10]);
+        let capacity = instances.capacity();
+        // The instances of the longer set stay in the buffer beyond the
+        // length, but aren't drawn and the buffer keeps its size
+        instances.set(&device, &queue, &[[2.0; 4]; 3]);
+        assert_eq!(instances.len(), 3);
+        assert_eq!(instances.capacity(), capacity);
+        instances.set(&device, &queue, &[]);
+        assert!(instances.is_empty());
+        assert_eq!(instances.capacity(), capacity);
+    }
+}
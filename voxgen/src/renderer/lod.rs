@@ -0,0 +1,88 @@
+use std::collections::HashSet;
+
+use vek::Vec3;
+
+use crate::world::chunk::{Chunk, ChunkPos, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+
+/// Blocks the camera has to move past the LOD distance before a chunk
+/// switches, so chunks right at the distance don't flicker between meshes
+pub const LOD_HYSTERESIS: f32 = 4.0;
+
+/// Picks the chunks drawn with their half resolution meshes, the ones
+/// farther than `distance` chunks from the camera
+pub struct LodSelector {
+    /// Chunks from the camera at which the half resolution meshes are
+    /// drawn, 0 to always draw the full ones
+    pub distance: u32,
+    far: HashSet<ChunkPos>,
+    /// The chunks that were far before the last update
+    previous: HashSet<ChunkPos>,
+}
+
+impl LodSelector {
+    pub fn new(distance: u32) -> Self {
+        Self {
+            distance,
+            far: HashSet::new(),
+            previous: HashSet::new(),
+        }
+    }
+
+    /// Switches the chunks that moved past the distance since the last
+    /// update, by more than the hysteresis
+    pub fn update(&mut self, chunks: &[Chunk], eye: Vec3<f32>) {
+        std::mem::swap(&mut self.far, &mut self.previous);
+        self.far.clear();
+        if self.distance == 0 {
+            return;
+        }
+        let distance = (self.distance as usize * CHUNK_WIDTH) as f32;
+        let previous = &self.previous;
+        self.far.extend(
+            chunks
+                .iter()
+                .filter(|chunk| {
+                    let threshold = if previous.contains(&chunk.pos) {
+                        distance - LOD_HYSTERESIS
+                    } else {
+                        distance + LOD_HYSTERESIS
+                    };
+                    center_distance(chunk.pos, eye) > threshold
+                })
+                .map(|chunk| chunk.pos),
+        );
+    }
+
+    /// Whether the chunk is drawn with its half resolution meshes
+    pub fn is_far(&self, pos: ChunkPos) -> bool {
+        self.far.contains(&pos)
+    }
+}
+
+/// Distance between the camera and the center of the chunk
+fn center_distance(pos: ChunkPos, eye: Vec3<f32>) -> f32 {
+    // Blocks are centered on integer coordinates
+    let center = pos.to_world().map(|v| v as f32)
+        + Vec3::new(CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH).map(|v| v as f32 / 2.0 - 0.5);
+    center.distance(eye)
+}
+
+/// Vertices of the chunks drawn this frame, to show what LOD saves
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LodStats {
+    /// Chunks drawn with their half resolution meshes
+    pub chunks: usize,
+    pub vertices: usize,
+    /// Vertices that would be drawn with only the full meshes
+    pub full_vertices: usize,
+}
+
+impl LodStats {
+    /// Fraction of the full vertices that LOD left out
+    pub fn saved(&self) -> f32 {
+        if self.full_vertices == 0 {
+            return 0.0;
+        }
+        1.0 - self.vertices as f32 / self.full_vertices as f32
+    }
+}
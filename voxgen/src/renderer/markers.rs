@@ -0,0 +1,60 @@
+/// Whether passes and draws are wrapped in debug groups, which frame
+/// captures like RenderDoc's list by name. Release builds leave them out,
+/// as drivers record them even when nothing captures the frame.
+pub const ENABLED: bool = cfg!(debug_assertions);
+
+/// Command encoders and passes, which can all hold debug groups
+pub trait DebugMarkers {
+    fn push_debug_group(&mut self, label: &str);
+    fn pop_debug_group(&mut self);
+    fn insert_debug_marker(&mut self, label: &str);
+}
+
+impl DebugMarkers for wgpu::CommandEncoder {
+    fn push_debug_group(&mut self, label: &str) {
+        wgpu::CommandEncoder::push_debug_group(self, label);
+    }
+
+    fn pop_debug_group(&mut self) {
+        wgpu::CommandEncoder::pop_debug_group(self);
+    }
+
+    fn insert_debug_marker(&mut self, label: &str) {
+        wgpu::CommandEncoder::insert_debug_marker(self, label);
+    }
+}
+
+impl DebugMarkers for wgpu::RenderPass<'_> {
+    fn push_debug_group(&mut self, label: &str) {
+        wgpu::RenderPass::push_debug_group(self, label);
+    }
+
+    fn pop_debug_group(&mut self) {
+        wgpu::RenderPass::pop_debug_group(self);
+    }
+
+    fn insert_debug_marker(&mut self, label: &str) {
+        wgpu::RenderPass::insert_debug_marker(self, label);
+    }
+}
+
+/// Starts a group of the commands recorded until the matching [`pop`]
+pub fn push(target: &mut impl DebugMarkers, label: &str) {
+    if ENABLED {
+        target.push_debug_group(label);
+    }
+}
+
+/// Ends the group started by the last [`push`]
+pub fn pop(target: &mut impl DebugMarkers) {
+    if ENABLED {
+        target.pop_debug_group();
+    }
+}
+
+/// Marks a single point between the commands
+pub fn insert(target: &mut impl DebugMarkers, label: &str) {
+    if ENABLED {
+        target.insert_debug_marker(label);
+    }
+}
@@ -1,5 +1,3 @@
-use std::vec;
-
 use vek::Vec3;
 
 #[repr(C)]
@@ -10,7 +8,7 @@ pub struct LineVertex {
 }
 impl LineVertex {
     const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
-        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
 
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
@@ -20,92 +18,57 @@ impl LineVertex {
         }
     }
 
-    pub fn new(at: [i32; 3], color: [f32; 3]) -> Self {
+    pub fn new(pos: Vec3<f32>, color: [f32; 3]) -> Self {
         Self {
-            pos: [at[0] as f32, at[1] as f32 + 257.0, at[2] as f32],
+            pos: pos.into_array(),
             color,
         }
     }
-}
-
-pub fn make_coordinate_mesh() -> (Vec<LineVertex>, Vec<u16>) {
-    let color = [1.0, 0.1, 0.0];
-    const UNIT: i32 = 1;
-    let data = [
-        // X
-        LineVertex::new([0, 0, 0], color),
-        LineVertex::new([UNIT, 0, 0], color),
-        // Y
-        LineVertex::new([0, 0, 0], color),
-        LineVertex::new([0, UNIT, 0], color),
-        // Z
-        LineVertex::new([0, 0, 0], color),
-        LineVertex::new([0, 0, UNIT], color),
-    ];
-    // data.iter_mut().for_each(|v| v.pos[1] += 256.0);
-    let index_data: &[u16] = &[
-        0, 1, //z
-        2, 3, //x
-        4, 5, //y
-    ];
 
-    (data.to_vec(), index_data.to_vec())
+    /// The same vertex with its position relative to `origin`
+    pub fn relative_to(self, origin: Vec3<f32>) -> Self {
+        Self::new(Vec3::from(self.pos) - origin, self.color)
+    }
 }
 
-pub fn make_line_mesh(world_pos: Vec3<i32>) -> ([LineVertex; 2], [u16; 2]) {
-    let from = [world_pos.x, world_pos.y, world_pos.z];
-    let to = [1 + world_pos.x, world_pos.y, world_pos.z];
-    
-    let indices = [0, 1];
-    let vertices = [
-        LineVertex::new(from, [1.0, 0.0, 0.0]),
-        LineVertex::new(to, [1.0, 0.0, 0.0]),
-    ];
-    (vertices, indices)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct OutlineVertex {
+    pos: [f32; 3],
 }
-pub fn make_cube_mesh(offset: Vec3<i32>) -> (Vec<LineVertex>, Vec<u16>) {
-    let c = [1.0, 0.0, 0.0];
-    const UNIT: i32 = 1;
 
-    let indices = vec![
-        // +Z
-        0, 1, 1, 2, 2, 3, 3, 0, // -Z
-        4, 5, 5, 6, 6, 7, 7, 4, // +X
-        8, 9, 9, 10, 10, 11, 11, 8, // -X
-        12, 13, 13, 14, 14, 15, 15, 12, // +Y
-        16, 17, 17, 18, 18, 19, 19, 16, // -Y
-        20, 21, 21, 22, 22, 23, 23, 20,
-    ];
+impl OutlineVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x3];
 
-    let vertices: Vec<LineVertex> = vec![
-        // Facing +Z
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
 
-        // Facing -Z
-        LineVertex::new([0, 0, UNIT], c),
-        LineVertex::new([UNIT, 0, UNIT], c),
-        LineVertex::new([UNIT, UNIT, UNIT], c),
-        LineVertex::new([0, UNIT, UNIT], c),
-        // Facing +X
-        LineVertex::new([UNIT, 0, 0], c),
-        LineVertex::new([UNIT, 0, UNIT], c),
-        LineVertex::new([UNIT, UNIT, UNIT], c),
-        LineVertex::new([UNIT, UNIT, 0], c),
-        // Facing -X
-        LineVertex::new([0, 0, 0], c),
-        LineVertex::new([0, 0, UNIT], c),
-        LineVertex::new([0, UNIT, UNIT], c),
-        LineVertex::new([0, UNIT, 0], c),
-        // Facing +Y
-        LineVertex::new([0, UNIT, 0], c),
-        LineVertex::new([UNIT, UNIT, 0], c),
-        LineVertex::new([UNIT, UNIT, UNIT], c),
-        LineVertex::new([0, UNIT, UNIT], c),
-        // Facing -Y
-        LineVertex::new([0, 0, 0], c),
-        LineVertex::new([UNIT, 0, 0], c),
-        LineVertex::new([UNIT, 0, UNIT], c),
-        LineVertex::new([0, 0, UNIT], c),
+/// The 12 edges of a block centered on the origin, slightly
+/// inflated so they don't z-fight with the block faces.
+pub fn make_outline_mesh() -> (Vec<OutlineVertex>, Vec<u16>) {
+    const HALF: f32 = 0.502;
+    let vertices = (0..8)
+        .map(|i| OutlineVertex {
+            pos: [
+                if i & 1 == 0 { -HALF } else { HALF },
+                if i & 2 == 0 { -HALF } else { HALF },
+                if i & 4 == 0 { -HALF } else { HALF },
+            ],
+        })
+        .collect();
+    let indices = vec![
+        // X edges
+        0, 1, 2, 3, 4, 5, 6, 7, //
+        // Y edges
+        0, 2, 1, 3, 4, 6, 5, 7, //
+        // Z edges
+        0, 4, 1, 5, 2, 6, 3, 7,
     ];
-
     (vertices, indices)
 }
@@ -1,3 +1,6 @@
+pub mod border;
+pub mod entity;
 pub mod line;
+pub mod particle;
 pub mod quad;
 pub mod vertex;
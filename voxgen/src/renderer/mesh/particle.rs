@@ -0,0 +1,27 @@
+/// A particle drawn as a camera facing quad, one per instance
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct ParticleInstance {
+    pub pos: [f32; 3],
+    pub size: f32,
+    /// Top left and bottom right corners in the atlas
+    pub uv: [f32; 4],
+    pub opacity: f32,
+}
+
+impl ParticleInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32,
+        2 => Float32x4,
+        3 => Float32,
+    ];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
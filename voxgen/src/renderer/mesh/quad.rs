@@ -1,6 +1,10 @@
 use vek::Vec3;
 
-use crate::{block::BlockId, direction::Direction};
+use crate::{
+    block::{BlockId, BlockShape},
+    direction::Direction,
+    renderer::atlas::TILE_UV_SIZE,
+};
 
 use super::vertex::Vertex;
 
@@ -11,16 +15,141 @@ pub struct Quad {
 }
 
 impl Quad {
-    pub fn new(id: &BlockId, dir: Direction, offset: Vec3<i32>) -> Self {
+    pub fn new(id: BlockId, dir: Direction, offset: Vec3<i32>) -> Self {
         Self {
             vertices: dir.quad_vertices(id, offset),
             dir,
         }
     }
+
+    /// A face of a slab, which fills the lower half of the block.
+    /// The sides show the lower half of the texture.
+    pub fn slab(id: BlockId, dir: Direction, offset: Vec3<i32>) -> Self {
+        Self::lowered(id, dir, offset, 0.5)
+    }
+
+    /// A face of a block filled up to `height` from its bottom, like a
+    /// slab or flowing water. The sides show the bottom of the texture.
+    pub fn lowered(id: BlockId, dir: Direction, offset: Vec3<i32>, height: f32) -> Self {
+        let mut quad = Self::new(id, dir, offset);
+        for vertex in quad.vertices.iter_mut() {
+            if vertex.pos()[1] > offset.y as f32 {
+                let uv_offset = match dir {
+                    Direction::Up | Direction::Down => 0.0,
+                    _ => TILE_UV_SIZE * (1.0 - height),
+                };
+                *vertex = vertex.translated([0.0, height - 1.0, 0.0], [0.0, uv_offset]);
+            }
+        }
+        quad
+    }
+
+    /// A face of a block twice as large, filling the 2x2x2 blocks whose
+    /// lowest corner block is at twice `cell`. The texture is stretched over it.
+    pub fn doubled(id: BlockId, dir: Direction, cell: Vec3<i32>) -> Self {
+        let mut quad = Self::new(id, dir, cell);
+        // Blocks are centered on integer coordinates, so the cell is centered
+        // between its blocks
+        for vertex in quad.vertices.iter_mut() {
+            let pos = vertex.pos();
+            *vertex = vertex.translated(pos.map(|v| v + 0.5), [0.0, 0.0]);
+        }
+        quad
+    }
+
+    /// Two quads crossing diagonally through the block, like plants.
+    /// Each is emitted facing both ways since back faces are culled.
+    pub fn cross(id: BlockId, offset: Vec3<i32>) -> [Self; 4] {
+        let texture_id = id.texture_id(Direction::Front);
+        let at = offset.map(|v| v as f32);
+        let vertex = |x: f32, y: f32, z: f32, uv: [u8; 2]| {
+            Vertex::new([at.x + x, at.y + y, at.z + z], uv, &texture_id)
+                .with_emission(Vertex::emission(id))
+        };
+        let diagonal = [
+            vertex(-0.5, -0.5, -0.5, [0, 1]),
+            vertex(-0.5, 0.5, -0.5, [0, 0]),
+            vertex(0.5, 0.5, 0.5, [1, 0]),
+            vertex(0.5, -0.5, 0.5, [1, 1]),
+        ];
+        let anti_diagonal = [
+            vertex(-0.5, -0.5, 0.5, [0, 1]),
+            vertex(-0.5, 0.5, 0.5, [0, 0]),
+            vertex(0.5, 0.5, -0.5, [1, 0]),
+            vertex(0.5, -0.5, -0.5, [1, 1]),
+        ];
+        let reversed = |mut vertices: [Vertex; 4]| {
+            vertices.reverse();
+            vertices
+        };
+        [
+            Self {
+                vertices: diagonal,
+                dir: Direction::Front,
+            },
+            Self {
+                vertices: reversed(diagonal),
+                dir: Direction::Back,
+            },
+            Self {
+                vertices: anti_diagonal,
+                dir: Direction::Front,
+            },
+            Self {
+                vertices: reversed(anti_diagonal),
+                dir: Direction::Back,
+            },
+        ]
+    }
+
+    /// Sets the block and sky light of each vertex, from 0 to 1. The quad is
+    /// split along the diagonal whose corners are lit more alike, otherwise
+    /// a single dark or bright corner would streak across the whole face.
+    pub fn with_light(mut self, light: [[f32; 2]; 4]) -> Self {
+        for (vertex, light) in self.vertices.iter_mut().zip(light) {
+            *vertex = vertex.with_light(light);
+        }
+        let brightness = light.map(|[block, sky]| block + sky);
+        if (brightness[0] - brightness[2]).abs() > (brightness[1] - brightness[3]).abs() {
+            // The indices split every quad between its first and third vertex
+            self.vertices.rotate_left(1);
+        }
+        self
+    }
+}
+
+/// Every face of a block around the origin, shaded by the side it faces
+/// so that its shape reads at any time of day. Used to draw blocks
+/// outside of the chunks.
+pub fn block_model(id: BlockId) -> Vec<Vertex> {
+    let origin = Vec3::zero();
+    let quads = match id.shape() {
+        BlockShape::Cross => Quad::cross(id, origin).to_vec(),
+        BlockShape::Slab => Direction::ALL
+            .into_iter()
+            .map(|dir| Quad::slab(id, dir, origin))
+            .collect(),
+        _ => Direction::ALL
+            .into_iter()
+            .map(|dir| Quad::new(id, dir, origin))
+            .collect::<Vec<_>>(),
+    };
+    quads
+        .into_iter()
+        .flat_map(|quad| {
+            let shade = match quad.dir {
+                Direction::Up => 1.0,
+                Direction::Down => 0.5,
+                Direction::Left | Direction::Right => 0.8,
+                Direction::Back | Direction::Front => 0.65,
+            };
+            quad.with_light([[0.0, shade]; 4]).vertices
+        })
+        .collect()
 }
 
 impl Direction {
-    fn quad_vertices(&self, id: &BlockId, at: Vec3<i32>) -> [Vertex; 4] {
+    fn quad_vertices(self, id: BlockId, at: Vec3<i32>) -> [Vertex; 4] {
         let neg_x: f32 = -0.5;
         let pos_x: f32 = 0.5;
         let neg_y: f32 = -0.5;
@@ -30,41 +159,41 @@ impl Direction {
 
         match self {
             Direction::Left => [
-                Vertex::quad(neg_x, neg_y, neg_z, at, [0, 1], &id, self),
-                Vertex::quad(neg_x, pos_y, neg_z, at, [0, 0], &id, self),
-                Vertex::quad(neg_x, pos_y, pos_z, at, [1, 0], &id, self),
-                Vertex::quad(neg_x, neg_y, pos_z, at, [1, 1], &id, self),
+                Vertex::quad(neg_x, neg_y, neg_z, at, [0, 1], id, self),
+                Vertex::quad(neg_x, pos_y, neg_z, at, [0, 0], id, self),
+                Vertex::quad(neg_x, pos_y, pos_z, at, [1, 0], id, self),
+                Vertex::quad(neg_x, neg_y, pos_z, at, [1, 1], id, self),
             ],
             Direction::Right => [
-                Vertex::quad(pos_x, neg_y, pos_z, at, [0, 1], &id, self),
-                Vertex::quad(pos_x, pos_y, pos_z, at, [0, 0], &id, self),
-                Vertex::quad(pos_x, pos_y, neg_z, at, [1, 0], &id, self),
-                Vertex::quad(pos_x, neg_y, neg_z, at, [1, 1], &id, self),
+                Vertex::quad(pos_x, neg_y, pos_z, at, [0, 1], id, self),
+                Vertex::quad(pos_x, pos_y, pos_z, at, [0, 0], id, self),
+                Vertex::quad(pos_x, pos_y, neg_z, at, [1, 0], id, self),
+                Vertex::quad(pos_x, neg_y, neg_z, at, [1, 1], id, self),
             ],
             Direction::Down => [
-                Vertex::quad(pos_x, neg_y, neg_z, at, [0, 1], &id, self),
-                Vertex::quad(neg_x, neg_y, neg_z, at, [0, 0], &id, self),
-                Vertex::quad(neg_x, neg_y, pos_z, at, [1, 0], &id, self),
-                Vertex::quad(pos_x, neg_y, pos_z, at, [1, 1], &id, self),
+                Vertex::quad(pos_x, neg_y, neg_z, at, [0, 1], id, self),
+                Vertex::quad(neg_x, neg_y, neg_z, at, [0, 0], id, self),
+                Vertex::quad(neg_x, neg_y, pos_z, at, [1, 0], id, self),
+                Vertex::quad(pos_x, neg_y, pos_z, at, [1, 1], id, self),
             ],
             Direction::Up => [
-                Vertex::quad(pos_x, pos_y, pos_z, at, [0, 1], &id, self),
-                Vertex::quad(neg_x, pos_y, pos_z, at, [0, 0], &id, self),
-                Vertex::quad(neg_x, pos_y, neg_z, at, [1, 0], &id, self),
-                Vertex::quad(pos_x, pos_y, neg_z, at, [1, 1], &id, self),
+                Vertex::quad(pos_x, pos_y, pos_z, at, [0, 1], id, self),
+                Vertex::quad(neg_x, pos_y, pos_z, at, [0, 0], id, self),
+                Vertex::quad(neg_x, pos_y, neg_z, at, [1, 0], id, self),
+                Vertex::quad(pos_x, pos_y, neg_z, at, [1, 1], id, self),
             ],
 
             Direction::Back => [
-                Vertex::quad(neg_x, neg_y, neg_z, at, [0, 1], &id, self),
-                Vertex::quad(pos_x, neg_y, neg_z, at, [1, 1], &id, self),
-                Vertex::quad(pos_x, pos_y, neg_z, at, [1, 0], &id, self),
-                Vertex::quad(neg_x, pos_y, neg_z, at, [0, 0], &id, self),
+                Vertex::quad(neg_x, neg_y, neg_z, at, [0, 1], id, self),
+                Vertex::quad(pos_x, neg_y, neg_z, at, [1, 1], id, self),
+                Vertex::quad(pos_x, pos_y, neg_z, at, [1, 0], id, self),
+                Vertex::quad(neg_x, pos_y, neg_z, at, [0, 0], id, self),
             ],
             Direction::Front => [
-                Vertex::quad(neg_x, pos_y, pos_z, at, [0, 0], &id, self),
-                Vertex::quad(pos_x, pos_y, pos_z, at, [1, 0], &id, self),
-                Vertex::quad(pos_x, neg_y, pos_z, at, [1, 1], &id, self),
-                Vertex::quad(neg_x, neg_y, pos_z, at, [0, 1], &id, self),
+                Vertex::quad(neg_x, pos_y, pos_z, at, [0, 0], id, self),
+                Vertex::quad(pos_x, pos_y, pos_z, at, [1, 0], id, self),
+                Vertex::quad(pos_x, neg_y, pos_z, at, [1, 1], id, self),
+                Vertex::quad(neg_x, neg_y, pos_z, at, [0, 1], id, self),
             ],
         }
     }
@@ -4,6 +4,7 @@ use crate::{
     block::BlockId,
     direction::Direction,
     renderer::atlas::{atlas_uv_mapping, TextureId},
+    world::light::MAX_LIGHT,
 };
 
 #[repr(C)]
@@ -11,20 +12,42 @@ use crate::{
 pub struct Vertex {
     pos: [f32; 3],
     uv: [f32; 2],
+    /// Frame count and seconds per frame of the tile, `[1.0, 1.0]` for static tiles
+    animation: [f32; 2],
+    /// Block and sky light on the face, from 0 to 1
+    light: [f32; 2],
+    /// Light the block emits, from 0 to 1, which makes it glow with bloom
+    emission: f32,
 }
 impl Vertex {
     pub const INDEX_BUFFER_FORMAT: Option<wgpu::IndexFormat> = Some(wgpu::IndexFormat::Uint16);
 
-    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
-        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x2,
+        2 => Float32x2,
+        3 => Float32x2,
+        4 => Float32,
+    ];
 
     pub fn new(pos: [f32; 3], uv: [u8; 2], texture_id: &TextureId) -> Self {
         Self {
             pos,
             uv: atlas_uv_mapping(texture_id, uv[0], uv[1]),
+            animation: Self::animation(texture_id),
+            light: [1.0, 1.0],
+            emission: 0.0,
         }
     }
 
+    pub fn pos(&self) -> [f32; 3] {
+        self.pos
+    }
+
+    pub fn uv(&self) -> [f32; 2] {
+        self.uv
+    }
+
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
@@ -39,13 +62,48 @@ impl Vertex {
         v3: f32,
         at: Vec3<i32>,
         uv: [u8; 2],
-        id: &BlockId,
-        dir: &Direction,
+        id: BlockId,
+        dir: Direction,
     ) -> Self {
-        let texture_uv = id.map_texture(uv, dir);
+        let texture_id = id.texture_id(dir);
         Self {
             pos: [v1 + at.x as f32, v2 + at.y as f32, v3 + at.z as f32],
-            uv: texture_uv,
+            uv: atlas_uv_mapping(&texture_id, uv[0], uv[1]),
+            animation: Self::animation(&texture_id),
+            light: [1.0, 1.0],
+            emission: Self::emission(id),
         }
     }
+
+    /// Moves the vertex and its texture coordinates by the given amounts
+    pub fn translated(mut self, pos: [f32; 3], uv: [f32; 2]) -> Self {
+        self.pos = [
+            self.pos[0] + pos[0],
+            self.pos[1] + pos[1],
+            self.pos[2] + pos[2],
+        ];
+        self.uv = [self.uv[0] + uv[0], self.uv[1] + uv[1]];
+        self
+    }
+
+    pub fn with_light(mut self, light: [f32; 2]) -> Self {
+        self.light = light;
+        self
+    }
+
+    pub fn with_emission(mut self, emission: f32) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    /// The emission of every vertex of the block
+    pub fn emission(id: BlockId) -> f32 {
+        id.light_emission() as f32 / MAX_LIGHT as f32
+    }
+
+    fn animation(texture_id: &TextureId) -> [f32; 2] {
+        texture_id
+            .animation()
+            .map_or([1.0, 1.0], |anim| [anim.frames as f32, anim.frame_time])
+    }
 }
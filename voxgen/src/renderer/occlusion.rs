@@ -0,0 +1,193 @@
+use std::collections::{HashMap, VecDeque};
+
+use vek::Vec3;
+
+use crate::{
+    direction::Direction,
+    scene::frustum::Frustum,
+    world::{
+        chunk::{block_pos, to_local, Chunk, ChunkMesh, ChunkPos, CHUNK_DEPTH, CHUNK_WIDTH},
+        visibility::{CHUNK_SECTIONS, SECTION_HEIGHT},
+    },
+};
+
+/// A bit for every section of a chunk, from the bottom up
+pub type SectionMask = u16;
+
+pub const ALL_SECTIONS: SectionMask = SectionMask::MAX;
+
+/// A section reached by the flood fill
+struct Step {
+    chunk: usize,
+    section: usize,
+    /// The face the section was entered through, `None` for the first sections
+    entered: Option<Direction>,
+    /// Bits of the directions taken to get here, never turned back on
+    directions: u8,
+}
+
+/// Culls the chunk sections hidden behind terrain.
+///
+/// Sections are flood filled outwards from the camera, entering a section
+/// through one face and leaving it only through the faces its blocks
+/// connect that face to, and never back towards the camera. Sections the
+/// fill never reaches can't be seen and aren't drawn.
+///
+/// GPU occlusion queries against the last depth buffer would catch more,
+/// like a single pillar hiding a chunk, but wgpu has no occlusion queries
+/// in render passes yet, and their results would arrive a frame late so
+/// chunks would pop in when turning quickly. The flood fill is computed
+/// on the CPU from the connectivity kept with each chunk, has no latency,
+/// and never hides a section that can be seen since lines of sight only
+/// ever move away from the camera. It is coarse however: any opening
+/// through a section, like a cave, lets the fill through all of it.
+pub struct OcclusionCuller {
+    pub enabled: bool,
+    /// Sections reached by the last fill, by chunk index
+    visible: Vec<SectionMask>,
+    /// Chunk indices by position, rebuilt every fill
+    indices: HashMap<ChunkPos, usize>,
+    queue: VecDeque<Step>,
+}
+
+impl OcclusionCuller {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            visible: Vec::new(),
+            indices: HashMap::new(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Finds the sections that can be seen from `eye`. Every section is
+    /// visible while disabled, or when the camera is outside the loaded chunks.
+    pub fn update(&mut self, chunks: &[Chunk], eye: Vec3<f32>, frustum: &Frustum) {
+        self.visible.clear();
+        if !self.enabled {
+            self.visible.resize(chunks.len(), ALL_SECTIONS);
+            return;
+        }
+        self.visible.resize(chunks.len(), 0);
+        self.indices.clear();
+        self.indices.extend(
+            chunks
+                .iter()
+                .enumerate()
+                .map(|(index, chunk)| (chunk.pos, index)),
+        );
+
+        let eye_block = block_pos(eye);
+        let eye_section = to_local(eye_block).y as usize / SECTION_HEIGHT;
+        match self.indices.get(&ChunkPos::from_block(eye_block)).copied() {
+            Some(chunk) => self.visit(chunk, eye_section, None, 0),
+            None => {
+                self.visible.fill(ALL_SECTIONS);
+                return;
+            }
+        }
+
+        while let Some(step) = self.queue.pop_front() {
+            let visibility = chunks[step.chunk].visibility.section(step.section);
+            for dir in Direction::ALL {
+                if step.directions & dir_bit(dir.opposite()) != 0 {
+                    continue;
+                }
+                if let Some(entered) = step.entered {
+                    if !visibility.connects(entered, dir) {
+                        continue;
+                    }
+                }
+                let (chunk, section) = match self.neighbor(chunks, step.chunk, step.section, dir) {
+                    Some(neighbor) => neighbor,
+                    None => continue,
+                };
+                if self.visible[chunk] & (1 << section) != 0
+                    || !section_in_view(chunks[chunk].pos, section, frustum)
+                {
+                    continue;
+                }
+                self.visit(
+                    chunk,
+                    section,
+                    Some(dir.opposite()),
+                    step.directions | dir_bit(dir),
+                );
+            }
+        }
+    }
+
+    fn visit(&mut self, chunk: usize, section: usize, entered: Option<Direction>, directions: u8) {
+        self.visible[chunk] |= 1 << section;
+        self.queue.push_back(Step {
+            chunk,
+            section,
+            entered,
+            directions,
+        });
+    }
+
+    /// The chunk index and section next to the section, if it is loaded
+    fn neighbor(
+        &self,
+        chunks: &[Chunk],
+        chunk: usize,
+        section: usize,
+        dir: Direction,
+    ) -> Option<(usize, usize)> {
+        let offset = dir.normalized();
+        // Sections stacked over the chunks of the column
+        let section = section as i32 + offset.y;
+        let pos = chunks[chunk].pos
+            + ChunkPos::new(
+                offset.x,
+                section.div_euclid(CHUNK_SECTIONS as i32),
+                offset.z,
+            );
+        let section = section.rem_euclid(CHUNK_SECTIONS as i32) as usize;
+        self.indices.get(&pos).map(|chunk| (*chunk, section))
+    }
+
+    /// The sections of the chunk at the index that were reached by the last fill
+    pub fn visible_sections(&self, chunk: usize) -> SectionMask {
+        self.visible.get(chunk).copied().unwrap_or(ALL_SECTIONS)
+    }
+}
+
+impl Default for OcclusionCuller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn dir_bit(dir: Direction) -> u8 {
+    1 << usize::from(dir)
+}
+
+fn section_in_view(pos: ChunkPos, section: usize, frustum: &Frustum) -> bool {
+    // Blocks are centered on integer coordinates
+    let min = (pos.to_world() + Vec3::new(0, (section * SECTION_HEIGHT) as i32, 0))
+        .map(|v| v as f32)
+        - 0.5;
+    let size = Vec3::new(CHUNK_WIDTH, SECTION_HEIGHT, CHUNK_DEPTH).map(|v| v as f32);
+    frustum.intersects_aabb(min, min + size)
+}
+
+/// The index ranges of the mesh to draw for the visible sections,
+/// with neighboring sections merged into one range
+pub fn section_ranges(
+    mesh: &ChunkMesh,
+    visible: SectionMask,
+) -> impl Iterator<Item = std::ops::Range<u32>> + '_ {
+    let mut section = 0;
+    std::iter::from_fn(move || {
+        while section < CHUNK_SECTIONS && visible & (1 << section) == 0 {
+            section += 1;
+        }
+        let start = section;
+        while section < CHUNK_SECTIONS && visible & (1 << section) != 0 {
+            section += 1;
+        }
+        (start < CHUNK_SECTIONS).then(|| mesh.sections[start]..mesh.sections[section])
+    })
+}
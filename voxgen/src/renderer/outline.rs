@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use vek::Mat4;
+
+use super::{
+    buffer::Buffer, pipelines::outline::OutlinePipeline, texture::Texture, upload::Uploader,
+};
+
+/// Dark lines along the edges of blocks, for a toon look
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutlineSettings {
+    pub enabled: bool,
+    /// How sharply faces must turn or the depth must jump for an edge,
+    /// from 0 for every slight bump to 1 for almost nothing
+    pub threshold: f32,
+    /// Width of the lines in pixels of the scene
+    pub thickness: u32,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 0.3,
+            thickness: 1,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineUniform {
+    inverse_transform: [[f32; 4]; 4],
+    threshold: f32,
+    thickness: f32,
+    _padding: [f32; 2],
+}
+
+/// Darkens the scene where the depth buffer shows the depth jumping or
+/// the faces turning. The normals of the faces are reconstructed from the
+/// depth, so the world is drawn as usual.
+pub struct Outline {
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: OutlinePipeline,
+    uniform: Buffer<OutlineUniform>,
+    settings: OutlineSettings,
+}
+
+impl Outline {
+    /// `cfg` is the configuration the scene is drawn with
+    pub fn new(device: &wgpu::Device, cfg: &wgpu::SurfaceConfiguration, depth: &Texture) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Outline bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let settings = OutlineSettings::default();
+        let uniform = Buffer::new(
+            device,
+            "Outline uniform buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            &[OutlineUniform {
+                inverse_transform: Mat4::identity().into_col_arrays(),
+                threshold: settings.threshold,
+                thickness: settings.thickness as f32,
+                _padding: [0.0; 2],
+            }],
+        );
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, depth, &uniform);
+        let pipeline = OutlinePipeline::new(device, cfg, &[&bind_group_layout]);
+        Self {
+            bind_group_layout,
+            bind_group,
+            pipeline,
+            uniform,
+            settings,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        depth: &Texture,
+        uniform: &Buffer<OutlineUniform>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Outline bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&depth.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform.buf.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Reads the depth buffer recreated at the new size of the scene
+    pub fn resize(&mut self, device: &wgpu::Device, depth: &Texture) {
+        self.bind_group =
+            Self::create_bind_group(device, &self.bind_group_layout, depth, &self.uniform);
+    }
+
+    pub fn set_settings(&mut self, settings: OutlineSettings) {
+        self.settings = settings;
+    }
+
+    /// Follows the camera, `transform` being the one the scene is drawn
+    /// with
+    pub fn update(&self, device: &wgpu::Device, uploader: &mut Uploader, transform: Mat4<f32>) {
+        if !self.settings.enabled {
+            return;
+        }
+        self.uniform
+            .stage(
+                device,
+                uploader,
+                &[OutlineUniform {
+                    inverse_transform: transform.inverted().into_col_arrays(),
+                    threshold: self.settings.threshold,
+                    thickness: self.settings.thickness as f32,
+                    _padding: [0.0; 2],
+                }],
+                0,
+            )
+            .expect("The uniform buffer holds one uniform");
+    }
+
+    /// Darkens the edges in the scene, no pass at all while outlines are off
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, scene: &wgpu::TextureView) {
+        if !self.settings.enabled {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Outline Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: scene,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,120 @@
+use vek::Vec3;
+
+use crate::scene::{
+    camera::Camera,
+    particles::{ParticleSystem, MAX_PARTICLES, PARTICLE_SIZE},
+};
+
+use super::{
+    atlas::Atlas, buffer::Buffer, instance::InstanceBuffer, mesh::particle::ParticleInstance,
+    pipelines::particle::ParticlePipeline,
+};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BillboardUniform {
+    right: [f32; 4],
+    up: [f32; 4],
+}
+
+/// Draws the particles of the scene as textured quads facing the camera
+pub struct ParticleRenderer {
+    pipeline: ParticlePipeline,
+    /// Has room for every particle the system can have at once
+    instances: InstanceBuffer<ParticleInstance>,
+    uniform: Buffer<BillboardUniform>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ParticleRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        sfc: &wgpu::SurfaceConfiguration,
+        atlas: &Atlas,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        // Shares the layout of the camera transform, a single uniform buffer
+        let uniform = Buffer::new(
+            device,
+            "Billboard uniform buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            &[BillboardUniform {
+                right: [1.0, 0.0, 0.0, 0.0],
+                up: [0.0, 1.0, 0.0, 0.0],
+            }],
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Particle bind group"),
+            layout: transform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform.buf.as_entire_binding(),
+            }],
+        });
+        let layouts = [
+            &atlas.bind_group_layout,
+            transform_bind_group_layout,
+            transform_bind_group_layout,
+        ];
+        Self {
+            pipeline: ParticlePipeline::new(device, sfc, &layouts),
+            instances: InstanceBuffer::new(device, "Particle instance buffer", MAX_PARTICLES),
+            uniform,
+            bind_group,
+        }
+    }
+
+    /// Uploads the particles at their position `alpha` of the way
+    /// between the last two ticks, relative to the render origin
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        particles: &ParticleSystem,
+        camera: &Camera,
+        alpha: f32,
+    ) {
+        let origin = camera.render_origin(alpha).map(|v| v as f32);
+        let instances = particles
+            .particles()
+            .map(|particle| ParticleInstance {
+                pos: (Vec3::lerp(particle.prev_pos, particle.pos, alpha) - origin).into_array(),
+                size: PARTICLE_SIZE,
+                uv: particle.uv,
+                opacity: particle.opacity(),
+            })
+            .collect::<Vec<_>>();
+        self.instances.set(device, queue, &instances);
+
+        let forward = camera.view_direction();
+        let right = camera.view_up().cross(forward).normalized();
+        let up = forward.cross(right);
+        self.uniform
+            .update(
+                queue,
+                &[BillboardUniform {
+                    right: right.with_w(0.0).into_array(),
+                    up: up.with_w(0.0).into_array(),
+                }],
+                0,
+            )
+            .expect("The uniform buffer holds one uniform");
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        atlas: &'a Atlas,
+        global_uniforms: &'a wgpu::BindGroup,
+    ) {
+        if self.instances.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline.pipeline);
+        render_pass.set_bind_group(0, &atlas.bind_group, &[]);
+        render_pass.set_bind_group(1, global_uniforms, &[]);
+        render_pass.set_bind_group(2, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instances.slice());
+        render_pass.draw(0..6, 0..self.instances.len() as u32);
+    }
+}
@@ -0,0 +1,84 @@
+use wgpu::BindGroupLayout;
+
+/// The passes of the bloom chain, which share their shader and layout
+pub struct BloomPipelines {
+    /// Halves the scene, keeping only what is brighter than the threshold
+    pub threshold: wgpu::RenderPipeline,
+    /// Halves a mip into the next one
+    pub downsample: wgpu::RenderPipeline,
+    /// Blurs a mip and adds it onto the previous one
+    pub upsample: wgpu::RenderPipeline,
+}
+
+impl BloomPipelines {
+    pub fn new(
+        device: &wgpu::Device,
+        cfg: &wgpu::SurfaceConfiguration,
+        bg_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        let shader = device
+            .create_shader_module(wgpu::include_wgsl!("../../../../assets/shaders/bloom.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bloom pipeline layout"),
+            bind_group_layouts: bg_layouts,
+            push_constant_ranges: &[],
+        });
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::REPLACE,
+        };
+        let pipeline = |label: &str, entry_point: &str, blend: wgpu::BlendState| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: cfg.format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            })
+        };
+        Self {
+            threshold: pipeline(
+                "Bloom threshold pipeline",
+                "fs_threshold",
+                wgpu::BlendState::REPLACE,
+            ),
+            downsample: pipeline(
+                "Bloom downsample pipeline",
+                "fs_downsample",
+                wgpu::BlendState::REPLACE,
+            ),
+            upsample: pipeline("Bloom upsample pipeline", "fs_upsample", additive),
+        }
+    }
+}
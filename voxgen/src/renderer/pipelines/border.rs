@@ -0,0 +1,67 @@
+use wgpu::BindGroupLayout;
+
+use crate::renderer::{mesh::border::BorderVertex, texture::Texture};
+
+pub struct BorderPipeline {
+    pub pipeline: wgpu::RenderPipeline,
+}
+
+impl BorderPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        cfg: &wgpu::SurfaceConfiguration,
+        bg_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!(
+            "../../../../assets/shaders/border.wgsl"
+        ));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Border pipeline layout"),
+            bind_group_layouts: bg_layouts,
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Border pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[BorderVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: cfg.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // The wall is seen from both sides
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                // Hidden by terrain but never hides the water behind it
+                depth_write_enabled: false,
+                depth_compare: Texture::DEPTH_COMPARE,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+        Self { pipeline }
+    }
+}
@@ -7,10 +7,12 @@ pub struct DebugPipeline {
 }
 
 impl DebugPipeline {
+    /// Without `depth_test` the lines are drawn over everything else
     pub fn new(
         device: &wgpu::Device,
         cfg: &wgpu::SurfaceConfiguration,
         bg_layouts: &[&BindGroupLayout],
+        depth_test: bool,
     ) -> Self {
         let shader = device
             .create_shader_module(wgpu::include_wgsl!("../../../../assets/shaders/line.wgsl"));
@@ -50,8 +52,12 @@ impl DebugPipeline {
             },
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_write_enabled: depth_test,
+                depth_compare: if depth_test {
+                    Texture::DEPTH_COMPARE
+                } else {
+                    wgpu::CompareFunction::Always
+                },
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
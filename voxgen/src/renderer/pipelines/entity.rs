@@ -0,0 +1,68 @@
+use wgpu::BindGroupLayout;
+
+use crate::renderer::{
+    mesh::{entity::EntityInstance, vertex::Vertex},
+    texture::Texture,
+};
+
+/// Draws instanced meshes textured from the block atlas, shaded like the chunks
+pub struct EntityPipeline {
+    pub pipeline: wgpu::RenderPipeline,
+}
+
+impl EntityPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        cfg: &wgpu::SurfaceConfiguration,
+        bg_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        let shader = device
+            .create_shader_module(wgpu::include_wgsl!("../../../../assets/shaders/cube.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Entity pipeline layout"),
+            bind_group_layouts: bg_layouts,
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Entity pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_entity",
+                buffers: &[Vertex::desc(), EntityInstance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: cfg.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: Texture::DEPTH_COMPARE,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+        Self { pipeline }
+    }
+}
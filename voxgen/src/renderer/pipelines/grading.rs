@@ -0,0 +1,57 @@
+use wgpu::BindGroupLayout;
+
+pub struct GradingPipeline {
+    pub pipeline: wgpu::RenderPipeline,
+}
+
+impl GradingPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        cfg: &wgpu::SurfaceConfiguration,
+        bg_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!(
+            "../../../../assets/shaders/grading.wgsl"
+        ));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grading pipeline layout"),
+            bind_group_layouts: bg_layouts,
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Grading pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: cfg.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+        Self { pipeline }
+    }
+}
@@ -1,2 +1,10 @@
+pub mod bloom;
+pub mod border;
 pub mod debug;
+pub mod entity;
+pub mod fxaa;
+pub mod grading;
+pub mod outline;
+pub mod particle;
+pub mod selection;
 pub mod voxel;
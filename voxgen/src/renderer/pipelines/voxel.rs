@@ -8,9 +8,11 @@ impl VoxelPipeline {
     pub fn new(
         device: &wgpu::Device,
         shader: &wgpu::ShaderModule,
+        vertex_entry_point: &str,
         cfg: &wgpu::SurfaceConfiguration,
         bg_layouts: &[&wgpu::BindGroupLayout],
         polygon_mode: wgpu::PolygonMode,
+        transparent: bool,
     ) -> Self {
         let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Voxel pipeline layout"),
@@ -20,19 +22,31 @@ impl VoxelPipeline {
             push_constant_ranges: &[],
         });
         let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Voxel pipeline"),
+            label: Some(if transparent {
+                "Transparent voxel pipeline"
+            } else {
+                "Voxel pipeline"
+            }),
             layout: Some(&layout),
             vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
+                module: shader,
+                entry_point: vertex_entry_point,
                 buffers: &[Vertex::desc()],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
+                module: shader,
+                entry_point: if transparent {
+                    "fs_transparent"
+                } else {
+                    "fs_main"
+                },
                 targets: &[Some(wgpu::ColorTargetState {
                     format: cfg.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
+                    blend: Some(if transparent {
+                        wgpu::BlendState::ALPHA_BLENDING
+                    } else {
+                        wgpu::BlendState::REPLACE
+                    }),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -47,8 +61,10 @@ impl VoxelPipeline {
             },
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                // Transparent faces are tested against opaque geometry
+                // but must not hide each other
+                depth_write_enabled: !transparent,
+                depth_compare: Texture::DEPTH_COMPARE,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
@@ -0,0 +1,401 @@
+use std::ops::Range;
+
+use bytemuck::Pod;
+
+use crate::world::chunk::ChunkMesh;
+
+use super::{
+    buffer::{Buffer, ChunkBuffer},
+    mesh::vertex::Vertex,
+    upload::Uploader,
+};
+
+/// Size of the pages chunk meshes are allocated from. Meshes larger
+/// than a page get a page of their own.
+pub const PAGE_BYTES: usize = 32 * 1024 * 1024;
+
+/// Hands out ranges of a fixed size space, like the elements of a buffer.
+/// Freed ranges are merged with the free ones around them so the space
+/// doesn't end up split into pieces too small for anything.
+#[derive(Debug, Clone)]
+pub struct RangeAllocator {
+    size: usize,
+    /// Free ranges sorted by start, never empty nor touching each other
+    free: Vec<Range<usize>>,
+}
+
+impl RangeAllocator {
+    pub fn new(size: usize) -> Self {
+        let mut free = Vec::new();
+        if size > 0 {
+            free.push(0..size);
+        }
+        Self { size, free }
+    }
+
+    /// Takes a range of `len` from the smallest free range it fits in,
+    /// leaving the larger free ranges for larger allocations
+    pub fn alloc(&mut self, len: usize) -> Option<Range<usize>> {
+        if len == 0 {
+            return None;
+        }
+        let (index, free) = self
+            .free
+            .iter()
+            .enumerate()
+            .filter(|(_, free)| free.len() >= len)
+            .min_by_key(|(_, free)| free.len())?;
+        let range = free.start..free.start + len;
+        if free.len() == len {
+            self.free.remove(index);
+        } else {
+            self.free[index].start += len;
+        }
+        Some(range)
+    }
+
+    /// Returns the range, which must have been allocated and not freed since
+    pub fn free(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let index = self.free.partition_point(|free| free.start < range.start);
+        let merges_prev = index > 0 && self.free[index - 1].end == range.start;
+        let merges_next = index < self.free.len() && self.free[index].start == range.end;
+        match (merges_prev, merges_next) {
+            (true, true) => {
+                self.free[index - 1].end = self.free[index].end;
+                self.free.remove(index);
+            }
+            (true, false) => self.free[index - 1].end = range.end,
+            (false, true) => self.free[index].start = range.start,
+            (false, false) => self.free.insert(index, range),
+        }
+    }
+
+    /// Resizes the allocation without moving it, shrinking it or growing it
+    /// into the free range right after it. Returns `None` if it can't grow
+    /// there, in which case the allocation is left as it was.
+    pub fn resize(&mut self, range: Range<usize>, len: usize) -> Option<Range<usize>> {
+        let resized = range.start..range.start + len;
+        if len <= range.len() {
+            self.free(resized.end..range.end);
+            return Some(resized);
+        }
+        let index = self.free.partition_point(|free| free.start < range.end);
+        let next = self
+            .free
+            .get(index)
+            .filter(|free| free.start == range.end)?;
+        if next.end < resized.end {
+            return None;
+        }
+        if next.end == resized.end {
+            self.free.remove(index);
+        } else {
+            self.free[index].start = resized.end;
+        }
+        Some(resized)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Total size of the free ranges
+    pub fn free_space(&self) -> usize {
+        self.free.iter().map(|free| free.len()).sum()
+    }
+
+    /// Size of the largest range that can be allocated
+    pub fn largest_free(&self) -> usize {
+        self.free.iter().map(|free| free.len()).max().unwrap_or(0)
+    }
+
+    /// Number of free ranges, 1 when nothing is fragmented
+    pub fn free_ranges(&self) -> usize {
+        self.free.len()
+    }
+}
+
+/// Elements of a page of a [`MeshPool`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    pub page: usize,
+    pub range: Range<usize>,
+}
+
+struct Page<T: Copy + Pod> {
+    buffer: Buffer<T>,
+    allocator: RangeAllocator,
+}
+
+/// Suballocates meshes from a few large buffers instead of creating
+/// a buffer for every mesh, which fragments GPU memory as chunks are
+/// loaded, unloaded and remeshed. Pages are kept once created.
+pub struct MeshPool<T: Copy + Pod> {
+    /// Name of the pages in frame captures
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    /// Elements of each page
+    page_size: usize,
+    pages: Vec<Page<T>>,
+}
+
+impl<T: Copy + Pod> MeshPool<T> {
+    pub fn new(label: &'static str, usage: wgpu::BufferUsages) -> Self {
+        Self {
+            label,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            page_size: PAGE_BYTES / std::mem::size_of::<T>(),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Allocates `len` elements from the first page with room for them,
+    /// creating a page if none has. `None` for empty allocations.
+    pub fn alloc(&mut self, device: &wgpu::Device, len: usize) -> Option<Allocation> {
+        if len == 0 {
+            return None;
+        }
+        for (page, data) in self.pages.iter_mut().enumerate() {
+            if let Some(range) = data.allocator.alloc(len) {
+                return Some(Allocation { page, range });
+            }
+        }
+        let size = self.page_size.max(len);
+        let mut allocator = RangeAllocator::new(size);
+        let range = allocator.alloc(len)?;
+        self.pages.push(Page {
+            buffer: Buffer::with_capacity(device, self.label, self.usage, size),
+            allocator,
+        });
+        Some(Allocation {
+            page: self.pages.len() - 1,
+            range,
+        })
+    }
+
+    pub fn free(&mut self, allocation: Allocation) {
+        self.pages[allocation.page].allocator.free(allocation.range);
+    }
+
+    /// Resizes the allocation to `len` elements, in place if it fits there
+    /// and moved elsewhere otherwise. The contents are not kept.
+    pub fn realloc(
+        &mut self,
+        device: &wgpu::Device,
+        allocation: Option<Allocation>,
+        len: usize,
+    ) -> Option<Allocation> {
+        let allocation = match allocation {
+            Some(allocation) => allocation,
+            None => return self.alloc(device, len),
+        };
+        if len == 0 {
+            self.free(allocation);
+            return None;
+        }
+        let allocator = &mut self.pages[allocation.page].allocator;
+        match allocator.resize(allocation.range.clone(), len) {
+            Some(range) => Some(Allocation {
+                page: allocation.page,
+                range,
+            }),
+            None => {
+                self.free(allocation);
+                self.alloc(device, len)
+            }
+        }
+    }
+
+    /// Writes the data at the start of the allocation, which must have room for it
+    pub fn write(
+        &self,
+        device: &wgpu::Device,
+        uploader: &mut Uploader,
+        allocation: &Allocation,
+        data: &[T],
+    ) {
+        debug_assert!(data.len() <= allocation.range.len());
+        self.pages[allocation.page]
+            .buffer
+            .stage(device, uploader, data, allocation.range.start)
+            .expect("Allocations lie inside their page");
+    }
+
+    /// The buffer of the page, to be bound before drawing its allocations
+    pub fn buffer(&self, page: usize) -> &wgpu::Buffer {
+        &self.pages[page].buffer.buf
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let size = std::mem::size_of::<T>() as u64;
+        let mut stats = PoolStats {
+            pages: self.pages.len(),
+            ..PoolStats::default()
+        };
+        for page in &self.pages {
+            let allocator = &page.allocator;
+            stats.capacity_bytes += allocator.size() as u64 * size;
+            stats.free_bytes += allocator.free_space() as u64 * size;
+            stats.largest_free_bytes = stats
+                .largest_free_bytes
+                .max(allocator.largest_free() as u64 * size);
+        }
+        stats
+    }
+}
+
+/// How full and fragmented a pool is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub pages: usize,
+    pub capacity_bytes: u64,
+    pub free_bytes: u64,
+    /// The largest allocation that fits without a new page.
+    /// Much smaller than `free_bytes` when the free space is fragmented.
+    pub largest_free_bytes: u64,
+}
+
+impl std::ops::Add for PoolStats {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            pages: self.pages + rhs.pages,
+            capacity_bytes: self.capacity_bytes + rhs.capacity_bytes,
+            free_bytes: self.free_bytes + rhs.free_bytes,
+            largest_free_bytes: self.largest_free_bytes.max(rhs.largest_free_bytes),
+        }
+    }
+}
+
+/// The vertex and index pools chunk meshes are uploaded to
+pub struct ChunkPool {
+    pub vertices: MeshPool<Vertex>,
+    pub indices: MeshPool<u32>,
+}
+
+impl ChunkPool {
+    pub fn new() -> Self {
+        Self {
+            vertices: MeshPool::new("Chunk vertex pool", wgpu::BufferUsages::VERTEX),
+            indices: MeshPool::new("Chunk index pool", wgpu::BufferUsages::INDEX),
+        }
+    }
+
+    /// Uploads the mesh into the allocations of the buffer, reusing
+    /// them in place when the mesh fits
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        uploader: &mut Uploader,
+        buffer: &mut ChunkBuffer,
+        mesh: &ChunkMesh,
+    ) {
+        buffer.vertices =
+            self.vertices
+                .realloc(device, buffer.vertices.take(), mesh.vertices.len());
+        buffer.indices = self
+            .indices
+            .realloc(device, buffer.indices.take(), mesh.indices.len());
+        if let Some(vertices) = &buffer.vertices {
+            self.vertices
+                .write(device, uploader, vertices, &mesh.vertices);
+        }
+        if let Some(indices) = &buffer.indices {
+            self.indices.write(device, uploader, indices, &mesh.indices);
+        }
+        buffer.indices_len = mesh.num_elements;
+    }
+
+    /// Returns the allocations of the buffer to the pool, leaving it empty
+    pub fn free(&mut self, buffer: &mut ChunkBuffer) {
+        if let Some(vertices) = buffer.vertices.take() {
+            self.vertices.free(vertices);
+        }
+        if let Some(indices) = buffer.indices.take() {
+            self.indices.free(indices);
+        }
+        buffer.indices_len = 0;
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.vertices.stats() + self.indices.stats()
+    }
+}
+
+impl Default for ChunkPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alloc_until_full() {
+        let mut allocator = RangeAllocator::new(100);
+        assert_eq!(allocator.alloc(0), None);
+        assert_eq!(allocator.alloc(30), Some(0..30));
+        assert_eq!(allocator.alloc(30), Some(30..60));
+        assert_eq!(allocator.alloc(50), None);
+        assert_eq!(allocator.alloc(40), Some(60..100));
+        assert_eq!(allocator.free_space(), 0);
+        assert_eq!(allocator.alloc(1), None);
+        assert_eq!(RangeAllocator::new(0).alloc(1), None);
+    }
+
+    #[test]
+    fn adjacent_frees_merge() {
+        let mut allocator = RangeAllocator::new(100);
+        let ranges = [10, 20, 30, 40].map(|len| allocator.alloc(len).unwrap());
+        // Apart from each other, then joined by the range between them
+        allocator.free(ranges[0].clone());
+        allocator.free(ranges[2].clone());
+        assert_eq!(allocator.free_ranges(), 2);
+        assert_eq!(allocator.largest_free(), 30);
+        allocator.free(ranges[1].clone());
+        assert_eq!(allocator.free_ranges(), 1);
+        assert_eq!(allocator.largest_free(), 60);
+        // Merged with the next free range only
+        allocator.free(ranges[3].clone());
+        assert_eq!(allocator.free_ranges(), 1);
+        assert_eq!(allocator.largest_free(), 100);
+        assert_eq!(allocator.alloc(100), Some(0..100));
+    }
+
+    #[test]
+    fn exact_fit_hole_is_reused() {
+        let mut allocator = RangeAllocator::new(100);
+        let a = allocator.alloc(10).unwrap();
+        let b = allocator.alloc(25).unwrap();
+        let _c = allocator.alloc(10).unwrap();
+        allocator.free(b.clone());
+        assert_eq!(allocator.free_ranges(), 2);
+        // The hole is smaller than the space at the end, so it's taken
+        // and no free range is left behind
+        assert_eq!(allocator.alloc(25), Some(b));
+        assert_eq!(allocator.free_ranges(), 1);
+        allocator.free(a);
+        assert_eq!(allocator.alloc(5), Some(0..5));
+        assert_eq!(allocator.free_space(), 100 - 5 - 25 - 10);
+    }
+
+    #[test]
+    fn resize_in_place() {
+        let mut allocator = RangeAllocator::new(100);
+        let a = allocator.alloc(10).unwrap();
+        let b = allocator.alloc(10).unwrap();
+        assert_eq!(allocator.resize(a.clone(), 15), None);
+        assert_eq!(allocator.resize(b.clone(), 30), Some(10..40));
+        assert_eq!(allocator.resize(10..40, 5), Some(10..15));
+        assert_eq!(allocator.free_space(), 85);
+        assert_eq!(allocator.resize(10..15, 90), Some(10..100));
+        assert_eq!(allocator.free_space(), 0);
+        assert_eq!(allocator.resize(a, 10), Some(0..10));
+    }
+}
@@ -0,0 +1,426 @@
+use serde::{Deserialize, Serialize};
+use vek::Mat4;
+
+use super::{
+    bloom::{Bloom, BloomSettings, EMISSIVE_BRIGHTNESS},
+    buffer::Buffer,
+    fxaa::Fxaa,
+    outline::{Outline, OutlineSettings},
+    pipelines::grading::GradingPipeline,
+    texture::{Filter, Mipmaps, SamplerSettings, Texture},
+    upload::Uploader,
+};
+
+/// Smallest fraction of the frame resolution the scene is drawn at
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+
+/// How the colors of the scene are adjusted before they are shown
+#[repr(C)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Serialize, Deserialize, bytemuck::Pod, bytemuck::Zeroable,
+)]
+#[serde(default)]
+pub struct ColorGrading {
+    /// Stops the scene is brightened by, negative to darken it
+    pub exposure: f32,
+    /// Above 1 brightens the dark colors, below 1 darkens them
+    pub gamma: f32,
+    /// 0 for grayscale, above 1 for more vivid colors
+    pub saturation: f32,
+    /// Above 1 spreads the colors away from middle gray
+    pub contrast: f32,
+}
+
+impl ColorGrading {
+    /// Shows the scene as it was drawn
+    pub const NEUTRAL: Self = Self {
+        exposure: 0.0,
+        gamma: 1.0,
+        saturation: 1.0,
+        contrast: 1.0,
+    };
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self::NEUTRAL
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradingUniform {
+    grading: ColorGrading,
+    /// How much of the bloom is added onto the scene, 0 without bloom
+    bloom_intensity: f32,
+    _padding: [f32; 3],
+}
+
+impl GradingUniform {
+    fn new(grading: ColorGrading, bloom: BloomSettings) -> Self {
+        Self {
+            grading,
+            bloom_intensity: if bloom.enabled { bloom.intensity } else { 0.0 },
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+/// Draws the scene into an intermediate texture with room for colors
+/// brighter than white, blooms its brightest parts, then grades and
+/// upscales it into the frame, optionally smoothing its edges with FXAA
+/// afterwards. The UI is drawn over the frame afterwards,
+/// so its colors are never graded and it stays at full resolution.
+pub struct PostProcess {
+    /// What the scene is drawn into, the size of the frame times the
+    /// render scale
+    scene: Texture,
+    /// Depth of the scene, the same size as `scene`
+    depth: Texture,
+    bloom: Bloom,
+    outline: Outline,
+    /// Fraction of the frame resolution the scene is drawn at
+    render_scale: f32,
+    /// How the scene is sampled when it is upscaled to the frame
+    upscale_filter: Filter,
+    upscale_sampler: wgpu::Sampler,
+    /// Only kept while FXAA is on
+    fxaa: Option<Fxaa>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: GradingPipeline,
+    uniform: Buffer<GradingUniform>,
+    grading: ColorGrading,
+    bloom_settings: BloomSettings,
+}
+
+impl PostProcess {
+    /// Format of the texture the scene is drawn into
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    /// `cfg` is the configuration of the frame the scene is graded into
+    pub fn new(device: &wgpu::Device, cfg: &wgpu::SurfaceConfiguration) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post process bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bloom_settings = BloomSettings::default();
+        let uniform = Buffer::new(
+            device,
+            "Grading uniform buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            &[GradingUniform::new(ColorGrading::NEUTRAL, bloom_settings)],
+        );
+        let render_scale = 1.0;
+        let scene_config = Self::scaled_config(cfg, render_scale);
+        let scene = Self::create_scene(device, &scene_config);
+        let bloom = Bloom::new(device, &scene_config, &scene);
+        let depth = Texture::with_depth(&scene_config, device);
+        let outline = Outline::new(device, &scene_config, &depth);
+        let upscale_filter = Filter::Linear;
+        let upscale_sampler = Self::create_upscale_sampler(device, upscale_filter);
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &scene,
+            &upscale_sampler,
+            &bloom,
+            &uniform,
+        );
+        let pipeline = GradingPipeline::new(device, cfg, &[&bind_group_layout]);
+        Self {
+            scene,
+            depth,
+            bloom,
+            outline,
+            render_scale,
+            upscale_filter,
+            upscale_sampler,
+            fxaa: None,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+            uniform,
+            grading: ColorGrading::NEUTRAL,
+            bloom_settings,
+        }
+    }
+
+    /// The configuration of the scene targets for a frame configured with
+    /// `cfg`, which the pipelines drawing the scene are created with
+    pub fn scene_config(&self, cfg: &wgpu::SurfaceConfiguration) -> wgpu::SurfaceConfiguration {
+        Self::scaled_config(cfg, self.render_scale)
+    }
+
+    fn scaled_config(
+        cfg: &wgpu::SurfaceConfiguration,
+        render_scale: f32,
+    ) -> wgpu::SurfaceConfiguration {
+        let scaled = |size: u32| ((size as f32 * render_scale).round() as u32).max(1);
+        wgpu::SurfaceConfiguration {
+            format: Self::FORMAT,
+            width: scaled(cfg.width),
+            height: scaled(cfg.height),
+            ..cfg.clone()
+        }
+    }
+
+    fn create_scene(device: &wgpu::Device, scene_config: &wgpu::SurfaceConfiguration) -> Texture {
+        Texture::render_target(
+            device,
+            (scene_config.width, scene_config.height),
+            Self::FORMAT,
+            "Scene",
+        )
+    }
+
+    /// Samples the scene with the same filter up close and far away, as the
+    /// scene is only ever drawn at its size or larger
+    fn create_upscale_sampler(device: &wgpu::Device, filter: Filter) -> wgpu::Sampler {
+        SamplerSettings {
+            mag_filter: filter,
+            min_filter: filter,
+            mipmaps: Mipmaps::Off,
+            anisotropy: 1,
+        }
+        .create_sampler(device, false)
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        scene: &Texture,
+        upscale_sampler: &wgpu::Sampler,
+        bloom: &Bloom,
+        uniform: &Buffer<GradingUniform>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Post process bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&scene.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(upscale_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform.buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&bloom.output().view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&bloom.output().sampler),
+                },
+            ],
+        })
+    }
+
+    /// Recreates the scene targets and the bloom mips at the new size of
+    /// the frame
+    pub fn resize(&mut self, device: &wgpu::Device, cfg: &wgpu::SurfaceConfiguration) {
+        let scene_config = self.scene_config(cfg);
+        self.scene = Self::create_scene(device, &scene_config);
+        self.depth = Texture::with_depth(&scene_config, device);
+        self.bloom.resize(device, &scene_config, &self.scene);
+        self.outline.resize(device, &self.depth);
+        self.recreate_bind_group(device);
+        if let Some(fxaa) = &mut self.fxaa {
+            fxaa.resize(device, cfg);
+        }
+    }
+
+    fn recreate_bind_group(&mut self, device: &wgpu::Device) {
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.scene,
+            &self.upscale_sampler,
+            &self.bloom,
+            &self.uniform,
+        );
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Draws the scene at a fraction of the frame resolution, recreating
+    /// the scene targets when it changes
+    pub fn set_render_scale(
+        &mut self,
+        device: &wgpu::Device,
+        cfg: &wgpu::SurfaceConfiguration,
+        render_scale: f32,
+    ) {
+        let render_scale = render_scale.clamp(MIN_RENDER_SCALE, 1.0);
+        if render_scale != self.render_scale {
+            self.render_scale = render_scale;
+            self.resize(device, cfg);
+        }
+    }
+
+    pub fn set_upscale_filter(&mut self, device: &wgpu::Device, filter: Filter) {
+        if filter != self.upscale_filter {
+            self.upscale_filter = filter;
+            self.upscale_sampler = Self::create_upscale_sampler(device, filter);
+            self.recreate_bind_group(device);
+        }
+    }
+
+    /// Creates or drops the FXAA pass, so that it costs nothing while off
+    pub fn set_fxaa(&mut self, device: &wgpu::Device, cfg: &wgpu::SurfaceConfiguration, on: bool) {
+        if on != self.fxaa.is_some() {
+            self.fxaa = if on {
+                Some(Fxaa::new(device, cfg))
+            } else {
+                None
+            };
+        }
+    }
+
+    pub fn set_settings(
+        &mut self,
+        queue: &wgpu::Queue,
+        grading: ColorGrading,
+        bloom: BloomSettings,
+    ) {
+        if grading != self.grading || bloom != self.bloom_settings {
+            self.grading = grading;
+            self.bloom_settings = bloom;
+            self.uniform
+                .update(queue, &[GradingUniform::new(grading, bloom)], 0)
+                .expect("The uniform buffer holds one uniform");
+            self.bloom.set_threshold(queue, bloom.threshold);
+        }
+    }
+
+    pub fn set_outline(&mut self, settings: OutlineSettings) {
+        self.outline.set_settings(settings);
+    }
+
+    /// Follows the camera with the outlines, `transform` being the one the
+    /// scene is drawn with
+    pub fn update_outline(
+        &self,
+        device: &wgpu::Device,
+        uploader: &mut Uploader,
+        transform: Mat4<f32>,
+    ) {
+        self.outline.update(device, uploader, transform);
+    }
+
+    /// Darkens the edges of what was drawn so far, before the depth buffer
+    /// is cleared for the held block
+    pub fn render_outline(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.outline.render(encoder, &self.scene.view);
+    }
+
+    /// Brightness emissive blocks add on top of their light, none
+    /// without bloom so that they look the same as other blocks
+    pub fn emission(&self) -> f32 {
+        if self.bloom_settings.enabled {
+            EMISSIVE_BRIGHTNESS
+        } else {
+            0.0
+        }
+    }
+
+    /// The texture the scene is drawn into
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        &self.scene.view
+    }
+
+    pub fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth.view
+    }
+
+    /// Physical size of the scene targets
+    pub fn scene_size(&self) -> (u32, u32) {
+        let size = self.scene.texture.size();
+        (size.width, size.height)
+    }
+
+    /// Blooms and grades the scene into the frame, replacing what it had.
+    /// With FXAA on it is graded into the input of FXAA instead, which
+    /// [`Self::render_fxaa`] then draws into the frame.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, frame: &wgpu::TextureView) {
+        if self.bloom_settings.enabled {
+            self.bloom.render(encoder);
+        }
+        let target = match &self.fxaa {
+            Some(fxaa) => fxaa.input_view(),
+            None => frame,
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Grading Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+    /// Smooths the edges of the graded frame, no pass at all without FXAA
+    pub fn render_fxaa(&self, encoder: &mut wgpu::CommandEncoder, frame: &wgpu::TextureView) {
+        if let Some(fxaa) = &self.fxaa {
+            fxaa.render(encoder, frame);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::scene::camera::CameraUniform;
+
+use super::buffer::Buffer;
+
+/// Corner of the window a screen element is drawn in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    BottomLeft,
+    BottomRight,
+}
+
+/// A 3D element drawn in a square in a corner of the window, with a
+/// transform of its own instead of the one of the main camera.
+/// The transform is bound like the camera uniform, so the shaders of
+/// the world and of the debug lines can draw it.
+pub struct ScreenElement {
+    corner: Corner,
+    /// Side of the square, in logical pixels
+    size: f32,
+    /// Distance from the corner of the window, in logical pixels
+    margin: f32,
+    uniform: Buffer<CameraUniform>,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl ScreenElement {
+    pub fn new(
+        device: &wgpu::Device,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+        label: &str,
+        corner: Corner,
+        size: f32,
+        margin: f32,
+    ) -> Self {
+        let uniform = Buffer::new(
+            device,
+            "Screen element uniform buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            &[CameraUniform::empty()],
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout: transform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform.buf.as_entire_binding(),
+            }],
+        });
+        Self {
+            corner,
+            size,
+            margin,
+            uniform,
+            bind_group,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, uniform: CameraUniform) {
+        self.uniform
+            .update(queue, &[uniform], 0)
+            .expect("The uniform buffer holds one uniform");
+    }
+
+    /// Restricts drawing to the square of the element in a target of the
+    /// given physical size. Returns false when the target is too small to fit it.
+    pub fn set_viewport(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        (width, height): (u32, u32),
+        scale_factor: f32,
+    ) -> bool {
+        let size = self.size * scale_factor;
+        let margin = self.margin * scale_factor;
+        if size + margin > width as f32 || size + margin > height as f32 {
+            return false;
+        }
+        let x = match self.corner {
+            Corner::BottomLeft => margin,
+            Corner::BottomRight => width as f32 - size - margin,
+        };
+        render_pass.set_viewport(x, height as f32 - size - margin, size, size, 0.0, 1.0);
+        true
+    }
+}
@@ -0,0 +1,147 @@
+use vek::Vec3;
+
+use super::{
+    buffer::Buffer,
+    mesh::line::{make_outline_mesh, OutlineVertex},
+    pipelines::selection::SelectionPipeline,
+    Renderable,
+};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SelectionUniform {
+    /// Center of the block and the size of the outline
+    offset: [f32; 4],
+}
+
+/// Smallest the outline gets right before the block breaks
+const BROKEN_SCALE: f32 = 0.4;
+
+/// Draws an outline around the block the player is looking at. The outline
+/// shrinks as the block is being broken.
+pub struct SelectionRenderer {
+    pipeline: SelectionPipeline,
+    vertices: Buffer<OutlineVertex>,
+    indices: Buffer<u16>,
+    num_indices: u32,
+    uniform: Buffer<SelectionUniform>,
+    bind_group: wgpu::BindGroup,
+    target: Option<Vec3<i32>>,
+    /// How far breaking the target went, from 0 to 1
+    progress: f32,
+    /// The render origin the outline is placed relative to
+    origin: Vec3<i32>,
+}
+
+impl Renderable for SelectionRenderer {
+    fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        global_uniforms: &'a wgpu::BindGroup,
+    ) {
+        if self.target.is_none() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline.pipeline);
+        render_pass.set_bind_group(0, global_uniforms, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertices.buf.slice(..));
+        render_pass.set_index_buffer(self.indices.buf.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+}
+
+impl SelectionRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        sfc: &wgpu::SurfaceConfiguration,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Selection bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let uniform = Buffer::new(
+            device,
+            "Selection uniform buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            &[SelectionUniform {
+                offset: [0.0, 0.0, 0.0, 1.0],
+            }],
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Selection bind group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform.buf.as_entire_binding(),
+            }],
+        });
+        let pipeline = SelectionPipeline::new(
+            device,
+            sfc,
+            &[transform_bind_group_layout, &bind_group_layout],
+        );
+        let (vertices, indices) = make_outline_mesh();
+
+        Self {
+            pipeline,
+            vertices: Buffer::new(
+                device,
+                "Selection vertex buffer",
+                wgpu::BufferUsages::VERTEX,
+                &vertices,
+            ),
+            indices: Buffer::new(
+                device,
+                "Selection index buffer",
+                wgpu::BufferUsages::INDEX,
+                &indices,
+            ),
+            num_indices: indices.len() as u32,
+            uniform,
+            bind_group,
+            target: None,
+            progress: 0.0,
+            origin: Vec3::zero(),
+        }
+    }
+
+    /// Moves the outline to the given block, or hides it when `None`,
+    /// and shrinks it by the break progress of the block.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        target: Option<Vec3<i32>>,
+        progress: f32,
+        origin: Vec3<i32>,
+    ) {
+        if target != self.target || progress != self.progress || origin != self.origin {
+            if let Some(pos) = target {
+                let pos = (pos - origin).map(|v| v as f32);
+                let scale = 1.0 - (1.0 - BROKEN_SCALE) * progress;
+                self.uniform
+                    .update(
+                        queue,
+                        &[SelectionUniform {
+                            offset: [pos.x, pos.y, pos.z, scale],
+                        }],
+                        0,
+                    )
+                    .expect("The uniform buffer holds one uniform");
+            }
+            self.target = target;
+            self.progress = progress;
+            self.origin = origin;
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
@@ -6,6 +10,10 @@ pub struct Texture {
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// Depth is reversed, 1 at the near plane and 0 at the far plane, which
+    /// spreads the float precision evenly and avoids z-fighting far away
+    pub const DEPTH_CLEAR: f32 = 0.0;
+    pub const DEPTH_COMPARE: wgpu::CompareFunction = wgpu::CompareFunction::GreaterEqual;
 
     pub fn with_depth(sfc_cfg: &wgpu::SurfaceConfiguration, device: &wgpu::Device) -> Self {
         let size = wgpu::Extent3d {
@@ -27,13 +35,14 @@ impl Texture {
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("depth sampler"),
             address_mode_u: wgpu::AddressMode::Repeat,
             address_mode_v: wgpu::AddressMode::Repeat,
             address_mode_w: wgpu::AddressMode::Repeat,
             mag_filter: wgpu::FilterMode::Nearest,
             min_filter: wgpu::FilterMode::Nearest,
             mipmap_filter: wgpu::FilterMode::Nearest,
-            compare: Some(wgpu::CompareFunction::LessEqual),
+            compare: Some(Self::DEPTH_COMPARE),
             lod_min_clamp: 0.0,
             lod_max_clamp: 1000.0,
             ..Default::default()
@@ -46,23 +55,74 @@ impl Texture {
         }
     }
 
+    /// A color texture to draw into and sample afterwards, like the
+    /// scene before it is post-processed
+    pub fn render_target(
+        device: &wgpu::Device,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
     pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         bytes: &[u8],
         label: &str,
-    ) -> Self {
-        let img = image::load_from_memory(bytes).unwrap();
-        Self::from_image(device, queue, &img, Some(label))
+        mip_levels: u32,
+    ) -> Result<Self, image::ImageError> {
+        let img = image::load_from_memory(bytes)?;
+        Ok(Self::from_image(
+            device,
+            queue,
+            &img,
+            Some(label),
+            mip_levels,
+        ))
     }
 
+    /// Uploads the image along with `mip_levels - 1` mipmaps, each half
+    /// the size of the previous one
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
+        mip_levels: u32,
     ) -> Self {
-        let rgba = img.to_rgba8();
+        let mut rgba = img.to_rgba8();
 
         let size = wgpu::Extent3d {
             width: img.width(),
@@ -72,7 +132,7 @@ impl Texture {
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count: mip_levels,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
@@ -80,24 +140,34 @@ impl Texture {
             view_formats: &[],
         });
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * img.width()),
-                rows_per_image: Some(img.height()),
-            },
-            size,
-        );
+        for mip_level in 0..mip_levels {
+            if mip_level > 0 {
+                rgba = downsample(&rgba);
+            }
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &rgba,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * rgba.width()),
+                    rows_per_image: Some(rgba.height()),
+                },
+                wgpu::Extent3d {
+                    width: rgba.width(),
+                    height: rgba.height(),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
@@ -114,3 +184,168 @@ impl Texture {
         }
     }
 }
+
+/// Halves the size of the image, each texel averaging a square of four.
+/// Colors are weighted by their opacity, so that the color of invisible
+/// texels doesn't bleed into the edges of cut out textures.
+fn downsample(img: &image::RgbaImage) -> image::RgbaImage {
+    let (width, height) = ((img.width() / 2).max(1), (img.height() / 2).max(1));
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        let mut color = [0u32; 3];
+        let mut alpha = 0u32;
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let texel = img.get_pixel(
+                (x * 2 + dx).min(img.width() - 1),
+                (y * 2 + dy).min(img.height() - 1),
+            );
+            let a = texel[3] as u32;
+            for channel in 0..3 {
+                color[channel] += texel[channel] as u32 * a;
+            }
+            alpha += a;
+        }
+        let color = match alpha {
+            0 => [0; 3],
+            _ => color.map(|sum| (sum / alpha) as u8),
+        };
+        image::Rgba([color[0], color[1], color[2], (alpha / 4) as u8])
+    })
+}
+
+/// How texels are blended when a texture is drawn at another size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    /// The closest texel, which keeps the texels crisp
+    Nearest,
+    /// A blend of the four closest texels
+    Linear,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 2] = [Filter::Nearest, Filter::Linear];
+}
+
+impl From<Filter> for wgpu::FilterMode {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => wgpu::FilterMode::Nearest,
+            Filter::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Filter::Nearest => "nearest",
+            Filter::Linear => "linear",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Which mipmaps are sampled when a texture is drawn smaller than it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mipmaps {
+    /// Always the full size texture, which shimmers far away
+    Off,
+    /// The closest mipmap
+    Nearest,
+    /// A blend of the two closest mipmaps
+    Linear,
+}
+
+impl Mipmaps {
+    pub const ALL: [Mipmaps; 3] = [Mipmaps::Off, Mipmaps::Nearest, Mipmaps::Linear];
+}
+
+impl Display for Mipmaps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Mipmaps::Off => "off",
+            Mipmaps::Nearest => "nearest",
+            Mipmaps::Linear => "linear",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How the block textures are sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplerSettings {
+    /// Filter up close, where a texel covers several pixels
+    pub mag_filter: Filter,
+    /// Filter far away, where a pixel covers several texels
+    pub min_filter: Filter,
+    pub mipmaps: Mipmaps,
+    /// Most samples taken along surfaces seen at a grazing angle, 1 turns
+    /// anisotropic filtering off. Only applies when every filter is linear.
+    pub anisotropy: u16,
+}
+
+impl SamplerSettings {
+    /// Crisp texels up close and smooth mipmaps far away
+    pub const PIXEL_PERFECT: Self = Self {
+        mag_filter: Filter::Nearest,
+        min_filter: Filter::Linear,
+        mipmaps: Mipmaps::Linear,
+        anisotropy: 1,
+    };
+    /// The closest texel of the full size texture everywhere
+    pub const UNFILTERED: Self = Self {
+        mag_filter: Filter::Nearest,
+        min_filter: Filter::Nearest,
+        mipmaps: Mipmaps::Off,
+        anisotropy: 1,
+    };
+
+    /// Whether the anisotropy applies, which needs every filter to be linear
+    pub fn is_anisotropic(&self) -> bool {
+        self.anisotropy > 1
+            && self.mag_filter == Filter::Linear
+            && self.min_filter == Filter::Linear
+            && self.mipmaps == Mipmaps::Linear
+    }
+
+    /// A sampler of these settings. The anisotropy is left out unless
+    /// `anisotropic_filtering` says the adapter supports it.
+    pub fn create_sampler(
+        &self,
+        device: &wgpu::Device,
+        anisotropic_filtering: bool,
+    ) -> wgpu::Sampler {
+        let anisotropy_clamp = if self.is_anisotropic() && anisotropic_filtering {
+            self.anisotropy
+        } else {
+            1
+        };
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Texture sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: self.mag_filter.into(),
+            min_filter: self.min_filter.into(),
+            mipmap_filter: match self.mipmaps {
+                Mipmaps::Linear => wgpu::FilterMode::Linear,
+                Mipmaps::Off | Mipmaps::Nearest => wgpu::FilterMode::Nearest,
+            },
+            // Only the full size texture is sampled without mipmaps
+            lod_max_clamp: match self.mipmaps {
+                Mipmaps::Off => 0.0,
+                Mipmaps::Nearest | Mipmaps::Linear => 32.0,
+            },
+            anisotropy_clamp,
+            ..Default::default()
+        })
+    }
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self::PIXEL_PERFECT
+    }
+}
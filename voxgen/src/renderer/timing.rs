@@ -0,0 +1,138 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Passes timed on the GPU, in the order they are recorded
+pub const TIMED_PASSES: [&str; 5] = ["World", "Debug", "Post", "FXAA", "UI"];
+/// One timestamp before the first pass and one after each pass
+const QUERY_COUNT: u32 = TIMED_PASSES.len() as u32 + 1;
+const BUFFER_SIZE: u64 = QUERY_COUNT as u64 * wgpu::QUERY_SIZE as u64;
+
+/// A buffer the resolved timestamps of a frame are copied into
+struct Readback {
+    buffer: wgpu::Buffer,
+    /// Set by the map callback once the timestamps can be read
+    mapped: Arc<AtomicBool>,
+    /// Whether the buffer holds timestamps that were not read yet
+    in_use: bool,
+}
+
+/// Measures how long each pass takes on the GPU with timestamp queries.
+///
+/// Timestamps are read back a frame or two after they were written,
+/// alternating between two buffers so the CPU never waits for the GPU.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readbacks: [Readback; 2],
+    /// The readback buffer used by the current frame
+    current: usize,
+    /// Whether the timestamps of the current frame were copied for reading
+    copied: bool,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Milliseconds spent in each of the [`TIMED_PASSES`]
+    timings: Option<[f32; TIMED_PASSES.len()]>,
+}
+
+impl GpuTimer {
+    /// Returns `None` if the device can't write timestamps
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Pass Timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Resolve Buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = || Readback {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp Readback Buffer"),
+                size: BUFFER_SIZE,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            mapped: Arc::new(AtomicBool::new(false)),
+            in_use: false,
+        };
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readbacks: [readback(), readback()],
+            current: 0,
+            copied: false,
+            period: queue.get_timestamp_period(),
+            timings: None,
+        })
+    }
+
+    /// Milliseconds spent in each of the [`TIMED_PASSES`] during a recent frame
+    pub fn timings(&self) -> Option<[f32; TIMED_PASSES.len()]> {
+        self.timings
+    }
+
+    /// Writes the timestamp marking the end of the given pass,
+    /// or the start of the frame for `None`.
+    pub fn write(&self, encoder: &mut wgpu::CommandEncoder, after_pass: Option<usize>) {
+        let index = after_pass.map_or(0, |pass| pass as u32 + 1);
+        encoder.write_timestamp(&self.query_set, index);
+    }
+
+    /// Resolves the timestamps of the frame. They are skipped if both
+    /// readback buffers are still waiting for the GPU.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let readback = &self.readbacks[self.current];
+        self.copied = !readback.in_use;
+        if !self.copied {
+            return;
+        }
+        encoder.resolve_query_set(&self.query_set, 0..QUERY_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &readback.buffer, 0, BUFFER_SIZE);
+    }
+
+    /// Starts mapping the timestamps resolved this frame. Must be called
+    /// after the command buffer of the frame was submitted.
+    pub fn after_submit(&mut self) {
+        if self.copied {
+            let readback = &mut self.readbacks[self.current];
+            readback.in_use = true;
+            let mapped = Arc::clone(&readback.mapped);
+            readback
+                .buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    mapped.store(result.is_ok(), Ordering::Release);
+                });
+        }
+        self.current = (self.current + 1) % self.readbacks.len();
+    }
+
+    /// Reads the timestamps of previous frames that reached the CPU
+    pub fn collect(&mut self, device: &wgpu::Device) {
+        device.poll(wgpu::Maintain::Poll);
+        for readback in self.readbacks.iter_mut() {
+            if !readback.in_use || !readback.mapped.swap(false, Ordering::Acquire) {
+                continue;
+            }
+            let data = readback.buffer.slice(..).get_mapped_range();
+            let ticks: &[u64] = bytemuck::cast_slice(&data);
+            let mut timings = [0.0; TIMED_PASSES.len()];
+            for (pass, timing) in timings.iter_mut().enumerate() {
+                let elapsed = ticks[pass + 1].wrapping_sub(ticks[pass]);
+                *timing = elapsed as f32 * self.period / 1_000_000.0;
+            }
+            drop(data);
+            readback.buffer.unmap();
+            readback.in_use = false;
+            self.timings = Some(timings);
+        }
+    }
+}
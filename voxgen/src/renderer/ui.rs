@@ -1,9 +1,11 @@
 use vek::Vec3;
-use wgpu::{CommandEncoder, SurfaceTexture};
+use wgpu::CommandEncoder;
 
 use crate::{
-    renderer::Renderer,
-    scene::camera::CameraController,
+    input::Bindings,
+    renderer::{Renderer, RendererError, WorldRenderer},
+    scene::{hotbar::Hotbar, inventory::Inventory, Scene},
+    settings::Settings,
     ui::{self, EguiInstance},
 };
 
@@ -15,9 +17,14 @@ pub struct RendererBorrow<'a> {
     surface_config: &'a wgpu::SurfaceConfiguration,
     gui: &'a mut EguiInstance,
     // camera_controller: &'a mut CameraController,
-    wireframe: &'a mut bool,
+    world: &'a mut WorldRenderer,
     delta_time: f32,
     pos: Vec3<f32>,
+    hotbar: &'a Hotbar,
+    inventory: &'a Inventory,
+    bindings: &'a mut Bindings,
+    settings: &'a mut Settings,
+    atlas_texture: egui::TextureId,
 }
 
 pub struct UIRenderer<'frame> {
@@ -29,7 +36,9 @@ impl<'a> RendererBorrow<'a> {
         encoder: &'a mut wgpu::CommandEncoder,
         renderer: &'a mut Renderer,
         dt: f32,
-        pos: Vec3<f32>,
+        scene: &'a Scene,
+        bindings: &'a mut Bindings,
+        settings: &'a mut Settings,
     ) -> Self {
         Self {
             encoder,
@@ -39,9 +48,14 @@ impl<'a> RendererBorrow<'a> {
             surface_config: &renderer.config,
             gui: &mut renderer.gui,
             // camera_controller: &mut renderer.scene.camera_controller,
-            wireframe: &mut renderer.world_renderer.wireframe,
+            world: &mut renderer.world_renderer,
             delta_time: dt,
-            pos,
+            pos: scene.camera_pos(),
+            hotbar: &scene.hotbar,
+            inventory: &scene.inventory,
+            bindings,
+            settings,
+            atlas_texture: renderer.atlas_ui_texture,
         }
     }
 }
@@ -50,26 +64,120 @@ impl<'frame> UIRenderer<'frame> {
         enconder: &'frame mut CommandEncoder,
         renderer: &'frame mut Renderer,
         dt: f32,
-        pos: Vec3<f32>,
+        scene: &'frame Scene,
+        bindings: &'frame mut Bindings,
+        settings: &'frame mut Settings,
     ) -> Self {
-        let renderer: RendererBorrow = RendererBorrow::new(enconder, renderer, dt, pos);
+        let renderer: RendererBorrow =
+            RendererBorrow::new(enconder, renderer, dt, scene, bindings, settings);
         Self { renderer }
     }
 
-    pub fn draw_egui(&mut self, tex: &SurfaceTexture, scale_factor: f32) {
+    pub fn draw_egui(
+        &mut self,
+        view: &wgpu::TextureView,
+        scale_factor: f32,
+    ) -> Result<(), RendererError> {
+        self.renderer.gui.scale_input(
+            (
+                self.renderer.surface_config.width,
+                self.renderer.surface_config.height,
+            ),
+            scale_factor,
+        );
         self.renderer.gui.platform.begin_frame();
         // Draw UI
+        if self.renderer.gui.state.camera_in_block {
+            ui::draw_block_overlay(&mut self.renderer.gui.platform);
+        }
 
         ui::draw_camera_settings(
             &mut self.renderer.gui.platform,
             // &mut self.renderer.camera_controller,
             self.renderer.pos,
+            self.renderer.settings,
         );
         ui::draw_debugging_settings(
             &mut self.renderer.gui.platform,
             self.renderer.delta_time,
-            &mut self.renderer.wireframe,
+            &mut self.renderer.world.wireframe,
+            &mut self.renderer.world.chunk_markers,
+            &mut self.renderer.gui.state.menu_action,
+        );
+        self.renderer
+            .gui
+            .state
+            .frame_graph
+            .draw(&mut self.renderer.gui.platform);
+        self.renderer
+            .gui
+            .state
+            .log_window
+            .draw(&mut self.renderer.gui.platform);
+        self.renderer
+            .gui
+            .state
+            .console
+            .draw(&mut self.renderer.gui.platform);
+        ui::chunk_stats::draw(
+            &mut self.renderer.gui.platform,
+            self.renderer.world.chunk_manager().stats(),
+            self.renderer.world.lod_stats(),
+            self.renderer.gui.state.upload_benchmark,
+            &mut self.renderer.gui.state.menu_action,
+        );
+        ui::draw_controls(&mut self.renderer.gui.platform, self.renderer.bindings);
+        self.renderer
+            .gui
+            .state
+            .overlay
+            .draw(&mut self.renderer.gui.platform);
+        ui::draw_pause_menu(
+            &mut self.renderer.gui.platform,
+            &mut self.renderer.gui.state,
+            self.renderer.settings,
+        );
+        if self.renderer.gui.state.hud_visible {
+            ui::draw_crosshair(&mut self.renderer.gui.platform);
+            ui::draw_hotbar(
+                &mut self.renderer.gui.platform,
+                self.renderer.hotbar,
+                self.renderer.inventory,
+                self.renderer.atlas_texture,
+            );
+            self.renderer
+                .gui
+                .state
+                .minimap
+                .draw(&mut self.renderer.gui.platform);
+            self.renderer
+                .gui
+                .state
+                .hud
+                .draw(&mut self.renderer.gui.platform);
+        }
+        self.renderer.gui.state.inventory.draw(
+            &mut self.renderer.gui.platform,
+            self.renderer.inventory,
+            self.renderer.hotbar,
+            self.renderer.atlas_texture,
         );
+        match self.renderer.gui.state.loading {
+            Some(progress) => ui::draw_loading_screen(&mut self.renderer.gui.platform, progress),
+            None if self.renderer.gui.state.pending_chunks > 0 => {
+                ui::draw_loading(
+                    &mut self.renderer.gui.platform,
+                    self.renderer.gui.state.pending_chunks,
+                );
+            }
+            None => (),
+        }
+        if self.renderer.gui.state.queued_saves > 0 {
+            ui::draw_saving(
+                &mut self.renderer.gui.platform,
+                self.renderer.gui.state.queued_saves,
+            );
+        }
         let output = self.renderer.gui.platform.end_frame(None);
 
         let paint_jobs = self
@@ -86,10 +194,11 @@ impl<'frame> UIRenderer<'frame> {
         };
 
         let texture_delta = output.textures_delta;
-        self.renderer
-            .egui_render_pass
-            .add_textures(self.renderer.device, self.renderer.queue, &texture_delta)
-            .unwrap();
+        self.renderer.egui_render_pass.add_textures(
+            self.renderer.device,
+            self.renderer.queue,
+            &texture_delta,
+        )?;
 
         self.renderer.egui_render_pass.update_buffers(
             self.renderer.device,
@@ -97,20 +206,16 @@ impl<'frame> UIRenderer<'frame> {
             &paint_jobs,
             &screen_descriptor,
         );
+        self.renderer.egui_render_pass.execute(
+            self.renderer.encoder,
+            view,
+            &paint_jobs,
+            &screen_descriptor,
+            None,
+        )?;
         self.renderer
             .egui_render_pass
-            .execute(
-                self.renderer.encoder,
-                &tex.texture
-                    .create_view(&wgpu::TextureViewDescriptor::default()),
-                &paint_jobs,
-                &screen_descriptor,
-                None,
-            )
-            .unwrap();
-        self.renderer
-            .egui_render_pass
-            .remove_textures(texture_delta)
-            .expect("Failed to remove texture");
+            .remove_textures(texture_delta)?;
+        Ok(())
     }
 }
@@ -0,0 +1,165 @@
+use std::time::Instant;
+
+use wgpu::util::StagingBelt;
+
+use crate::world::chunk::ChunkMesh;
+
+/// Size of the staging buffers of the belt, larger writes get one of their own
+const STAGING_CHUNK_SIZE: wgpu::BufferAddress = 4 * 1024 * 1024;
+/// Chunk meshes uploaded by [`benchmark`]
+pub const BENCHMARK_CHUNKS: usize = 16;
+
+/// Records buffer writes into the command encoder of the next frame through
+/// a staging belt, instead of `Queue::write_buffer` which may allocate a
+/// staging buffer on every call. The belt is finished before the encoder
+/// is submitted and recalled after, see [`Uploader::submit`].
+pub struct Uploader {
+    belt: StagingBelt,
+    /// Created by the first write since the last submit
+    encoder: Option<wgpu::CommandEncoder>,
+}
+
+impl Uploader {
+    pub fn new() -> Self {
+        Self {
+            belt: StagingBelt::new(STAGING_CHUNK_SIZE),
+            encoder: None,
+        }
+    }
+
+    /// Copies the data into the buffer at the offset in bytes once the
+    /// encoder is submitted. Offset and size must be multiples of 4.
+    pub fn write(
+        &mut self,
+        device: &wgpu::Device,
+        buffer: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        data: &[u8],
+    ) {
+        let size = match wgpu::BufferSize::new(data.len() as u64) {
+            Some(size) => size,
+            None => return,
+        };
+        let encoder = self.encoder.get_or_insert_with(|| create_encoder(device));
+        self.belt
+            .write_buffer(encoder, buffer, offset, size, device)
+            .copy_from_slice(data);
+    }
+
+    /// The encoder holding the writes since the last submit, for the frame
+    /// to be recorded into after them
+    pub fn take_encoder(&mut self, device: &wgpu::Device) -> wgpu::CommandEncoder {
+        self.encoder
+            .take()
+            .unwrap_or_else(|| create_encoder(device))
+    }
+
+    /// Closes the staging buffers written so far. Must be called before
+    /// submitting the encoder the writes were recorded into.
+    pub fn finish(&mut self) {
+        self.belt.finish();
+    }
+
+    /// Reclaims the staging buffers once the GPU is done with them.
+    /// Must be called after submitting.
+    pub fn recall(&mut self) {
+        self.belt.recall();
+    }
+
+    /// Submits the writes on their own, for frames that end before
+    /// their encoder is submitted
+    pub fn submit(&mut self, queue: &wgpu::Queue) {
+        self.finish();
+        if let Some(encoder) = self.encoder.take() {
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+        self.recall();
+    }
+}
+
+impl Default for Uploader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn create_encoder(device: &wgpu::Device) -> wgpu::CommandEncoder {
+    device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Render enconder"),
+    })
+}
+
+/// Time taken to upload a burst of chunk meshes, with `Queue::write_buffer`
+/// like before the staging belt and through it
+#[derive(Debug, Clone, Copy)]
+pub struct UploadBenchmark {
+    pub bytes: u64,
+    pub write_buffer_ms: f64,
+    pub staging_belt_ms: f64,
+}
+
+/// Uploads `BENCHMARK_CHUNKS` of the meshes, repeating them if there are
+/// fewer, into scratch buffers both ways. Each way is timed until the GPU
+/// finished the copies, after a first run that is left out so that neither
+/// pays for allocating its staging memory. `None` without meshes.
+pub fn benchmark(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    meshes: &[&ChunkMesh],
+) -> Option<UploadBenchmark> {
+    if meshes.is_empty() {
+        return None;
+    }
+    let burst = meshes
+        .iter()
+        .cycle()
+        .take(BENCHMARK_CHUNKS)
+        .flat_map(|mesh| {
+            [
+                bytemuck::cast_slice::<_, u8>(&mesh.vertices),
+                bytemuck::cast_slice(&mesh.indices),
+            ]
+        })
+        .filter(|data| !data.is_empty())
+        .collect::<Vec<_>>();
+    let bytes = burst.iter().map(|data| data.len() as u64).sum::<u64>();
+    let target = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Upload benchmark"),
+        size: bytes.max(4),
+        usage: wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let offsets = burst.iter().scan(0, |offset, data| {
+        let start = *offset;
+        *offset += data.len() as u64;
+        Some(start)
+    });
+    let offsets = offsets.collect::<Vec<_>>();
+
+    let time = |upload: &mut dyn FnMut()| {
+        upload();
+        device.poll(wgpu::Maintain::Wait);
+        let start = Instant::now();
+        upload();
+        device.poll(wgpu::Maintain::Wait);
+        start.elapsed().as_secs_f64() * 1000.0
+    };
+    let write_buffer_ms = time(&mut || {
+        for (data, offset) in burst.iter().zip(&offsets) {
+            queue.write_buffer(&target, *offset, data);
+        }
+        queue.submit(None);
+    });
+    let mut uploader = Uploader::new();
+    let staging_belt_ms = time(&mut || {
+        for (data, offset) in burst.iter().zip(&offsets) {
+            uploader.write(device, &target, *offset, data);
+        }
+        uploader.submit(queue);
+    });
+    Some(UploadBenchmark {
+        bytes,
+        write_buffer_ms,
+        staging_belt_ms,
+    })
+}
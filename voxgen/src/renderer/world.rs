@@ -1,45 +1,240 @@
 use crate::{
-    scene::camera::Camera,
-    world::{chunk::ChunkPos, chunk_manager::ChunkManager},
+    block::BlockId,
+    scene::frustum::Frustum,
+    world::{
+        border::WorldBorder,
+        chunk::{Chunk, ChunkMesh, ChunkPos, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH},
+        chunk_manager::ChunkManager,
+        events::WorldEvent,
+        export,
+        light::Lighting,
+        storage::{WorldStorage, WORLD_DIR},
+    },
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
 };
-use log::info;
-use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use vek::Vec3;
 
-use super::{atlas::Atlas, pipelines::voxel::VoxelPipeline, Renderable};
+use super::{
+    atlas::{Atlas, ATLAS_PNG},
+    buffer::ChunkBuffer,
+    chunk_uniforms::ChunkUniforms,
+    lod::{LodSelector, LodStats},
+    markers,
+    occlusion::{self, OcclusionCuller, SectionMask, ALL_SECTIONS},
+    pipelines::voxel::VoxelPipeline,
+    pool::ChunkPool,
+    upload::{self, UploadBenchmark, Uploader},
+    RendererError,
+};
 
 pub const RENDER_DISTANCE: i32 = 4;
+/// Chunks away from the camera at which chunks are drawn at half resolution
+pub const DEFAULT_LOD_DISTANCE: u32 = 2;
+
+/// The order to draw chunks in, relative to the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawOrder {
+    /// Nearest first, so opaque geometry behind it fails the depth test early
+    FrontToBack,
+    /// Farthest first, so blended geometry composites correctly
+    BackToFront,
+}
+
+/// Sorts the indices of the chunks inside the frustum by the distance of their
+/// center to `eye`. `out` is cleared first so its allocation can be reused every frame.
+pub fn sort_chunks(
+    chunks: &[Chunk],
+    eye: Vec3<f32>,
+    frustum: &Frustum,
+    order: DrawOrder,
+    out: &mut Vec<(f32, usize)>,
+) {
+    out.clear();
+    let visible = chunks.iter().enumerate().filter(|(_, chunk)| {
+        // Blocks are centered on integer coordinates
+        let min = chunk.pos.to_world().map(|v| v as f32) - 0.5;
+        let size = Vec3::new(CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH).map(|v| v as f32);
+        frustum.intersects_aabb(min, min + size)
+    });
+    out.extend(visible.map(|(index, chunk)| {
+        // Blocks are centered on integer coordinates
+        let center = chunk.pos.to_world().map(|v| v as f32)
+            + Vec3::new(CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH).map(|v| v as f32 / 2.0 - 0.5);
+        (center.distance_squared(eye), index)
+    }));
+    match order {
+        DrawOrder::FrontToBack => out.sort_unstable_by(|a, b| a.0.total_cmp(&b.0)),
+        DrawOrder::BackToFront => out.sort_unstable_by(|a, b| b.0.total_cmp(&a.0)),
+    }
+}
 
 pub struct WorldRenderer {
     chunk_manager: ChunkManager,
     pipeline: VoxelPipeline,
-    pipeline_wireframe: VoxelPipeline,
+    /// Missing when the adapter can't draw lines
+    pipeline_wireframe: Option<VoxelPipeline>,
+    /// Blends water and glass over the opaque chunks
+    pipeline_transparent: VoxelPipeline,
     pub wireframe: bool,
+    /// Whether the draws of each chunk are grouped under its position in
+    /// frame captures, only with [`markers::ENABLED`]
+    pub chunk_markers: bool,
     pub atlas: Atlas,
+    /// Where each chunk is, as chunk meshes are relative to their origin
+    chunk_uniforms: ChunkUniforms,
+    /// Events processed during the last tick
+    events: Vec<WorldEvent>,
+    /// Chunk indices in the order they are drawn this frame
+    draw_order: Vec<(f32, usize)>,
+    /// Chunk indices in the order their transparent meshes are drawn this frame
+    transparent_order: Vec<(f32, usize)>,
+    occlusion: OcclusionCuller,
+    /// Chunks in view hidden by the occlusion culling this frame
+    occluded: usize,
+    lod: LodSelector,
+    /// Vertices of the chunks drawn this frame
+    lod_stats: LodStats,
+}
+
+/// Draws the visible sections of the given chunk buffer at the uniform offset
+/// of its chunk, skipping empty ones
+fn draw_buffer<'a>(
+    render_pass: &mut wgpu::RenderPass<'a>,
+    uniforms: &'a ChunkUniforms,
+    pool: &'a ChunkPool,
+    chunk: &Chunk,
+    buffer: &ChunkBuffer,
+    mesh: &ChunkMesh,
+    visible: SectionMask,
+) {
+    let (offset, vertices, indices) = match (
+        uniforms.offset(chunk.pos),
+        &buffer.vertices,
+        &buffer.indices,
+    ) {
+        (Some(offset), Some(vertices), Some(indices)) if buffer.indices_len > 0 => {
+            (offset, vertices, indices)
+        }
+        _ => return,
+    };
+    render_pass.set_bind_group(2, uniforms.bind_group(), &[offset]);
+    // The pages hold many chunks, the indices of each start from 0
+    render_pass.set_vertex_buffer(0, pool.vertices.buffer(vertices.page).slice(..));
+    render_pass.set_index_buffer(
+        pool.indices.buffer(indices.page).slice(..),
+        wgpu::IndexFormat::Uint32,
+    );
+    let first_index = indices.range.start as u32;
+    let base_vertex = vertices.range.start as i32;
+    if visible == ALL_SECTIONS {
+        render_pass.draw_indexed(
+            first_index..first_index + buffer.indices_len,
+            base_vertex,
+            0..1,
+        );
+        return;
+    }
+    for range in occlusion::section_ranges(mesh, visible).filter(|range| !range.is_empty()) {
+        render_pass.draw_indexed(
+            first_index + range.start..first_index + range.end,
+            base_vertex,
+            0..1,
+        );
+    }
 }
 
-impl Renderable for WorldRenderer {
-    fn render<'a>(
+/// Groups the draws of a chunk under its position in frame captures
+fn push_chunk_marker(render_pass: &mut wgpu::RenderPass, pos: ChunkPos) {
+    markers::push(render_pass, &format!("Chunk {} {} {}", pos.x, pos.y, pos.z));
+}
+
+impl WorldRenderer {
+    fn wireframe_pipeline(&self) -> Option<&VoxelPipeline> {
+        self.pipeline_wireframe.as_ref().filter(|_| self.wireframe)
+    }
+
+    pub fn render_opaque<'a>(
         &'a self,
         render_pass: &mut wgpu::RenderPass<'a>,
         global_uniforms: &'a wgpu::BindGroup,
     ) {
-        if self.wireframe {
-            render_pass.set_pipeline(&self.pipeline_wireframe.pipeline);
-        } else {
-            render_pass.set_pipeline(&self.pipeline.pipeline);
+        match self.wireframe_pipeline() {
+            Some(wireframe) => render_pass.set_pipeline(&wireframe.pipeline),
+            None => render_pass.set_pipeline(&self.pipeline.pipeline),
+        }
+
+        render_pass.set_bind_group(0, &self.atlas.bind_group, &[]);
+        render_pass.set_bind_group(1, global_uniforms, &[]);
+
+        let marked = markers::ENABLED && self.chunk_markers;
+        let chunks = self.chunk_manager.chunks();
+        for (_, index) in &self.draw_order {
+            if let Some(chunk) = chunks.get(*index) {
+                let (buffer, mesh) = if self.lod.is_far(chunk.pos) {
+                    (&chunk.lod.buffer, &chunk.lod.mesh)
+                } else {
+                    (&chunk.buffer, &chunk.mesh)
+                };
+                if marked {
+                    push_chunk_marker(render_pass, chunk.pos);
+                }
+                draw_buffer(
+                    render_pass,
+                    &self.chunk_uniforms,
+                    self.chunk_manager.pool(),
+                    chunk,
+                    buffer,
+                    mesh,
+                    self.occlusion.visible_sections(*index),
+                );
+                if marked {
+                    markers::pop(render_pass);
+                }
+            }
         }
+    }
 
+    /// Transparent faces go last so they blend over everything behind them
+    pub fn render_transparent<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        global_uniforms: &'a wgpu::BindGroup,
+    ) {
+        match self.wireframe_pipeline() {
+            Some(wireframe) => render_pass.set_pipeline(&wireframe.pipeline),
+            None => render_pass.set_pipeline(&self.pipeline_transparent.pipeline),
+        }
         render_pass.set_bind_group(0, &self.atlas.bind_group, &[]);
         render_pass.set_bind_group(1, global_uniforms, &[]);
 
-        for chunk in self.chunk_manager.chunks() {
-            render_pass.set_vertex_buffer(0, chunk.buffer.vertex_buf.buf.slice(..));
-            render_pass.set_index_buffer(
-                chunk.buffer.index_buf.buf.slice(..),
-                wgpu::IndexFormat::Uint32,
-            );
-            render_pass.draw_indexed(0..chunk.buffer.indices_len, 0, 0..1);
+        let marked = markers::ENABLED && self.chunk_markers;
+        let chunks = self.chunk_manager.chunks();
+        for (_, index) in &self.transparent_order {
+            if let Some(chunk) = chunks.get(*index) {
+                let (buffer, mesh) = if self.lod.is_far(chunk.pos) {
+                    (&chunk.lod.transparent_buffer, &chunk.lod.transparent_mesh)
+                } else {
+                    (&chunk.transparent_buffer, &chunk.transparent_mesh)
+                };
+                if marked {
+                    push_chunk_marker(render_pass, chunk.pos);
+                }
+                draw_buffer(
+                    render_pass,
+                    &self.chunk_uniforms,
+                    self.chunk_manager.pool(),
+                    chunk,
+                    buffer,
+                    mesh,
+                    self.occlusion.visible_sections(*index),
+                );
+                if marked {
+                    markers::pop(render_pass);
+                }
+            }
         }
     }
 }
@@ -51,48 +246,256 @@ impl WorldRenderer {
         queue: &wgpu::Queue,
         cfg: &wgpu::SurfaceConfiguration,
         transform_bind_group_layout: &wgpu::BindGroupLayout,
-    ) -> Self {
+    ) -> Result<Self, RendererError> {
         let shader =
             device.create_shader_module(wgpu::include_wgsl!("../../../assets/shaders/cube.wgsl"));
-        let texture_atlas = include_bytes!("../../../assets/atlas.png");
-        let atlas = Atlas::new(texture_atlas, &device, &queue);
-        let pipeline = VoxelPipeline::new(
-            device,
-            &shader,
-            cfg,
-            &[&atlas.bind_group_layout, &transform_bind_group_layout],
-            wgpu::PolygonMode::Fill,
-        );
-
-        let pipeline_wireframe = VoxelPipeline::new(
-            device,
-            &shader,
-            cfg,
-            &[&atlas.bind_group_layout, &transform_bind_group_layout],
-            wgpu::PolygonMode::Line,
-        );
-        let mut world = Self {
-            chunk_manager: ChunkManager::new(),
-            pipeline,
+        let atlas = Atlas::new(ATLAS_PNG, device, queue)?;
+        let chunk_uniforms = ChunkUniforms::new(device);
+        let layouts = [
+            &atlas.bind_group_layout,
+            transform_bind_group_layout,
+            chunk_uniforms.layout(),
+        ];
+        let pipeline = |polygon_mode, transparent| {
+            VoxelPipeline::new(
+                device,
+                &shader,
+                "vs_chunk",
+                cfg,
+                &layouts,
+                polygon_mode,
+                transparent,
+            )
+        };
+        let pipeline_wireframe = device
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE)
+            .then(|| pipeline(wgpu::PolygonMode::Line, false));
+        // Chunks are streamed in by `tick`, behind the loading screen at startup
+        Ok(Self {
+            chunk_manager: ChunkManager::new(Arc::new(WorldStorage::new(WORLD_DIR))),
+            pipeline: pipeline(wgpu::PolygonMode::Fill, false),
             pipeline_wireframe,
+            pipeline_transparent: pipeline(wgpu::PolygonMode::Fill, true),
             atlas,
+            chunk_uniforms,
             wireframe: false,
-        };
-        world
+            chunk_markers: false,
+            events: Vec::new(),
+            draw_order: Vec::new(),
+            transparent_order: Vec::new(),
+            occlusion: OcclusionCuller::new(),
+            occluded: 0,
+            lod: LodSelector::new(DEFAULT_LOD_DISTANCE),
+            lod_stats: LodStats::default(),
+        })
+    }
+
+    pub fn tick(&mut self, player_pos: Vec3<f32>, device: &wgpu::Device, uploader: &mut Uploader) {
+        let player_chunk_pos = ChunkPos::from_world(player_pos);
+        // Edits must be applied before their chunk can be unloaded
+        self.events = self.chunk_manager.process_events();
+        self.chunk_manager.tick(player_chunk_pos, device, uploader);
+        self.chunk_manager.remesh_dirty(device, uploader);
+    }
+
+    /// Gives the newly loaded chunks their uniform slot and frees the
+    /// slots of unloaded ones, placing the chunks relative to the render
+    /// origin. Must be called before drawing.
+    pub fn update_chunk_uniforms(
+        &mut self,
+        device: &wgpu::Device,
+        uploader: &mut Uploader,
+        origin: Vec3<i32>,
+    ) {
+        self.chunk_uniforms
+            .update(device, uploader, self.chunk_manager.chunks(), origin);
+    }
+
+    /// Times uploading the meshes of the loaded chunks with and without
+    /// the staging belt, see [`upload::benchmark`]
+    pub fn benchmark_uploads(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Option<UploadBenchmark> {
+        let meshes = self
+            .chunk_manager
+            .chunks()
+            .iter()
+            .map(|chunk| &chunk.mesh)
+            .collect::<Vec<_>>();
+        upload::benchmark(device, queue, &meshes)
+    }
+
+    /// Orders the chunks in view for drawing from the given camera position,
+    /// leaving out the ones hidden by terrain, and picks their meshes.
+    /// Must be called after the chunks were loaded or unloaded.
+    pub fn sort_for_drawing(&mut self, eye: Vec3<f32>, frustum: &Frustum) {
+        self.occlusion
+            .update(self.chunk_manager.chunks(), eye, frustum);
+        self.lod.update(self.chunk_manager.chunks(), eye);
+        sort_chunks(
+            self.chunk_manager.chunks(),
+            eye,
+            frustum,
+            DrawOrder::FrontToBack,
+            &mut self.draw_order,
+        );
+        sort_chunks(
+            self.chunk_manager.chunks(),
+            eye,
+            frustum,
+            DrawOrder::BackToFront,
+            &mut self.transparent_order,
+        );
+        let occlusion = &self.occlusion;
+        let in_view = self.draw_order.len();
+        self.draw_order
+            .retain(|(_, index)| occlusion.visible_sections(*index) != 0);
+        self.transparent_order
+            .retain(|(_, index)| occlusion.visible_sections(*index) != 0);
+        self.occluded = in_view - self.draw_order.len();
+
+        let chunks = self.chunk_manager.chunks();
+        self.lod_stats = LodStats::default();
+        for (_, index) in &self.draw_order {
+            let chunk = &chunks[*index];
+            let full = chunk.mesh.vertices.len() + chunk.transparent_mesh.vertices.len();
+            self.lod_stats.full_vertices += full;
+            if self.lod.is_far(chunk.pos) {
+                self.lod_stats.chunks += 1;
+                self.lod_stats.vertices += chunk.lod.vertices();
+            } else {
+                self.lod_stats.vertices += full;
+            }
+        }
+    }
+
+    /// Chunks this many chunks away from the camera are drawn with their
+    /// half resolution meshes, 0 to always draw the full ones
+    pub fn set_lod_distance(&mut self, distance: u32) {
+        self.lod.distance = distance;
+    }
+
+    /// Vertices of the chunks drawn this frame, with and without LOD
+    pub fn lod_stats(&self) -> LodStats {
+        self.lod_stats
+    }
+
+    pub fn set_occlusion_culling(&mut self, enabled: bool) {
+        self.occlusion.enabled = enabled;
+    }
+
+    /// Whether chunks hidden by terrain are left out
+    pub fn occlusion_culling(&self) -> bool {
+        self.occlusion.enabled
+    }
+
+    /// Chunks in view that were left out as terrain hides them this frame
+    pub fn occluded_chunks(&self) -> usize {
+        self.occluded
+    }
+
+    /// The events processed during the last tick
+    pub fn events(&self) -> &[WorldEvent] {
+        &self.events
+    }
+
+    pub fn set_lighting(&mut self, lighting: Lighting) {
+        self.chunk_manager.set_lighting(lighting);
+    }
+
+    pub fn set_border(&mut self, border: WorldBorder) {
+        self.chunk_manager.set_border(border);
+    }
+
+    /// Remeshes the loaded chunks a few at a time, nearest first
+    pub fn remesh_all(&mut self) {
+        self.chunk_manager.remesh_all();
+    }
+
+    pub fn chunk_manager(&self) -> &ChunkManager {
+        &self.chunk_manager
+    }
+
+    pub fn chunk_manager_mut(&mut self) -> &mut ChunkManager {
+        &mut self.chunk_manager
+    }
+
+    /// Writes the meshes of the loaded chunks as a Wavefront OBJ file
+    pub fn export_obj(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let meshes = self
             .chunk_manager
-            .load_chunks(ChunkPos::from_world(Vec3::zero()), device);
-        let v_count = world
+            .chunks()
+            .iter()
+            .map(|chunk| (chunk.pos, &chunk.mesh));
+        export::export_obj(path.as_ref(), meshes)
+    }
+
+    /// Exports the loaded chunks as a binary glTF on a background thread
+    pub fn export_glb(&self, path: impl Into<PathBuf>) {
+        let chunks = self
             .chunk_manager
             .chunks()
-            .par_iter()
-            .map(|c| c.buffer.vertex_buf.len())
-            .sum::<usize>();
-        info!("Vertices count: {}", v_count);
-        world
+            .iter()
+            .filter(|chunk| chunk.mesh.num_elements > 0)
+            .map(|chunk| (chunk.pos, chunk.mesh.clone()))
+            .collect();
+        export::export_glb(path.into(), chunks);
     }
 
-    pub fn tick(&mut self, player_pos: Vec3<f32>, device: &wgpu::Device) {
-        let player_chunk_pos = ChunkPos::from_world(player_pos);
-        self.chunk_manager.tick(player_chunk_pos, device);
+    /// Queues every chunk edited since the last save to be written
+    /// on the saving thread
+    pub fn save(&mut self) {
+        // Edits made since the last tick haven't flagged their chunks yet
+        let events = self.chunk_manager.process_events();
+        self.events.extend(events);
+        let chunks = self.chunk_manager.take_modified();
+        if !chunks.is_empty() {
+            self.chunk_manager.saver().queue_chunks(chunks);
+        }
+    }
+
+    /// Returns the block at the given world position,
+    /// or `None` if the position is not inside a loaded chunk.
+    pub fn block_at_world(&self, pos: Vec3<i32>) -> Option<BlockId> {
+        self.chunk_manager.get_block(pos)
+    }
+
+    /// Height of the highest block that isn't air in the loaded chunks
+    /// of the column, or `None` if they are all empty or none is loaded.
+    pub fn height_at_world(&self, x: i32, z: i32) -> Option<i32> {
+        self.chunk_manager.height_at_world(x, z)
+    }
+
+    /// Whether the block at the given world position is solid.
+    /// Positions outside of loaded chunks are not solid.
+    pub fn is_solid_world(&self, pos: Vec3<i32>) -> bool {
+        self.block_at_world(pos)
+            .is_some_and(|block| block.is_solid())
+    }
+
+    /// Replaces the block at the given world position, remeshing the affected
+    /// chunks on the next tick. Returns the previous block, or `None` if the
+    /// position is not inside a loaded chunk.
+    pub fn set_block_world(&mut self, pos: Vec3<i32>, id: BlockId) -> Option<BlockId> {
+        self.chunk_manager.set_block(pos, id)
+    }
+
+    /// Returns the metadata of the block at the given world position,
+    /// or `None` if the position is not inside a loaded chunk.
+    pub fn meta_at_world(&self, pos: Vec3<i32>) -> Option<u8> {
+        self.chunk_manager.get_meta(pos)
+    }
+
+    /// Like [`WorldRenderer::set_block_world`], also setting the metadata of the block
+    pub fn set_block_meta_world(
+        &mut self,
+        pos: Vec3<i32>,
+        id: BlockId,
+        meta: u8,
+    ) -> Option<BlockId> {
+        self.chunk_manager.set_block_with_meta(pos, id, meta)
     }
 }
@@ -0,0 +1,90 @@
+use vek::Vec3;
+
+use crate::block::BlockId;
+
+/// How far breaking the targeted block went
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BreakProgress {
+    pub pos: Vec3<i32>,
+    pub block: BlockId,
+    /// From 0 when the player started to 1 when the block breaks
+    pub progress: f32,
+}
+
+/// Accumulates break progress on the targeted block while the break button
+/// is held. The block breaks after its hardness in seconds.
+#[derive(Debug, Default)]
+pub struct Breaking {
+    held: bool,
+    /// Breaks blocks as soon as the button is pressed instead
+    pub instant: bool,
+    current: Option<BreakProgress>,
+}
+
+impl Breaking {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_held(&mut self, held: bool) {
+        self.held = held;
+        if !held {
+            self.current = None;
+        }
+    }
+
+    /// Forgets the progress and the held button, for when the input
+    /// can't be followed anymore like in the pause menu
+    pub fn cancel(&mut self) {
+        self.set_held(false);
+    }
+
+    pub fn progress(&self) -> Option<BreakProgress> {
+        self.current
+    }
+
+    /// Advances the progress on the targeted block, starting over when the
+    /// target changed. Returns the block to break once the progress completes.
+    pub fn update(&mut self, target: Option<(Vec3<i32>, BlockId)>, dt: f32) -> Option<Vec3<i32>> {
+        if !self.held || self.instant {
+            self.current = None;
+            return None;
+        }
+        let (pos, block) = match target {
+            Some(target) => target,
+            None => {
+                self.current = None;
+                return None;
+            }
+        };
+        let hardness = match block.properties().hardness {
+            Some(hardness) if !block.is_air() => hardness,
+            _ => {
+                self.current = None;
+                return None;
+            }
+        };
+        let current = match self.current {
+            Some(current) if current.pos == pos && current.block == block => current,
+            _ => BreakProgress {
+                pos,
+                block,
+                progress: 0.0,
+            },
+        };
+        let progress = if hardness > 0.0 {
+            current.progress + dt / hardness
+        } else {
+            1.0
+        };
+        if progress >= 1.0 {
+            self.current = None;
+            return Some(pos);
+        }
+        self.current = Some(BreakProgress {
+            progress,
+            ..current
+        });
+        None
+    }
+}
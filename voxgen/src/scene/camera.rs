@@ -1,55 +1,221 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use vek::{Mat4, Vec3};
-use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
+use crate::{
+    input::Action,
+    world::chunk::{ChunkPos, SURFACE_HEIGHT},
+};
 
-use crate::world::chunk::CHUNK_HEIGHT;
+use super::frustum::Frustum;
+use bevy_ecs::prelude::Component;
+use vek::{FrustumPlanes, Mat4, Vec3};
 
-type Point3 = Vec3<f32>;
+/// Positions in the world are kept in double precision, everything
+/// rendered is relative to `Camera::render_origin` in single precision
+type Point3 = Vec3<f64>;
 
+pub const SPRINT_SPEED_MULTIPLIER: f32 = 1.5;
+pub const CROUCH_SPEED_MULTIPLIER: f32 = 0.3;
+/// How much wider the field of view gets while sprinting
+pub const SPRINT_FOV_SCALE: f32 = 1.15;
+/// Time it takes to blend between movement states, in seconds
+pub const TRANSITION_TIME: f32 = 0.15;
+/// Degrees turned per unit of mouse motion at a sensitivity of 1
+const DEGREES_PER_MOUSE_UNIT: f32 = 0.15;
+/// Default vertical field of view, in degrees
+pub const DEFAULT_FOV: f32 = 70.0;
+/// Default distance between the player and the camera in third person
+pub const THIRD_PERSON_DISTANCE: f32 = 4.0;
+/// Where the camera starts, before the player is moved to the world spawn
+pub const SPAWN_POS: Vec3<f32> = Vec3::new(8.0, SURFACE_HEIGHT as f32 + 2.5, 8.0);
+/// Height the top-down view looks down from, above the player. The
+/// view reaches as far below the player.
+const TOP_DOWN_HEIGHT: f32 = 64.0;
+/// Range of the top-down zoom, as half the visible height in blocks
+const MIN_ORTHO_ZOOM: f32 = 4.0;
+const MAX_ORTHO_ZOOM: f32 = 256.0;
+/// Blocks panned per second at a zoom of 1, panning gets faster when zoomed out
+const PAN_SPEED: f32 = 1.5;
+
+#[derive(Component)]
 pub struct Camera {
     pitch: f32,
     yaw: f32,
     pub pos: Point3,
+    /// Position before the last simulation step, used to interpolate
+    /// between steps when rendering
+    prev_pos: Point3,
     target: Vec3<f32>,
-    pub fov_y_deg: f32,
+    /// Vertical field of view, in degrees
+    pub fov_degrees: f32,
+    /// Multiplier applied on top of `fov_degrees`, e.g. while sprinting
+    pub fov_scale: f32,
     width: f32,
     height: f32,
     pub near_plane: f32,
     pub far_plane: f32,
+    pub view_mode: ViewMode,
+    /// Distance of the camera from the player in third person
+    pub third_person_distance: f32,
+    /// Current distance from the player, shorter than `third_person_distance`
+    /// when a block is in the way
+    arm_length: f32,
+    pub projection: Projection,
+    /// Half the height of the top-down view, in blocks
+    pub ortho_zoom: f32,
+    /// Offset of the top-down view from the player
+    ortho_pan: Vec3<f32>,
 }
 
 impl Camera {
     pub fn new(width: f32, height: f32) -> Self {
+        let pos = SPAWN_POS.map(|v| v as f64);
         Self {
-            pos: Vec3::new(8.0, CHUNK_HEIGHT as f32 + 1.0, 8.0),
+            pos,
+            prev_pos: pos,
             target: Vec3::new(0.0, 1.0, 2.0),
-            fov_y_deg: 45.0,
+            fov_degrees: DEFAULT_FOV,
+            fov_scale: 1.0,
             width,
             height,
             near_plane: 0.1,
             far_plane: 400.0,
             yaw: 0.0, // Point torwards Z,
             pitch: 10.0,
+            view_mode: ViewMode::FirstPerson,
+            third_person_distance: THIRD_PERSON_DISTANCE,
+            arm_length: 0.0,
+            projection: Projection::Perspective,
+            ortho_zoom: 32.0,
+            ortho_pan: Vec3::zero(),
+        }
+    }
+
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Compass heading of the view in degrees, clockwise from north (+Z).
+    /// East is +X, which is to the right when facing north.
+    pub fn heading(&self) -> f32 {
+        self.target
+            .x
+            .atan2(self.target.z)
+            .to_degrees()
+            .rem_euclid(360.0)
+    }
+
+    /// The normalized direction the camera is looking at
+    pub fn direction(&self) -> Vec3<f32> {
+        self.target
+    }
+
+    /// The direction the view is rendered towards, which faces
+    /// the player in front of them
+    pub fn view_direction(&self) -> Vec3<f32> {
+        if self.projection == Projection::Orthographic {
+            return -Vec3::unit_y();
+        }
+        match self.view_mode {
+            ViewMode::ThirdPersonFront => -self.target,
+            _ => self.target,
         }
     }
 
+    /// The up direction of the view, north in the top-down view
+    pub fn view_up(&self) -> Vec3<f32> {
+        match self.projection {
+            Projection::Perspective => Vec3::unit_y(),
+            Projection::Orthographic => Vec3::unit_z(),
+        }
+    }
+
+    /// Direction from the player to the camera, zero in first person
+    pub fn arm_direction(&self) -> Vec3<f32> {
+        match self.view_mode {
+            ViewMode::FirstPerson => Vec3::zero(),
+            ViewMode::ThirdPersonBehind => -self.target,
+            ViewMode::ThirdPersonFront => self.target,
+        }
+    }
+
+    /// Shortens the distance to the player, e.g. to stay in front of blocks
+    pub fn set_arm_length(&mut self, length: f32) {
+        self.arm_length = length.clamp(0.0, self.third_person_distance);
+    }
+
+    /// Where the view is rendered from. `pos` is the position of the
+    /// player's eyes, which the camera orbits around in third person.
+    pub fn eye_pos(&self, alpha: f32) -> Point3 {
+        match self.projection {
+            Projection::Perspective => {
+                let arm = self.arm_direction() * self.arm_length;
+                self.render_pos(alpha) + arm.map(|v| v as f64)
+            }
+            Projection::Orthographic => {
+                let center = self.render_pos(alpha) + self.ortho_pan.map(|v| v as f64);
+                Vec3::new(center.x, center.y + TOP_DOWN_HEIGHT as f64, center.z)
+            }
+        }
+    }
+
+    /// Switches to the given projection, centering the
+    /// top-down view on the player when entering it
+    pub fn set_projection(&mut self, projection: Projection) {
+        if projection == Projection::Orthographic && self.projection != projection {
+            self.ortho_pan = Vec3::zero();
+        }
+        self.projection = projection;
+    }
+
+    /// Moves the top-down view by the given velocity, in blocks per second
+    /// at a zoom of 1
+    pub fn pan(&mut self, velocity: Vec3<f32>, dt: f32) {
+        self.ortho_pan += velocity * self.ortho_zoom * PAN_SPEED * dt;
+    }
+
+    /// Zooms the top-down view in by the number of scroll steps,
+    /// or out when negative
+    pub fn zoom(&mut self, steps: f32) {
+        self.ortho_zoom =
+            (self.ortho_zoom * 0.9f32.powf(steps)).clamp(MIN_ORTHO_ZOOM, MAX_ORTHO_ZOOM);
+    }
+
+    /// Moves the camera to where a simulation step left it
+    pub fn move_to(&mut self, pos: Point3) {
+        self.prev_pos = self.pos;
+        self.pos = pos;
+    }
+
+    /// Moves the camera without interpolating from the previous position
+    pub fn teleport(&mut self, pos: Point3) {
+        self.prev_pos = pos;
+        self.pos = pos;
+    }
+
+    /// The position to render at, `alpha` of the way between
+    /// the last two simulation steps
+    pub fn render_pos(&self, alpha: f32) -> Point3 {
+        Vec3::lerp(self.prev_pos, self.pos, alpha as f64)
+    }
+
     /// Translate the camera position
     pub fn translate(&mut self, offset: Vec3<f32>) {
-        self.pos += offset;
+        self.pos += offset.map(|v| v as f64);
     }
 
     /// Rotate around X and Y axis
     pub fn rotate(&mut self, x: f32, y: f32) {
-        self.yaw += x;
-        self.pitch += y;
+        self.set_rotation(self.yaw + x, self.pitch + y);
+    }
 
-        if self.pitch > 89.0 {
-            self.pitch = 89.0;
-        }
-        if self.pitch < -89.0 {
-            self.pitch = -89.0;
-        }
+    /// Turns to the given yaw and pitch, in degrees
+    pub fn set_rotation(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-89.0, 89.0);
 
         let (yaw_sin, yaw_cos) = self.yaw.to_radians().sin_cos();
         let (pitch_sin, pitch_cos) = self.pitch.to_radians().sin_cos();
@@ -63,20 +229,118 @@ impl Camera {
         self.height = new_height;
     }
 
-    pub fn update_proj(&self) -> Mat4<f32> {
-        let proj = Mat4::perspective_fov_lh_zo(
-            self.fov_y_deg.to_radians(),
-            self.width,
-            self.height,
-            self.near_plane,
-            self.far_plane,
-        );
-        // let model = Mat4::translation_3d(Vec3::new(10.0, 0.0, 0.0));
-        let view: Mat4<f32> = Mat4::look_at_lh(self.pos, self.target + self.pos, Vec3::unit_y());
-        proj * view
+    /// Maps positions relative to `render_origin` to clip space
+    pub fn update_proj(&self, alpha: f32) -> Mat4<f32> {
+        let proj = match self.projection {
+            Projection::Perspective => Mat4::perspective_fov_lh_zo(
+                (self.fov_degrees * self.fov_scale).to_radians(),
+                self.width,
+                self.height,
+                self.near_plane,
+                self.far_plane,
+            ),
+            Projection::Orthographic => {
+                let half_width = self.ortho_zoom * self.width / self.height;
+                Mat4::orthographic_lh_zo(FrustumPlanes {
+                    left: -half_width,
+                    right: half_width,
+                    bottom: -self.ortho_zoom,
+                    top: self.ortho_zoom,
+                    near: self.near_plane,
+                    far: TOP_DOWN_HEIGHT * 2.0,
+                })
+            }
+        };
+        // The view is relative to the render origin, which keeps the
+        // translation small however far the camera is from the world origin
+        let pos = self.eye_offset(alpha);
+        let view: Mat4<f32> = Mat4::look_at_lh(pos, self.view_direction() + pos, self.view_up());
+        reverse_z() * proj * view
+    }
+
+    /// Origin of the chunk the eye is in. Everything is rendered relative
+    /// to it, so the positions sent to the GPU stay small enough for single
+    /// precision anywhere in the world.
+    pub fn render_origin(&self, alpha: f32) -> Vec3<i32> {
+        let block = self.eye_pos(alpha).map(|v| v.floor() as i32);
+        ChunkPos::from_block(block).to_world()
+    }
+
+    /// Position of the eye relative to the render origin
+    pub fn eye_offset(&self, alpha: f32) -> Vec3<f32> {
+        let origin = self.render_origin(alpha).map(|v| v as f64);
+        (self.eye_pos(alpha) - origin).map(|v| v as f32)
+    }
+
+    /// The volume visible from the camera this frame
+    pub fn frustum(&self, alpha: f32) -> Frustum {
+        Frustum::from_matrix(self.update_proj(alpha), self.render_origin(alpha))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    Perspective,
+    /// Looking straight down at the player, for inspecting terrain
+    Orthographic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewMode {
+    FirstPerson,
+    /// Looking over the player's shoulder
+    ThirdPersonBehind,
+    /// Looking back at the player's face
+    ThirdPersonFront,
+}
+
+impl ViewMode {
+    pub fn next(self) -> Self {
+        match self {
+            ViewMode::FirstPerson => ViewMode::ThirdPersonBehind,
+            ViewMode::ThirdPersonBehind => ViewMode::ThirdPersonFront,
+            ViewMode::ThirdPersonFront => ViewMode::FirstPerson,
+        }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementMode {
+    /// Affected by gravity and collides with blocks
+    Walk,
+    /// Moves freely through the world
+    Fly,
+    /// The player stays in place while the movement keys pan the top-down view
+    Pan,
+}
+
+/// The cardinal direction closest to a heading in degrees, see `Camera::heading`
+pub fn compass_point(heading: f32) -> &'static str {
+    const POINTS: [&str; 4] = ["N", "E", "S", "W"];
+    POINTS[(heading / 90.0).round() as usize % POINTS.len()]
+}
+
+/// Maps depth `z` to `1 - z`, see `Texture::DEPTH_CLEAR`
+#[rustfmt::skip]
+pub fn reverse_z() -> Mat4<f32> {
+    Mat4::new(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, -1.0, 1.0,
+        0.0, 0.0, 0.0, 1.0,
+    )
+}
+
+/// Moves `current` towards `target` by at most `max_step`
+pub fn approach(current: f32, target: f32, max_step: f32) -> f32 {
+    if current < target {
+        (current + max_step).min(target)
+    } else {
+        (current - max_step).max(target)
+    }
+}
+
+#[derive(Component)]
 pub struct CameraController {
     amount_left: f32,
     amount_right: f32,
@@ -88,6 +352,17 @@ pub struct CameraController {
     mouse_dy: f32,
     pub speed: f32,
     pub sensitivity: f32,
+    pub invert_y: bool,
+    pub mode: MovementMode,
+    sprinting: bool,
+    sprint_key_held: bool,
+    last_jump_press: Option<Instant>,
+    last_forward_press: Option<Instant>,
+    fly_key_held: bool,
+    /// The mode to return to when panning stops
+    mode_before_pan: MovementMode,
+    /// Ignores mouse look while the camera is moved along a recorded path
+    pub scripted: bool,
 }
 impl CameraController {
     pub fn new() -> Self {
@@ -101,67 +376,192 @@ impl CameraController {
             mouse_dx: 0.0,
             mouse_dy: 0.0,
             speed: 7.0,
-            // TODO: find out why sensitivity has to be so high
-            sensitivity: 70.0,
+            sensitivity: 1.0,
+            invert_y: false,
+            mode: MovementMode::Walk,
+            sprinting: false,
+            sprint_key_held: false,
+            last_jump_press: None,
+            last_forward_press: None,
+            fly_key_held: false,
+            mode_before_pan: MovementMode::Walk,
+            scripted: false,
+        }
+    }
+
+    /// Returns the yaw and pitch offsets requested by the mouse motion
+    /// received since the last call, in degrees.
+    pub fn take_rotation(&mut self) -> (f32, f32) {
+        if self.scripted {
+            self.mouse_dx = 0.0;
+            self.mouse_dy = 0.0;
+            return (0.0, 0.0);
         }
+        // Mouse deltas are distances, so they must not be scaled by the frame time
+        let scale = self.sensitivity * DEGREES_PER_MOUSE_UNIT;
+        let offset_x = self.mouse_dx * scale;
+        let offset_y = self.mouse_dy * scale;
+        let offset_y = if self.invert_y { offset_y } else { -offset_y };
+
+        self.mouse_dx = 0.0;
+        self.mouse_dy = 0.0;
+        (offset_x, offset_y)
     }
 
-    pub fn update(&mut self, camera: &mut Camera, dt: Duration) {
-        let dt = dt.as_secs_f32();
+    /// Velocity requested by the movement keys, in blocks per second
+    pub fn velocity(&self, camera: &Camera) -> Vec3<f32> {
         let (yaw_sin, yaw_cos) = camera.yaw.to_radians().sin_cos();
         let forward = Vec3::new(yaw_cos, 0.0, -yaw_sin);
         let right = Vec3::new(yaw_sin, 0.0, yaw_cos);
-        let multiplier = self.speed * dt;
 
-        // Translation in x y z
-        let dx = forward * (self.amount_forward - self.amount_backward) * multiplier;
-        let dy = Vec3::new(0.0, (self.amount_up - self.amount_down) * multiplier, 0.0);
-        let dz = right * (self.amount_left - self.amount_right) * multiplier;
-        // Translate using WASD or arrow keys
-        camera.translate(dx + dy + dz);
+        let dx = forward * (self.amount_forward - self.amount_backward);
+        let dy = Vec3::new(0.0, self.amount_up - self.amount_down, 0.0);
+        let dz = right * (self.amount_left - self.amount_right);
+
+        let mut horizontal_speed = self.speed;
+        if self.is_sprinting() {
+            horizontal_speed *= SPRINT_SPEED_MULTIPLIER;
+        } else if self.is_crouching() {
+            horizontal_speed *= CROUCH_SPEED_MULTIPLIER;
+        }
+        (dx + dz) * horizontal_speed + dy * self.speed
+    }
 
-        let offset_x = self.mouse_dx * self.sensitivity * dt;
-        let offset_y = self.mouse_dy * self.sensitivity * dt;
-        camera.rotate(offset_x, -offset_y);
+    /// Direction the top-down view is panned in, north (+Z) when moving forward
+    pub fn pan_direction(&self) -> Vec3<f32> {
+        Vec3::new(
+            self.amount_right - self.amount_left,
+            0.0,
+            self.amount_forward - self.amount_backward,
+        )
+    }
 
+    /// Starts or stops panning, returning to the previous mode after
+    pub fn set_panning(&mut self, panning: bool) {
+        if panning && self.mode != MovementMode::Pan {
+            self.mode_before_pan = self.mode;
+            self.mode = MovementMode::Pan;
+        } else if !panning && self.mode == MovementMode::Pan {
+            self.mode = self.mode_before_pan;
+        }
+    }
+
+    /// The mode the player returns to once panning stops
+    pub fn base_mode(&self) -> MovementMode {
+        match self.mode {
+            MovementMode::Pan => self.mode_before_pan,
+            mode => mode,
+        }
+    }
+
+    /// Sprinting only applies while moving forward
+    pub fn is_sprinting(&self) -> bool {
+        (self.sprinting || self.sprint_key_held) && self.amount_forward > 0.0
+    }
+
+    /// Crouching while walking, descending while flying
+    pub fn is_crouching(&self) -> bool {
+        self.mode == MovementMode::Walk && self.amount_down > 0.0
+    }
+
+    pub fn wants_jump(&self) -> bool {
+        self.amount_up > 0.0
+    }
+
+    pub fn toggle_fly(&mut self) {
+        self.mode = match self.mode {
+            MovementMode::Walk => MovementMode::Fly,
+            MovementMode::Fly => MovementMode::Walk,
+            MovementMode::Pan => MovementMode::Pan,
+        };
+    }
+
+    /// Releases every movement input. Used when release events can't be
+    /// received, e.g. when the window loses focus or input gets locked.
+    pub fn clear_pressed(&mut self) {
+        self.amount_left = 0.0;
+        self.amount_right = 0.0;
+        self.amount_forward = 0.0;
+        self.amount_backward = 0.0;
+        self.amount_up = 0.0;
+        self.amount_down = 0.0;
         self.mouse_dx = 0.0;
         self.mouse_dy = 0.0;
+        self.sprinting = false;
+        self.sprint_key_held = false;
+        self.fly_key_held = false;
     }
 
-    pub fn handle_keyboard_events(&mut self, input: &KeyboardInput) {
-        let amount = if input.state == ElementState::Pressed {
-            1.0
-        } else {
-            0.0
-        };
-        if let Some(key) = input.virtual_keycode {
-            return match key {
-                VirtualKeyCode::W | VirtualKeyCode::Up => {
-                    self.amount_forward = amount;
-                }
-                VirtualKeyCode::S | VirtualKeyCode::Down => {
-                    self.amount_backward = amount;
-                }
-                VirtualKeyCode::A | VirtualKeyCode::Left => {
-                    self.amount_left = amount;
+    pub fn handle_action(&mut self, action: Action, pressed: bool) {
+        let amount = if pressed { 1.0 } else { 0.0 };
+        match action {
+            Action::MoveForward => {
+                // Double tapping forward starts sprinting
+                if amount > 0.0 && self.amount_forward == 0.0 {
+                    let now = Instant::now();
+                    match self.last_forward_press {
+                        Some(last) if now - last < Duration::from_millis(300) => {
+                            self.sprinting = true;
+                            self.last_forward_press = None;
+                        }
+                        _ => self.last_forward_press = Some(now),
+                    }
+                } else if amount == 0.0 {
+                    self.sprinting = false;
                 }
-                VirtualKeyCode::D | VirtualKeyCode::Right => {
-                    self.amount_right = amount;
+                self.amount_forward = amount;
+            }
+            Action::Sprint => {
+                self.sprint_key_held = amount > 0.0;
+            }
+            Action::MoveBackward => {
+                self.amount_backward = amount;
+            }
+            Action::MoveLeft => {
+                self.amount_left = amount;
+            }
+            Action::MoveRight => {
+                self.amount_right = amount;
+            }
+            Action::Jump => {
+                // Double tapping jump toggles flight
+                if amount > 0.0 && self.amount_up == 0.0 {
+                    let now = Instant::now();
+                    match self.last_jump_press {
+                        Some(last) if now - last < Duration::from_millis(300) => {
+                            self.toggle_fly();
+                            self.last_jump_press = None;
+                        }
+                        _ => self.last_jump_press = Some(now),
+                    }
                 }
-                VirtualKeyCode::Space => {
-                    self.amount_up = amount;
+                self.amount_up = amount;
+            }
+            Action::ToggleFly => {
+                // Ignore key repeats while the key is held
+                if amount > 0.0 && !self.fly_key_held {
+                    self.toggle_fly();
                 }
-                VirtualKeyCode::LShift => {
-                    self.amount_down = amount;
-                }
-                _ => (),
-            };
+                self.fly_key_held = amount > 0.0;
+            }
+            Action::Crouch => {
+                self.amount_down = amount;
+            }
+            _ => (),
         }
     }
 
+    /// Accumulates the motion until the next update, as several
+    /// events can arrive within a single frame
     pub fn handle_mouse_events(&mut self, delta_x: f64, delta_y: f64) {
-        self.mouse_dx = delta_x as f32;
-        self.mouse_dy = delta_y as f32;
+        self.mouse_dx += delta_x as f32;
+        self.mouse_dy += delta_y as f32;
+    }
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -169,21 +569,38 @@ impl CameraController {
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
     pub transform: [[f32; 4]; 4],
+    /// Seconds since the renderer started, used to animate textures
+    pub time: f32,
+    /// How bright the sky is, from 0 at night to 1 at noon
+    pub daylight: f32,
+    /// How quickly the fog thickens with distance, 0 without fog
+    pub fog_density: f32,
+    /// Brightness added to emissive blocks so that they bloom, 0 without bloom
+    pub emission: f32,
+    /// Position of the eye relative to the render origin, `w` is unused
+    pub eye: [f32; 4],
+    /// Color distant faces fade to in the fog, `a` is unused
+    pub fog_color: [f32; 4],
 }
 impl CameraUniform {
     pub fn new(mat: vek::Mat4<f32>) -> Self {
         Self {
             transform: mat.into_col_arrays(),
+            time: 0.0,
+            daylight: 1.0,
+            fog_density: 0.0,
+            emission: 0.0,
+            eye: [0.0; 4],
+            fog_color: [0.0; 4],
         }
     }
-    pub fn update(&mut self, camera: &Camera) {
-        self.transform = camera.update_proj().into_col_arrays();
+    pub fn update(&mut self, camera: &Camera, alpha: f32) {
+        self.transform = camera.update_proj(alpha).into_col_arrays();
+        self.eye = camera.eye_offset(alpha).with_w(1.0).into_array();
     }
 
     pub fn empty() -> Self {
-        Self {
-            transform: vek::Mat4::identity().into_col_arrays(),
-        }
+        Self::new(vek::Mat4::identity())
     }
     pub fn to_mat(&mut self) -> Mat4<f32> {
         Mat4::from_col_arrays(self.transform)
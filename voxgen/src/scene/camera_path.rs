@@ -0,0 +1,285 @@
+use std::{error::Error, fmt::Write, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{renderer::timing::TIMED_PASSES, world::chunk::SURFACE_HEIGHT};
+
+use super::camera::Camera;
+
+/// Where recorded paths and the timings of their playback are written
+pub const RECORDINGS_DIR: &str = "recordings";
+/// Time between two recorded samples, in seconds
+const SAMPLE_INTERVAL: f32 = 0.1;
+/// Time the playback advances every frame. Using a fixed step instead of
+/// the frame time renders the same frames on every run.
+const PLAYBACK_STEP: f32 = 1.0 / 60.0;
+/// Distance from the origin along X of the path made by `CameraPath::far_flyover`
+pub const FAR_FLYOVER_X: f64 = 10_000_000.0;
+/// Seconds the far flyover stays at its start while the chunks load
+const FAR_FLYOVER_WAIT: f32 = 3.0;
+/// Seconds the far flyover moves for
+const FAR_FLYOVER_DURATION: f32 = 10.0;
+/// Blocks per second the far flyover moves at, slow enough to see every
+/// fraction of a block it passes through
+const FAR_FLYOVER_SPEED: f64 = 1.5;
+
+/// Position and orientation of the camera at one point of a path
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PathSample {
+    /// In double precision, so paths far from the origin play back smoothly
+    pub pos: [f64; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl PathSample {
+    fn of(camera: &Camera) -> Self {
+        Self {
+            pos: camera.pos.into_array(),
+            yaw: camera.yaw(),
+            pitch: camera.pitch(),
+        }
+    }
+}
+
+/// Camera poses sampled at a fixed interval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraPath {
+    /// Seconds between two samples
+    pub interval: f32,
+    pub samples: Vec<PathSample>,
+}
+
+impl CameraPath {
+    pub fn load(name: &str) -> Result<Self, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path_file(name))?;
+        let path: Self = serde_json::from_str(&contents)?;
+        if path.samples.is_empty() || path.interval <= 0.0 {
+            return Err("the path has no samples".into());
+        }
+        Ok(path)
+    }
+
+    pub fn save(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(RECORDINGS_DIR)?;
+        std::fs::write(path_file(name), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// A slow flight over the terrain `FAR_FLYOVER_X` blocks from the origin,
+    /// where single precision positions are a whole block apart, to check
+    /// that the rendered world stays steady that far out
+    pub fn far_flyover() -> Self {
+        let waiting = (FAR_FLYOVER_WAIT / SAMPLE_INTERVAL) as usize;
+        let moving = (FAR_FLYOVER_DURATION / SAMPLE_INTERVAL) as usize;
+        let samples = (0..waiting + moving)
+            .map(|i| {
+                let time = i.saturating_sub(waiting) as f64 * SAMPLE_INTERVAL as f64;
+                let along = time * FAR_FLYOVER_SPEED;
+                PathSample {
+                    pos: [
+                        FAR_FLYOVER_X + along,
+                        SURFACE_HEIGHT as f64 + 8.0,
+                        along * 0.5,
+                    ],
+                    yaw: 20.0 + time as f32 * 3.0,
+                    pitch: -25.0,
+                }
+            })
+            .collect();
+        Self {
+            interval: SAMPLE_INTERVAL,
+            samples,
+        }
+    }
+
+    /// Seconds from the first to the last sample
+    pub fn duration(&self) -> f32 {
+        self.samples.len().saturating_sub(1) as f32 * self.interval
+    }
+
+    /// The pose at the given time, following a Catmull-Rom spline
+    /// through the samples
+    pub fn sample(&self, time: f32) -> PathSample {
+        let last = self.samples.len() - 1;
+        let position = (time / self.interval).clamp(0.0, last as f32);
+        let index = (position as usize).min(last.saturating_sub(1));
+        let t = position - index as f32;
+        let at = |offset: isize| {
+            let i = (index as isize + offset).clamp(0, last as isize) as usize;
+            self.samples[i]
+        };
+        let (p0, p1, p2, p3) = (at(-1), at(0), at(1), at(2));
+        let spline = |a: f64, b: f64, c: f64, d: f64| catmull_rom(a, b, c, d, t as f64);
+        let angle =
+            |a: f32, b: f32, c: f32, d: f32| spline(a as f64, b as f64, c as f64, d as f64) as f32;
+        PathSample {
+            pos: [0, 1, 2]
+                .map(|axis| spline(p0.pos[axis], p1.pos[axis], p2.pos[axis], p3.pos[axis])),
+            yaw: angle(p0.yaw, p1.yaw, p2.yaw, p3.yaw),
+            pitch: angle(p0.pitch, p1.pitch, p2.pitch, p3.pitch),
+        }
+    }
+}
+
+/// Interpolates between `b` and `c`, with `a` and `d` shaping the curve
+fn catmull_rom(a: f64, b: f64, c: f64, d: f64, t: f64) -> f64 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * b
+        + (c - a) * t
+        + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2
+        + (3.0 * b - a - 3.0 * c + d) * t3)
+}
+
+fn path_file(name: &str) -> PathBuf {
+    PathBuf::from(RECORDINGS_DIR).join(format!("{}.json", name))
+}
+
+fn timings_file(name: &str) -> PathBuf {
+    PathBuf::from(RECORDINGS_DIR).join(format!("{}_timings.csv", name))
+}
+
+/// Timings of a single frame during playback, in milliseconds
+#[derive(Debug, Clone, Copy)]
+pub struct FrameTiming {
+    update_ms: f64,
+    render_ms: f64,
+    gpu_ms: Option<[f32; TIMED_PASSES.len()]>,
+}
+
+/// Recording or playing back a camera path
+pub enum CameraPathMode {
+    Idle,
+    Recording {
+        name: String,
+        path: CameraPath,
+        /// Seconds since the last sample
+        elapsed: f32,
+    },
+    Playing {
+        name: String,
+        path: CameraPath,
+        time: f32,
+        timings: Vec<FrameTiming>,
+    },
+}
+
+impl CameraPathMode {
+    pub fn is_idle(&self) -> bool {
+        matches!(self, CameraPathMode::Idle)
+    }
+
+    pub fn record(name: &str, camera: &Camera) -> Self {
+        CameraPathMode::Recording {
+            name: name.to_owned(),
+            path: CameraPath {
+                interval: SAMPLE_INTERVAL,
+                samples: vec![PathSample::of(camera)],
+            },
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn play(name: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(CameraPathMode::Playing {
+            name: name.to_owned(),
+            path: CameraPath::load(name)?,
+            time: 0.0,
+            timings: Vec::new(),
+        })
+    }
+
+    /// Advances by a frame. Samples the camera while recording and
+    /// returns the pose to move it to while playing.
+    pub fn update(&mut self, frame_time: Duration, camera: &Camera) -> Option<PathSample> {
+        match self {
+            CameraPathMode::Idle => None,
+            CameraPathMode::Recording { path, elapsed, .. } => {
+                *elapsed += frame_time.as_secs_f32();
+                while *elapsed >= path.interval {
+                    *elapsed -= path.interval;
+                    path.samples.push(PathSample::of(camera));
+                }
+                None
+            }
+            CameraPathMode::Playing { path, time, .. } => {
+                let sample = path.sample(*time);
+                *time += PLAYBACK_STEP;
+                Some(sample)
+            }
+        }
+    }
+
+    /// Whether the playback went past the end of its path
+    pub fn is_finished(&self) -> bool {
+        match self {
+            CameraPathMode::Playing { path, time, .. } => *time > path.duration(),
+            _ => false,
+        }
+    }
+
+    /// Records the timings of the last frame while playing
+    pub fn record_timings(
+        &mut self,
+        update: Duration,
+        render: Duration,
+        gpu_ms: Option<[f32; TIMED_PASSES.len()]>,
+    ) {
+        if let CameraPathMode::Playing { timings, .. } = self {
+            timings.push(FrameTiming {
+                update_ms: update.as_secs_f64() * 1000.0,
+                render_ms: render.as_secs_f64() * 1000.0,
+                gpu_ms,
+            });
+        }
+    }
+
+    /// Saves the recorded path or the playback timings and goes back to idle.
+    /// Returns a message describing what was saved.
+    pub fn finish(&mut self) -> Result<String, Box<dyn Error>> {
+        match std::mem::replace(self, CameraPathMode::Idle) {
+            CameraPathMode::Idle => Err("Nothing is being recorded or played".into()),
+            CameraPathMode::Recording { name, path, .. } => {
+                path.save(&name)?;
+                Ok(format!(
+                    "Recorded {} samples to {}",
+                    path.samples.len(),
+                    path_file(&name).display()
+                ))
+            }
+            CameraPathMode::Playing { name, timings, .. } => {
+                let file = timings_file(&name);
+                std::fs::create_dir_all(RECORDINGS_DIR)?;
+                std::fs::write(&file, timings_csv(&timings))?;
+                Ok(format!(
+                    "Saved the timings of {} frames to {}",
+                    timings.len(),
+                    file.display()
+                ))
+            }
+        }
+    }
+}
+
+fn timings_csv(timings: &[FrameTiming]) -> String {
+    let mut csv = String::from("frame,update_ms,render_ms");
+    for pass in TIMED_PASSES {
+        let _ = write!(csv, ",gpu_{}_ms", pass.to_lowercase());
+    }
+    csv.push('\n');
+    for (i, timing) in timings.iter().enumerate() {
+        let _ = write!(csv, "{},{:.3},{:.3}", i, timing.update_ms, timing.render_ms);
+        for pass in 0..TIMED_PASSES.len() {
+            match timing.gpu_ms {
+                Some(gpu_ms) => {
+                    let _ = write!(csv, ",{:.3}", gpu_ms[pass]);
+                }
+                // Left empty when timestamp queries are unsupported
+                None => csv.push(','),
+            }
+        }
+        csv.push('\n');
+    }
+    csv
+}
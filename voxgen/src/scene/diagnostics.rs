@@ -0,0 +1,82 @@
+use bevy_ecs::prelude::Resource;
+use vek::Vec3;
+
+use crate::{renderer::debug, world::raycast::RaycastHit};
+
+use super::physics::Aabb;
+
+const RAY_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+const TARGET_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
+const FACE_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+const PLAYER_COLOR: [f32; 3] = [0.0, 1.0, 0.0];
+const CONSIDERED_COLOR: [f32; 3] = [0.0, 0.5, 1.0];
+/// Keeps the outlines from z-fighting with the block faces
+const OUTLINE_OFFSET: f32 = 0.01;
+
+/// Picking ray of the camera
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vec3<f32>,
+    pub direction: Vec3<f32>,
+    pub length: f32,
+}
+
+/// The last results of the raycast and collision tests. They are only
+/// recorded while enabled, so that nothing is collected when not drawn.
+#[derive(Resource, Default)]
+pub struct RayDiagnostics {
+    enabled: bool,
+    pub ray: Option<Ray>,
+    pub hit: Option<RaycastHit>,
+    /// Collision box of the player after the last step
+    pub player: Option<Aabb>,
+    /// Blocks the swept collision test looked at during the last step
+    pub considered: Vec<Vec3<i32>>,
+}
+
+impl RayDiagnostics {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Forgets the recorded results when disabled
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled {
+            self.enabled = true;
+        } else {
+            *self = Self::default();
+        }
+    }
+
+    /// Queues the recorded results to be drawn over the next frame
+    pub fn draw(&self) {
+        let mut draw = debug::draw();
+        if let Some(ray) = self.ray {
+            let length = self.hit.map_or(ray.length, |hit| hit.distance);
+            draw.line(ray.origin, ray.origin + ray.direction * length, RAY_COLOR);
+        }
+        if let Some(hit) = self.hit {
+            let block = Aabb::block(hit.block_pos);
+            draw.aabb(
+                block.min - OUTLINE_OFFSET,
+                block.max + OUTLINE_OFFSET,
+                TARGET_COLOR,
+            );
+            // A flat box on the face the ray entered through
+            if let Some(face) = hit.face {
+                let normal = face.normalized().map(|v| v as f32);
+                let center = hit.block_pos.map(|v| v as f32) + normal * (0.5 + OUTLINE_OFFSET);
+                let extent = normal.map(|v| if v == 0.0 { 0.5 } else { 0.0 });
+                draw.aabb(center - extent, center + extent, FACE_COLOR);
+                draw.line(center - extent, center + extent, FACE_COLOR);
+            }
+        }
+        if let Some(player) = self.player {
+            draw.aabb(player.min, player.max, PLAYER_COLOR);
+        }
+        for pos in &self.considered {
+            let block = Aabb::block(*pos);
+            draw.aabb(block.min, block.max, CONSIDERED_COLOR);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use bevy_ecs::prelude::Component;
+use vek::{Quaternion, Vec3};
+
+use crate::{
+    block::{registry::registry, BlockId},
+    ecs::Transform,
+    renderer::WorldRenderer,
+    world::{chunk, storage::DropRecord},
+};
+
+use super::physics::{sweep_axis, Aabb, GRAVITY, TERMINAL_VELOCITY};
+
+/// Side of the cube a drop is drawn as, in blocks
+pub const DROP_SIZE: f32 = 0.25;
+/// Distance from the player within which drops are collected, by default
+pub const DEFAULT_PICKUP_RADIUS: f32 = 1.5;
+/// Seconds a drop lies around before it despawns, by default
+pub const DEFAULT_DROP_LIFETIME: f32 = 300.0;
+/// Seconds before a new drop can be collected, so that it's seen popping out
+pub const PICKUP_DELAY: f32 = 0.5;
+/// Drops of the same block closer than this are merged into one
+pub const MERGE_DISTANCE: f32 = 0.75;
+/// Upwards speed a drop pops out of the broken block with
+const POP_SPEED: f32 = 4.0;
+/// Radians the cube turns per second
+const SPIN_SPEED: f32 = 2.0;
+/// Height of the bob above the resting position, in blocks
+const BOB_HEIGHT: f32 = 0.1;
+/// Radians of the bob per second
+const BOB_SPEED: f32 = 2.5;
+
+/// A broken block lying in the world as a small spinning cube, until the
+/// player collects it or it despawns
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ItemDrop {
+    pub block: BlockId,
+    pub count: u32,
+    /// Center of the cube before it bobs
+    pub pos: Vec3<f32>,
+    /// Downwards speed is negative, in blocks per second
+    pub vertical_speed: f32,
+    /// Seconds since the drop spawned or was loaded
+    pub age: f32,
+}
+
+impl ItemDrop {
+    /// A drop popping out of the block broken at `pos`
+    pub fn new(block: BlockId, count: u32, pos: Vec3<i32>) -> Self {
+        Self {
+            block,
+            count,
+            pos: pos.map(|v| v as f32),
+            vertical_speed: POP_SPEED,
+            age: 0.0,
+        }
+    }
+
+    /// A saved drop, unless its block isn't registered anymore or the
+    /// record is invalid
+    pub fn restore(record: &DropRecord) -> Option<Self> {
+        let valid = registry().contains(record.block)
+            && !record.block.is_air()
+            && record.count > 0
+            && record.pos.iter().all(|v| v.is_finite());
+        valid.then(|| Self {
+            block: record.block,
+            count: record.count,
+            pos: Vec3::from(record.pos),
+            vertical_speed: 0.0,
+            // Old drops may already be collected
+            age: PICKUP_DELAY,
+        })
+    }
+
+    pub fn record(&self) -> DropRecord {
+        DropRecord {
+            pos: self.pos.into_array(),
+            block: self.block,
+            count: self.count,
+        }
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        Aabb::new(self.pos - DROP_SIZE / 2.0, self.pos + DROP_SIZE / 2.0)
+    }
+
+    /// Ages the drop and lets it fall onto the block beneath it. Drops
+    /// in unloaded chunks stay where they are, and drops stuck in a block
+    /// placed over them are pushed on top of it.
+    pub fn step(&mut self, dt: f32, world: &WorldRenderer) {
+        self.age += dt;
+        let cell = chunk::block_pos(self.pos);
+        match world.block_at_world(cell) {
+            None => return,
+            Some(block) if block.is_solid() => {
+                // Blocks are centered on integer coordinates
+                self.pos.y = cell.y as f32 + 0.5 + DROP_SIZE / 2.0;
+                self.vertical_speed = 0.0;
+                return;
+            }
+            Some(_) => (),
+        }
+        self.vertical_speed = (self.vertical_speed - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+        let delta = self.vertical_speed * dt;
+        let allowed = sweep_axis(&self.aabb(), 1, delta, |pos| world.is_solid_world(pos));
+        self.pos.y += allowed;
+        if allowed != delta {
+            self.vertical_speed = 0.0;
+        }
+    }
+
+    /// Whether the player can collect the drop from `pos`
+    pub fn can_pickup(&self, pos: Vec3<f32>, radius: f32) -> bool {
+        self.age >= PICKUP_DELAY && self.pos.distance_squared(pos) <= radius * radius
+    }
+
+    /// Whether the drop and `other` are close enough stacks of the same block
+    pub fn can_merge(&self, other: &ItemDrop) -> bool {
+        self.block == other.block
+            && self.pos.distance_squared(other.pos) <= MERGE_DISTANCE * MERGE_DISTANCE
+    }
+
+    /// Where the cube is drawn, spinning and bobbing above its position
+    pub fn transform(&self) -> Transform {
+        let bob = BOB_HEIGHT * 0.5 * (1.0 - (self.age * BOB_SPEED).cos());
+        Transform {
+            pos: self.pos + Vec3::unit_y() * bob,
+            rotation: Quaternion::rotation_y(self.age * SPIN_SPEED),
+            scale: DROP_SIZE,
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use bevy_ecs::prelude::Component;
+use vek::Vec3;
+
+use crate::{
+    block::BlockId,
+    renderer::WorldRenderer,
+    world::{
+        chunk::{self, Chunk, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH},
+        events::WorldEvent,
+    },
+};
+
+use super::physics::{GRAVITY, TERMINAL_VELOCITY};
+
+/// A block that lost the block beneath it, falling until it lands
+/// and becomes a block again
+#[derive(Component, Debug, Clone, Copy)]
+pub struct FallingBlock {
+    pub block: BlockId,
+    /// Downwards speed, in blocks per second
+    pub speed: f32,
+}
+
+/// What happened to a falling block during a tick
+pub enum Fall {
+    /// Still in the air, at the new position
+    Falling(Vec3<f32>),
+    /// Landed on a solid block and fits in the cell above it
+    Landed(Vec3<i32>),
+    /// Fell into an unloaded chunk, or landed in a cell that's already taken
+    Lost,
+}
+
+impl FallingBlock {
+    pub fn new(block: BlockId) -> Self {
+        Self { block, speed: 0.0 }
+    }
+
+    /// Moves the block centered at `pos` down by one step
+    pub fn step(&mut self, pos: Vec3<f32>, dt: f32, world: &WorldRenderer) -> Fall {
+        self.speed = (self.speed + GRAVITY * dt).min(TERMINAL_VELOCITY);
+        let next = pos - Vec3::unit_y() * self.speed * dt;
+        // The cell the bottom face is in, blocks are centered on integer coordinates
+        let below = chunk::block_pos(next - Vec3::unit_y() * 0.5);
+        match world.block_at_world(below) {
+            None => Fall::Lost,
+            Some(block) if block.is_solid() => {
+                let cell = below + Vec3::unit_y();
+                if world.block_at_world(cell) == Some(BlockId::AIR) {
+                    Fall::Landed(cell)
+                } else {
+                    Fall::Lost
+                }
+            }
+            Some(_) => Fall::Falling(next),
+        }
+    }
+}
+
+/// The blocks that may have lost their support in the given events:
+/// the ones above removed blocks, the ones placed in the air and the
+/// ones generated over caves. Sorted and without duplicates.
+pub fn unsupported_blocks(events: &[WorldEvent], world: &WorldRenderer) -> Vec<Vec3<i32>> {
+    let mut unsupported = Vec::new();
+    for event in events {
+        match event {
+            WorldEvent::BlockChanged { pos, .. } => {
+                let candidates = [*pos, *pos + Vec3::unit_y()];
+                unsupported.extend(
+                    candidates
+                        .into_iter()
+                        .filter(|pos| is_unsupported(world, *pos)),
+                );
+            }
+            WorldEvent::ChunkLoaded(chunk_pos) => {
+                let chunk = match world.chunk_manager().chunk(*chunk_pos) {
+                    Some(chunk) => chunk,
+                    None => continue,
+                };
+                let origin = chunk_pos.to_world();
+                let blocks = chunk.blocks.to_vec();
+                for (index, block) in blocks.iter().enumerate() {
+                    let local = Chunk::local_pos_of(index);
+                    // The bottom layer rests on the chunk below, if it is loaded
+                    if block.falls() && is_unsupported(world, origin + local) {
+                        unsupported.push(origin + local);
+                    }
+                }
+                // The chunk may be the air beneath blocks of the chunk above
+                for x in 0..CHUNK_WIDTH as i32 {
+                    for z in 0..CHUNK_DEPTH as i32 {
+                        let above = origin + Vec3::new(x, CHUNK_HEIGHT as i32, z);
+                        if is_unsupported(world, above) {
+                            unsupported.push(above);
+                        }
+                    }
+                }
+            }
+            WorldEvent::NeighborChanged { .. } | WorldEvent::ChunkUnloaded(_) => (),
+        }
+    }
+    unsupported.sort_unstable_by_key(|pos| (pos.x, pos.y, pos.z));
+    unsupported.dedup();
+    unsupported
+}
+
+/// Whether the block at `pos` falls and has air beneath it
+fn is_unsupported(world: &WorldRenderer, pos: Vec3<i32>) -> bool {
+    world.block_at_world(pos).is_some_and(BlockId::falls)
+        && world.block_at_world(pos - Vec3::unit_y()) == Some(BlockId::AIR)
+}
@@ -0,0 +1,47 @@
+use vek::{Mat4, Vec3, Vec4};
+
+/// The six planes bounding what a camera sees. Extracted from the
+/// view-projection matrix, so perspective pyramids and orthographic
+/// boxes are handled alike.
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    /// `(normal, distance)` packed as `xyz` and `w`, normals point inwards
+    planes: [Vec4<f32>; 6],
+    /// World position the planes are relative to
+    origin: Vec3<i32>,
+}
+
+impl Frustum {
+    /// Builds the frustum of a matrix mapping depth to `[0, 1]` like wgpu,
+    /// from positions relative to `origin`. The depth planes are the same
+    /// whether depth is reversed or not.
+    pub fn from_matrix(view_proj: Mat4<f32>, origin: Vec3<i32>) -> Self {
+        let [row_x, row_y, row_z, row_w] = view_proj.into_row_arrays().map(Vec4::<f32>::from);
+        let planes = [
+            row_w + row_x,
+            row_w - row_x,
+            row_w + row_y,
+            row_w - row_y,
+            row_z,
+            row_w - row_z,
+        ]
+        .map(|plane| plane / plane.xyz().magnitude());
+        Self { planes, origin }
+    }
+
+    /// Whether any part of the box between the two corners may be visible,
+    /// with the corners in world space
+    pub fn intersects_aabb(&self, min: Vec3<f32>, max: Vec3<f32>) -> bool {
+        let origin = self.origin.map(|v| v as f32);
+        let (min, max) = (min - origin, max - origin);
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the normal
+            let corner = Vec3::new(
+                if plane.x >= 0.0 { max.x } else { min.x },
+                if plane.y >= 0.0 { max.y } else { min.y },
+                if plane.z >= 0.0 { max.z } else { min.z },
+            );
+            plane.xyz().dot(corner) + plane.w >= 0.0
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use crate::block::BlockId;
+
+pub const HOTBAR_SLOTS: usize = 9;
+
+/// The blocks the player can place. Empty slots hold `BlockId::AIR`.
+pub struct Hotbar {
+    pub slots: [BlockId; HOTBAR_SLOTS],
+    selected: usize,
+}
+
+impl Hotbar {
+    pub fn new() -> Self {
+        let mut slots = [BlockId::AIR; HOTBAR_SLOTS];
+        slots[0] = BlockId::DIRT;
+        slots[1] = BlockId::GRASS;
+        slots[2] = BlockId::STONE;
+        slots[3] = BlockId::GLASS;
+        slots[4] = BlockId::WATER;
+        slots[5] = BlockId::TORCH;
+        slots[6] = BlockId::STONE_SLAB;
+        slots[7] = BlockId::GRASS_TUFT;
+        slots[8] = BlockId::SAND;
+        Self { slots, selected: 0 }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_block(&self) -> BlockId {
+        self.slots[self.selected]
+    }
+
+    pub fn select(&mut self, slot: usize) {
+        if slot < HOTBAR_SLOTS {
+            self.selected = slot;
+        }
+    }
+
+    /// Switches to the slot holding the given block, or puts
+    /// the block in the active slot if it's not in the hotbar.
+    pub fn pick(&mut self, block: BlockId) {
+        match self.slots.iter().position(|slot| *slot == block) {
+            Some(slot) => self.selected = slot,
+            None => self.slots[self.selected] = block,
+        }
+    }
+
+    /// Puts the block in the slot, for slots filled from the inventory
+    pub fn set(&mut self, slot: usize, block: BlockId) {
+        if slot < HOTBAR_SLOTS {
+            self.slots[slot] = block;
+        }
+    }
+
+    /// Moves the selection by the given amount of slots, wrapping around
+    pub fn scroll(&mut self, steps: i32) {
+        self.selected = (self.selected as i32 + steps).rem_euclid(HOTBAR_SLOTS as i32) as usize;
+    }
+}
+
+impl Default for Hotbar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
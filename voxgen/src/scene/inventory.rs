@@ -0,0 +1,123 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::{registry::registry, BlockId};
+
+/// How long the hotbar flashes after trying to place a block that ran out
+const DENIED_FLASH: Duration = Duration::from_millis(300);
+
+/// Whether placing blocks uses up the ones collected by breaking them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Every block can be placed without limit
+    #[default]
+    Creative,
+    /// Broken blocks are collected and placing one consumes it
+    Survival,
+}
+
+/// The blocks the player collected. The hotbar slots refer to these
+/// stacks by block, so a slot is empty once its stack is used up.
+#[derive(Debug, Default)]
+pub struct Inventory {
+    pub mode: GameMode,
+    stacks: HashMap<BlockId, u32>,
+    /// When placing a block last failed because it ran out
+    denied: Option<Instant>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restores saved stacks, dropping blocks that aren't registered anymore
+    pub fn from_stacks(stacks: &[(BlockId, u32)]) -> Self {
+        let registry = registry();
+        Self {
+            stacks: stacks
+                .iter()
+                .filter(|(block, count)| registry.contains(*block) && !block.is_air() && *count > 0)
+                .copied()
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// How many of the block are left, `None` when they are unlimited
+    pub fn count(&self, block: BlockId) -> Option<u32> {
+        match self.mode {
+            GameMode::Creative => None,
+            GameMode::Survival => Some(self.stacks.get(&block).copied().unwrap_or(0)),
+        }
+    }
+
+    /// Collects several of the block at once
+    pub fn give(&mut self, block: BlockId, count: u32) {
+        if self.mode == GameMode::Survival && !block.is_air() && count > 0 {
+            let stack = self.stacks.entry(block).or_insert(0);
+            *stack = stack.saturating_add(count);
+        }
+    }
+
+    /// Uses up one of the block to place it. Returns `false` and flashes
+    /// the hotbar when there are none left.
+    pub fn take(&mut self, block: BlockId) -> bool {
+        if self.mode == GameMode::Creative {
+            return true;
+        }
+        match self.stacks.get_mut(&block) {
+            Some(count) => {
+                *count -= 1;
+                if *count == 0 {
+                    self.stacks.remove(&block);
+                }
+                true
+            }
+            None => {
+                self.denied = Some(Instant::now());
+                false
+            }
+        }
+    }
+
+    /// The collected blocks and their counts, by block id
+    pub fn stacks(&self) -> Vec<(BlockId, u32)> {
+        let mut stacks = self
+            .stacks
+            .iter()
+            .map(|(block, count)| (*block, *count))
+            .collect::<Vec<_>>();
+        stacks.sort_by_key(|(block, _)| u8::from(*block));
+        stacks
+    }
+
+    /// The blocks shown in the inventory window. In creative mode
+    /// every block is available.
+    pub fn available(&self) -> Vec<(BlockId, Option<u32>)> {
+        match self.mode {
+            GameMode::Creative => registry()
+                .ids()
+                .filter(|block| !block.is_air())
+                .map(|block| (block, None))
+                .collect(),
+            GameMode::Survival => self
+                .stacks()
+                .into_iter()
+                .map(|(block, count)| (block, Some(count)))
+                .collect(),
+        }
+    }
+
+    /// How strongly the hotbar flashes after a denied placement, from 1
+    /// right after it to 0 once the flash is over
+    pub fn denied_flash(&self) -> f32 {
+        self.denied.map_or(0.0, |denied| {
+            1.0 - (denied.elapsed().as_secs_f32() / DENIED_FLASH.as_secs_f32()).min(1.0)
+        })
+    }
+}
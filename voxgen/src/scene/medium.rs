@@ -0,0 +1,43 @@
+use vek::Vec3;
+
+use crate::{block::BlockId, renderer::WorldRenderer, world::chunk};
+
+/// How far the eye has to be past the water surface to enter or leave the
+/// water. Bobbing at the surface would toggle the effect every frame otherwise.
+const SURFACE_HYSTERESIS: f32 = 0.1;
+
+/// What the camera is in, which changes how the world is seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraMedium {
+    #[default]
+    Air,
+    /// Tinted and foggy
+    Water,
+    /// Hidden behind a dark overlay rather than seen through the block
+    Solid,
+}
+
+impl CameraMedium {
+    /// The medium around the eye, given the one it was in on the last frame
+    pub fn update(self, world: &WorldRenderer, eye: Vec3<f32>) -> Self {
+        let block_at = |offset: f32| {
+            world
+                .block_at_world(chunk::block_pos(eye + Vec3::unit_y() * offset))
+                .unwrap_or(BlockId::AIR)
+        };
+        if block_at(0.0).is_opaque() {
+            return CameraMedium::Solid;
+        }
+        // Leaving takes rising above the surface, entering sinking below it
+        let offset = if self == CameraMedium::Water {
+            -SURFACE_HYSTERESIS
+        } else {
+            SURFACE_HYSTERESIS
+        };
+        if block_at(offset) == BlockId::WATER {
+            CameraMedium::Water
+        } else {
+            CameraMedium::Air
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::VecDeque;
+
+use bevy_ecs::prelude::Component;
+use vek::Vec3;
+
+use crate::{
+    block::BlockId,
+    renderer::{debug, WorldRenderer},
+    world::{chunk, pathfinding},
+};
+
+use super::{
+    particles::Rng,
+    physics::{Aabb, PlayerBody},
+};
+
+/// Farthest a mob wanders from where it stands, along x and z
+pub const WANDER_RADIUS: i32 = 16;
+/// Walking speed of mobs, in blocks per second
+const MOB_SPEED: f32 = 3.0;
+/// Blocks above or below a random spot searched for ground to stand on
+const FLOOR_RANGE: i32 = 4;
+/// Longest wait between two walks, in seconds
+const MAX_IDLE: f32 = 3.0;
+/// Seconds spent walking to a single cell before looking for another path
+const STUCK_TIME: f32 = 2.0;
+/// How close to the center of a cell the feet must come to reach it
+const REACH_DISTANCE: f32 = 0.2;
+/// How close to the center of a higher cell the feet must be to jump
+/// onto it, from the cell next to it
+const JUMP_DISTANCE: f32 = 1.0;
+const MOB_COLOR: [f32; 3] = [1.0, 0.2, 0.8];
+const PATH_COLOR: [f32; 3] = [0.2, 0.8, 1.0];
+const GOAL_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
+
+fn is_solid(world: &WorldRenderer) -> impl Fn(Vec3<i32>) -> Option<bool> + '_ {
+    move |pos| world.block_at_world(pos).map(BlockId::is_solid)
+}
+
+/// A test mob walking to random places it can reach, with the body and
+/// the physics of the player. Its feet are `PlayerBody::eye_height`
+/// below its transform.
+#[derive(Component, Debug)]
+pub struct Wanderer {
+    /// Cells left to walk through, the next one first
+    path: VecDeque<Vec3<i32>>,
+    /// Seconds to wait before picking the next goal
+    idle: f32,
+    /// Seconds spent walking to the next cell of the path
+    stuck: f32,
+    rng: Rng,
+}
+
+impl Wanderer {
+    /// `seed` picks where it wanders and must not be 0
+    pub fn new(seed: u32) -> Self {
+        Self {
+            path: VecDeque::new(),
+            idle: 0.0,
+            stuck: 0.0,
+            rng: Rng(seed),
+        }
+    }
+
+    /// Where the walk ends, if walking
+    pub fn goal(&self) -> Option<Vec3<i32>> {
+        self.path.back().copied()
+    }
+
+    /// The cell the feet are in
+    fn cell(feet: Vec3<f32>) -> Vec3<i32> {
+        // The feet rest on the bottom of the cell while standing
+        chunk::block_pos(feet + Vec3::unit_y() * 0.1)
+    }
+
+    /// Sets the velocity of the body to walk along the path, picking a
+    /// new goal after waiting a bit once it's done
+    pub fn steer(
+        &mut self,
+        body: &mut PlayerBody,
+        feet: Vec3<f32>,
+        dt: f32,
+        world: &WorldRenderer,
+    ) {
+        body.velocity.x = 0.0;
+        body.velocity.z = 0.0;
+        let cell = Self::cell(feet);
+        if let Some(next) = self.path.front() {
+            let offset = (next.map(|v| v as f32) - feet).with_y(0.0);
+            if *next == cell && offset.magnitude() < REACH_DISTANCE {
+                self.path.pop_front();
+                self.stuck = 0.0;
+                if self.path.is_empty() {
+                    self.idle = self.rng.next_f32() * MAX_IDLE;
+                }
+                return;
+            }
+            self.stuck += dt;
+            if self.stuck > STUCK_TIME {
+                self.repath(feet, world);
+                return;
+            }
+            let velocity = offset.try_normalized().unwrap_or_default() * MOB_SPEED;
+            body.velocity.x = velocity.x;
+            body.velocity.z = velocity.z;
+            if next.y > cell.y && offset.magnitude() < JUMP_DISTANCE {
+                body.jump();
+            }
+            return;
+        }
+        self.idle -= dt;
+        if self.idle <= 0.0 && body.on_ground {
+            self.pick_goal(cell, world);
+        }
+    }
+
+    /// Tries a random spot within [`WANDER_RADIUS`], trying another one
+    /// on the next step if it can't be reached
+    fn pick_goal(&mut self, cell: Vec3<i32>, world: &WorldRenderer) {
+        let diameter = (2 * WANDER_RADIUS + 1) as f32;
+        let offset = Vec3::new(self.rng.next_f32(), 0.0, self.rng.next_f32()) * diameter;
+        let spot = cell + offset.map(|v| v as i32) - Vec3::new(WANDER_RADIUS, 0, WANDER_RADIUS);
+        let goal = match pathfinding::floor_near(spot, FLOOR_RANGE, is_solid(world)) {
+            Some(goal) => goal,
+            None => return,
+        };
+        self.walk_to(cell, goal, world);
+    }
+
+    fn walk_to(&mut self, cell: Vec3<i32>, goal: Vec3<i32>, world: &WorldRenderer) -> bool {
+        let start = pathfinding::floor_near(cell, 1, is_solid(world)).unwrap_or(cell);
+        self.stuck = 0.0;
+        match pathfinding::find_path(start, goal, is_solid(world)) {
+            Some(path) => {
+                self.path = path.into();
+                true
+            }
+            None => {
+                self.path.clear();
+                false
+            }
+        }
+    }
+
+    /// Looks for another way to the goal, waiting for a new one if
+    /// there's none
+    pub fn repath(&mut self, feet: Vec3<f32>, world: &WorldRenderer) {
+        let goal = match self.goal() {
+            Some(goal) => goal,
+            None => return,
+        };
+        if !self.walk_to(Self::cell(feet), goal, world) {
+            self.idle = self.rng.next_f32() * MAX_IDLE;
+        }
+    }
+
+    /// Whether a cell left on the path can't be walked through anymore
+    pub fn is_blocked(&self, world: &WorldRenderer) -> bool {
+        self.path
+            .iter()
+            .any(|cell| !pathfinding::is_walkable(*cell, is_solid(world)))
+    }
+
+    /// Queues the box of the body and the rest of the path to be drawn
+    pub fn draw(&self, aabb: Aabb) {
+        let mut draw = debug::draw();
+        draw.aabb(aabb.min, aabb.max, MOB_COLOR);
+        // Along the ground, from the feet through the centers of the cells
+        let feet = Vec3::new(
+            (aabb.min.x + aabb.max.x) / 2.0,
+            aabb.min.y,
+            (aabb.min.z + aabb.max.z) / 2.0,
+        );
+        let mut from = feet;
+        for cell in &self.path {
+            let to = cell.map(|v| v as f32) - Vec3::unit_y() * 0.45;
+            draw.line(from, to, PATH_COLOR);
+            from = to;
+        }
+        if let Some(goal) = self.goal() {
+            let goal = Aabb::block(goal);
+            draw.aabb(goal.min, goal.max, GOAL_COLOR);
+        }
+    }
+}
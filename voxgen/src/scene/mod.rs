@@ -1,42 +1,330 @@
+pub mod breaking;
 pub mod camera;
+pub mod camera_path;
+pub mod diagnostics;
+pub mod drops;
+pub mod falling;
+pub mod frustum;
+pub mod hotbar;
+pub mod inventory;
+pub mod medium;
+pub mod mob;
+pub mod neighbors;
+pub mod particles;
+pub mod physics;
+pub mod random_ticks;
+pub mod water;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::{ecs::{Transform, BoundingBox}, renderer::Renderer};
-use bevy_ecs::{schedule::Schedule, system::Commands};
-use vek::{Vec3, Vec2};
+use crate::{
+    block::{registry::registry, BlockId},
+    ecs::{
+        self, DeltaTime, Follow, MeshHandle, Orbit, Player, PreviousTransform, TimeOfDay, Transform,
+    },
+    input::Action,
+    renderer::{debug, Renderer, WorldRenderer},
+    settings::Settings,
+    world::{
+        border::WorldBorder,
+        chunk::{self, Chunk},
+        events::WorldEvent,
+        history::{Edit, EditHistory},
+        raycast::{self, RaycastHit},
+        spawn,
+        storage::{self, PlayerRecord, WorldMeta},
+        structure::Structure,
+        ticks::TickQueue,
+    },
+};
+use bevy_ecs::{
+    prelude::{Entity, With, World},
+    schedule::Schedule,
+    world::Mut,
+};
+use vek::Vec3;
 
-use self::camera::{Camera, CameraController};
+use self::{
+    breaking::Breaking,
+    camera::{Camera, CameraController, MovementMode, Projection, SPAWN_POS},
+    camera_path::CameraPathMode,
+    diagnostics::{Ray, RayDiagnostics},
+    drops::{ItemDrop, DEFAULT_DROP_LIFETIME, DEFAULT_PICKUP_RADIUS},
+    falling::{Fall, FallingBlock},
+    hotbar::{Hotbar, HOTBAR_SLOTS},
+    inventory::Inventory,
+    medium::CameraMedium,
+    mob::Wanderer,
+    particles::ParticleSystem,
+    physics::{
+        Aabb, CollisionMap, PlayerBody, EYE_HEIGHT, PLAYER_HEIGHT, PLAYER_WIDTH, TERMINAL_VELOCITY,
+    },
+    random_ticks::RandomTicker,
+};
+
+/// Distance kept between the third person camera and the blocks behind it
+const CAMERA_MARGIN: f32 = 0.2;
+/// Color of the player's box in third person
+const PLAYER_COLOR: [f32; 3] = [1.0, 0.6, 0.2];
+/// Most positions remembered for going back after teleporting
+const MAX_TELEPORT_HISTORY: usize = 32;
+/// Farthest the player can teleport from the origin along each axis.
+/// Further out positions lose too much float precision.
+const WORLD_LIMIT: f32 = 1_000_000.0;
 
 pub struct Scene {
-    pub camera: Camera,
-    pub camera_controller: camera::CameraController,
-    pub world: bevy_ecs::world::World,
+    pub world: World,
+    schedule: Schedule,
+    player: Entity,
+    camera: Entity,
+    /// How far away blocks can be reached, in blocks
+    pub reach: f32,
+    /// How close the player must come to drops to collect them, in blocks
+    pub pickup_radius: f32,
+    /// Seconds before a drop nobody collected despawns
+    pub drop_lifetime: f32,
+    pub hotbar: Hotbar,
+    /// Blocks collected by breaking them, which placing uses up in survival
+    pub inventory: Inventory,
+    pub history: EditHistory,
+    /// Blocks copied with the `copy` command, pasted with `paste`
+    pub clipboard: Option<Structure>,
+    pub particles: ParticleSystem,
+    /// How far the rendered frame is between the last two ticks, from 0 to 1
+    pub interpolation: f32,
+    pub camera_path: CameraPathMode,
+    /// What the eye was in on the last frame
+    pub camera_medium: CameraMedium,
+    /// When the player last clicked to break or place a block
+    pub last_swing: Option<Instant>,
+    /// Progress on the block the break button is held on
+    pub breaking: Breaking,
+    /// Positions of the player's feet before each teleport, latest last
+    teleport_history: Vec<Vec3<f32>>,
+    /// Where the player's feet are placed in a new world and by `spawn`
+    pub spawn: Vec3<f32>,
+    /// Blocks waiting to flow, saved with the world
+    pub block_ticks: TickQueue,
+    /// Grows and decays grass
+    pub random_ticks: RandomTicker,
+    modifiers: winit::event::ModifiersState,
 }
 
 impl Scene {
-    pub fn camera_pos(&self) -> Vec3<f32> {
-        self.camera.pos
-    }
-
     pub fn new(renderer: &Renderer, window_width: f32, window_height: f32) -> Self {
         let camera = Camera::new(window_width, window_height);
-        let camera_controller = CameraController::new();
-        let mut world = bevy_ecs::world::World::new();
-        let mut schedule = Schedule::new();
-        schedule.add_system(init_entities);
-        schedule.run(&mut world);
-        Self {
-            camera,
-            camera_controller,
+        let mut world = World::new();
+        world.init_resource::<DeltaTime>();
+        world.init_resource::<TimeOfDay>();
+        world.init_resource::<CollisionMap>();
+        world.init_resource::<RayDiagnostics>();
+        world.init_resource::<WorldBorder>();
+        let player = world
+            .spawn((
+                Player,
+                Transform::at(camera.pos.map(|v| v as f32)),
+                PlayerBody::new(),
+                CameraController::new(),
+            ))
+            .id();
+        let camera = world.spawn((camera, Follow(player))).id();
+
+        let chunks = renderer.world().chunk_manager();
+        let meta = chunks.storage().load_meta();
+        let seed = meta
+            .as_ref()
+            .map_or_else(storage::new_seed, |meta| meta.seed);
+
+        let mut scene = Self {
             world,
+            schedule: ecs::schedule(),
+            player,
+            camera,
+            reach: 6.0,
+            pickup_radius: DEFAULT_PICKUP_RADIUS,
+            drop_lifetime: DEFAULT_DROP_LIFETIME,
+            hotbar: Hotbar::new(),
+            inventory: Inventory::new(),
+            history: EditHistory::new(100),
+            clipboard: None,
+            particles: ParticleSystem::new(),
+            interpolation: 1.0,
+            camera_path: CameraPathMode::Idle,
+            camera_medium: CameraMedium::Air,
+            last_swing: None,
+            breaking: Breaking::new(),
+            teleport_history: Vec::new(),
+            spawn: SPAWN_POS - Vec3::unit_y() * EYE_HEIGHT,
+            block_ticks: TickQueue::new(),
+            random_ticks: RandomTicker::with_seed(seed),
+            modifiers: winit::event::ModifiersState::empty(),
+        };
+        match &meta {
+            Some(meta) => {
+                scene
+                    .world
+                    .insert_resource(TimeOfDay(meta.time_of_day.rem_euclid(1.0)));
+                scene.spawn = Vec3::from(meta.spawn);
+                scene.block_ticks = TickQueue::restore(&meta.block_ticks, water::FLOW_DELAY);
+                for record in &meta.drops {
+                    match ItemDrop::restore(record) {
+                        Some(drop) => scene.spawn_drop(drop),
+                        None => log::warn!("Invalid saved drop {:?}, removing it", record),
+                    }
+                }
+            }
+            None => {
+                scene.spawn = spawn::find_spawn(|x, z| Some(Chunk::generated_surface(x, z)));
+                log::info!("New world, spawning at {:?}", scene.spawn);
+            }
+        }
+        match meta.and_then(|meta| meta.player) {
+            Some(record) => scene.restore_player(&record),
+            None => {
+                log::warn!("No saved player, starting at the world spawn");
+                scene.move_player(scene.spawn);
+                // The spawn of a new world is kept from the start
+                chunks.saver().queue_meta(scene.world_meta());
+            }
         }
+        scene
+    }
+
+    /// What is saved along with the world
+    pub fn world_meta(&self) -> WorldMeta {
+        let camera = self.camera();
+        WorldMeta {
+            seed: self.random_ticks.seed(),
+            time_of_day: self.time_of_day(),
+            spawn: self.spawn.into_array(),
+            player: Some(PlayerRecord {
+                feet: self.player_feet().into_array(),
+                yaw: camera.yaw(),
+                pitch: camera.pitch(),
+                hotbar_slot: self.hotbar.selected() as u8,
+                flying: self.controller().base_mode() == MovementMode::Fly,
+                hotbar: self.hotbar.slots.to_vec(),
+                inventory: self.inventory.stacks(),
+            }),
+            block_ticks: self.block_ticks.saved(),
+            drops: self
+                .world
+                .iter_entities()
+                .filter_map(|entity| entity.get::<ItemDrop>())
+                .map(ItemDrop::record)
+                .collect(),
+        }
+    }
+
+    /// Puts the player back where it was saved. Like after a teleport,
+    /// physics wait for the chunk under it to be generated.
+    fn restore_player(&mut self, record: &PlayerRecord) {
+        let valid = record
+            .feet
+            .iter()
+            .chain([&record.yaw, &record.pitch])
+            .all(|v| v.is_finite())
+            && (record.hotbar_slot as usize) < HOTBAR_SLOTS;
+        if !valid {
+            log::warn!(
+                "Invalid saved player {:?}, starting at the world spawn",
+                record
+            );
+            self.move_player(self.spawn);
+            return;
+        }
+        self.move_player(Vec3::from(record.feet));
+        self.camera_mut().set_rotation(record.yaw, record.pitch);
+        self.hotbar.select(record.hotbar_slot as usize);
+        if record.flying {
+            self.controller_mut().mode = MovementMode::Fly;
+        }
+        let registry = registry();
+        if record.hotbar.len() == HOTBAR_SLOTS
+            && record.hotbar.iter().all(|block| registry.contains(*block))
+        {
+            for (slot, block) in record.hotbar.iter().enumerate() {
+                self.hotbar.set(slot, *block);
+            }
+        } else {
+            log::warn!(
+                "Invalid saved hotbar {:?}, keeping the default one",
+                record.hotbar
+            );
+        }
+        self.inventory = Inventory::from_stacks(&record.inventory);
+    }
+
+    pub fn camera(&self) -> &Camera {
+        self.world
+            .get::<Camera>(self.camera)
+            .expect("The camera entity is never despawned")
+    }
+
+    pub fn camera_mut(&mut self) -> Mut<'_, Camera> {
+        self.world
+            .get_mut::<Camera>(self.camera)
+            .expect("The camera entity is never despawned")
+    }
+
+    pub fn camera_pos(&self) -> Vec3<f32> {
+        self.camera().pos.map(|v| v as f32)
+    }
+
+    pub fn controller(&self) -> &CameraController {
+        self.world
+            .get::<CameraController>(self.player)
+            .expect("The player entity is never despawned")
+    }
+
+    /// Whether the player walks along the ground
+    pub fn is_walking(&self) -> bool {
+        let body = self
+            .world
+            .get::<PlayerBody>(self.player)
+            .expect("The player entity is never despawned");
+        self.controller().mode == MovementMode::Walk
+            && body.on_ground
+            && body.velocity.with_y(0.0).magnitude_squared() > 0.01
+    }
+
+    pub fn controller_mut(&mut self) -> Mut<'_, CameraController> {
+        self.world
+            .get_mut::<CameraController>(self.player)
+            .expect("The player entity is never despawned")
+    }
+
+    /// Releases the held keys when the engine locks or unlocks the input.
+    /// Their release events go to the menus instead of the scene, so the
+    /// player would otherwise keep moving.
+    pub fn on_input_lock_changed(&mut self) {
+        self.controller_mut().clear_pressed();
+    }
+
+    /// Progress through the current day, from 0 at midnight to 1
+    pub fn time_of_day(&self) -> f32 {
+        self.world.resource::<TimeOfDay>().0
     }
 
     pub fn handle_input_events(&mut self, event: &winit::event::Event<()>) {
         if let winit::event::Event::WindowEvent { event, .. } = event {
-            if let winit::event::WindowEvent::KeyboardInput { input, .. } = event {
-                self.camera_controller.handle_keyboard_events(input);
+            match event {
+                winit::event::WindowEvent::ModifiersChanged(modifiers) => {
+                    self.modifiers = *modifiers;
+                }
+                winit::event::WindowEvent::MouseWheel { delta, .. } => {
+                    let y = match delta {
+                        winit::event::MouseScrollDelta::LineDelta(_, y) => *y,
+                        winit::event::MouseScrollDelta::PixelDelta(pos) => pos.y as f32,
+                    };
+                    if self.camera().projection == Projection::Orthographic {
+                        self.camera_mut().zoom(y);
+                    } else if y != 0.0 {
+                        // Scrolling up moves the selection to the left
+                        self.hotbar.scroll(-y.signum() as i32);
+                    }
+                }
+                _ => (),
             }
         }
 
@@ -45,28 +333,655 @@ impl Scene {
             ..
         } = event
         {
-            self.camera_controller.handle_mouse_events(delta.0, delta.1);
+            self.controller_mut().handle_mouse_events(delta.0, delta.1);
         }
     }
 
-    pub fn update(&mut self, delta_time: Duration) {
-        let mut transform = self.world.query::<&mut Transform>();
-        for mut transform in transform.iter_mut(&mut self.world) {
-            transform.pos = self.camera.pos.map(|x| x as i32);
-            log::info!("Transform: {:?}", transform.pos);
+    pub fn border(&self) -> WorldBorder {
+        *self.world.resource::<WorldBorder>()
+    }
+
+    /// Casts a ray from the camera and returns the first block within reach,
+    /// stopping at the world border
+    pub fn target_block(&self, world: &WorldRenderer) -> Option<RaycastHit> {
+        let origin = self.camera_pos();
+        let direction = self.camera().direction();
+        let reach = self.border().ray_length(origin, direction, self.reach);
+        raycast::raycast(origin, direction, reach, |pos| world.block_at_world(pos))
+    }
+
+    /// Pulls the third person camera in front of blocks that would hide
+    /// the player, and draws the player's box when it's in view
+    pub fn update_view(&mut self, world: &WorldRenderer) {
+        let camera = self.camera();
+        let direction = camera.arm_direction();
+        let top_down = camera.projection == Projection::Orthographic;
+        if direction == Vec3::zero() && !top_down {
+            return;
+        }
+        let pivot = camera.render_pos(self.interpolation).map(|v| v as f32);
+        let distance = self
+            .border()
+            .ray_length(pivot, direction, camera.third_person_distance);
+        if direction != Vec3::zero() {
+            let hit = raycast::raycast(pivot, direction, distance, |pos| {
+                world.block_at_world(pos).filter(|block| block.is_opaque())
+            });
+            let length = hit.map_or(distance, |hit| hit.distance - CAMERA_MARGIN);
+            self.camera_mut().set_arm_length(length);
+        }
+
+        let body = self
+            .world
+            .get::<PlayerBody>(self.player)
+            .expect("The player entity is never despawned");
+        let aabb = body.aabb(pivot);
+        debug::draw().aabb(aabb.min, aabb.max, PLAYER_COLOR);
+    }
+
+    /// Finds what the eye is in, once `update_view` placed it for the frame
+    pub fn update_medium(&mut self, world: &WorldRenderer) {
+        let eye = self.camera().eye_pos(self.interpolation).map(|v| v as f32);
+        self.camera_medium = self.camera_medium.update(world, eye);
+    }
+
+    /// Records the picking ray and queues the raycast and collision
+    /// results to be drawn, if enabled with `debug ray on`
+    pub fn draw_diagnostics(&mut self, world: &WorldRenderer) {
+        if !self.world.resource::<RayDiagnostics>().enabled() {
+            return;
+        }
+        let ray = Ray {
+            origin: self.camera_pos(),
+            direction: self.camera().direction(),
+            length: self.reach,
+        };
+        let hit = self.target_block(world);
+        let mut diagnostics = self.world.resource_mut::<RayDiagnostics>();
+        diagnostics.ray = Some(ray);
+        diagnostics.hit = hit;
+        diagnostics.draw();
+    }
+
+    /// Handles an action translated from the bound input
+    pub fn on_action(&mut self, action: Action, pressed: bool, world: &mut WorldRenderer) {
+        self.controller_mut().handle_action(action, pressed);
+        if action == Action::Break {
+            self.breaking.set_held(pressed);
+        }
+        if !pressed {
+            return;
+        }
+        if let Some(slot) = action.hotbar_slot() {
+            self.hotbar.select(slot);
+        }
+        if matches!(action, Action::Break | Action::Place) {
+            self.last_swing = Some(Instant::now());
+        }
+        match action {
+            Action::Break if self.breaking.instant => self.break_block(world),
+            Action::Place => self.place_block(world),
+            Action::PickBlock => self.pick_block(world),
+            Action::ToggleCameraMode => {
+                let mut camera = self.camera_mut();
+                camera.view_mode = camera.view_mode.next();
+            }
+            Action::ToggleTopDownView => self.toggle_top_down_view(),
+            Action::Undo if self.modifiers.ctrl() => self.history.undo(world.chunk_manager_mut()),
+            Action::Redo if self.modifiers.ctrl() => self.history.redo(world.chunk_manager_mut()),
+            _ => (),
+        }
+    }
+
+    /// Switches between the top-down view, where the movement keys pan
+    /// the view instead of moving the player, and the normal camera
+    fn toggle_top_down_view(&mut self) {
+        let top_down = self.camera().projection == Projection::Perspective;
+        self.controller_mut().set_panning(top_down);
+        self.camera_mut().set_projection(if top_down {
+            Projection::Orthographic
+        } else {
+            Projection::Perspective
+        });
+    }
+
+    fn break_block(&mut self, world: &mut WorldRenderer) {
+        if let Some(hit) = self.target_block(world) {
+            if hit.block.is_breakable() {
+                self.set_block(world, hit.block_pos, BlockId::AIR);
+                self.particles.spawn_block_burst(hit.block_pos, hit.block);
+                self.spawn_drop(ItemDrop::new(hit.block, 1, hit.block_pos));
+            }
+        }
+    }
+
+    /// Advances breaking the targeted block while the break button is held,
+    /// breaking it once the progress completes. Without `active`, like while
+    /// paused, the progress is dropped.
+    pub fn update_breaking(&mut self, world: &mut WorldRenderer, dt: f32, active: bool) {
+        if !active {
+            self.breaking.cancel();
+            return;
+        }
+        let target = self
+            .target_block(world)
+            .map(|hit| (hit.block_pos, hit.block));
+        if self.breaking.update(target, dt).is_some() {
+            self.break_block(world);
+        }
+    }
+
+    /// Edits the world, recording the change in the history
+    fn set_block(&mut self, world: &mut WorldRenderer, pos: Vec3<i32>, id: BlockId) {
+        if let Some(old) = world.set_block_world(pos, id) {
+            self.history.record(Edit { pos, old, new: id });
+        }
+    }
+
+    fn place_block(&mut self, world: &mut WorldRenderer) {
+        let block = self.hotbar.selected_block();
+        if block.is_air() {
+            return;
+        }
+        // Nothing is placed from inside a block, which has no face to place against
+        if let Some((hit, face)) = self
+            .target_block(world)
+            .and_then(|hit| Some((hit, hit.face?)))
+        {
+            // The cell in front of the face we are looking at,
+            // which may belong to a neighbor chunk
+            let target = hit.block_pos + face.normalized();
+            let eye_pos = self.camera_pos();
+            let camera_cell = chunk::block_pos(eye_pos);
+            let body = self
+                .world
+                .get::<PlayerBody>(self.player)
+                .expect("The player entity is never despawned");
+            if target == camera_cell || body.aabb(eye_pos).intersects(&Aabb::block(target)) {
+                return;
+            }
+            if world.block_at_world(target) == Some(BlockId::AIR) && self.inventory.take(block) {
+                self.set_block(world, target, block);
+            }
+        }
+    }
+
+    fn pick_block(&mut self, world: &WorldRenderer) {
+        let block = self
+            .target_block(world)
+            .and_then(|hit| world.block_at_world(hit.block_pos));
+        if let Some(block) = block {
+            self.hotbar.pick(block);
+        }
+    }
+
+    /// Rotates the camera by the mouse motion. Unlike movement this
+    /// happens every frame so looking around stays responsive.
+    pub fn look(&mut self) {
+        let (offset_x, offset_y) = self.controller_mut().take_rotation();
+        if let Some(mut camera) = self.world.get_mut::<Camera>(self.camera) {
+            camera.rotate(offset_x, offset_y);
+        }
+    }
+
+    /// Samples the camera while recording a path, or moves the player along
+    /// the path being played back. Finishing the playback saves its timings.
+    pub fn update_camera_path(&mut self, frame_time: Duration) {
+        let camera = self
+            .world
+            .get::<Camera>(self.camera)
+            .expect("The camera entity is never despawned");
+        let sample = match self.camera_path.update(frame_time, camera) {
+            Some(sample) => sample,
+            None => return,
+        };
+        if self.camera_path.is_finished() {
+            let _ = self.stop_camera_path();
+            return;
+        }
+        let pos = Vec3::from(sample.pos);
+        let mut camera = self.camera_mut();
+        camera.teleport(pos);
+        camera.set_rotation(sample.yaw, sample.pitch);
+        // The camera keeps the precision of the path, the player only
+        // needs to be close enough to stream the chunks around it
+        if let Some(mut transform) = self.world.get_mut::<Transform>(self.player) {
+            transform.pos = pos.map(|v| v as f32);
+        }
+        if let Some(mut body) = self.world.get_mut::<PlayerBody>(self.player) {
+            body.velocity = Vec3::zero();
+        }
+    }
+
+    /// Starts moving the camera along a recorded path
+    pub fn play_camera_path(&mut self, name: &str) -> Result<(), String> {
+        self.camera_path =
+            CameraPathMode::play(name).map_err(|e| format!("Failed to load {}: {}", name, e))?;
+        self.controller_mut().scripted = true;
+        Ok(())
+    }
+
+    /// Saves the recorded path or the playback timings, returning what was saved
+    pub fn stop_camera_path(&mut self) -> Result<String, String> {
+        self.controller_mut().scripted = false;
+        let result = self.camera_path.finish().map_err(|e| e.to_string());
+        match &result {
+            Ok(message) => log::info!("{}", message),
+            Err(e) => log::error!("Failed to save the camera path: {}", e),
+        }
+        result
+    }
+
+    /// Position of the player's feet
+    pub fn player_feet(&self) -> Vec3<f32> {
+        let pos = self
+            .world
+            .get::<Transform>(self.player)
+            .expect("The player entity is never despawned")
+            .pos;
+        pos - Vec3::unit_y() * EYE_HEIGHT
+    }
+
+    /// Moves the player's feet to the position, clamped to the bounds of the
+    /// world and the border, and remembers where it was for `back`. Returns the destination.
+    /// The chunks around it must be streamed right away to show them
+    /// on the next frame, physics stay frozen until they are loaded.
+    pub fn teleport(&mut self, feet: Vec3<f32>) -> Vec3<f32> {
+        if self.teleport_history.len() == MAX_TELEPORT_HISTORY {
+            self.teleport_history.remove(0);
+        }
+        let from = self.player_feet();
+        self.teleport_history.push(from);
+        self.move_player(feet)
+    }
+
+    /// Teleports back to where the player was before the last teleport
+    pub fn teleport_back(&mut self) -> Option<Vec3<f32>> {
+        let feet = self.teleport_history.pop()?;
+        Some(self.move_player(feet))
+    }
+
+    fn move_player(&mut self, feet: Vec3<f32>) -> Vec3<f32> {
+        let feet = Vec3::new(
+            feet.x.clamp(-WORLD_LIMIT, WORLD_LIMIT),
+            feet.y.clamp(-WORLD_LIMIT, WORLD_LIMIT),
+            feet.z.clamp(-WORLD_LIMIT, WORLD_LIMIT),
+        );
+        let feet = self.border().clamp(feet, PLAYER_WIDTH / 2.0);
+        let pos = feet + Vec3::unit_y() * EYE_HEIGHT;
+        self.camera_mut().teleport(pos.map(|v| v as f64));
+        if let Some(mut transform) = self.world.get_mut::<Transform>(self.player) {
+            transform.pos = pos;
+        }
+        if let Some(mut body) = self.world.get_mut::<PlayerBody>(self.player) {
+            body.velocity = Vec3::zero();
+        }
+        feet
+    }
+
+    /// Runs the systems of the scene for one simulation step
+    pub fn tick(&mut self, delta_time: Duration, world: &mut WorldRenderer) {
+        // Edits made since the last step are undone together
+        self.history.commit();
+        let dt = delta_time.as_secs_f32();
+        self.world.insert_resource(DeltaTime(dt));
+
+        // Everything the player could collide with during this tick
+        let player = self.world.get::<Transform>(self.player).map(|t| t.pos);
+        let body = self.world.get::<PlayerBody>(self.player);
+        if let (Some(pos), Some(body)) = (player, body) {
+            let reach = TERMINAL_VELOCITY * dt + 1.0;
+            let aabb = body.aabb(pos);
+            let area = Aabb::new(aabb.min - reach, aabb.max + reach);
+            let collisions = CollisionMap::capture(world, area, pos);
+            self.world.insert_resource(collisions);
+        }
+        self.schedule.run(&mut self.world);
+        self.block_ticks.step();
+        self.random_ticks.tick(world.chunk_manager_mut());
+        self.drop_falling_blocks(dt, world);
+        self.update_drops(dt, world);
+        self.walk_mobs(dt, world);
+        self.particles.tick(dt, world);
+    }
+
+    /// Turns the blocks that lost the block beneath them during the
+    /// last world tick into falling entities
+    pub fn update_falling_blocks(&mut self, world: &mut WorldRenderer) {
+        for pos in falling::unsupported_blocks(world.events(), world) {
+            let block = match world.set_block_world(pos, BlockId::AIR) {
+                Some(block) => block,
+                None => continue,
+            };
+            let transform = Transform::at(pos.map(|v| v as f32));
+            self.world.spawn((
+                transform,
+                PreviousTransform(transform),
+                MeshHandle::Block(block),
+                FallingBlock::new(block),
+            ));
+        }
+    }
+
+    /// Breaks the blocks whose neighbors changed during the last world
+    /// tick in a way they can't stay with, like torches losing their support
+    pub fn update_neighbors(&mut self, world: &mut WorldRenderer) {
+        for pos in neighbors::broken_blocks(world.events(), |pos| world.block_at_world(pos)) {
+            if let Some(block) = world.set_block_world(pos, BlockId::AIR) {
+                self.particles.spawn_block_burst(pos, block);
+                self.spawn_drop(ItemDrop::new(block, 1, pos));
+            }
+        }
+    }
+
+    /// Schedules the fluids around the blocks changed during the last world
+    /// tick to flow, then runs the due block ticks, at most
+    /// `TICKS_PER_FRAME`. Ticks needing an unloaded chunk wait for it.
+    pub fn update_water(&mut self, world: &mut WorldRenderer) {
+        for pos in neighbors::scheduled_blocks(world.events(), |pos| world.block_at_world(pos)) {
+            self.block_ticks.schedule(pos, water::FLOW_DELAY);
+        }
+        for event in world.events() {
+            if let WorldEvent::ChunkLoaded(chunk_pos) = event {
+                self.block_ticks.chunk_loaded(*chunk_pos, water::FLOW_DELAY);
+            }
+        }
+        for _ in 0..water::TICKS_PER_FRAME {
+            let pos = match self.block_ticks.pop_due() {
+                Some(pos) => pos,
+                None => break,
+            };
+            if let Err(chunk_pos) = water::flow(world, pos) {
+                self.block_ticks.wait_for(chunk_pos, pos);
+            }
+        }
+    }
+
+    /// Moves the falling blocks down, placing back the ones that landed
+    fn drop_falling_blocks(&mut self, dt: f32, world: &mut WorldRenderer) {
+        let mut settled = Vec::new();
+        let mut query = self
+            .world
+            .query::<(Entity, &mut FallingBlock, &mut Transform)>();
+        for (entity, mut falling, mut transform) in query.iter_mut(&mut self.world) {
+            match falling.step(transform.pos, dt, world) {
+                Fall::Falling(pos) => transform.pos = pos,
+                Fall::Landed(cell) => settled.push((entity, falling.block, Some(cell))),
+                Fall::Lost => settled.push((entity, falling.block, None)),
+            }
+        }
+        for (entity, block, cell) in settled {
+            let pos = self.world.get::<Transform>(entity).map(|t| t.pos);
+            self.world.despawn(entity);
+            match cell {
+                Some(cell) => {
+                    world.set_block_world(cell, block);
+                }
+                None => log::info!(
+                    "A falling {} at {:?} left the loaded world or had nowhere to land",
+                    block.name(),
+                    pos
+                ),
+            }
+        }
+    }
+
+    fn spawn_drop(&mut self, drop: ItemDrop) {
+        let transform = drop.transform();
+        self.world.spawn((
+            transform,
+            PreviousTransform(transform),
+            MeshHandle::Block(drop.block),
+            drop,
+        ));
+    }
+
+    /// Moves the drops, merges the ones of the same block lying together,
+    /// collects the ones the player came close to into the inventory and
+    /// despawns the ones that lived out their lifetime
+    fn update_drops(&mut self, dt: f32, world: &WorldRenderer) {
+        let center = self.player_feet() + Vec3::unit_y() * PLAYER_HEIGHT / 2.0;
+        let mut query = self.world.query::<(Entity, &mut ItemDrop)>();
+        let mut drops = Vec::new();
+        for (entity, mut drop) in query.iter_mut(&mut self.world) {
+            drop.step(dt, world);
+            drops.push((entity, *drop));
+        }
+
+        let mut removed = Vec::new();
+        for i in 0..drops.len() {
+            let (entity, drop) = drops[i];
+            if drop.age >= self.drop_lifetime {
+                removed.push(entity);
+            } else if drop.can_pickup(center, self.pickup_radius) {
+                self.inventory.give(drop.block, drop.count);
+                removed.push(entity);
+            } else {
+                // Later drops join the earlier stack they lie next to,
+                // which stays as young as its youngest part
+                let merged = drops[..i]
+                    .iter_mut()
+                    .find(|(other, earlier)| !removed.contains(other) && earlier.can_merge(&drop));
+                if let Some((_, earlier)) = merged {
+                    earlier.count = earlier.count.saturating_add(drop.count);
+                    earlier.age = earlier.age.min(drop.age);
+                    removed.push(entity);
+                }
+            }
+        }
+
+        for (entity, drop) in drops {
+            if removed.contains(&entity) {
+                self.world.despawn(entity);
+            } else if let Some(mut entity) = self.world.get_entity_mut(entity) {
+                entity.insert((drop, drop.transform()));
+            }
+        }
+    }
+
+    /// Spawns a block circling around `center`, to try out entity rendering
+    pub fn spawn_orbiting_block(&mut self, center: Vec3<f32>, radius: f32, block: BlockId) {
+        // Blocks on different circles start at different angles to spread around
+        let angle = radius % std::f32::consts::TAU;
+        let orbit = Orbit {
+            center,
+            radius,
+            speed: 0.5,
+            angle,
+        };
+        let transform = Transform::at(center + Vec3::new(angle.cos(), 0.0, angle.sin()) * radius);
+        self.world.spawn((
+            transform,
+            PreviousTransform(transform),
+            MeshHandle::Block(block),
+            orbit,
+        ));
+    }
+
+    /// Despawns every orbiting block, returning how many there were
+    pub fn despawn_orbiting_blocks(&mut self) -> usize {
+        let entities = self
+            .world
+            .query_filtered::<Entity, With<Orbit>>()
+            .iter(&self.world)
+            .collect::<Vec<_>>();
+        for entity in &entities {
+            self.world.despawn(*entity);
+        }
+        entities.len()
+    }
+
+    /// Spawns a test mob with its feet at `feet`, wandering around
+    pub fn spawn_mob(&mut self, feet: Vec3<f32>) {
+        let body = PlayerBody::new();
+        let transform = Transform::at(feet + Vec3::unit_y() * body.eye_height);
+        // Mobs spawned together wander apart
+        let mobs = self.world.query::<&Wanderer>().iter(&self.world).count();
+        let seed = (mobs as u32 + 1).wrapping_mul(0x9e37_79b9);
+        self.world.spawn((
+            transform,
+            PreviousTransform(transform),
+            body,
+            Wanderer::new(seed),
+        ));
+    }
+
+    /// Despawns every mob, returning how many there were
+    pub fn despawn_mobs(&mut self) -> usize {
+        let entities = self
+            .world
+            .query_filtered::<Entity, With<Wanderer>>()
+            .iter(&self.world)
+            .collect::<Vec<_>>();
+        for entity in &entities {
+            self.world.despawn(*entity);
+        }
+        entities.len()
+    }
+
+    /// Walks the mobs along their paths, colliding like the player
+    fn walk_mobs(&mut self, dt: f32, world: &WorldRenderer) {
+        let mut query = self
+            .world
+            .query::<(&mut Wanderer, &mut PlayerBody, &mut Transform)>();
+        for (mut wanderer, mut body, mut transform) in query.iter_mut(&mut self.world) {
+            let feet = transform.pos - Vec3::unit_y() * body.eye_height;
+            wanderer.steer(&mut body, feet, dt, world);
+            let reach = TERMINAL_VELOCITY * dt + 1.0;
+            let aabb = body.aabb(transform.pos);
+            let area = Aabb::new(aabb.min - reach, aabb.max + reach);
+            let collisions = CollisionMap::capture(world, area, transform.pos);
+            transform.pos = body.step(transform.pos, dt, false, &collisions, None);
+        }
+    }
+
+    /// Finds other paths for the mobs whose path got blocked during the
+    /// last world tick
+    pub fn update_mobs(&mut self, world: &WorldRenderer) {
+        let changed = world
+            .events()
+            .iter()
+            .any(|event| matches!(event, WorldEvent::BlockChanged { .. }));
+        if !changed {
+            return;
+        }
+        let mut query = self
+            .world
+            .query::<(&mut Wanderer, &PlayerBody, &Transform)>();
+        for (mut wanderer, body, transform) in query.iter_mut(&mut self.world) {
+            if wanderer.is_blocked(world) {
+                let feet = transform.pos - Vec3::unit_y() * body.eye_height;
+                wanderer.repath(feet, world);
+            }
+        }
+    }
+
+    /// Draws the boxes of the mobs and the paths they follow
+    pub fn draw_mobs(&mut self) {
+        let mut query = self
+            .world
+            .query::<(&Wanderer, &PlayerBody, &Transform, &PreviousTransform)>();
+        for (wanderer, body, transform, previous) in query.iter(&self.world) {
+            let pos = transform.interpolate(&previous.0, self.interpolation).pos;
+            wanderer.draw(body.aabb(pos));
+        }
+    }
+
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        let mut controller = self.controller_mut();
+        controller.sensitivity = settings.sensitivity;
+        controller.invert_y = settings.invert_y;
+        let mut camera = self.camera_mut();
+        camera.fov_degrees = settings.fov;
+        camera.third_person_distance = settings.third_person_distance;
+        self.breaking.instant = settings.instant_break;
+        self.inventory.mode = settings.game_mode;
+        self.random_ticks.speed = settings.random_tick_speed;
+        self.pickup_radius = settings.pickup_radius;
+        self.drop_lifetime = settings.drop_lifetime;
+
+        let border = settings.world_border();
+        if border != self.border() {
+            self.world.insert_resource(border);
+            // A shrinking border pushes the player back inside right away
+            let feet = self.player_feet();
+            if border.clamp(feet, PLAYER_WIDTH / 2.0) != feet {
+                self.move_player(feet);
+            }
         }
-        self.camera_controller.update(&mut self.camera, delta_time);
     }
 
     pub fn resize(&mut self, width: f32, height: f32) {
-        self.camera.on_resize(width, height);
+        if let Some(mut camera) = self.world.get_mut::<Camera>(self.camera) {
+            camera.on_resize(width, height);
+        }
     }
 }
 
-fn init_entities(mut command: Commands) {
-    log::info!("Initializing transform");
-    command.spawn(Transform { pos: Vec3::zero() })
-    .insert(BoundingBox {
-        size: Vec2::new(20, 20),
-    });
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        engine::TICK_DURATION,
+        world::{chunk_manager::ChunkManager, storage::WorldStorage},
+    };
+
+    /// A scene on the offscreen renderer with the chunks around the player
+    /// loaded, saving into a temporary directory
+    fn loaded_scene() -> (Renderer, Scene, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut renderer =
+            pollster::block_on(Renderer::new_offscreen(64, 64)).expect("no adapter to render with");
+        let world = renderer.world_mut();
+        *world.chunk_manager_mut() = ChunkManager::new(Arc::new(WorldStorage::new(dir.path())));
+        world.set_border(WorldBorder::new(1));
+        let scene = Scene::new(&renderer, 64.0, 64.0);
+        renderer.load_chunks_around(scene.camera_pos());
+        (renderer, scene, dir)
+    }
+
+    /// Runs the scene for `ticks` steps and returns how far the player moved
+    fn distance_moved(scene: &mut Scene, renderer: &mut Renderer, ticks: usize) -> f32 {
+        let start = scene.player_feet();
+        for _ in 0..ticks {
+            scene.tick(TICK_DURATION, renderer.world_mut());
+        }
+        scene.player_feet().distance(start)
+    }
+
+    #[test]
+    fn the_seed_and_time_of_day_are_restored() {
+        let (mut renderer, mut scene, _dir) = loaded_scene();
+        distance_moved(&mut scene, &mut renderer, 60);
+        let meta = scene.world_meta();
+        assert_ne!(meta.time_of_day, TimeOfDay::default().0);
+
+        let saver = renderer.world().chunk_manager().saver();
+        saver.queue_meta(meta.clone());
+        saver.flush(Duration::from_secs(5)).unwrap();
+        let restored = Scene::new(&renderer, 64.0, 64.0);
+        assert_eq!(restored.time_of_day(), meta.time_of_day);
+        assert_eq!(restored.world_meta().seed, meta.seed);
+    }
+
+    #[test]
+    fn locking_the_input_stops_the_player() {
+        let (mut renderer, mut scene, _dir) = loaded_scene();
+        // Settles on the ground first
+        distance_moved(&mut scene, &mut renderer, 60);
+
+        scene.on_action(Action::MoveForward, true, renderer.world_mut());
+        assert!(distance_moved(&mut scene, &mut renderer, 30) > 1.0);
+
+        // Opening a menu locks the input, so the release never reaches the scene
+        scene.on_input_lock_changed();
+        distance_moved(&mut scene, &mut renderer, 30);
+        assert!(distance_moved(&mut scene, &mut renderer, 30) < 1e-4);
+
+        // Closing it again doesn't bring back the released key
+        scene.on_input_lock_changed();
+        assert!(distance_moved(&mut scene, &mut renderer, 30) < 1e-4);
+    }
 }
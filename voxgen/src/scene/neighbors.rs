@@ -0,0 +1,111 @@
+use vek::Vec3;
+
+use crate::{
+    block::{BlockId, NeighborBehavior},
+    world::events::WorldEvent,
+};
+
+/// The blocks that break because of how they react to the neighbor
+/// changes in the given events. Sorted and without duplicates.
+/// `block_at` returns the blocks of the loaded chunks, `None` elsewhere.
+pub fn broken_blocks(
+    events: &[WorldEvent],
+    block_at: impl Fn(Vec3<i32>) -> Option<BlockId>,
+) -> Vec<Vec3<i32>> {
+    let mut broken = events
+        .iter()
+        .filter_map(|event| match event {
+            WorldEvent::NeighborChanged { pos, .. } => Some(*pos),
+            _ => None,
+        })
+        .filter(|pos| {
+            let block = match block_at(*pos) {
+                Some(block) => block,
+                None => return false,
+            };
+            match block.neighbor_behavior() {
+                NeighborBehavior::Ignore | NeighborBehavior::Flows => false,
+                NeighborBehavior::NeedsSupport => !is_supported(&block_at, *pos),
+            }
+        })
+        .collect::<Vec<_>>();
+    broken.sort_unstable_by_key(|pos| (pos.x, pos.y, pos.z));
+    broken.dedup();
+    broken
+}
+
+/// The blocks that get a tick scheduled because of the given events: the
+/// flowing ones next to a changed block and the changed ones that flow
+/// themselves, in the order of the events
+pub fn scheduled_blocks(
+    events: &[WorldEvent],
+    block_at: impl Fn(Vec3<i32>) -> Option<BlockId>,
+) -> Vec<Vec3<i32>> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            WorldEvent::BlockChanged { pos, .. } | WorldEvent::NeighborChanged { pos, .. } => {
+                Some(*pos)
+            }
+            _ => None,
+        })
+        .filter(|pos| matches!(block_at(*pos), Some(block) if block.flows()))
+        .collect()
+}
+
+/// Whether the block beneath `pos` holds it up. Blocks above unloaded
+/// chunks stay put, as what is beneath them is not known.
+fn is_supported(block_at: impl Fn(Vec3<i32>) -> Option<BlockId>, pos: Vec3<i32>) -> bool {
+    block_at(pos - Vec3::unit_y()) != Some(BlockId::AIR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{chunk::ChunkPos, chunk_manager::ChunkManager};
+
+    const UPPER: ChunkPos = ChunkPos::new(0, 8, 0);
+    const LOWER: ChunkPos = ChunkPos::new(0, 7, 0);
+
+    /// Places a torch and processes the events of placing it
+    fn place_torch(world: &mut ChunkManager, pos: Vec3<i32>) {
+        world.set_block(pos + Vec3::unit_y(), BlockId::AIR);
+        world.set_block(pos, BlockId::TORCH);
+        world.process_events();
+    }
+
+    fn broken(world: &mut ChunkManager) -> Vec<Vec3<i32>> {
+        let events = world.process_events();
+        broken_blocks(&events, |pos| world.get_block(pos))
+    }
+
+    #[test]
+    fn torch_breaks_when_the_chunk_below_loses_its_support() {
+        let (mut world, _dir) = ChunkManager::with_chunks([UPPER, LOWER]);
+        // The lowest block of the upper chunk, standing on the lower one
+        let torch = UPPER.to_world() + Vec3::new(4, 0, 4);
+        place_torch(&mut world, torch);
+        let support = torch - Vec3::unit_y();
+        assert_eq!(ChunkPos::from_block(support), LOWER);
+
+        // Notified, but still standing
+        world.set_block(torch + Vec3::unit_x(), BlockId::AIR);
+        assert!(broken(&mut world).is_empty());
+        world.set_block(support, BlockId::AIR);
+        assert_eq!(broken(&mut world), [torch]);
+    }
+
+    #[test]
+    fn edits_next_to_unloaded_chunks_break_nothing() {
+        let (mut world, _dir) = ChunkManager::with_chunks([UPPER]);
+        // Above the unloaded lower chunk and next to the unloaded one at -X
+        let torch = UPPER.to_world();
+        place_torch(&mut world, torch);
+        world.set_block(torch + Vec3::unit_x(), BlockId::AIR);
+        assert!(broken(&mut world).is_empty());
+        // Breaking the torch notifies the unloaded neighbors too
+        world.set_block(torch, BlockId::AIR);
+        assert!(broken(&mut world).is_empty());
+        assert_eq!(world.get_block(torch - Vec3::unit_y()), None);
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+
+use vek::Vec3;
+
+use crate::{
+    block::BlockId,
+    direction::Direction,
+    renderer::{atlas::TILE_UV_SIZE, WorldRenderer},
+    world::chunk,
+};
+
+use super::physics::GRAVITY;
+
+/// Most particles alive at once, the oldest are dropped to make room
+pub const MAX_PARTICLES: usize = 1024;
+/// Particles spawned when a block breaks
+const BURST_SIZE: usize = 24;
+/// Seconds a particle lives, fading out towards the end
+const LIFETIME: f32 = 0.7;
+/// Side of a particle, in blocks
+pub const PARTICLE_SIZE: f32 = 0.15;
+/// Fraction of the block tile a particle shows
+const TILE_FRACTION: f32 = 0.25;
+/// Speed particles fly out of the block with, in blocks per second
+const BURST_SPEED: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    pub pos: Vec3<f32>,
+    /// Position at the previous tick, for interpolation
+    pub prev_pos: Vec3<f32>,
+    pub velocity: Vec3<f32>,
+    /// Seconds since the particle spawned
+    pub age: f32,
+    /// Top left and bottom right corners of the part of the atlas shown
+    pub uv: [f32; 4],
+    /// Whether the particle lies on the block below it
+    resting: bool,
+}
+
+impl Particle {
+    /// From 1 while young down to 0 at the end of its life
+    pub fn opacity(&self) -> f32 {
+        // Fully opaque for the first half of its life
+        (2.0 * (1.0 - self.age / LIFETIME)).min(1.0)
+    }
+}
+
+/// Small pieces of broken blocks, simulated on the CPU
+pub struct ParticleSystem {
+    particles: VecDeque<Particle>,
+    rng: Rng,
+}
+
+impl ParticleSystem {
+    pub fn new() -> Self {
+        Self {
+            particles: VecDeque::with_capacity(MAX_PARTICLES),
+            rng: Rng(0x9e37_79b9),
+        }
+    }
+
+    pub fn particles(&self) -> impl Iterator<Item = &Particle> {
+        self.particles.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Bursts pieces of the block out of its position
+    pub fn spawn_block_burst(&mut self, pos: Vec3<i32>, block: BlockId) {
+        let center = pos.map(|v| v as f32);
+        let tile = block.map_texture([0, 0], Direction::Front);
+        let piece = TILE_UV_SIZE * TILE_FRACTION;
+        for _ in 0..BURST_SIZE {
+            let offset = Vec3::new(
+                self.rng.next_f32(),
+                self.rng.next_f32(),
+                self.rng.next_f32(),
+            ) - 0.5;
+            let spawn = center + offset * 0.8;
+            // Outwards from the center and a bit upwards
+            let velocity = (offset + Vec3::unit_y() * 0.5) * BURST_SPEED;
+            // A random piece of the tile
+            let u = tile[0] + self.rng.next_f32() * (TILE_UV_SIZE - piece);
+            let v = tile[1] + self.rng.next_f32() * (TILE_UV_SIZE - piece);
+            self.push(Particle {
+                pos: spawn,
+                prev_pos: spawn,
+                velocity,
+                age: 0.0,
+                uv: [u, v, u + piece, v + piece],
+                resting: false,
+            });
+        }
+    }
+
+    fn push(&mut self, particle: Particle) {
+        if self.particles.len() == MAX_PARTICLES {
+            self.particles.pop_front();
+        }
+        self.particles.push_back(particle);
+    }
+
+    /// Moves the particles by one step, landing them on the top of the
+    /// block they fall into, and drops the ones that lived out their lifetime.
+    /// Only the block at the center of a particle is tested for collisions.
+    pub fn tick(&mut self, dt: f32, world: &WorldRenderer) {
+        self.particles.retain_mut(|particle| {
+            particle.age += dt;
+            particle.prev_pos = particle.pos;
+            if particle.age >= LIFETIME {
+                return false;
+            }
+            if particle.resting {
+                return true;
+            }
+            particle.velocity.y -= GRAVITY * dt;
+            let next = particle.pos + particle.velocity * dt;
+            let block = chunk::block_pos(next);
+            if !world.is_solid_world(block) {
+                particle.pos = next;
+                return true;
+            }
+            // Blocks are centered on integer coordinates
+            let top = block.y as f32 + 0.5;
+            let half = PARTICLE_SIZE * 0.5;
+            if particle.velocity.y < 0.0 && particle.pos.y >= top {
+                particle.pos = Vec3::new(next.x, top + half, next.z);
+                particle.velocity = Vec3::zero();
+                particle.resting = true;
+            } else {
+                // Slides down walls and ceilings instead of going through
+                particle.velocity = Vec3::new(0.0, particle.velocity.y.min(0.0), 0.0);
+            }
+            true
+        });
+    }
+}
+
+impl Default for ParticleSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Xorshift generator, good enough to scatter particles and pick where
+/// mobs wander. The seed must not be 0.
+#[derive(Debug)]
+pub struct Rng(pub u32);
+
+impl Rng {
+    /// A number between 0 and 1
+    pub fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
@@ -0,0 +1,258 @@
+use bevy_ecs::prelude::{Component, Resource};
+use vek::Vec3;
+
+use crate::{
+    renderer::WorldRenderer,
+    world::chunk::{self, ChunkPos},
+};
+
+pub const PLAYER_WIDTH: f32 = 0.6;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+/// Height of the camera above the player's feet
+pub const EYE_HEIGHT: f32 = 1.62;
+pub const CROUCH_EYE_HEIGHT: f32 = 1.27;
+pub const GRAVITY: f32 = 28.0;
+pub const JUMP_SPEED: f32 = 8.5;
+pub const TERMINAL_VELOCITY: f32 = 50.0;
+/// Tolerance for floating point error when boxes touch
+const COLLISION_EPSILON: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3<f32>,
+    pub max: Vec3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3<f32>, max: Vec3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// The bounding box of the block at the given position
+    pub fn block(pos: Vec3<i32>) -> Self {
+        let center = pos.map(|v| v as f32);
+        Self::new(center - 0.5, center + 0.5)
+    }
+
+    /// The collision box of a player standing at the given feet position
+    pub fn player(feet: Vec3<f32>) -> Self {
+        let half_width = PLAYER_WIDTH / 2.0;
+        Self::new(
+            Vec3::new(feet.x - half_width, feet.y, feet.z - half_width),
+            Vec3::new(
+                feet.x + half_width,
+                feet.y + PLAYER_HEIGHT,
+                feet.z + half_width,
+            ),
+        )
+    }
+
+    pub fn translated(&self, offset: Vec3<f32>) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self::new(
+            Vec3::partial_min(self.min, other.min),
+            Vec3::partial_max(self.max, other.max),
+        )
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] < other.max[axis] && self.max[axis] > other.min[axis])
+    }
+
+    /// Positions of the blocks overlapping the box. Blocks that only
+    /// touch one of its faces are not included.
+    pub fn blocks(&self) -> impl Iterator<Item = Vec3<i32>> {
+        // Blocks are centered on integer coordinates
+        let min = self
+            .min
+            .map(|v| (v + 0.5 + COLLISION_EPSILON).floor() as i32);
+        let max = self
+            .max
+            .map(|v| (v + 0.5 - COLLISION_EPSILON).ceil() as i32 - 1);
+        (min.x..=max.x).flat_map(move |x| {
+            (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| Vec3::new(x, y, z)))
+        })
+    }
+}
+
+/// Solidity of the blocks in an area of the world, copied out of it
+/// so that physics can run as a system without borrowing the world.
+#[derive(Resource, Default)]
+pub struct CollisionMap {
+    min: Vec3<i32>,
+    size: Vec3<i32>,
+    solid: Vec<bool>,
+    /// Whether the chunk at the center of the area and the one
+    /// beneath the bottom of the area are loaded
+    pub chunk_loaded: bool,
+}
+
+impl CollisionMap {
+    /// Copies the blocks overlapping `area`, which is centered on `center`
+    pub fn capture(world: &WorldRenderer, area: Aabb, center: Vec3<f32>) -> Self {
+        let beneath = chunk::block_pos(center).with_y(chunk::block_pos(area.min).y - 1);
+        let chunks = world.chunk_manager();
+        let chunk_loaded = chunks.chunk(ChunkPos::from_world(center)).is_some()
+            && chunks.chunk(ChunkPos::from_block(beneath)).is_some();
+        Self::from_fn(area, chunk_loaded, |pos| world.is_solid_world(pos))
+    }
+
+    /// Asks `is_solid` for every block overlapping `area`
+    pub fn from_fn(area: Aabb, chunk_loaded: bool, is_solid: impl Fn(Vec3<i32>) -> bool) -> Self {
+        let min = chunk::block_pos(area.min);
+        let max = chunk::block_pos(area.max);
+        let size = max - min + 1;
+        let mut solid = Vec::with_capacity(size.product() as usize);
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    solid.push(is_solid(Vec3::new(x, y, z)));
+                }
+            }
+        }
+        Self {
+            min,
+            size,
+            solid,
+            chunk_loaded,
+        }
+    }
+
+    /// Blocks outside of the captured area are treated as air
+    pub fn is_solid(&self, pos: Vec3<i32>) -> bool {
+        let local = pos - self.min;
+        if (0..3).any(|axis| local[axis] < 0 || local[axis] >= self.size[axis]) {
+            return false;
+        }
+        let index = local.x + local.y * self.size.x + local.z * self.size.x * self.size.y;
+        self.solid[index as usize]
+    }
+}
+
+/// Physics state of the player while walking
+#[derive(Component)]
+pub struct PlayerBody {
+    pub velocity: Vec3<f32>,
+    pub on_ground: bool,
+    /// Current height of the camera above the feet
+    pub eye_height: f32,
+}
+
+impl PlayerBody {
+    pub fn new() -> Self {
+        Self {
+            velocity: Vec3::zero(),
+            on_ground: false,
+            eye_height: EYE_HEIGHT,
+        }
+    }
+
+    /// The collision box of the player whose eyes are at the given position
+    pub fn aabb(&self, eye_pos: Vec3<f32>) -> Aabb {
+        Aabb::player(eye_pos - Vec3::unit_y() * self.eye_height)
+    }
+
+    /// Applies gravity and moves the player by its velocity, colliding
+    /// against solid blocks. Returns the new eye position.
+    ///
+    /// While crouching on the ground the player can't walk off block edges.
+    /// The blocks tested for collisions are pushed to `considered` if given.
+    pub fn step(
+        &mut self,
+        eye_pos: Vec3<f32>,
+        dt: f32,
+        crouching: bool,
+        world: &CollisionMap,
+        mut considered: Option<&mut Vec<Vec3<i32>>>,
+    ) -> Vec3<f32> {
+        let aabb = self.aabb(eye_pos);
+        // Don't fall forever when the chunk below isn't loaded
+        if !world.chunk_loaded {
+            self.velocity.y = 0.0;
+        } else {
+            self.velocity.y = (self.velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+        }
+
+        let mut is_solid = |pos| {
+            if let Some(considered) = considered.as_mut() {
+                considered.push(pos);
+            }
+            world.is_solid(pos)
+        };
+        let mut moved = aabb;
+        // Resolving one axis at a time lets the player slide along walls
+        for axis in [1, 0, 2] {
+            let delta = self.velocity[axis] * dt;
+            let mut allowed = sweep_axis(&moved, axis, delta, &mut is_solid);
+            if crouching && self.on_ground && axis != 1 {
+                let mut offset = Vec3::zero();
+                offset[axis] = allowed;
+                let next = moved.translated(offset);
+                // Nothing to stand on after moving
+                if sweep_axis(&next, 1, -0.6, &mut is_solid) == -0.6 {
+                    allowed = 0.0;
+                }
+            }
+            moved.min[axis] += allowed;
+            moved.max[axis] += allowed;
+            if allowed != delta {
+                if axis == 1 {
+                    self.on_ground = delta < 0.0;
+                }
+                self.velocity[axis] = 0.0;
+            } else if axis == 1 {
+                self.on_ground = false;
+            }
+        }
+        eye_pos + (moved.min - aabb.min)
+    }
+
+    pub fn jump(&mut self) {
+        if self.on_ground {
+            self.velocity.y = JUMP_SPEED;
+            self.on_ground = false;
+        }
+    }
+}
+
+impl Default for PlayerBody {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns how far the box can move along the axis before hitting a solid block.
+pub fn sweep_axis(
+    aabb: &Aabb,
+    axis: usize,
+    delta: f32,
+    mut is_solid: impl FnMut(Vec3<i32>) -> bool,
+) -> f32 {
+    if delta == 0.0 {
+        return 0.0;
+    }
+    let mut offset = Vec3::zero();
+    offset[axis] = delta;
+    let swept = aabb.union(&aabb.translated(offset));
+
+    let mut allowed = delta;
+    for pos in swept.blocks().filter(|pos| is_solid(*pos)) {
+        let block = Aabb::block(pos);
+        if delta > 0.0 {
+            let gap = block.min[axis] - aabb.max[axis];
+            // Blocks the box is already stuck in are ignored
+            if gap >= -COLLISION_EPSILON {
+                allowed = allowed.min(gap.max(0.0));
+            }
+        } else {
+            let gap = block.max[axis] - aabb.min[axis];
+            if gap <= COLLISION_EPSILON {
+                allowed = allowed.max(gap.min(0.0));
+            }
+        }
+    }
+    allowed
+}
@@ -0,0 +1,253 @@
+use vek::Vec3;
+
+use crate::{
+    block::BlockId,
+    world::{
+        chunk::{Chunk, TOTAL_CHUNK_SIZE},
+        chunk_manager::ChunkManager,
+        light::{LightKind, MAX_LIGHT},
+    },
+};
+
+/// Blocks of every loaded chunk ticked per simulation step by default
+pub const DEFAULT_RANDOM_TICK_SPEED: u32 = 1;
+/// Seeds the choice of the ticked blocks when the world has no seed
+const RANDOM_TICK_SEED: u64 = 0x5eed_6a55;
+
+/// Ticks random blocks of the loaded chunks every simulation step, which
+/// slowly changes them: grass covered by an opaque block decays to dirt
+/// and dirt under the open sky next to grass grows grass. The blocks
+/// picked only depend on the seed and the number of steps ticked.
+pub struct RandomTicker {
+    seed: u64,
+    /// Steps ticked so far
+    counter: u64,
+    /// Blocks of every loaded chunk ticked per step, 0 to not tick any
+    pub speed: u32,
+}
+
+impl RandomTicker {
+    pub fn new() -> Self {
+        Self::with_seed(RANDOM_TICK_SEED)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            counter: 0,
+            speed: DEFAULT_RANDOM_TICK_SPEED,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Ticks `speed` random blocks of every loaded chunk and returns
+    /// how many of them changed
+    pub fn tick(&mut self, world: &mut ChunkManager) -> usize {
+        if self.speed == 0 {
+            return 0;
+        }
+        let mut rng = Rng::new(self.seed, self.counter);
+        self.counter += 1;
+        // Blocks change once all are picked, so that the order of the
+        // chunks doesn't change which blocks the others see
+        let mut changes = Vec::new();
+        for chunk in world.chunks() {
+            let origin = chunk.pos.to_world();
+            for _ in 0..self.speed {
+                let index = rng.below(TOTAL_CHUNK_SIZE as u64) as usize;
+                let pos = origin + Chunk::local_pos_of(index);
+                let neighbor = pos + Vec3::new(rng.offset(), rng.offset(), rng.offset());
+                if let Some(block) = random_tick(world, pos, neighbor) {
+                    changes.push((pos, block));
+                }
+            }
+        }
+        for (pos, block) in &changes {
+            world.set_block(*pos, *block);
+        }
+        changes.len()
+    }
+}
+
+impl Default for RandomTicker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the block at `pos` becomes when randomly ticked, if it changes.
+/// `neighbor` is a random block of the 3x3x3 cube around it, which grass
+/// spreads from.
+pub fn random_tick(world: &ChunkManager, pos: Vec3<i32>, neighbor: Vec3<i32>) -> Option<BlockId> {
+    let above = pos + Vec3::unit_y();
+    match world.get_block(pos)? {
+        BlockId::GRASS if world.get_block(above)?.is_opaque() => Some(BlockId::DIRT),
+        BlockId::DIRT
+            if world.get_block(neighbor)? == BlockId::GRASS
+                && !world.get_block(above)?.is_opaque()
+                && world.get_light(above, LightKind::Sky)? == MAX_LIGHT =>
+        {
+            Some(BlockId::GRASS)
+        }
+        _ => None,
+    }
+}
+
+/// SplitMix64, seeded anew every step so that the numbers only depend
+/// on the seed and the step
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64, step: u64) -> Self {
+        Self(seed ^ step.wrapping_mul(0x2545_f491_4f6c_dd1d))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 to `n` excluded
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// -1, 0 or 1
+    fn offset(&mut self) -> i32 {
+        self.below(3) as i32 - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::{ChunkPos, CHUNK_DEPTH, CHUNK_WIDTH, SURFACE_HEIGHT};
+
+    /// The chunk with the generated grass on its top layer
+    const SURFACE: ChunkPos = ChunkPos::new(0, 15, 0);
+    /// The open sky above it
+    const SKY: ChunkPos = ChunkPos::new(0, 16, 0);
+    const SEED: u64 = 42;
+
+    fn surface(x: i32, z: i32) -> Vec3<i32> {
+        Vec3::new(x, SURFACE_HEIGHT, z)
+    }
+
+    /// The surface chunk with the grass turned to dirt but for its center
+    /// column, and the grass of the first row covered by stone
+    fn prepared_world() -> (ChunkManager, tempfile::TempDir) {
+        let (mut world, dir) = ChunkManager::with_chunks([SURFACE, SKY]);
+        assert_eq!(ChunkPos::from_block(surface(0, 0)), SURFACE);
+        for x in 0..CHUNK_WIDTH as i32 {
+            for z in 1..CHUNK_DEPTH as i32 {
+                if (x, z) != (8, 8) {
+                    world.set_block(surface(x, z), BlockId::DIRT);
+                }
+            }
+            world.set_block(surface(x, 0) + Vec3::unit_y(), BlockId::STONE);
+        }
+        world.process_events();
+        (world, dir)
+    }
+
+    fn top_layer(world: &ChunkManager) -> Vec<BlockId> {
+        (0..CHUNK_WIDTH as i32)
+            .flat_map(|x| (0..CHUNK_DEPTH as i32).map(move |z| surface(x, z)))
+            .map(|pos| world.get_block(pos).unwrap())
+            .collect()
+    }
+
+    /// Ticks until nothing changes anymore, at most `max_steps` times
+    fn tick_until_settled(ticker: &mut RandomTicker, world: &mut ChunkManager, max_steps: usize) {
+        let mut settled = 0;
+        for _ in 0..max_steps {
+            let changed = ticker.tick(world);
+            world.process_events();
+            settled = if changed == 0 { settled + 1 } else { 0 };
+            if settled == 16 {
+                return;
+            }
+        }
+    }
+
+    #[test]
+    fn grass_decays_and_spreads_under_the_sky() {
+        let (mut world, _dir) = prepared_world();
+        let mut ticker = RandomTicker::with_seed(SEED);
+        ticker.speed = TOTAL_CHUNK_SIZE as u32;
+        tick_until_settled(&mut ticker, &mut world, 500);
+
+        for x in 0..CHUNK_WIDTH as i32 {
+            // Covered by stone
+            assert_eq!(world.get_block(surface(x, 0)), Some(BlockId::DIRT));
+            for z in 1..CHUNK_DEPTH as i32 {
+                assert_eq!(world.get_block(surface(x, z)), Some(BlockId::GRASS));
+            }
+        }
+        // The dirt under the surface has no sky to grow grass under
+        let below = surface(8, 7) - Vec3::unit_y();
+        assert_eq!(world.get_block(below), Some(BlockId::DIRT));
+    }
+
+    #[test]
+    fn same_seed_converts_the_same_blocks() {
+        let run = |seed| {
+            let (mut world, _dir) = prepared_world();
+            let mut ticker = RandomTicker::with_seed(seed);
+            ticker.speed = 256;
+            let changed = (0..8)
+                .map(|_| {
+                    let changed = ticker.tick(&mut world);
+                    world.process_events();
+                    changed
+                })
+                .collect::<Vec<_>>();
+            (changed, top_layer(&world))
+        };
+        let first = run(SEED);
+        assert!(first.0.iter().sum::<usize>() > 0);
+        assert_eq!(first, run(SEED));
+        assert_ne!(first, run(SEED + 1));
+    }
+
+    #[test]
+    fn tick_rules() {
+        let (world, _dir) = prepared_world();
+        let grass = surface(8, 8);
+        // Next to the grass, under the sky
+        assert_eq!(
+            random_tick(&world, surface(8, 9), grass),
+            Some(BlockId::GRASS)
+        );
+        // The neighbor picked isn't grass
+        assert_eq!(random_tick(&world, surface(8, 9), surface(8, 10)), None);
+        // Under the surface, without sky
+        let below = surface(8, 9) - Vec3::unit_y();
+        assert_eq!(random_tick(&world, below, grass), None);
+        assert_eq!(
+            random_tick(&world, surface(8, 0), grass),
+            Some(BlockId::DIRT)
+        );
+        assert_eq!(random_tick(&world, grass, surface(8, 9)), None);
+        // Unloaded
+        assert_eq!(random_tick(&world, surface(-8, 8), grass), None);
+    }
+
+    #[test]
+    fn speed_zero_changes_nothing() {
+        let (mut world, _dir) = prepared_world();
+        let before = top_layer(&world);
+        let mut ticker = RandomTicker::with_seed(SEED);
+        ticker.speed = 0;
+        for _ in 0..16 {
+            assert_eq!(ticker.tick(&mut world), 0);
+        }
+        assert_eq!(top_layer(&world), before);
+    }
+}
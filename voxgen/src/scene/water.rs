@@ -0,0 +1,112 @@
+use vek::Vec3;
+
+use crate::{
+    block::{BlockId, MAX_FLUID_LEVEL},
+    direction::Direction,
+    renderer::WorldRenderer,
+    world::chunk::ChunkPos,
+};
+
+/// Steps between a fluid block changing and its neighbors flowing,
+/// 12 blocks a second
+pub const FLOW_DELAY: u64 = 5;
+/// Most block ticks run in a single frame, the others wait for the next ones
+pub const TICKS_PER_FRAME: usize = 256;
+/// Level of a fluid fed by the block above it, which spreads like a source
+const FALLING_LEVEL: u8 = 1;
+
+/// Flows the fluid block at `pos` for one tick. A flowing block first
+/// takes the level its neighbors feed it, drying up if none does. The
+/// fluid then falls into the air beneath it, or if it rests on a block,
+/// spreads one level weaker into the air beside it. Returns the chunk to
+/// wait for if the block or one it depends on isn't loaded.
+pub fn flow(world: &mut WorldRenderer, pos: Vec3<i32>) -> Result<(), ChunkPos> {
+    let fluid = block(world, pos)?;
+    if !fluid.flows() {
+        return Ok(());
+    }
+    let mut level = level(world, pos)?;
+    if level > 0 {
+        match fed_level(world, fluid, pos)? {
+            None => {
+                world.set_block_world(pos, BlockId::AIR);
+                return Ok(());
+            }
+            Some(fed) if fed != level => {
+                world.set_block_meta_world(pos, fluid, fed);
+                level = fed;
+            }
+            Some(_) => (),
+        }
+    }
+
+    let below = pos - Vec3::unit_y();
+    if block(world, below)?.is_air() {
+        world.set_block_meta_world(below, fluid, FALLING_LEVEL);
+        return Ok(());
+    }
+    if level >= MAX_FLUID_LEVEL || !rests_on(world, below)? {
+        return Ok(());
+    }
+    let sides = Direction::HORIZONTAL
+        .into_iter()
+        .map(|dir| {
+            let side = pos + dir.normalized();
+            block(world, side).map(|block| (side, block))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    for (side, block) in sides {
+        if block.is_air() {
+            world.set_block_meta_world(side, fluid, level + 1);
+        }
+    }
+    Ok(())
+}
+
+/// The level the neighbors of the flowing block at `pos` feed it: the
+/// level of a fluid falling into it, or one weaker than the strongest
+/// fluid beside it that spreads. `None` if nothing feeds it.
+fn fed_level(
+    world: &WorldRenderer,
+    fluid: BlockId,
+    pos: Vec3<i32>,
+) -> Result<Option<u8>, ChunkPos> {
+    if block(world, pos + Vec3::unit_y())? == fluid {
+        return Ok(Some(FALLING_LEVEL));
+    }
+    let mut fed = None;
+    for dir in Direction::HORIZONTAL {
+        let side = pos + dir.normalized();
+        if block(world, side)? != fluid {
+            continue;
+        }
+        let level = level(world, side)?;
+        if level < MAX_FLUID_LEVEL && rests_on(world, side - Vec3::unit_y())? {
+            fed = Some(fed.map_or(level + 1, |fed: u8| fed.min(level + 1)));
+        }
+    }
+    Ok(fed)
+}
+
+/// Whether a fluid above `below` rests on it and spreads sideways. Fluids
+/// fall through air, and the flowing fluid of a falling column doesn't
+/// hold up the fluid above it.
+fn rests_on(world: &WorldRenderer, below: Vec3<i32>) -> Result<bool, ChunkPos> {
+    let block = block(world, below)?;
+    if block.is_air() {
+        return Ok(false);
+    }
+    Ok(!block.flows() || level(world, below)? == 0)
+}
+
+fn block(world: &WorldRenderer, pos: Vec3<i32>) -> Result<BlockId, ChunkPos> {
+    world
+        .block_at_world(pos)
+        .ok_or_else(|| ChunkPos::from_block(pos))
+}
+
+fn level(world: &WorldRenderer, pos: Vec3<i32>) -> Result<u8, ChunkPos> {
+    world
+        .meta_at_world(pos)
+        .ok_or_else(|| ChunkPos::from_block(pos))
+}
@@ -0,0 +1,191 @@
+use std::path::Path;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    renderer::{
+        adapter::{AdapterPreferences, Backend, PowerPreference},
+        bloom::BloomSettings,
+        outline::OutlineSettings,
+        post_process::ColorGrading,
+        texture::{Filter, SamplerSettings},
+        world::DEFAULT_LOD_DISTANCE,
+    },
+    scene::{
+        camera::{DEFAULT_FOV, THIRD_PERSON_DISTANCE},
+        drops::{DEFAULT_DROP_LIFETIME, DEFAULT_PICKUP_RADIUS},
+        inventory::GameMode,
+        random_ticks::DEFAULT_RANDOM_TICK_SPEED,
+    },
+    world::{border::WorldBorder, light::Lighting},
+};
+
+pub const SETTINGS_PATH: &str = "config/settings.toml";
+
+/// User preferences persisted between runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Mouse look speed multiplier
+    pub sensitivity: f32,
+    pub invert_y: bool,
+    /// Vertical field of view in degrees, before the sprint widening
+    pub fov: f32,
+    /// Fade light across faces instead of lighting each face evenly
+    pub smooth_lighting: bool,
+    /// Skip drawing the chunks hidden behind terrain
+    pub occlusion_culling: bool,
+    /// Chunks away from the camera at which chunks are drawn at half
+    /// resolution, 0 to always draw them in full
+    pub lod_distance: u32,
+    /// How the block textures are filtered
+    pub texture_filtering: SamplerSettings,
+    /// Filtering used while the comparison key is held, to see the
+    /// difference with `texture_filtering`
+    pub compare_filtering: SamplerSettings,
+    /// How the colors of the world are adjusted, the UI is left as is
+    pub color_grading: ColorGrading,
+    /// Glow around emissive blocks like torches
+    pub bloom: BloomSettings,
+    /// Dark lines along the edges of blocks
+    pub outline: OutlineSettings,
+    /// Fraction of the window resolution the world is drawn at before
+    /// being upscaled, the UI stays at full resolution
+    pub render_scale: f32,
+    /// How the world is upscaled when drawn below full resolution
+    pub upscale_filter: Filter,
+    /// Smooth the jagged edges of the world with FXAA
+    pub fxaa: bool,
+    /// Distance between the player and the camera in third person
+    pub third_person_distance: f32,
+    /// Color the world is tinted and fogged with underwater
+    pub underwater_tint: [f32; 3],
+    /// How quickly the fog thickens underwater
+    pub underwater_fog_density: f32,
+    /// Volume of every sound, from 0 to 1
+    pub master_volume: f32,
+    /// Volume of block and footstep sounds relative to the master volume
+    pub effects_volume: f32,
+    /// Borderless fullscreen instead of a window
+    pub fullscreen: bool,
+    /// Index of the monitor fullscreen is shown on
+    pub monitor: usize,
+    /// Size of the UI relative to the OS scale factor
+    pub ui_scale: f32,
+    /// Most frames per second while the window is focused, 0 for no cap
+    pub fps_cap: u32,
+    /// Chunks between the origin chunk and the world border, 0 for no border
+    pub world_border: u32,
+    /// Break blocks on click instead of holding the button for their hardness
+    pub instant_break: bool,
+    /// Whether placing blocks uses up the ones collected by breaking them
+    pub game_mode: GameMode,
+    /// How close the player must come to broken blocks to collect them
+    pub pickup_radius: f32,
+    /// Seconds before broken blocks nobody collected despawn
+    pub drop_lifetime: f32,
+    /// Shows short messages like saves and command results in a corner
+    pub message_feed: bool,
+    /// Random blocks of every chunk ticked per step, which makes grass
+    /// spread and decay, 0 to turn random ticks off
+    pub random_tick_speed: u32,
+    /// Graphics API to use, takes effect after a restart
+    pub backend: Backend,
+    /// Which GPU to use, takes effect after a restart
+    pub power_preference: PowerPreference,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            sensitivity: 1.0,
+            invert_y: false,
+            fov: DEFAULT_FOV,
+            smooth_lighting: true,
+            occlusion_culling: true,
+            lod_distance: DEFAULT_LOD_DISTANCE,
+            texture_filtering: SamplerSettings::PIXEL_PERFECT,
+            compare_filtering: SamplerSettings::UNFILTERED,
+            color_grading: ColorGrading::NEUTRAL,
+            bloom: BloomSettings::default(),
+            outline: OutlineSettings::default(),
+            render_scale: 1.0,
+            upscale_filter: Filter::Linear,
+            fxaa: false,
+            third_person_distance: THIRD_PERSON_DISTANCE,
+            underwater_tint: [0.05, 0.2, 0.45],
+            underwater_fog_density: 0.15,
+            master_volume: 0.8,
+            effects_volume: 1.0,
+            fullscreen: false,
+            monitor: 0,
+            ui_scale: 1.0,
+            fps_cap: 0,
+            world_border: 0,
+            instant_break: false,
+            game_mode: GameMode::Creative,
+            pickup_radius: DEFAULT_PICKUP_RADIUS,
+            drop_lifetime: DEFAULT_DROP_LIFETIME,
+            message_feed: true,
+            random_tick_speed: DEFAULT_RANDOM_TICK_SPEED,
+            backend: Backend::Auto,
+            power_preference: PowerPreference::Low,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        load_toml(SETTINGS_PATH).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        save_toml(SETTINGS_PATH, self);
+    }
+
+    pub fn adapter_preferences(&self) -> AdapterPreferences {
+        AdapterPreferences {
+            backend: self.backend,
+            power: self.power_preference,
+        }
+    }
+
+    pub fn world_border(&self) -> WorldBorder {
+        match self.world_border {
+            0 => WorldBorder::NONE,
+            radius => WorldBorder::new(radius),
+        }
+    }
+
+    pub fn lighting(&self) -> Lighting {
+        if self.smooth_lighting {
+            Lighting::Smooth
+        } else {
+            Lighting::Flat
+        }
+    }
+}
+
+/// Reads a TOML config file. Returns `None` if the file doesn't exist
+/// or can't be parsed.
+pub fn load_toml<T: DeserializeOwned>(path: &str) -> Option<T> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    toml::from_str(&contents)
+        .map_err(|e| log::warn!("Invalid config in {}: {}", path, e))
+        .ok()
+}
+
+/// Writes a TOML config file, creating its directory if needed
+pub fn save_toml<T: Serialize>(path: &str, value: &T) {
+    let result = toml::to_string_pretty(value)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| {
+            if let Some(dir) = Path::new(path).parent() {
+                std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(path, contents).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        log::error!("Failed to save {}: {}", path, e);
+    }
+}
@@ -0,0 +1,86 @@
+use egui::plot::{Bar, BarChart, Plot};
+use egui_winit_platform::Platform;
+
+use crate::{
+    renderer::{
+        lod::LodStats,
+        upload::{UploadBenchmark, BENCHMARK_CHUNKS},
+    },
+    world::chunk_stats::{ChunkMetric, ChunkStats, CSV_PATH},
+};
+
+use super::MenuAction;
+
+/// Histograms of the time spent generating, meshing and uploading chunks
+pub fn draw(
+    platform: &mut Platform,
+    stats: &ChunkStats,
+    lod: LodStats,
+    benchmark: Option<UploadBenchmark>,
+    action: &mut Option<MenuAction>,
+) {
+    egui::Window::new("Chunk Stats")
+        .default_open(false)
+        .show(&platform.context(), |ui| {
+            if ui.button("Save CSV").clicked() {
+                match stats.save_csv() {
+                    Ok(()) => log::info!("Saved the chunk stats to {}", CSV_PATH),
+                    Err(e) => log::error!("Failed to save the chunk stats: {}", e),
+                }
+            }
+            ui.label(format!(
+                "LOD: {} chunks at half resolution, {} of {} vertices drawn ({:.0}% saved)",
+                lod.chunks,
+                lod.vertices,
+                lod.full_vertices,
+                lod.saved() * 100.0
+            ));
+            ui.horizontal(|ui| {
+                if ui.button("Benchmark uploads").clicked() {
+                    *action = Some(MenuAction::BenchmarkUploads);
+                }
+                if let Some(benchmark) = benchmark {
+                    ui.label(format!(
+                        "{} chunks ({:.1} MiB): write_buffer {:.2} ms, staging belt {:.2} ms",
+                        BENCHMARK_CHUNKS,
+                        benchmark.bytes as f64 / (1024.0 * 1024.0),
+                        benchmark.write_buffer_ms,
+                        benchmark.staging_belt_ms
+                    ));
+                }
+            });
+            for metric in ChunkMetric::ALL {
+                let histogram = stats.histogram(metric);
+                egui::CollapsingHeader::new(metric.name())
+                    .default_open(metric == ChunkMetric::Generation)
+                    .show(ui, |ui| {
+                        ui.label(format!(
+                            "p50 {:.3}, p95 {:.3}, max {:.3} ({} chunks)",
+                            histogram.percentile(0.5),
+                            histogram.percentile(0.95),
+                            histogram.max(),
+                            histogram.len()
+                        ));
+                        let bars = histogram
+                            .counts()
+                            .iter()
+                            .enumerate()
+                            .map(|(bucket, count)| {
+                                let (min, max) = histogram.bucket_range(bucket);
+                                Bar::new(bucket as f64, *count as f64)
+                                    .width(0.9)
+                                    .name(format!("{} to {}", min, max))
+                            })
+                            .collect();
+                        Plot::new(metric.name())
+                            .height(100.0)
+                            .allow_drag(false)
+                            .allow_zoom(false)
+                            .allow_scroll(false)
+                            .show_x(false)
+                            .include_y(0.0)
+                            .show(ui, |plot| plot.bar_chart(BarChart::new(bars)));
+                    });
+            }
+        });
+}
@@ -0,0 +1,282 @@
+use std::{collections::VecDeque, path::Path};
+
+use egui_winit_platform::Platform;
+
+use crate::commands::{self, Completion};
+
+/// Maximum number of output lines kept in the console
+pub const CONSOLE_LINES: usize = 200;
+/// Where the entered commands are kept between sessions, one per line
+pub const HISTORY_PATH: &str = "config/console_history.txt";
+/// Maximum number of commands kept in the history
+pub const HISTORY_SIZE: usize = 100;
+
+/// The candidates of the last completion, cycled through by pressing tab again
+struct CompletionCycle {
+    completion: Completion,
+    /// The candidate in the input, `None` while only their common prefix is
+    index: Option<usize>,
+}
+
+/// A window to type commands into. Submitted commands are run
+/// by the engine after the frame.
+#[derive(Default)]
+pub struct ConsoleWindow {
+    input: String,
+    output: VecDeque<String>,
+    submitted: Vec<String>,
+    /// Entered commands, oldest first
+    history: VecDeque<String>,
+    /// The history entry in the input while browsing it with the arrow keys
+    browsing: Option<usize>,
+    /// The input from before browsing the history
+    draft: String,
+    cycle: Option<CompletionCycle>,
+}
+
+impl ConsoleWindow {
+    /// Creates the console with the history of the last sessions
+    pub fn new() -> Self {
+        let history = std::fs::read_to_string(HISTORY_PATH)
+            .map(|history| history.lines().map(str::to_owned).collect())
+            .unwrap_or_default();
+        Self {
+            history,
+            ..Self::default()
+        }
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        if self.output.len() == CONSOLE_LINES {
+            self.output.pop_front();
+        }
+        self.output.push_back(line.into());
+    }
+
+    /// Commands entered since the last call
+    pub fn take_submitted(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.submitted)
+    }
+
+    fn submit(&mut self, command: String) {
+        self.print(format!("> {}", command));
+        if self.history.back() != Some(&command) {
+            if self.history.len() == HISTORY_SIZE {
+                self.history.pop_front();
+            }
+            self.history.push_back(command.clone());
+            self.save_history();
+        }
+        self.submitted.push(command);
+    }
+
+    fn save_history(&self) {
+        let history = self
+            .history
+            .iter()
+            .fold(String::new(), |history, command| history + command + "\n");
+        let result = Path::new(HISTORY_PATH)
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(HISTORY_PATH, history));
+        if let Err(e) = result {
+            log::error!("Failed to save {}: {}", HISTORY_PATH, e);
+        }
+    }
+
+    /// Moves through the history, towards older commands when `back`
+    fn browse(&mut self, back: bool) {
+        let index = match (self.browsing, back) {
+            (None, true) if !self.history.is_empty() => {
+                self.draft = self.input.clone();
+                Some(self.history.len() - 1)
+            }
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < self.history.len() => Some(index + 1),
+            (Some(_), false) => None,
+            (None, _) => return,
+        };
+        self.input = match index {
+            Some(index) => self.history[index].clone(),
+            None => std::mem::take(&mut self.draft),
+        };
+        self.browsing = index;
+    }
+
+    /// Completes the word at the end of the input. A single candidate is
+    /// inserted right away, several are extended to their common prefix
+    /// and then cycled through on the next presses.
+    fn complete(&mut self) {
+        if let Some(cycle) = &mut self.cycle {
+            let candidates = &cycle.completion.candidates;
+            let index = cycle
+                .index
+                .map_or(0, |index| (index + 1) % candidates.len());
+            cycle.index = Some(index);
+            self.input.truncate(cycle.completion.start);
+            self.input += &candidates[index];
+            return;
+        }
+        let completion = commands::complete(&self.input);
+        // Without candidates the word is kept as typed
+        if !completion.candidates.is_empty() {
+            self.input.truncate(completion.start);
+        }
+        match completion.candidates.as_slice() {
+            [] => {}
+            [candidate] => {
+                self.input += candidate;
+                self.input.push(' ');
+            }
+            [first, rest @ ..] => {
+                let prefix = rest.iter().fold(first.as_str(), |prefix, candidate| {
+                    common_prefix(prefix, candidate)
+                });
+                self.input += prefix;
+                self.cycle = Some(CompletionCycle {
+                    completion,
+                    index: None,
+                });
+            }
+        }
+    }
+
+    pub fn draw(&mut self, platform: &mut Platform) {
+        egui::Window::new("Console")
+            .default_open(false)
+            .default_size([400.0, 200.0])
+            .show(&platform.context(), |ui| {
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .max_height(150.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for line in &self.output {
+                            ui.monospace(line);
+                        }
+                    });
+                ui.separator();
+                let output = egui::TextEdit::singleline(&mut self.input)
+                    .hint_text("Type help for a list of commands")
+                    .desired_width(f32::INFINITY)
+                    // Keeps tab from moving the focus to the next widget
+                    .lock_focus(true)
+                    .show(ui);
+                let response = output.response;
+                if response.changed() {
+                    self.browsing = None;
+                    self.cycle = None;
+                }
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    let command = std::mem::take(&mut self.input);
+                    if !command.trim().is_empty() {
+                        self.submit(command);
+                    }
+                    self.browsing = None;
+                    self.cycle = None;
+                    response.request_focus();
+                } else if response.has_focus() {
+                    let (tab, up, down) = ui.input(|i| {
+                        (
+                            i.key_pressed(egui::Key::Tab),
+                            i.key_pressed(egui::Key::ArrowUp),
+                            i.key_pressed(egui::Key::ArrowDown),
+                        )
+                    });
+                    let input = self.input.clone();
+                    if tab {
+                        self.complete();
+                    } else if up || down {
+                        self.cycle = None;
+                        self.browse(up);
+                    }
+                    // Edited inputs continue from their end
+                    if self.input != input {
+                        let mut state = output.state;
+                        let end = egui::text::CCursor::new(self.input.chars().count());
+                        state.set_ccursor_range(Some(egui::text::CCursorRange::one(end)));
+                        egui::TextEdit::store_state(ui.ctx(), response.id, state);
+                    }
+                }
+                if let Some(cycle) = &self.cycle {
+                    ui.horizontal_wrapped(|ui| {
+                        for (index, candidate) in cycle.completion.candidates.iter().enumerate() {
+                            let selected = cycle.index == Some(index);
+                            ui.add(egui::SelectableLabel::new(
+                                selected,
+                                egui::RichText::new(candidate).monospace(),
+                            ));
+                        }
+                    });
+                }
+            });
+    }
+}
+
+/// The longest start `a` shares with `b`
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let end = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((index, _), _)| index);
+    &a[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Presses tab `presses` times after typing `input`
+    fn tab(input: &str, presses: usize) -> String {
+        let mut console = ConsoleWindow {
+            input: input.to_owned(),
+            ..ConsoleWindow::default()
+        };
+        for _ in 0..presses {
+            console.complete();
+        }
+        console.input
+    }
+
+    #[test]
+    fn completes_a_single_block_name() {
+        assert_eq!(tab("give sa", 1), "give sand ");
+        assert_eq!(tab("give glo", 1), "give glowstone ");
+        assert_eq!(tab("give bedrock", 1), "give bedrock ");
+    }
+
+    #[test]
+    fn extends_to_the_common_prefix_then_cycles() {
+        assert_eq!(tab("give gl", 1), "give gl");
+        assert_eq!(tab("give gl", 2), "give glass");
+        assert_eq!(tab("give gl", 3), "give glowstone");
+        assert_eq!(tab("give gl", 4), "give glass");
+
+        assert_eq!(tab("give st", 1), "give stone");
+        assert_eq!(tab("give st", 3), "give stone_slab");
+        assert_eq!(tab("give grass", 1), "give grass");
+        assert_eq!(tab("give grass", 2), "give grass");
+        assert_eq!(tab("give grass", 3), "give grass_tuft");
+    }
+
+    #[test]
+    fn unknown_and_unlisted_blocks_stay_as_typed() {
+        assert_eq!(tab("give xyz", 1), "give xyz");
+        // Air can't be given
+        assert_eq!(tab("give ai", 1), "give ai");
+        // Only the first argument of give is a block
+        assert_eq!(tab("give stone 6", 1), "give stone 6");
+    }
+
+    #[test]
+    fn block_names_complete_from_the_registry() {
+        let names = commands::ArgKind::Block.complete("");
+        assert_eq!(names.len(), crate::block::registry::registry().len() - 1);
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            commands::ArgKind::Block.complete("sto"),
+            ["stone", "stone_slab"]
+        );
+    }
+}
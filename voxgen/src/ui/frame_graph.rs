@@ -0,0 +1,101 @@
+use std::{collections::VecDeque, fmt::Write, time::Duration};
+
+use egui::plot::{HLine, Legend, Line, Plot, PlotPoints};
+use egui_winit_platform::Platform;
+
+/// Number of frames kept in the graph
+pub const FRAME_HISTORY: usize = 240;
+pub const CSV_PATH: &str = "frame_times.csv";
+
+#[derive(Debug, Clone, Copy)]
+struct FrameSample {
+    update_ms: f64,
+    render_ms: f64,
+}
+
+/// Ring buffer of the most recent update and render times
+pub struct FrameGraph {
+    samples: VecDeque<FrameSample>,
+    /// Stops recording so a hitch can be inspected
+    pub frozen: bool,
+}
+
+impl FrameGraph {
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(FRAME_HISTORY),
+            frozen: false,
+        }
+    }
+
+    pub fn record(&mut self, update: Duration, render: Duration) {
+        if self.frozen {
+            return;
+        }
+        if self.samples.len() == FRAME_HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(FrameSample {
+            update_ms: update.as_secs_f64() * 1000.0,
+            render_ms: render.as_secs_f64() * 1000.0,
+        });
+    }
+
+    /// Writes the captured samples to `CSV_PATH`
+    pub fn save_csv(&self) {
+        let mut csv = String::from("frame,update_ms,render_ms\n");
+        for (i, sample) in self.samples.iter().enumerate() {
+            let _ = writeln!(csv, "{},{:.3},{:.3}", i, sample.update_ms, sample.render_ms);
+        }
+        match std::fs::write(CSV_PATH, csv) {
+            Ok(()) => log::info!("Saved {} frame times to {}", self.samples.len(), CSV_PATH),
+            Err(e) => log::error!("Failed to save frame times: {}", e),
+        }
+    }
+
+    fn points(&self, value: impl Fn(&FrameSample) -> f64) -> PlotPoints {
+        self.samples
+            .iter()
+            .enumerate()
+            .map(|(i, sample)| [i as f64, value(sample)])
+            .collect()
+    }
+
+    pub fn draw(&mut self, platform: &mut Platform) {
+        egui::Window::new("Frame Times")
+            .default_open(false)
+            .show(&platform.context(), |ui| {
+                ui.horizontal(|ui| {
+                    let label = if self.frozen { "Resume" } else { "Freeze" };
+                    if ui.button(label).clicked() {
+                        self.frozen = !self.frozen;
+                    }
+                    if ui.button("Save CSV").clicked() {
+                        self.save_csv();
+                    }
+                });
+                Plot::new("Frame Time Plot")
+                    .height(150.0)
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .include_x(0.0)
+                    .include_x(FRAME_HISTORY as f64)
+                    .include_y(0.0)
+                    .include_y(40.0)
+                    .legend(Legend::default())
+                    .show(ui, |plot| {
+                        // 60 and 30 FPS
+                        plot.hline(HLine::new(1000.0 / 60.0).name("16.6 ms"));
+                        plot.hline(HLine::new(1000.0 / 30.0).name("33.3 ms"));
+                        plot.line(Line::new(self.points(|s| s.update_ms)).name("Update"));
+                        plot.line(Line::new(self.points(|s| s.render_ms)).name("Render"));
+                    });
+            });
+    }
+}
+
+impl Default for FrameGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::VecDeque,
+    sync::mpsc::{self, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use egui_winit_platform::Platform;
+use log::Level;
+
+/// How long a message stays, including its fade out
+pub const MESSAGE_DURATION: Duration = Duration::from_secs(5);
+/// The end of `MESSAGE_DURATION` over which a message fades out
+const FADE_DURATION: Duration = Duration::from_secs(1);
+/// Most messages shown at once, older ones are dropped
+pub const MAX_MESSAGES: usize = 8;
+
+struct Message {
+    text: String,
+    level: Level,
+    posted: Instant,
+}
+
+/// Posts messages to the feed from any thread
+#[derive(Clone)]
+pub struct HudSender(Sender<(String, Level)>);
+
+impl HudSender {
+    pub fn post(&self, message: impl Into<String>, level: Level) {
+        // The feed lives as long as the UI
+        let _ = self.0.send((message.into(), level));
+    }
+}
+
+/// Short lived messages in the lower left corner, for feedback that
+/// doesn't need the console like saves and command results
+pub struct Hud {
+    sender: Sender<(String, Level)>,
+    receiver: Receiver<(String, Level)>,
+    messages: VecDeque<Message>,
+    /// Messages posted while disabled are dropped
+    pub enabled: bool,
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            messages: VecDeque::with_capacity(MAX_MESSAGES),
+            enabled: true,
+        }
+    }
+
+    /// A handle background threads post messages with
+    pub fn sender(&self) -> HudSender {
+        HudSender(self.sender.clone())
+    }
+
+    pub fn post(&mut self, message: impl Into<String>, level: Level) {
+        if !self.enabled {
+            return;
+        }
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(Message {
+            text: message.into(),
+            level,
+            posted: Instant::now(),
+        });
+    }
+
+    /// Adds the messages posted from other threads and drops the expired ones
+    fn update(&mut self) {
+        while let Ok((text, level)) = self.receiver.try_recv() {
+            self.post(text, level);
+        }
+        if !self.enabled {
+            self.messages.clear();
+        }
+        self.messages
+            .retain(|message| message.posted.elapsed() < MESSAGE_DURATION);
+    }
+
+    pub fn draw(&mut self, platform: &mut Platform) {
+        self.update();
+        if self.messages.is_empty() {
+            return;
+        }
+        egui::Area::new("Messages")
+            .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -50.0])
+            .interactable(false)
+            .show(&platform.context(), |ui| {
+                for message in &self.messages {
+                    let left = MESSAGE_DURATION.saturating_sub(message.posted.elapsed());
+                    let opacity = (left.as_secs_f32() / FADE_DURATION.as_secs_f32()).min(1.0);
+                    let color = match message.level {
+                        Level::Error => egui::Color32::from_rgb(255, 90, 90),
+                        Level::Warn => egui::Color32::from_rgb(255, 210, 80),
+                        _ => egui::Color32::WHITE,
+                    };
+                    let frame = egui::Frame::none()
+                        .fill(egui::Color32::from_black_alpha((150.0 * opacity) as u8))
+                        .inner_margin(egui::style::Margin::symmetric(6.0, 2.0))
+                        .rounding(2.0);
+                    frame.show(ui, |ui| {
+                        ui.colored_label(color.linear_multiply(opacity), &message.text);
+                    });
+                }
+            });
+    }
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,134 @@
+use egui_winit_platform::Platform;
+
+use crate::{
+    block::BlockId,
+    scene::{hotbar::Hotbar, inventory::Inventory},
+};
+
+use super::{paint_block, paint_count};
+
+const SLOT_SIZE: f32 = 40.0;
+/// Blocks per row of the inventory grid
+const COLUMNS: usize = 9;
+
+/// Lists the blocks the player has. Blocks are dragged onto the hotbar row
+/// to fill its slots, which the engine applies after the frame.
+#[derive(Default)]
+pub struct InventoryWindow {
+    pub open: bool,
+    /// The block being dragged from the grid
+    dragged: Option<BlockId>,
+    /// Hotbar slot a block was dropped on
+    dropped: Option<(usize, BlockId)>,
+}
+
+impl InventoryWindow {
+    /// The block dropped on a hotbar slot since the last call
+    pub fn take_dropped(&mut self) -> Option<(usize, BlockId)> {
+        self.dropped.take()
+    }
+
+    pub fn draw(
+        &mut self,
+        platform: &mut Platform,
+        inventory: &Inventory,
+        hotbar: &Hotbar,
+        atlas: egui::TextureId,
+    ) {
+        if !self.open {
+            self.dragged = None;
+            return;
+        }
+        let context = platform.context();
+        let released = context.input(|i| i.pointer.any_released());
+        egui::Window::new("Inventory")
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .collapsible(false)
+            .resizable(false)
+            .show(&context, |ui| {
+                let blocks = inventory.available();
+                if blocks.is_empty() {
+                    ui.label("Break blocks to collect them");
+                }
+                egui::Grid::new("Inventory grid")
+                    .spacing([4.0, 4.0])
+                    .show(ui, |ui| {
+                        for (index, (block, count)) in blocks.iter().enumerate() {
+                            let (rect, response) = ui.allocate_exact_size(
+                                egui::vec2(SLOT_SIZE, SLOT_SIZE),
+                                egui::Sense::drag(),
+                            );
+                            if response.drag_started() {
+                                self.dragged = Some(*block);
+                            }
+                            let painter = ui.painter();
+                            painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(150));
+                            paint_block(
+                                painter,
+                                atlas,
+                                rect.shrink(4.0),
+                                *block,
+                                egui::Color32::WHITE,
+                            );
+                            if let Some(count) = count {
+                                paint_count(painter, rect, *count);
+                            }
+                            response.on_hover_text(block.name());
+                            if (index + 1) % COLUMNS == 0 {
+                                ui.end_row();
+                            }
+                        }
+                    });
+                ui.separator();
+                ui.label("Drag blocks onto the hotbar");
+                ui.horizontal(|ui| {
+                    for (slot, block) in hotbar.slots.iter().enumerate() {
+                        let (rect, _) = ui.allocate_exact_size(
+                            egui::vec2(SLOT_SIZE, SLOT_SIZE),
+                            egui::Sense::hover(),
+                        );
+                        let hovered = self.dragged.is_some() && ui.rect_contains_pointer(rect);
+                        if hovered && released {
+                            self.dropped = self.dragged.map(|block| (slot, block));
+                        }
+                        let painter = ui.painter();
+                        painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(150));
+                        if !block.is_air() {
+                            paint_block(
+                                painter,
+                                atlas,
+                                rect.shrink(4.0),
+                                *block,
+                                egui::Color32::WHITE,
+                            );
+                        }
+                        let stroke = if hovered {
+                            egui::Stroke::new(3.0, egui::Color32::WHITE)
+                        } else {
+                            egui::Stroke::new(1.0, egui::Color32::GRAY)
+                        };
+                        painter.rect_stroke(rect, 2.0, stroke);
+                    }
+                });
+            });
+
+        // The dragged block follows the pointer above every window
+        if let (Some(block), Some(pos)) = (self.dragged, context.pointer_latest_pos()) {
+            let painter = context.layer_painter(egui::LayerId::new(
+                egui::Order::Tooltip,
+                egui::Id::new("Dragged block"),
+            ));
+            let rect = egui::Rect::from_center_size(pos, egui::vec2(SLOT_SIZE, SLOT_SIZE));
+            paint_block(
+                &painter,
+                atlas,
+                rect.shrink(4.0),
+                block,
+                egui::Color32::from_white_alpha(200),
+            );
+        }
+        if released {
+            self.dragged = None;
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use egui_winit_platform::Platform;
+use log::Level;
+
+use crate::logger;
+
+/// Filters of the log window
+pub struct LogWindow {
+    pub show_error: bool,
+    pub show_warn: bool,
+    pub show_info: bool,
+    /// Only records whose module contains this text are shown
+    pub module_filter: String,
+}
+
+impl Default for LogWindow {
+    fn default() -> Self {
+        Self {
+            show_error: true,
+            show_warn: true,
+            show_info: true,
+            module_filter: String::new(),
+        }
+    }
+}
+
+impl LogWindow {
+    fn shows(&self, level: Level) -> bool {
+        match level {
+            Level::Error => self.show_error,
+            Level::Warn => self.show_warn,
+            _ => self.show_info,
+        }
+    }
+
+    pub fn draw(&mut self, platform: &mut Platform) {
+        egui::Window::new("Log")
+            .default_open(false)
+            .default_size([500.0, 250.0])
+            .show(&platform.context(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.show_error, "Error");
+                    ui.checkbox(&mut self.show_warn, "Warn");
+                    ui.checkbox(&mut self.show_info, "Info");
+                    ui.label("Module:");
+                    ui.text_edit_singleline(&mut self.module_filter);
+                    if ui.button("Clear").clicked() {
+                        logger::clear();
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        logger::with_records(|records| {
+                            let visible = records.iter().filter(|entry| {
+                                self.shows(entry.level)
+                                    && entry.target.contains(self.module_filter.as_str())
+                            });
+                            for entry in visible {
+                                let color = match entry.level {
+                                    Level::Error => egui::Color32::RED,
+                                    Level::Warn => egui::Color32::YELLOW,
+                                    _ => ui.visuals().text_color(),
+                                };
+                                ui.colored_label(
+                                    color,
+                                    format!("[{} {}] {}", entry.level, entry.target, entry.message),
+                                );
+                            }
+                        });
+                    });
+            });
+    }
+}
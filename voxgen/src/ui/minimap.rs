@@ -0,0 +1,234 @@
+use std::collections::{HashMap, HashSet};
+
+use egui::{Color32, ColorImage, TextureHandle, TextureOptions};
+use egui_winit_platform::Platform;
+use vek::Vec3;
+
+use crate::{
+    renderer::world::RENDER_DISTANCE,
+    scene::camera::Camera,
+    world::{
+        chunk::{ChunkPos, CHUNK_DEPTH, CHUNK_WIDTH},
+        chunk_manager::ChunkManager,
+        events::WorldEvent,
+    },
+};
+
+/// Chunks shown on each side of the player's chunk
+const MAP_RADIUS: i32 = RENDER_DISTANCE;
+/// Side of the composed image, in pixels of one block each
+const IMAGE_SIZE: usize = (2 * MAP_RADIUS as usize + 1) * CHUNK_WIDTH;
+/// Side of the map on screen, in logical pixels
+const MAP_SIZE: f32 = 160.0;
+/// Distance from the top right corner of the window, in logical pixels
+const MAP_MARGIN: f32 = 10.0;
+/// Magnifications cycled through by clicking the map
+const ZOOM_LEVELS: [usize; 3] = [1, 2, 4];
+/// Color of columns without any block or in chunks that aren't loaded
+const EMPTY_COLOR: Color32 = Color32::from_rgb(20, 20, 20);
+const PLAYER_COLOR: Color32 = Color32::from_rgb(255, 60, 60);
+
+/// Top-down view of the loaded chunks around the player, colored by the
+/// highest block of each column
+pub struct Minimap {
+    /// Index into `ZOOM_LEVELS`
+    zoom: usize,
+    /// Colors of the columns of each chunk in range, kept until the chunk changes
+    chunk_images: HashMap<ChunkPos, ColorImage>,
+    /// Chunk at the center of the composed image
+    center: Option<ChunkPos>,
+    /// Chunks whose part of the texture is out of date
+    stale: HashSet<ChunkPos>,
+    image: ColorImage,
+    texture: Option<TextureHandle>,
+    player: Vec3<f32>,
+    /// Degrees clockwise from north the player looks towards
+    heading: f32,
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self {
+            zoom: 0,
+            chunk_images: HashMap::new(),
+            center: None,
+            stale: HashSet::new(),
+            image: ColorImage::new([IMAGE_SIZE; 2], EMPTY_COLOR),
+            texture: None,
+            player: Vec3::zero(),
+            heading: 0.0,
+        }
+    }
+}
+
+impl Minimap {
+    /// Recomposes the chunks that changed or came into range.
+    /// `events` are the world events processed this frame.
+    pub fn update(
+        &mut self,
+        context: &egui::Context,
+        chunks: &ChunkManager,
+        events: &[WorldEvent],
+        camera: &Camera,
+    ) {
+        self.player = camera.pos.map(|v| v as f32);
+        self.heading = camera.heading();
+        for event in events {
+            let pos = match event {
+                WorldEvent::BlockChanged { pos, .. } => ChunkPos::from_block(*pos).column(),
+                WorldEvent::ChunkLoaded(pos) | WorldEvent::ChunkUnloaded(pos) => pos.column(),
+                WorldEvent::NeighborChanged { .. } => continue,
+            };
+            self.chunk_images.remove(&pos);
+            self.stale.insert(pos);
+        }
+
+        let center = ChunkPos::from_world(self.player).column();
+        let moved = self.center != Some(center);
+        if moved {
+            self.center = Some(center);
+            self.chunk_images
+                .retain(|pos, _| Self::offset(center, *pos).is_some());
+        }
+        let stale = if moved {
+            self.stale.clear();
+            Self::chunks_in_range(center).collect::<Vec<_>>()
+        } else {
+            self.stale
+                .drain()
+                .filter(|pos| Self::offset(center, *pos).is_some())
+                .collect()
+        };
+        if stale.is_empty() && self.texture.is_some() {
+            return;
+        }
+
+        for pos in &stale {
+            let chunk_image = self
+                .chunk_images
+                .entry(*pos)
+                .or_insert_with(|| Self::chunk_image(chunks, *pos));
+            let [x, y] = Self::offset(center, *pos).expect("Only chunks in range are stale");
+            blit(&mut self.image, chunk_image, [x, y]);
+        }
+        match &mut self.texture {
+            // A new center shifts every chunk
+            Some(texture) if !moved => {
+                for pos in &stale {
+                    let offset =
+                        Self::offset(center, *pos).expect("Only chunks in range are stale");
+                    texture.set_partial(
+                        offset,
+                        self.chunk_images[pos].clone(),
+                        TextureOptions::NEAREST,
+                    );
+                }
+            }
+            Some(texture) => texture.set(self.image.clone(), TextureOptions::NEAREST),
+            None => {
+                self.texture = Some(context.load_texture(
+                    "Minimap",
+                    self.image.clone(),
+                    TextureOptions::NEAREST,
+                ));
+            }
+        }
+    }
+
+    fn chunks_in_range(center: ChunkPos) -> impl Iterator<Item = ChunkPos> {
+        (-MAP_RADIUS..=MAP_RADIUS).flat_map(move |x| {
+            (-MAP_RADIUS..=MAP_RADIUS).map(move |z| center + ChunkPos::new(x, 0, z))
+        })
+    }
+
+    /// Pixel of the top left corner of the chunk in the composed image,
+    /// or `None` if the chunk is out of range. North is up.
+    fn offset(center: ChunkPos, pos: ChunkPos) -> Option<[usize; 2]> {
+        let offset = pos - center;
+        if offset.x.abs() > MAP_RADIUS || offset.z.abs() > MAP_RADIUS {
+            return None;
+        }
+        let x = (offset.x + MAP_RADIUS) as usize * CHUNK_WIDTH;
+        let y = (MAP_RADIUS - offset.z) as usize * CHUNK_DEPTH;
+        Some([x, y])
+    }
+
+    /// Colors of the highest blocks of the chunk's columns
+    fn chunk_image(chunks: &ChunkManager, pos: ChunkPos) -> ColorImage {
+        let origin = pos.to_world();
+        let mut image = ColorImage::new([CHUNK_WIDTH, CHUNK_DEPTH], EMPTY_COLOR);
+        for z in 0..CHUNK_DEPTH {
+            for x in 0..CHUNK_WIDTH {
+                let block = chunks.highest_block(origin.x + x as i32, origin.z + z as i32);
+                if let Some((_, id)) = block {
+                    let [r, g, b] = id.map_color();
+                    // Rows go from north to south
+                    image[(x, CHUNK_DEPTH - 1 - z)] = Color32::from_rgb(r, g, b);
+                }
+            }
+        }
+        image
+    }
+
+    pub fn draw(&mut self, platform: &mut Platform) {
+        let (texture, center) = match (&self.texture, self.center) {
+            (Some(texture), Some(center)) => (texture.id(), center),
+            _ => return,
+        };
+        // The part of the image around the player, which is always in the center chunk
+        let span = (IMAGE_SIZE - CHUNK_WIDTH) as f32 / ZOOM_LEVELS[self.zoom] as f32;
+        let origin = center.to_world();
+        let player = egui::pos2(
+            (MAP_RADIUS as usize * CHUNK_WIDTH) as f32 + self.player.x + 0.5 - origin.x as f32,
+            ((MAP_RADIUS + 1) as usize * CHUNK_DEPTH) as f32
+                - (self.player.z + 0.5 - origin.z as f32),
+        );
+        let uv = egui::Rect::from_center_size(player, egui::vec2(span, span));
+        let uv = egui::Rect::from_min_max(
+            (uv.min.to_vec2() / IMAGE_SIZE as f32).to_pos2(),
+            (uv.max.to_vec2() / IMAGE_SIZE as f32).to_pos2(),
+        );
+
+        egui::Area::new("Minimap")
+            .anchor(egui::Align2::RIGHT_TOP, [-MAP_MARGIN, MAP_MARGIN])
+            .show(&platform.context(), |ui| {
+                let image = egui::Image::new(texture, [MAP_SIZE, MAP_SIZE])
+                    .uv(uv)
+                    .sense(egui::Sense::click());
+                let response = ui.add(image);
+                if response.clicked() {
+                    self.zoom = (self.zoom + 1) % ZOOM_LEVELS.len();
+                }
+                let rect = response.rect;
+                let painter = ui.painter();
+                painter.rect_stroke(rect, 0.0, egui::Stroke::new(2.0, Color32::BLACK));
+                // An arrow from the player towards where it looks
+                let center = rect.center();
+                let heading = self.heading.to_radians();
+                let direction = egui::vec2(heading.sin(), -heading.cos());
+                painter.circle_filled(center, 3.0, PLAYER_COLOR);
+                painter.arrow(
+                    center,
+                    direction * 12.0,
+                    egui::Stroke::new(2.0, PLAYER_COLOR),
+                );
+                painter.text(
+                    rect.left_bottom() + egui::vec2(4.0, -4.0),
+                    egui::Align2::LEFT_BOTTOM,
+                    format!("{}×", ZOOM_LEVELS[self.zoom]),
+                    egui::FontId::monospace(12.0),
+                    Color32::WHITE,
+                );
+            });
+    }
+}
+
+/// Copies `source` into `target` with its top left corner at `pos`
+fn blit(target: &mut ColorImage, source: &ColorImage, [x, y]: [usize; 2]) {
+    let [width, height] = source.size;
+    for row in 0..height {
+        let start = (y + row) * target.size[0] + x;
+        target.pixels[start..start + width]
+            .copy_from_slice(&source.pixels[row * width..(row + 1) * width]);
+    }
+}
@@ -1,44 +1,183 @@
+pub mod chunk_stats;
+pub mod console;
+pub mod frame_graph;
+pub mod hud;
+pub mod inventory;
+pub mod log_window;
+pub mod minimap;
+pub mod overlay;
+
 use egui_winit_platform::{Platform, PlatformDescriptor};
 use vek::Vec3;
 
+use crate::{
+    block::BlockId,
+    direction::Direction,
+    input::{Action, Bindings, Slot},
+    renderer::{
+        adapter::{Backend, PowerPreference},
+        bloom::BloomSettings,
+        markers,
+        outline::OutlineSettings,
+        post_process::{ColorGrading, MIN_RENDER_SCALE},
+        texture::{Filter, Mipmaps, SamplerSettings},
+        upload::UploadBenchmark,
+        world::RENDER_DISTANCE,
+    },
+    scene::{
+        hotbar::Hotbar,
+        inventory::{GameMode, Inventory},
+    },
+    settings::Settings,
+};
+
+use self::{
+    console::ConsoleWindow, frame_graph::FrameGraph, hud::Hud, inventory::InventoryWindow,
+    log_window::LogWindow, minimap::Minimap, overlay::DebugOverlay,
+};
+
 pub struct EguiInstance {
     pub platform: Platform,
-    pub(super) state: UIState,
+    pub state: UIState,
+    /// The OS scale factor, which the platform maps pointer positions with
+    os_scale_factor: f32,
 }
 
 pub struct UIState {
-    camera_pos: Vec3<f32>,
+    /// Whether the crosshair and hotbar are drawn
+    pub hud_visible: bool,
+    /// Whether the eye is inside an opaque block, which hides the world
+    pub camera_in_block: bool,
+    /// Chunks around the player still waiting to be generated
+    pub pending_chunks: usize,
+    /// Saves waiting to be written by the saving thread
+    pub queued_saves: usize,
+    /// Chunks loaded of the ones needed to start playing, while the
+    /// loading screen is shown
+    pub loading: Option<(usize, usize)>,
+    /// The screen of the pause menu being shown, if the game is paused
+    pub pause_menu: Option<PauseScreen>,
+    /// Button clicked in the pause menu, handled by the engine after the frame
+    pub menu_action: Option<MenuAction>,
+    /// Monitors fullscreen can be shown on, listed when the game is paused
+    pub monitors: Vec<String>,
+    pub overlay: DebugOverlay,
+    pub frame_graph: FrameGraph,
+    pub log_window: LogWindow,
+    pub console: ConsoleWindow,
+    pub minimap: Minimap,
+    pub inventory: InventoryWindow,
+    /// Feed of short lived messages
+    pub hud: Hud,
+    /// Result of the last upload benchmark
+    pub upload_benchmark: Option<UploadBenchmark>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseScreen {
+    Main,
+    Settings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    Resume,
+    Quit,
+    ExportObj,
+    ExportGlb,
+    BenchmarkUploads,
 }
 
 impl Default for UIState {
     fn default() -> Self {
         Self {
-            camera_pos: Vec3::zero(),
+            hud_visible: true,
+            camera_in_block: false,
+            pending_chunks: 0,
+            queued_saves: 0,
+            loading: None,
+            pause_menu: None,
+            menu_action: None,
+            monitors: Vec::new(),
+            overlay: DebugOverlay::default(),
+            frame_graph: FrameGraph::new(),
+            log_window: LogWindow::default(),
+            console: ConsoleWindow::new(),
+            minimap: Minimap::default(),
+            inventory: InventoryWindow::default(),
+            hud: Hud::new(),
+            upload_benchmark: None,
         }
     }
 }
 impl EguiInstance {
-    pub fn new(window: &winit::window::Window) -> Self {
+    pub fn new(physical_width: u32, physical_height: u32, scale_factor: f64) -> Self {
         let platform = Platform::new(PlatformDescriptor {
-            physical_width: window.inner_size().width,
-            physical_height: window.inner_size().height,
-            scale_factor: window.scale_factor(),
+            physical_width,
+            physical_height,
+            scale_factor,
             font_definitions: egui::FontDefinitions::default(),
             style: Default::default(),
         });
         Self {
             platform,
             state: Default::default(),
+            os_scale_factor: scale_factor as f32,
+        }
+    }
+
+    /// Sizes the next frame for `pixels_per_point`, the OS scale factor
+    /// times the UI scale. Must be called before `begin_frame` so that text
+    /// is rasterized at its final size instead of being stretched.
+    pub fn scale_input(&mut self, (width, height): (u32, u32), pixels_per_point: f32) {
+        let input = self.platform.raw_input_mut();
+        // The platform resets the scale whenever the OS one changes
+        if let Some(os_scale_factor) = input.pixels_per_point {
+            self.os_scale_factor = os_scale_factor;
+        }
+        input.pixels_per_point = Some(pixels_per_point);
+        input.screen_rect = Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(width as f32, height as f32) / pixels_per_point,
+        ));
+        // Pointer events are in points of the OS scale factor
+        let ui_scale = pixels_per_point / self.os_scale_factor;
+        for event in &mut input.events {
+            match event {
+                egui::Event::PointerMoved(pos)
+                | egui::Event::PointerButton { pos, .. }
+                | egui::Event::Touch { pos, .. } => *pos = (pos.to_vec2() / ui_scale).to_pos2(),
+                egui::Event::Scroll(delta) => *delta = *delta / ui_scale,
+                _ => (),
+            }
         }
     }
 }
 
-pub fn draw_debugging_settings(platform: &mut Platform, dt: f32, wireframe: &mut bool) {
+pub fn draw_debugging_settings(
+    platform: &mut Platform,
+    dt: f32,
+    wireframe: &mut bool,
+    chunk_markers: &mut bool,
+    action: &mut Option<MenuAction>,
+) {
     egui::Window::new("Debug Settings")
         .default_size([200.0, 200.0])
         .show(&platform.context(), |ui| {
             ui.label("[FPS]: ".to_owned() + &(1.0 / dt).to_string());
             ui.checkbox(wireframe, "Toggle Wireframe mode");
+            // Release builds record no markers at all
+            if markers::ENABLED {
+                ui.checkbox(chunk_markers, "Mark each chunk in frame captures");
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Export OBJ").clicked() {
+                    *action = Some(MenuAction::ExportObj);
+                }
+                if ui.button("Export glTF").clicked() {
+                    *action = Some(MenuAction::ExportGlb);
+                }
+            });
         });
 }
 
@@ -46,6 +185,7 @@ pub fn draw_camera_settings(
     platform: &mut Platform,
     // controller: &mut CameraController,
     pos: Vec3<f32>,
+    settings: &mut Settings,
 ) {
     egui::Window::new("Camera Settings")
         .default_size([200.0, 200.0])
@@ -56,11 +196,602 @@ pub fn draw_camera_settings(
             // ui.add(DragValue::new(&mut controller.speed));
             // ui.separator();
 
-            // ui.label("Sensitivity");
-            // ui.add(DragValue::new(&mut controller.sensitivity));
-            // ui.separator();
-
             ui.label("Position [X Y Z]");
             ui.label(format!("[{:.2} {:.2} {:.2}]", pos.x, pos.y, pos.z,));
+            ui.separator();
+            draw_mouse_settings(ui, settings);
+            ui.separator();
+            let fov =
+                ui.add(egui::Slider::new(&mut settings.fov, 30.0..=110.0).text("Field of view"));
+            let distance = ui.add(
+                egui::Slider::new(&mut settings.third_person_distance, 1.0..=10.0)
+                    .text("Third person distance"),
+            );
+            // Avoid writing the file on every frame of a drag
+            if [fov, distance]
+                .iter()
+                .any(|slider| slider.drag_released() || (slider.changed() && !slider.dragged()))
+            {
+                settings.save();
+            }
+        });
+}
+
+fn draw_mouse_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    let sensitivity =
+        ui.add(egui::Slider::new(&mut settings.sensitivity, 0.1..=5.0).text("Sensitivity"));
+    let invert_y = ui.checkbox(&mut settings.invert_y, "Invert Y");
+    // Avoid writing the file on every frame of a drag
+    if sensitivity.drag_released()
+        || (sensitivity.changed() && !sensitivity.dragged())
+        || invert_y.changed()
+    {
+        settings.save();
+    }
+}
+
+fn draw_gameplay_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    let mut changed = ui
+        .checkbox(&mut settings.instant_break, "Instant block breaking")
+        .changed();
+    changed |= ui
+        .checkbox(&mut settings.message_feed, "Message feed")
+        .changed();
+    let random_ticks = ui.add(
+        egui::Slider::new(&mut settings.random_tick_speed, 0..=16)
+            .text("Random ticks")
+            .custom_formatter(|speed, _| match speed as u32 {
+                0 => "Off".to_owned(),
+                speed => format!("{} per chunk", speed),
+            }),
+    );
+    let pickup = ui.add(
+        egui::Slider::new(&mut settings.pickup_radius, 0.5..=4.0)
+            .text("Pickup radius")
+            .suffix(" blocks"),
+    );
+    let lifetime = ui.add(
+        egui::Slider::new(&mut settings.drop_lifetime, 10.0..=900.0)
+            .text("Drop despawn time")
+            .suffix(" s"),
+    );
+    // Avoid writing the file on every frame of a drag
+    for slider in [random_ticks, pickup, lifetime] {
+        changed |= slider.drag_released() || (slider.changed() && !slider.dragged());
+    }
+    egui::ComboBox::from_label("Game mode")
+        .selected_text(format!("{:?}", settings.game_mode))
+        .show_ui(ui, |ui| {
+            for mode in [GameMode::Creative, GameMode::Survival] {
+                changed |= ui
+                    .selectable_value(&mut settings.game_mode, mode, format!("{:?}", mode))
+                    .changed();
+            }
+        });
+    if changed {
+        settings.save();
+    }
+}
+
+fn draw_graphics_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    let lighting = ui.checkbox(&mut settings.smooth_lighting, "Smooth lighting");
+    let occlusion = ui.checkbox(&mut settings.occlusion_culling, "Occlusion culling");
+    let lod = ui.add(
+        egui::Slider::new(&mut settings.lod_distance, 0..=RENDER_DISTANCE as u32)
+            .text("LOD distance")
+            .custom_formatter(|distance, _| match distance as u32 {
+                0 => "Off".to_owned(),
+                distance => format!("{} chunks", distance),
+            }),
+    );
+    let tint = ui
+        .horizontal(|ui| {
+            let tint = ui.color_edit_button_rgb(&mut settings.underwater_tint);
+            ui.label("Underwater tint");
+            tint
+        })
+        .inner;
+    let fog = ui.add(
+        egui::Slider::new(&mut settings.underwater_fog_density, 0.0..=0.5)
+            .text("Underwater fog density"),
+    );
+    ui.separator();
+    ui.label("Texture filtering");
+    let filtering = draw_sampler_settings(ui, "Texture filtering", &mut settings.texture_filtering);
+    let compare = ui
+        .collapsing("Comparison filtering", |ui| {
+            ui.label("Used instead while the CompareFiltering key is held");
+            draw_sampler_settings(ui, "Comparison filtering", &mut settings.compare_filtering)
+        })
+        .body_returned
+        .unwrap_or(false);
+    ui.separator();
+    let render_scale = ui.add(
+        egui::Slider::new(&mut settings.render_scale, MIN_RENDER_SCALE..=1.0)
+            .text("Render scale")
+            .custom_formatter(|scale, _| format!("{:.0}%", scale * 100.0)),
+    );
+    let upscale_filter = settings.upscale_filter;
+    ui.add_enabled_ui(settings.render_scale < 1.0, |ui| {
+        draw_filter_choice(ui, "Upscaling", &mut settings.upscale_filter);
+    });
+    let fxaa = ui
+        .checkbox(&mut settings.fxaa, "FXAA")
+        .on_hover_text("Smooths jagged edges, at the cost of some sharpness");
+    ui.separator();
+    let grading = draw_color_grading(ui, &mut settings.color_grading);
+    let bloom = draw_bloom_settings(ui, &mut settings.bloom);
+    let outline = draw_outline_settings(ui, &mut settings.outline);
+    // Avoid writing the file on every frame of a drag
+    if lighting.changed()
+        || occlusion.changed()
+        || fxaa.changed()
+        || tint.changed()
+        || filtering
+        || compare
+        || grading
+        || bloom
+        || outline
+        || settings.upscale_filter != upscale_filter
+        || [lod, fog, render_scale]
+            .iter()
+            .any(|slider| slider.drag_released() || (slider.changed() && !slider.dragged()))
+    {
+        settings.save();
+    }
+}
+
+/// Picks the filters of a sampler, returns whether any changed
+fn draw_sampler_settings(ui: &mut egui::Ui, id: &str, sampler: &mut SamplerSettings) -> bool {
+    let before = *sampler;
+    ui.push_id(id, |ui| {
+        draw_filter_choice(ui, "Up close", &mut sampler.mag_filter);
+        draw_filter_choice(ui, "Far away", &mut sampler.min_filter);
+        egui::ComboBox::from_label("Mipmaps")
+            .selected_text(sampler.mipmaps.to_string())
+            .show_ui(ui, |ui| {
+                for option in Mipmaps::ALL {
+                    ui.selectable_value(&mut sampler.mipmaps, option, option.to_string());
+                }
+            });
+        // Anisotropic filtering needs every filter to be linear
+        let linear = sampler.mag_filter == Filter::Linear
+            && sampler.min_filter == Filter::Linear
+            && sampler.mipmaps == Mipmaps::Linear;
+        let anisotropy_name = |anisotropy: u16| match anisotropy {
+            1 => "Off".to_owned(),
+            anisotropy => format!("{}x", anisotropy),
+        };
+        ui.add_enabled_ui(linear, |ui| {
+            egui::ComboBox::from_label("Anisotropic filtering")
+                .selected_text(anisotropy_name(sampler.anisotropy))
+                .show_ui(ui, |ui| {
+                    for option in [1, 2, 4, 8, 16] {
+                        ui.selectable_value(
+                            &mut sampler.anisotropy,
+                            option,
+                            anisotropy_name(option),
+                        );
+                    }
+                });
+        });
+    });
+    *sampler != before
+}
+
+fn draw_filter_choice(ui: &mut egui::Ui, label: &str, filter: &mut Filter) {
+    egui::ComboBox::from_label(label)
+        .selected_text(filter.to_string())
+        .show_ui(ui, |ui| {
+            for option in Filter::ALL {
+                ui.selectable_value(filter, option, option.to_string());
+            }
+        });
+}
+
+/// Sliders of the color grading, returns whether the settings should be
+/// saved
+fn draw_color_grading(ui: &mut egui::Ui, grading: &mut ColorGrading) -> bool {
+    ui.label("Color grading");
+    let sliders = [
+        ui.add(
+            egui::Slider::new(&mut grading.exposure, -2.0..=2.0)
+                .text("Exposure")
+                .suffix(" stops"),
+        ),
+        ui.add(egui::Slider::new(&mut grading.gamma, 0.5..=2.0).text("Gamma")),
+        ui.add(egui::Slider::new(&mut grading.saturation, 0.0..=2.0).text("Saturation")),
+        ui.add(egui::Slider::new(&mut grading.contrast, 0.5..=2.0).text("Contrast")),
+    ];
+    let reset = ui
+        .add_enabled(
+            *grading != ColorGrading::NEUTRAL,
+            egui::Button::new("Reset colors"),
+        )
+        .clicked();
+    if reset {
+        *grading = ColorGrading::NEUTRAL;
+    }
+    // Avoid writing the file on every frame of a drag
+    reset
+        || sliders
+            .iter()
+            .any(|slider| slider.drag_released() || (slider.changed() && !slider.dragged()))
+}
+
+/// Returns whether the settings should be saved
+fn draw_bloom_settings(ui: &mut egui::Ui, bloom: &mut BloomSettings) -> bool {
+    let enabled = ui.checkbox(&mut bloom.enabled, "Bloom");
+    let sliders = ui
+        .add_enabled_ui(bloom.enabled, |ui| {
+            [
+                ui.add(egui::Slider::new(&mut bloom.threshold, 0.5..=3.0).text("Bloom threshold")),
+                ui.add(egui::Slider::new(&mut bloom.intensity, 0.0..=1.0).text("Bloom intensity")),
+            ]
+        })
+        .inner;
+    // Avoid writing the file on every frame of a drag
+    enabled.changed()
+        || sliders
+            .iter()
+            .any(|slider| slider.drag_released() || (slider.changed() && !slider.dragged()))
+}
+
+/// Returns whether the settings should be saved
+fn draw_outline_settings(ui: &mut egui::Ui, outline: &mut OutlineSettings) -> bool {
+    let enabled = ui.checkbox(&mut outline.enabled, "Outlines");
+    let sliders = ui
+        .add_enabled_ui(outline.enabled, |ui| {
+            [
+                ui.add(
+                    egui::Slider::new(&mut outline.threshold, 0.05..=0.95)
+                        .text("Outline threshold"),
+                ),
+                ui.add(
+                    egui::Slider::new(&mut outline.thickness, 1..=4)
+                        .text("Outline thickness")
+                        .suffix(" px"),
+                ),
+            ]
+        })
+        .inner;
+    // Avoid writing the file on every frame of a drag
+    enabled.changed()
+        || sliders
+            .iter()
+            .any(|slider| slider.drag_released() || (slider.changed() && !slider.dragged()))
+}
+
+fn draw_display_settings(ui: &mut egui::Ui, settings: &mut Settings, monitors: &[String]) {
+    let fullscreen = ui.checkbox(&mut settings.fullscreen, "Fullscreen");
+    let mut monitor = settings.monitor;
+    // Only worth choosing with more than one monitor
+    if monitors.len() > 1 {
+        let selected = monitors
+            .get(monitor)
+            .map_or("Unplugged monitor", String::as_str);
+        egui::ComboBox::from_label("Monitor")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for (index, name) in monitors.iter().enumerate() {
+                    ui.selectable_value(&mut monitor, index, name);
+                }
+            });
+    }
+    let ui_scale = ui.add(egui::Slider::new(&mut settings.ui_scale, 0.5..=2.0).text("UI scale"));
+    let fps_cap = ui.add(
+        egui::Slider::new(&mut settings.fps_cap, 0..=240)
+            .text("Frame rate cap")
+            .custom_formatter(|fps, _| match fps as u32 {
+                0 => "None".to_owned(),
+                fps => fps.to_string(),
+            }),
+    );
+    // Avoid writing the file on every frame of a drag, which would
+    // also resize the slider under the pointer
+    if fullscreen.changed()
+        || monitor != settings.monitor
+        || [ui_scale, fps_cap]
+            .iter()
+            .any(|slider| slider.drag_released() || (slider.changed() && !slider.dragged()))
+    {
+        settings.monitor = monitor;
+        settings.save();
+    }
+}
+
+fn draw_adapter_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    let backend = settings.backend;
+    let power = settings.power_preference;
+    egui::ComboBox::from_label("Graphics backend")
+        .selected_text(backend.to_string())
+        .show_ui(ui, |ui| {
+            for option in Backend::ALL {
+                ui.selectable_value(&mut settings.backend, option, option.to_string());
+            }
+        });
+    egui::ComboBox::from_label("GPU power")
+        .selected_text(power.to_string())
+        .show_ui(ui, |ui| {
+            for option in PowerPreference::ALL {
+                ui.selectable_value(&mut settings.power_preference, option, option.to_string());
+            }
+        });
+    ui.label("The graphics backend and GPU change after a restart");
+    if settings.backend != backend || settings.power_preference != power {
+        settings.save();
+    }
+}
+
+fn draw_audio_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    let master =
+        ui.add(egui::Slider::new(&mut settings.master_volume, 0.0..=1.0).text("Master volume"));
+    let effects =
+        ui.add(egui::Slider::new(&mut settings.effects_volume, 0.0..=1.0).text("Effects volume"));
+    // Avoid writing the file on every frame of a drag
+    if [master, effects]
+        .iter()
+        .any(|slider| slider.drag_released() || (slider.changed() && !slider.dragged()))
+    {
+        settings.save();
+    }
+}
+
+/// Covers the world while the camera is inside a block, so that the
+/// faces around it aren't seen from behind
+pub fn draw_block_overlay(platform: &mut Platform) {
+    let context = platform.context();
+    let painter = context.layer_painter(egui::LayerId::background());
+    painter.rect_filled(context.screen_rect(), 0.0, egui::Color32::from_rgb(8, 8, 8));
+}
+
+pub fn draw_pause_menu(platform: &mut Platform, state: &mut UIState, settings: &mut Settings) {
+    let screen = match state.pause_menu {
+        Some(screen) => screen,
+        None => return,
+    };
+    let frame = egui::Frame::none().fill(egui::Color32::from_black_alpha(160));
+    egui::CentralPanel::default()
+        .frame(frame)
+        .show(&platform.context(), |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 3.0);
+                match screen {
+                    PauseScreen::Main => {
+                        ui.heading("Paused");
+                        ui.add_space(10.0);
+                        if ui.button("Resume").clicked() {
+                            state.menu_action = Some(MenuAction::Resume);
+                        }
+                        if ui.button("Settings").clicked() {
+                            state.pause_menu = Some(PauseScreen::Settings);
+                        }
+                        if ui.button("Quit").clicked() {
+                            state.menu_action = Some(MenuAction::Quit);
+                        }
+                    }
+                    PauseScreen::Settings => {
+                        ui.heading("Settings");
+                        ui.add_space(10.0);
+                        draw_mouse_settings(ui, settings);
+                        ui.separator();
+                        draw_gameplay_settings(ui, settings);
+                        ui.separator();
+                        draw_graphics_settings(ui, settings);
+                        ui.separator();
+                        draw_display_settings(ui, settings, &state.monitors);
+                        ui.separator();
+                        draw_adapter_settings(ui, settings);
+                        ui.separator();
+                        draw_audio_settings(ui, settings);
+                        if ui.button("Back").clicked() {
+                            state.pause_menu = Some(PauseScreen::Main);
+                        }
+                    }
+                }
+            });
+        });
+}
+
+/// Paints the front texture of the block into the rect, dimmed with `tint`
+pub fn paint_block(
+    painter: &egui::Painter,
+    atlas: egui::TextureId,
+    rect: egui::Rect,
+    block: BlockId,
+    tint: egui::Color32,
+) {
+    let min = block.map_texture([0, 0], Direction::Front);
+    let max = block.map_texture([1, 1], Direction::Front);
+    let uv = egui::Rect::from_min_max(egui::pos2(min[0], min[1]), egui::pos2(max[0], max[1]));
+    painter.image(atlas, rect, uv, tint);
+}
+
+/// Writes the size of a stack in the bottom right corner of its slot
+pub fn paint_count(painter: &egui::Painter, rect: egui::Rect, count: u32) {
+    painter.text(
+        rect.right_bottom() - egui::vec2(3.0, 1.0),
+        egui::Align2::RIGHT_BOTTOM,
+        count.to_string(),
+        egui::FontId::proportional(12.0),
+        egui::Color32::WHITE,
+    );
+}
+
+/// Draws the hotbar with the number of blocks left in each slot, if they are
+/// limited. The selected slot flashes red when its block ran out.
+pub fn draw_hotbar(
+    platform: &mut Platform,
+    hotbar: &Hotbar,
+    inventory: &Inventory,
+    atlas: egui::TextureId,
+) {
+    const SLOT_SIZE: f32 = 40.0;
+    egui::Area::new("Hotbar")
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -10.0])
+        .interactable(false)
+        .show(&platform.context(), |ui| {
+            ui.horizontal(|ui| {
+                for (slot, block) in hotbar.slots.iter().enumerate() {
+                    let (rect, _) = ui.allocate_exact_size(
+                        egui::vec2(SLOT_SIZE, SLOT_SIZE),
+                        egui::Sense::hover(),
+                    );
+                    let painter = ui.painter();
+                    painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(150));
+                    let selected = slot == hotbar.selected();
+                    let flash = inventory.denied_flash();
+                    if selected && flash > 0.0 {
+                        let alpha = (flash * 120.0) as u8;
+                        painter.rect_filled(
+                            rect,
+                            2.0,
+                            egui::Color32::from_rgba_unmultiplied(255, 40, 40, alpha),
+                        );
+                    }
+                    if !block.is_air() {
+                        let count = inventory.count(*block);
+                        // Blocks that ran out stay in their slot until collected again
+                        let tint = match count {
+                            Some(0) => egui::Color32::from_white_alpha(60),
+                            _ => egui::Color32::WHITE,
+                        };
+                        paint_block(painter, atlas, rect.shrink(4.0), *block, tint);
+                        if let Some(count) = count {
+                            paint_count(painter, rect, count);
+                        }
+                    }
+                    let stroke = if selected {
+                        egui::Stroke::new(3.0, egui::Color32::WHITE)
+                    } else {
+                        egui::Stroke::new(1.0, egui::Color32::GRAY)
+                    };
+                    painter.rect_stroke(rect, 2.0, stroke);
+                }
+            });
+        });
+}
+
+pub fn draw_crosshair(platform: &mut Platform) {
+    const HALF_SIZE: f32 = 8.0;
+    let context = platform.context();
+    let painter = context.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("Crosshair"),
+    ));
+    // Lines an even number of pixels wide around a pixel corner stay
+    // sharp at fractional scale factors
+    let center = painter.round_pos_to_pixels(context.screen_rect().center());
+    let width = |points: f32| {
+        let pixels_per_point = context.pixels_per_point();
+        let half_pixels = (points * pixels_per_point / 2.0).round().max(1.0);
+        2.0 * half_pixels / pixels_per_point
+    };
+    let horizontal = [
+        center - egui::vec2(HALF_SIZE, 0.0),
+        center + egui::vec2(HALF_SIZE, 0.0),
+    ];
+    let vertical = [
+        center - egui::vec2(0.0, HALF_SIZE),
+        center + egui::vec2(0.0, HALF_SIZE),
+    ];
+    // A dark outline behind the white lines keeps the crosshair
+    // visible against both the sky and dark terrain
+    let outline = egui::Stroke::new(width(4.0), egui::Color32::from_black_alpha(160));
+    let line = egui::Stroke::new(width(2.0), egui::Color32::WHITE);
+    for stroke in [outline, line] {
+        painter.line_segment(horizontal, stroke);
+        painter.line_segment(vertical, stroke);
+    }
+}
+
+/// Tells that the terrain around the player is still being generated
+pub fn draw_loading(platform: &mut Platform, pending_chunks: usize) {
+    egui::Area::new("Loading")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 40.0])
+        .interactable(false)
+        .show(&platform.context(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(format!(
+                    "Loading terrain... ({} chunks left)",
+                    pending_chunks
+                ));
+            });
+        });
+}
+
+/// Tells that saves are still being written in the background
+pub fn draw_saving(platform: &mut Platform, queued_saves: usize) {
+    egui::Area::new("Saving")
+        .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -10.0])
+        .interactable(false)
+        .show(&platform.context(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(format!("Saving... ({} left)", queued_saves));
+            });
+        });
+}
+
+/// Covers the window while the chunks around the spawn are generated
+pub fn draw_loading_screen(platform: &mut Platform, (loaded, requested): (usize, usize)) {
+    let frame = egui::Frame::none().fill(egui::Color32::from_rgb(20, 24, 30));
+    egui::CentralPanel::default()
+        .frame(frame)
+        .show(&platform.context(), |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 2.0 - 30.0);
+                ui.heading("Generating world");
+                ui.add_space(10.0);
+                let progress = loaded as f32 / requested.max(1) as f32;
+                ui.add(
+                    egui::ProgressBar::new(progress)
+                        .desired_width(300.0)
+                        .text(format!("{} / {} chunks", loaded, requested)),
+                );
+            });
+        });
+}
+
+/// Lists the key bindings. Clicking a binding waits for the next
+/// key or mouse button press to replace it.
+pub fn draw_controls(platform: &mut Platform, bindings: &mut Bindings) {
+    egui::Window::new("Controls")
+        .default_open(false)
+        .show(&platform.context(), |ui| {
+            let conflicts = bindings.conflicts();
+            egui::Grid::new("Bindings")
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    for action in Action::ALL {
+                        let color = if conflicts.contains(&action) {
+                            egui::Color32::RED
+                        } else {
+                            ui.visuals().text_color()
+                        };
+                        ui.colored_label(color, format!("{:?}", action));
+                        for slot in [Slot::Primary, Slot::Secondary] {
+                            let text = if bindings.rebinding == Some((action, slot)) {
+                                "Press a key...".to_owned()
+                            } else {
+                                bindings
+                                    .get(action, slot)
+                                    .map_or("None".to_owned(), |input| input.to_string())
+                            };
+                            if ui.button(text).clicked() {
+                                bindings.rebinding = Some((action, slot));
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+            if !conflicts.is_empty() {
+                ui.colored_label(egui::Color32::RED, "Some inputs are bound more than once");
+            }
+            ui.separator();
+            if ui.button("Reset to defaults").clicked() {
+                *bindings = Bindings::default();
+                bindings.save();
+            }
         });
 }
@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+use egui_winit_platform::Platform;
+
+/// The F3 debug overlay. Systems push lines into named sections
+/// every frame while it is visible; they are cleared once drawn.
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub visible: bool,
+    sections: Vec<(&'static str, Vec<String>)>,
+}
+
+impl DebugOverlay {
+    /// Adds a line to the section, creating it if needed.
+    /// Sections are drawn in the order they were first pushed.
+    pub fn push(&mut self, section: &'static str, line: impl Into<String>) {
+        let line = line.into();
+        match self.sections.iter_mut().find(|(name, _)| *name == section) {
+            Some((_, lines)) => lines.push(line),
+            None => self.sections.push((section, vec![line])),
+        }
+    }
+
+    pub fn draw(&mut self, platform: &mut Platform) {
+        if !self.visible {
+            self.sections.clear();
+            return;
+        }
+        egui::Area::new("Debug Overlay")
+            .anchor(egui::Align2::LEFT_TOP, [10.0, 10.0])
+            .interactable(false)
+            .show(&platform.context(), |ui| {
+                egui::Frame::none()
+                    .fill(egui::Color32::from_black_alpha(160))
+                    .inner_margin(6.0)
+                    .show(ui, |ui| {
+                        for (name, lines) in &self.sections {
+                            ui.strong(*name);
+                            for line in lines {
+                                ui.monospace(line);
+                            }
+                            ui.add_space(4.0);
+                        }
+                    });
+            });
+        self.sections.clear();
+    }
+}
+
+/// Frame time statistics over the last full second
+pub struct FrameStats {
+    window_start: Instant,
+    frames: u32,
+    total: f32,
+    min: f32,
+    max: f32,
+    /// Average frame time of the last second, in seconds
+    pub avg_frame_time: f32,
+    pub min_frame_time: f32,
+    pub max_frame_time: f32,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self {
+            window_start: Instant::now(),
+            frames: 0,
+            total: 0.0,
+            min: f32::INFINITY,
+            max: 0.0,
+            avg_frame_time: 0.0,
+            min_frame_time: 0.0,
+            max_frame_time: 0.0,
+        }
+    }
+
+    pub fn record(&mut self, dt: f32) {
+        self.frames += 1;
+        self.total += dt;
+        self.min = self.min.min(dt);
+        self.max = self.max.max(dt);
+        if self.window_start.elapsed() >= Duration::from_secs(1) {
+            self.avg_frame_time = self.total / self.frames as f32;
+            self.min_frame_time = self.min;
+            self.max_frame_time = self.max;
+            self.window_start = Instant::now();
+            self.frames = 0;
+            self.total = 0.0;
+            self.min = f32::INFINITY;
+            self.max = 0.0;
+        }
+    }
+
+    pub fn fps(&self) -> f32 {
+        if self.avg_frame_time > 0.0 {
+            1.0 / self.avg_frame_time
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,8 +1,23 @@
-use crate::renderer::Renderer;
+use raw_window_handle::{
+    HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle,
+};
+
+use winit::{
+    dpi::{LogicalSize, Position, Size},
+    monitor::MonitorHandle,
+    window::Fullscreen,
+};
+
+use crate::settings::Settings;
 
 pub struct WindowSettings {
     pub title: String,
+    /// Size of the window when it isn't fullscreen, in logical pixels
     pub size: (u32, u32),
+    /// Starts in borderless fullscreen instead of a window
+    pub fullscreen: bool,
+    /// Index of the monitor fullscreen is shown on
+    pub monitor: usize,
 }
 
 impl Default for WindowSettings {
@@ -10,43 +25,191 @@ impl Default for WindowSettings {
         Self {
             title: "VoxelEngine".to_string(),
             size: (1024, 768),
+            fullscreen: false,
+            monitor: 0,
         }
     }
 }
 
 pub struct Window {
     winit_impl: winit::window::Window,
+    /// Whether the cursor should be grabbed while the window is focused
+    wants_grab: bool,
+    /// Whether the cursor is actually grabbed right now
+    grabbed: bool,
+    focused: bool,
+    /// Monitor the window covers, if it is fullscreen
+    fullscreen_monitor: Option<usize>,
+    /// Size and position to restore when leaving fullscreen
+    windowed: (Size, Option<Position>),
+    /// Whether the window switched in or out of fullscreen and
+    /// hasn't been resized since
+    transitioning: bool,
 }
 
 impl Window {
-    pub fn new(settings: WindowSettings) -> (Self, Renderer, winit::event_loop::EventLoop<()>) {
-        let event_loop = winit::event_loop::EventLoop::new();
+    pub fn new<T>(
+        settings: WindowSettings,
+        event_loop: &winit::event_loop::EventLoop<T>,
+    ) -> Result<Self, winit::error::OsError> {
+        let size = LogicalSize::new(settings.size.0, settings.size.1);
+        let fullscreen_monitor = settings.fullscreen.then_some(settings.monitor);
+        let fullscreen = fullscreen_monitor.map(|index| {
+            let monitor = monitor(
+                event_loop.available_monitors(),
+                event_loop.primary_monitor(),
+                index,
+            );
+            Fullscreen::Borderless(monitor)
+        });
         let winit_impl = winit::window::WindowBuilder::new()
             .with_title(settings.title)
-            .with_inner_size(winit::dpi::LogicalSize::new(
-                settings.size.0,
-                settings.size.1,
-            ))
-            .build(&event_loop)
-            .unwrap();
+            .with_inner_size(size)
+            .with_fullscreen(fullscreen)
+            .build(event_loop)?;
+
+        Ok(Self {
+            winit_impl,
+            wants_grab: false,
+            grabbed: false,
+            focused: true,
+            fullscreen_monitor,
+            windowed: (size.into(), None),
+            transitioning: false,
+        })
+    }
+
+    /// Switches in or out of fullscreen, or to another monitor,
+    /// to match the settings
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        let target = settings.fullscreen.then_some(settings.monitor);
+        if target != self.fullscreen_monitor {
+            self.set_fullscreen(target);
+        }
+    }
+
+    /// Makes the window cover the monitor at the given index,
+    /// or go back to its windowed size and position with `None`.
+    /// The window is resized through the usual `Resized` event.
+    fn set_fullscreen(&mut self, monitor_index: Option<usize>) {
+        match monitor_index {
+            Some(index) => {
+                if self.fullscreen_monitor.is_none() {
+                    self.windowed = (
+                        self.winit_impl.inner_size().into(),
+                        self.winit_impl.outer_position().ok().map(Position::from),
+                    );
+                }
+                let monitor = monitor(
+                    self.winit_impl.available_monitors(),
+                    self.winit_impl.primary_monitor(),
+                    index,
+                );
+                self.winit_impl
+                    .set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+            }
+            None => {
+                self.winit_impl.set_fullscreen(None);
+                let (size, position) = self.windowed;
+                self.winit_impl.set_inner_size(size);
+                if let Some(position) = position {
+                    self.winit_impl.set_outer_position(position);
+                }
+            }
+        }
+        self.fullscreen_monitor = monitor_index;
+        self.transitioning = true;
+        // Some platforms release the cursor while the window changes mode
+        self.apply_grab(self.wants_grab && self.focused);
+    }
 
-        let renderer = pollster::block_on(Renderer::new(&winit_impl));
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Minimized windows have no area on some platforms
+    pub fn is_minimized(&self) -> bool {
+        let (width, height) = self.size();
+        self.winit_impl.is_minimized().unwrap_or(false) || width == 0 || height == 0
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen_monitor.is_some()
+    }
+
+    /// Whether the window switched in or out of fullscreen and hasn't
+    /// received its new size yet
+    pub fn is_transitioning(&self) -> bool {
+        self.transitioning
+    }
+
+    /// Called when the window is resized, which ends a fullscreen switch
+    pub fn on_resized(&mut self) {
+        self.transitioning = false;
+    }
 
-        let this = Self { winit_impl };
-        (this, renderer, event_loop)
+    /// Names and resolutions of the monitors fullscreen can be shown on,
+    /// in the order of their index
+    pub fn monitors(&self) -> Vec<String> {
+        self.winit_impl
+            .available_monitors()
+            .enumerate()
+            .map(|(index, monitor)| {
+                let size = monitor.size();
+                let name = monitor
+                    .name()
+                    .unwrap_or_else(|| format!("Monitor {}", index + 1));
+                format!("{} ({}×{})", name, size.width, size.height)
+            })
+            .collect()
     }
 
     pub fn grab_cursor(&mut self, grab: bool) {
-        self.winit_impl.set_cursor_visible(!grab);
-        // Not unwrapping to avoid panicking
-        if grab {
-            let _ = self
-                .winit_impl
-                .set_cursor_grab(winit::window::CursorGrabMode::Locked);
-        } else {
-            let _ = self
-                .winit_impl
-                .set_cursor_grab(winit::window::CursorGrabMode::None);
+        self.wants_grab = grab;
+        self.apply_grab(grab && self.focused);
+    }
+
+    pub fn is_cursor_grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    /// Releases the cursor while the window is in the background
+    /// and grabs it again once focus comes back.
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+        self.apply_grab(self.wants_grab && focused);
+    }
+
+    /// Retries grabbing the cursor if it failed before, which can happen
+    /// on some platforms until the window is focused.
+    pub fn update(&mut self) {
+        if self.wants_grab && self.focused && !self.grabbed {
+            self.apply_grab(true);
+        }
+    }
+
+    fn apply_grab(&mut self, grab: bool) {
+        use winit::window::CursorGrabMode;
+        if !grab {
+            let _ = self.winit_impl.set_cursor_grab(CursorGrabMode::None);
+            self.winit_impl.set_cursor_visible(true);
+            self.grabbed = false;
+            return;
+        }
+        // Not every platform supports locking the cursor in place
+        let result = self
+            .winit_impl
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| self.winit_impl.set_cursor_grab(CursorGrabMode::Confined));
+        match result {
+            Ok(()) => {
+                self.winit_impl.set_cursor_visible(false);
+                self.grabbed = true;
+            }
+            Err(e) => {
+                log::debug!("Failed to grab the cursor, retrying next frame: {}", e);
+                self.grabbed = false;
+            }
         }
     }
     pub fn scale_factor(&self) -> f32 {
@@ -57,3 +220,24 @@ impl Window {
         self.winit_impl.inner_size().into()
     }
 }
+
+/// The monitor at the given index, or the primary one if it was unplugged
+fn monitor(
+    mut monitors: impl Iterator<Item = MonitorHandle>,
+    primary: Option<MonitorHandle>,
+    index: usize,
+) -> Option<MonitorHandle> {
+    monitors.nth(index).or(primary)
+}
+
+unsafe impl HasRawWindowHandle for Window {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.winit_impl.raw_window_handle()
+    }
+}
+
+unsafe impl HasRawDisplayHandle for Window {
+    fn raw_display_handle(&self) -> RawDisplayHandle {
+        self.winit_impl.raw_display_handle()
+    }
+}
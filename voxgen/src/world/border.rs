@@ -0,0 +1,74 @@
+use bevy_ecs::prelude::Resource;
+use vek::{Vec2, Vec3};
+
+use super::chunk::{ChunkPos, CHUNK_DEPTH, CHUNK_WIDTH};
+
+/// Square limit of the world around the origin, in chunks. Chunks beyond it
+/// are never generated and the player can't cross it.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorldBorder {
+    /// Chunks from the origin chunk to the last one inside, `None` without limit
+    radius: Option<u32>,
+}
+
+impl WorldBorder {
+    pub const NONE: WorldBorder = WorldBorder { radius: None };
+
+    /// A border `radius` chunks away from the origin chunk on every side
+    pub fn new(radius: u32) -> Self {
+        Self {
+            radius: Some(radius),
+        }
+    }
+
+    pub fn radius(&self) -> Option<u32> {
+        self.radius
+    }
+
+    pub fn contains_chunk(&self, pos: ChunkPos) -> bool {
+        self.radius
+            .is_none_or(|radius| pos.x.abs().max(pos.z.abs()) <= radius as i32)
+    }
+
+    /// Corners of the border on the horizontal plane, from the lowest x and z
+    /// to the highest. Blocks are centered on integer coordinates.
+    pub fn bounds(&self) -> Option<(Vec2<f32>, Vec2<f32>)> {
+        let radius = self.radius? as f32;
+        let size = Vec2::new(CHUNK_WIDTH as f32, CHUNK_DEPTH as f32);
+        Some((size * -radius - 0.5, size * (radius + 1.0) - 0.5))
+    }
+
+    /// Moves the position inside the border, at least `margin` away from it
+    pub fn clamp(&self, pos: Vec3<f32>, margin: f32) -> Vec3<f32> {
+        match self.bounds() {
+            Some((min, max)) => Vec3::new(
+                pos.x.clamp(min.x + margin, max.x - margin),
+                pos.y,
+                pos.z.clamp(min.y + margin, max.y - margin),
+            ),
+            None => pos,
+        }
+    }
+
+    /// How far a ray starting inside the border goes before leaving it,
+    /// at most `max_distance`
+    pub fn ray_length(&self, origin: Vec3<f32>, direction: Vec3<f32>, max_distance: f32) -> f32 {
+        let (min, max) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return max_distance,
+        };
+        let exits = [
+            (origin.x, direction.x, min.x, max.x),
+            (origin.z, direction.z, min.y, max.y),
+        ];
+        exits
+            .into_iter()
+            .filter(|(_, direction, _, _)| *direction != 0.0)
+            .map(|(origin, direction, min, max)| {
+                let edge = if direction > 0.0 { max } else { min };
+                (edge - origin) / direction
+            })
+            .fold(max_distance, f32::min)
+            .max(0.0)
+    }
+}
@@ -1,95 +1,370 @@
 use crate::{
-    block::BlockId,
+    block::{self, BlockId, BlockShape},
     direction::Direction,
     renderer::{
         buffer::{compute_cube_indices, ChunkBuffer},
         mesh::quad::Quad,
         mesh::vertex::Vertex,
+        pool::ChunkPool,
+        upload::Uploader,
     },
 };
 
-use log::info;
+use super::{
+    chunk_stats::{ChunkMetric, ChunkReporter},
+    heightmap::Heightmap,
+    light::{LightKind, LightMap, Lighting, MAX_LIGHT},
+    lod::{self, LodMeshes},
+    palette::{BlockMeta, ChunkBlocks},
+    visibility::{ChunkVisibility, CHUNK_SECTIONS, SECTION_HEIGHT},
+};
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use vek::Vec3;
 
-pub const CHUNK_HEIGHT: usize = 256;
+/// Chunks are cubes stacked in columns, the world has no height limit
+pub const CHUNK_HEIGHT: usize = 16;
 pub const CHUNK_DEPTH: usize = 16;
 pub const CHUNK_WIDTH: usize = 16;
 pub const TOTAL_CHUNK_SIZE: usize = CHUNK_WIDTH * CHUNK_HEIGHT * CHUNK_DEPTH;
+/// Height of the grass of the generated terrain, bedrock is at 0
+pub const SURFACE_HEIGHT: i32 = 255;
 
 pub struct Chunk {
-    pub blocks: Vec<BlockId>,
+    pub blocks: ChunkBlocks,
     pub pos: ChunkPos,
     pub buffer: ChunkBuffer,
     pub mesh: ChunkMesh,
+    /// Buffer of the faces of transparent blocks, drawn after all opaque geometry
+    pub transparent_buffer: ChunkBuffer,
+    pub transparent_mesh: ChunkMesh,
+    /// Half resolution meshes drawn when the chunk is far away
+    pub lod: LodMeshes,
+    /// Block and sky light of every block. Only the sky columns
+    /// are lit until the chunk was loaded into the world.
+    pub light: LightMap,
+    /// Highest block of every column
+    pub heightmap: Heightmap,
+    /// Which faces of each section see each other, for occlusion culling
+    pub visibility: ChunkVisibility,
     pub loaded: bool,
+    /// Set when a block changed and the mesh needs to be rebuilt
+    pub dirty: bool,
+    /// Set when the assets were reloaded. The mesh is rebuilt along with
+    /// a few other stale ones per tick, nearest first.
+    pub stale: bool,
+    /// Set when a block changed since the chunk was last saved
+    pub modified: bool,
 }
 
 impl Chunk {
-    pub fn new(device: &wgpu::Device, pos: ChunkPos) -> Self {
-        let instant = std::time::Instant::now();
-        let (blocks, mesh) = Self::generate(pos);
-        let elapsed = instant.elapsed();
-        let buffer = ChunkBuffer::new(&device, &mesh.vertices, &mesh.indices, mesh.num_elements);
-        info!("Took {}ms to generate chunk", elapsed.as_millis());
+    pub fn new(pos: ChunkPos, lighting: Lighting, reporter: &ChunkReporter) -> Self {
+        let blocks = reporter.time(ChunkMetric::Generation, || Self::generate(pos));
+        let blocks = ChunkBlocks::from_slice(&blocks);
+        Self::from_blocks(pos, blocks, lighting, reporter)
+    }
 
+    /// Creates a chunk from generated or loaded blocks and builds its mesh.
+    /// The mesh is drawn once uploaded with [`Chunk::upload`].
+    pub fn from_blocks(
+        pos: ChunkPos,
+        blocks: ChunkBlocks,
+        lighting: Lighting,
+        reporter: &ChunkReporter,
+    ) -> Self {
+        let unpacked = blocks.to_vec();
+        let light = LightMap::with_sky_columns(&unpacked);
+        let heightmap = Heightmap::from_blocks(&unpacked);
+        let visibility = ChunkVisibility::from_blocks(&unpacked);
+        let ((mesh, transparent_mesh), lod) = reporter.time(ChunkMetric::Meshing, || {
+            let meshes = Self::build_mesh(&unpacked, blocks.meta(), &light, pos, lighting, |_| {
+                (BlockId::AIR, [0, MAX_LIGHT])
+            });
+            let lod = lod::build_lod_mesh(&unpacked, &light, pos, |_| [0, MAX_LIGHT]);
+            (meshes, lod)
+        });
+        reporter.record(
+            ChunkMetric::Vertices,
+            (mesh.vertices.len() + transparent_mesh.vertices.len()) as f64,
+        );
         Self {
             blocks,
-            buffer,
+            buffer: ChunkBuffer::default(),
             mesh,
+            transparent_buffer: ChunkBuffer::default(),
+            transparent_mesh,
+            lod: LodMeshes::new(lod),
+            light,
+            heightmap,
+            visibility,
             pos,
             loaded: true,
+            dirty: false,
+            stale: false,
+            modified: false,
         }
     }
 
-    pub fn generate(pos: ChunkPos) -> (Vec<BlockId>, ChunkMesh) {
-        let mut blocks = [BlockId::DIRT; TOTAL_CHUNK_SIZE];
+    /// Generates the terrain of the chunk at the given position
+    pub fn generate(pos: ChunkPos) -> Vec<BlockId> {
+        let origin = pos.to_world();
+        (0..TOTAL_CHUNK_SIZE)
+            .into_par_iter()
+            .map(|index| {
+                let pos = origin + Self::local_pos_of(index);
+                let (surface, top) = Self::generated_surface(pos.x, pos.z);
+                if pos.y == 0 {
+                    BlockId::BEDROCK
+                } else if pos.y == surface {
+                    top
+                } else if pos.y > 0 && pos.y < surface {
+                    BlockId::DIRT
+                } else {
+                    BlockId::AIR
+                }
+            })
+            .collect::<Vec<_>>()
+    }
 
-        let mut vertices = Vec::with_capacity(TOTAL_CHUNK_SIZE);
+    /// The height and block of the top of the generated terrain in the
+    /// column at the world position, known without generating its chunks
+    pub fn generated_surface(_x: i32, _z: i32) -> (i32, BlockId) {
+        (SURFACE_HEIGHT, BlockId::GRASS)
+    }
 
-        let verts = (0..CHUNK_WIDTH * CHUNK_HEIGHT * CHUNK_DEPTH)
+    /// Builds the opaque and transparent meshes of the unpacked blocks, emitting
+    /// only the faces that are not hidden by a neighbor inside the same chunk.
+    /// Vertices are relative to the chunk origin and grouped by section.
+    /// Faces are lit by the blocks in front of them, `border` returns the
+    /// block and its block and sky light outside the chunk by world position.
+    /// Fluids are lowered by the level in their metadata.
+    pub fn build_mesh(
+        blocks: &[BlockId],
+        meta: &BlockMeta,
+        light: &LightMap,
+        pos: ChunkPos,
+        lighting: Lighting,
+        border: impl Fn(Vec3<i32>) -> (BlockId, [u8; 2]) + Sync,
+    ) -> (ChunkMesh, ChunkMesh) {
+        let world_pos = pos.to_world();
+        // Whether the block relative to the chunk is opaque and its light
+        let sample = |local_pos: Vec3<i32>| {
+            if Chunk::is_pos_in_bounds(local_pos) {
+                let index = Self::index_of(local_pos);
+                (blocks[index].is_opaque(), light.levels(index))
+            } else {
+                let (block, levels) = border(world_pos + local_pos);
+                (block.is_opaque(), levels)
+            }
+        };
+        let mut quads = (0..TOTAL_CHUNK_SIZE)
             .into_par_iter()
+            .filter(|index| !blocks[*index].is_air())
             .map(|index| {
+                let block = blocks[index];
                 let x = index % CHUNK_WIDTH;
                 let y = (index / CHUNK_WIDTH) % CHUNK_HEIGHT;
                 let z = (index / (CHUNK_WIDTH * CHUNK_HEIGHT)) % CHUNK_DEPTH;
+                // The position of the block in the chunk, which meshes are relative to
+                let local_pos = Vec3::new(x as i32, y as i32, z as i32);
+
+                let section = y / SECTION_HEIGHT;
 
-                let block_in_chunk = if y == CHUNK_HEIGHT - 1 {
-                    BlockId::GRASS
+                let shape = block.shape();
+                if shape == BlockShape::Cross {
+                    let light = [to_brightness(sample(local_pos).1); 4];
+                    let quads = Quad::cross(block, local_pos).map(|quad| quad.with_light(light));
+                    return (section, block.is_transparent(), quads.to_vec());
+                }
+
+                let height = if block.flows() {
+                    // A fluid falling into the block fills it
+                    let above = local_pos + Vec3::unit_y();
+                    let above = if Chunk::is_pos_in_bounds(above) {
+                        blocks[Self::index_of(above)]
+                    } else {
+                        border(world_pos + above).0
+                    };
+                    if above == block {
+                        1.0
+                    } else {
+                        block::fluid_height(meta.get(index))
+                    }
+                } else if shape == BlockShape::Slab {
+                    0.5
                 } else {
-                    BlockId::DIRT
+                    1.0
                 };
-                // The position of the block in the chunk
-                let local_pos = Vec3::new(x as i32, y as i32, z as i32);
-                let world_pos = pos.to_world();
-                let translation = Vec3::new(
-                    local_pos.x + world_pos.x,
-                    local_pos.y,
-                    local_pos.z + world_pos.z,
-                );
 
                 let mut visible_quads = Vec::new();
-                (Direction::ALL).iter().for_each(|dir| {
+                Direction::ALL.into_iter().for_each(|dir| {
+                    // The top of a slab or a lowered fluid is inside its own
+                    // block and never hidden
+                    let inside = height < 1.0 && dir == Direction::Up;
                     let neighbor_pos = local_pos + dir.normalized();
-                    if !Chunk::is_pos_in_bounds(neighbor_pos) {
-                        visible_quads.push(Quad::new(&block_in_chunk, *dir, translation));
+                    if !inside
+                        && Chunk::is_pos_in_bounds(neighbor_pos)
+                        && !block.is_face_visible(blocks[Self::index_of(neighbor_pos)])
+                    {
                         return;
                     }
+                    let quad = if height < 1.0 {
+                        Quad::lowered(block, dir, local_pos, height)
+                    } else {
+                        Quad::new(block, dir, local_pos)
+                    };
+                    let light = match lighting {
+                        _ if inside => [to_brightness(sample(local_pos).1); 4],
+                        Lighting::Flat => [to_brightness(sample(neighbor_pos).1); 4],
+                        Lighting::Smooth => quad.vertices.map(|vertex| {
+                            // Each axis of the corner is -1 or 1 relative to the block
+                            let corner = (Vec3::<f32>::from(vertex.pos())
+                                - local_pos.map(|v| v as f32))
+                            .map(|v| v.signum() as i32);
+                            corner_light(&sample, neighbor_pos, corner - dir.normalized())
+                        }),
+                    };
+                    visible_quads.push(quad.with_light(light));
                 });
-                (index, visible_quads, block_in_chunk)
+                (section, block.is_transparent(), visible_quads)
             })
             .collect::<Vec<_>>();
+        Self::group_quads(&mut quads)
+    }
 
-        verts.iter().for_each(|quad| {
-            let (index, visible_quads, block) = quad;
-            visible_quads.iter().for_each(|quad| {
-                blocks[*index] = *block;
-                vertices.extend(quad.vertices);
-            });
+    /// Builds the opaque and transparent meshes out of the quads of each
+    /// block, given with the section of the block and whether it is transparent
+    pub fn group_quads(quads: &mut [(usize, bool, Vec<Quad>)]) -> (ChunkMesh, ChunkMesh) {
+        // Sections are drawn separately when occlusion culling hides some of them
+        quads.sort_by_key(|(section, _, _)| *section);
+
+        let quad_count = quads.iter().map(|(_, _, quads)| quads.len()).sum::<usize>();
+        let mut vertices = Vec::with_capacity(quad_count * 4);
+        let mut transparent_vertices = Vec::new();
+        let mut sections = [[0; CHUNK_SECTIONS + 1]; 2];
+        quads.iter().for_each(|(section, transparent, quads)| {
+            let (target, sections) = if *transparent {
+                (&mut transparent_vertices, &mut sections[1])
+            } else {
+                (&mut vertices, &mut sections[0])
+            };
+            quads.iter().for_each(|quad| target.extend(quad.vertices));
+            // Each quad has 4 vertices and 6 indices
+            sections[section + 1] = (target.len() / 4 * 6) as u32;
         });
+        // Sections without faces end where the previous one did
+        for sections in &mut sections {
+            for section in 1..=CHUNK_SECTIONS {
+                sections[section] = sections[section].max(sections[section - 1]);
+            }
+        }
 
         let indices = compute_cube_indices(vertices.len());
-        (blocks.to_vec(), ChunkMesh::new(vertices, indices))
+        let transparent_indices = compute_cube_indices(transparent_vertices.len());
+        (
+            ChunkMesh::new(vertices, indices, sections[0]),
+            ChunkMesh::new(transparent_vertices, transparent_indices, sections[1]),
+        )
+    }
+
+    /// Replaces the meshes built by [`Chunk::build_mesh`], which are drawn
+    /// once uploaded with [`Chunk::upload`]
+    pub fn set_meshes(&mut self, meshes: (ChunkMesh, ChunkMesh)) {
+        let (mesh, transparent_mesh) = meshes;
+        self.mesh = mesh;
+        self.transparent_mesh = transparent_mesh;
+        self.dirty = false;
+    }
+
+    /// Uploads the full and half resolution meshes to the pool, in place of the previous ones when they fit
+    pub fn upload(&mut self, pool: &mut ChunkPool, device: &wgpu::Device, uploader: &mut Uploader) {
+        pool.upload(device, uploader, &mut self.buffer, &self.mesh);
+        pool.upload(
+            device,
+            uploader,
+            &mut self.transparent_buffer,
+            &self.transparent_mesh,
+        );
+        pool.upload(device, uploader, &mut self.lod.buffer, &self.lod.mesh);
+        pool.upload(
+            device,
+            uploader,
+            &mut self.lod.transparent_buffer,
+            &self.lod.transparent_mesh,
+        );
+    }
+
+    /// Returns the allocations of the meshes to the pool
+    pub fn free(&mut self, pool: &mut ChunkPool) {
+        pool.free(&mut self.buffer);
+        pool.free(&mut self.transparent_buffer);
+        pool.free(&mut self.lod.buffer);
+        pool.free(&mut self.lod.transparent_buffer);
+    }
+
+    /// Returns the block at the given position relative to the chunk
+    pub fn get_block(&self, local_pos: Vec3<i32>) -> BlockId {
+        self.blocks.get(Self::index_of(local_pos))
+    }
+
+    /// Height of the highest block of the column that isn't air in this
+    /// chunk. `x`, `z` and the height are relative to the chunk.
+    pub fn height_at(&self, x: i32, z: i32) -> Option<i32> {
+        self.heightmap.height_at(x, z)
+    }
+
+    /// The highest block of the column that isn't air in this chunk, with
+    /// its height. `x`, `z` and the height are relative to the chunk.
+    pub fn highest_block(&self, x: i32, z: i32) -> Option<(i32, BlockId)> {
+        let y = self.height_at(x, z)?;
+        Some((y, self.get_block(Vec3::new(x, y, z))))
+    }
+
+    /// Replaces the block at the given position relative to the chunk
+    /// and returns the old one.
+    pub fn set_block(&mut self, local_pos: Vec3<i32>, id: BlockId) -> BlockId {
+        let old = self.blocks.set(Self::index_of(local_pos), id);
+        let blocks = &self.blocks;
+        self.heightmap.update(local_pos, id, |y| {
+            blocks.get(Self::index_of(Vec3::new(local_pos.x, y, local_pos.z)))
+        });
+        old
+    }
+
+    /// Returns the metadata of the block at the given position relative to the chunk
+    pub fn get_meta(&self, local_pos: Vec3<i32>) -> u8 {
+        self.blocks.get_meta(Self::index_of(local_pos))
+    }
+
+    /// Replaces the metadata of the block at the given position relative
+    /// to the chunk and returns the old one. Replacing the block clears it.
+    pub fn set_meta(&mut self, local_pos: Vec3<i32>, value: u8) -> u8 {
+        self.blocks.set_meta(Self::index_of(local_pos), value)
+    }
+
+    /// Returns the light at the given position relative to the chunk
+    pub fn get_light(&self, local_pos: Vec3<i32>, kind: LightKind) -> u8 {
+        self.light.get(Self::index_of(local_pos), kind)
+    }
+
+    pub fn set_light(&mut self, local_pos: Vec3<i32>, kind: LightKind, level: u8) {
+        self.light.set(Self::index_of(local_pos), kind, level);
+    }
+
+    /// Returns the position relative to the chunk of the block at `index`
+    pub fn local_pos_of(index: usize) -> Vec3<i32> {
+        Vec3::new(
+            (index % CHUNK_WIDTH) as i32,
+            ((index / CHUNK_WIDTH) % CHUNK_HEIGHT) as i32,
+            (index / (CHUNK_WIDTH * CHUNK_HEIGHT)) as i32,
+        )
+    }
+
+    pub fn index_of(local_pos: Vec3<i32>) -> usize {
+        compute_1d(
+            local_pos.x as usize,
+            local_pos.y as usize,
+            local_pos.z as usize,
+        )
     }
 
     /// Checks if a given position is in bounds of the chunk
@@ -108,12 +383,13 @@ impl Chunk {
 ///
 /// Example:
 ///
-/// If a chunk is 16 units wide and 16 units deep:
+/// If a chunk is 16 units wide, high and deep:
 ///
 /// World Position: (32, 0, -128) -> ChunkPos: (2, 0, -8).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ChunkPos {
     pub x: i32,
+    pub y: i32,
     pub z: i32,
 }
 
@@ -123,6 +399,7 @@ impl std::ops::Sub<ChunkPos> for ChunkPos {
     fn sub(self, rhs: ChunkPos) -> Self::Output {
         Self {
             x: self.x - rhs.x,
+            y: self.y - rhs.y,
             z: self.z - rhs.z,
         }
     }
@@ -134,46 +411,191 @@ impl std::ops::Add<ChunkPos> for ChunkPos {
     fn add(self, rhs: ChunkPos) -> Self::Output {
         Self {
             x: self.x + rhs.x,
+            y: self.y + rhs.y,
             z: self.z + rhs.z,
         }
     }
 }
 
 impl ChunkPos {
-    pub const ORIGIN: ChunkPos = ChunkPos::new(0, 0);
+    pub const ORIGIN: ChunkPos = ChunkPos::new(0, 0, 0);
 
-    pub const fn new(x: i32, z: i32) -> Self {
-        Self { x, z }
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
     }
 
     /// Returns the chunk pos at the given world pos
     pub fn from_world(pos: Vec3<f32>) -> Self {
         let x = (pos.x / CHUNK_WIDTH as f32).floor() as i32;
+        let y = (pos.y / CHUNK_HEIGHT as f32).floor() as i32;
         let z = (pos.z / CHUNK_DEPTH as f32).floor() as i32;
-        Self { x, z }
+        Self { x, y, z }
+    }
+
+    /// Returns the chunk pos containing the given block position
+    pub fn from_block(pos: Vec3<i32>) -> Self {
+        Self {
+            x: pos.x.div_euclid(CHUNK_WIDTH as i32),
+            y: pos.y.div_euclid(CHUNK_HEIGHT as i32),
+            z: pos.z.div_euclid(CHUNK_DEPTH as i32),
+        }
     }
 
     /// Returns the world pos of the current chunk.
     pub fn to_world(&self) -> Vec3<i32> {
-        Vec3::new(self.x * CHUNK_WIDTH as i32, 0, self.z * CHUNK_DEPTH as i32)
+        Vec3::new(
+            self.x * CHUNK_WIDTH as i32,
+            self.y * CHUNK_HEIGHT as i32,
+            self.z * CHUNK_DEPTH as i32,
+        )
     }
+
+    /// The column of chunks the chunk is stacked in, as the chunk at height 0
+    pub fn column(&self) -> Self {
+        Self { y: 0, ..*self }
+    }
+}
+
+/// Returns the position of the block containing the world position.
+/// Blocks are centered on integer coordinates.
+pub fn block_pos(pos: Vec3<f32>) -> Vec3<i32> {
+    pos.map(|v| (v + 0.5).floor() as i32)
+}
+
+/// Converts a block position in the world to a position relative to its chunk
+pub fn to_local(pos: Vec3<i32>) -> Vec3<i32> {
+    Vec3::new(
+        pos.x.rem_euclid(CHUNK_WIDTH as i32),
+        pos.y.rem_euclid(CHUNK_HEIGHT as i32),
+        pos.z.rem_euclid(CHUNK_DEPTH as i32),
+    )
+}
+
+/// Converts block and sky light levels to the 0 to 1 range of the shader
+fn to_brightness(levels: [u8; 2]) -> [f32; 2] {
+    levels.map(|level| level as f32 / MAX_LIGHT as f32)
+}
+
+/// Averages the light of the four blocks touching a corner of a face:
+/// the block in front of the face, the two beside it along `tangent` and
+/// the diagonal one. Opaque blocks don't count, and the diagonal is
+/// skipped if both blocks beside it are opaque since no light gets past them.
+fn corner_light(
+    sample: &impl Fn(Vec3<i32>) -> (bool, [u8; 2]),
+    front: Vec3<i32>,
+    tangent: Vec3<i32>,
+) -> [f32; 2] {
+    let first_axis = if tangent.x != 0 {
+        Vec3::new(tangent.x, 0, 0)
+    } else {
+        Vec3::new(0, tangent.y, 0)
+    };
+    let center = sample(front);
+    let side1 = sample(front + first_axis);
+    let side2 = sample(front + tangent - first_axis);
+    let diagonal = (!side1.0 || !side2.0).then(|| sample(front + tangent));
+
+    let mut sum = [0u32; 2];
+    let mut count = 0;
+    for (_, levels) in [Some(center), Some(side1), Some(side2), diagonal]
+        .into_iter()
+        .flatten()
+        .filter(|(opaque, _)| !opaque)
+    {
+        sum[0] += levels[0] as u32;
+        sum[1] += levels[1] as u32;
+        count += 1;
+    }
+    if count == 0 {
+        return to_brightness(center.1);
+    }
+    sum.map(|level| level as f32 / (count * MAX_LIGHT as u32) as f32)
 }
 
 pub fn compute_1d(x: usize, y: usize, z: usize) -> usize {
     x + y * CHUNK_WIDTH + z * CHUNK_WIDTH * CHUNK_HEIGHT
 }
 
+#[derive(Clone, Default)]
 pub struct ChunkMesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
     pub num_elements: u32,
+    /// The index each section starts at from the bottom up, followed by `num_elements`
+    pub sections: [u32; CHUNK_SECTIONS + 1],
 }
 impl ChunkMesh {
-    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+    pub fn new(
+        vertices: Vec<Vertex>,
+        indices: Vec<u32>,
+        sections: [u32; CHUNK_SECTIONS + 1],
+    ) -> Self {
         Self {
             vertices,
             num_elements: indices.len() as u32,
             indices,
+            sections,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Quads meshed for a chunk of air with the given blocks, surrounded by air
+    fn quad_count(placed: &[(Vec3<i32>, BlockId)]) -> usize {
+        let mut blocks = vec![BlockId::AIR; TOTAL_CHUNK_SIZE];
+        for (pos, block) in placed {
+            blocks[Chunk::index_of(*pos)] = *block;
         }
+        let (mesh, transparent_mesh) = Chunk::build_mesh(
+            &blocks,
+            &BlockMeta::default(),
+            &LightMap::new(),
+            ChunkPos::ORIGIN,
+            Lighting::Flat,
+            |_| (BlockId::AIR, [0, 0]),
+        );
+        // Each quad has 4 vertices and 6 indices
+        assert_eq!(mesh.vertices.len() * 6, mesh.indices.len() * 4);
+        (mesh.vertices.len() + transparent_mesh.vertices.len()) / 4
+    }
+
+    const POS: Vec3<i32> = Vec3::new(8, 8, 8);
+    const ABOVE: Vec3<i32> = Vec3::new(8, 9, 8);
+
+    #[test]
+    fn single_blocks_of_each_shape() {
+        assert_eq!(quad_count(&[(POS, BlockId::STONE)]), 6);
+        // The top of a slab is lowered, the other faces are the same
+        assert_eq!(quad_count(&[(POS, BlockId::STONE_SLAB)]), 6);
+        // Both diagonals, seen from both sides
+        assert_eq!(quad_count(&[(POS, BlockId::GRASS_TUFT)]), 4);
+    }
+
+    #[test]
+    fn full_blocks_hide_each_other() {
+        let side = POS + Vec3::unit_x();
+        assert_eq!(
+            quad_count(&[(POS, BlockId::STONE), (side, BlockId::STONE)]),
+            10
+        );
+    }
+
+    #[test]
+    fn slab_on_a_full_block() {
+        // The bottom of the slab is hidden, the top of the stone is not
+        let placed = [(POS, BlockId::STONE), (ABOVE, BlockId::STONE_SLAB)];
+        assert_eq!(quad_count(&placed), 6 + 5);
+        // Stacked slabs leave a gap between them, nothing is hidden
+        let placed = [(POS, BlockId::STONE_SLAB), (ABOVE, BlockId::STONE_SLAB)];
+        assert_eq!(quad_count(&placed), 12);
+    }
+
+    #[test]
+    fn cross_on_a_full_block() {
+        let placed = [(POS, BlockId::DIRT), (ABOVE, BlockId::GRASS_TUFT)];
+        assert_eq!(quad_count(&placed), 6 + 4);
     }
 }
@@ -1,72 +1,710 @@
-use super::chunk::{Chunk, ChunkPos};
-use crate::renderer::world::RENDER_DISTANCE;
-use log::info;
+use super::{
+    border::WorldBorder,
+    chunk::{self, Chunk, ChunkPos, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH},
+    chunk_stats::{ChunkMetric, ChunkStats},
+    events::WorldEvent,
+    heightmap::Columns,
+    light::{self, LightKind, Lighting, MAX_LIGHT},
+    lod,
+    palette::ChunkBlocks,
+    saver::ChunkSaver,
+    storage::WorldStorage,
+    visibility::ChunkVisibility,
+};
+use crate::{
+    block::BlockId,
+    direction::Direction,
+    renderer::{
+        pool::{ChunkPool, PoolStats},
+        upload::Uploader,
+        world::RENDER_DISTANCE,
+    },
+};
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
-use std::collections::HashSet;
+use std::{collections::HashMap, sync::Arc};
+use vek::Vec3;
+
+/// Most chunks generated in a single tick. Spreading the work over
+/// several frames keeps them short after a teleport.
+const MAX_LOADS_PER_TICK: usize = 8;
+/// Most stale chunks remeshed in a single tick, so that reloading the
+/// assets doesn't freeze the frame
+const MAX_STALE_REMESHES_PER_TICK: usize = 4;
 
 pub struct ChunkManager {
     chunks: Vec<Chunk>,
-    positions: HashSet<ChunkPos>,
+    /// Index of every loaded chunk in `chunks`
+    indices: HashMap<ChunkPos, usize>,
+    /// The loaded chunks of every column, to find the highest blocks
+    columns: Columns,
+    totals: ChunkTotals,
+    /// Time spent generating, meshing and uploading each chunk
+    stats: ChunkStats,
+    /// Writes edited chunks in the background
+    saver: ChunkSaver,
+    /// Events emitted since they were last processed
+    events: Vec<WorldEvent>,
+    lighting: Lighting,
+    /// Chunks in range that were still missing after the last tick
+    pending: usize,
+    /// Chunks beyond it are never generated
+    border: WorldBorder,
+    /// The GPU memory chunk meshes are uploaded to
+    pool: ChunkPool,
+}
+
+/// Running totals over the loaded chunks, updated as chunks
+/// are loaded, unloaded and remeshed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChunkTotals {
+    pub loaded: usize,
+    pub vertices: usize,
+    /// Size of the vertex and index buffers
+    pub gpu_bytes: u64,
+}
+
+impl ChunkTotals {
+    fn add(&mut self, chunk: &Chunk) {
+        self.loaded += 1;
+        self.vertices += chunk.buffer.vertices_len() + chunk.transparent_buffer.vertices_len();
+        self.gpu_bytes += Self::gpu_bytes(chunk);
+    }
+
+    fn remove(&mut self, chunk: &Chunk) {
+        self.loaded -= 1;
+        self.vertices -= chunk.buffer.vertices_len() + chunk.transparent_buffer.vertices_len();
+        self.gpu_bytes -= Self::gpu_bytes(chunk);
+    }
+
+    fn gpu_bytes(chunk: &Chunk) -> u64 {
+        chunk.buffer.size()
+            + chunk.transparent_buffer.size()
+            + chunk.lod.buffer.size()
+            + chunk.lod.transparent_buffer.size()
+    }
 }
 
 impl ChunkManager {
-    pub fn new() -> Self {
+    pub fn new(storage: Arc<WorldStorage>) -> Self {
         Self {
-            chunks: Vec::with_capacity(25),
-            positions: HashSet::new(),
+            chunks: Vec::new(),
+            indices: HashMap::new(),
+            columns: Columns::default(),
+            totals: ChunkTotals::default(),
+            stats: ChunkStats::new(),
+            saver: ChunkSaver::new(storage),
+            events: Vec::new(),
+            lighting: Lighting::Smooth,
+            pending: 0,
+            border: WorldBorder::NONE,
+            pool: ChunkPool::new(),
         }
     }
 
-    pub fn tick(&mut self, player_pos: ChunkPos, device: &wgpu::Device) {
+    /// A manager with only the chunks at `positions` loaded, saving into a
+    /// directory that is removed when the returned guard is dropped
+    #[cfg(test)]
+    pub fn with_chunks(positions: impl IntoIterator<Item = ChunkPos>) -> (Self, tempfile::TempDir) {
+        let dir = tempfile::tempdir().expect("no temporary directory to save into");
+        let mut manager = Self::new(Arc::new(WorldStorage::new(dir.path())));
+        let reporter = manager.stats.reporter();
+        for pos in positions {
+            let chunk = Chunk::new(pos, manager.lighting, &reporter);
+            manager.indices.insert(pos, manager.chunks.len());
+            manager.columns.insert(pos);
+            manager.totals.add(&chunk);
+            manager.chunks.push(chunk);
+        }
+        (manager, dir)
+    }
+
+    /// Unloads the chunks out of range of the player and loads the
+    /// nearest missing ones, at most `MAX_LOADS_PER_TICK` at a time.
+    pub fn tick(&mut self, player_pos: ChunkPos, device: &wgpu::Device, uploader: &mut Uploader) {
         let mut dirty = false;
         for chunk in self.chunks.iter_mut() {
             let distance = chunk.pos - player_pos;
-            let squared_distance = distance.x * distance.x + distance.z * distance.z;
+            let squared_distance =
+                distance.x * distance.x + distance.y * distance.y + distance.z * distance.z;
             if squared_distance > RENDER_DISTANCE * RENDER_DISTANCE {
                 dirty = true;
                 chunk.loaded = false;
-                self.positions.remove(&chunk.pos);
+                self.indices.remove(&chunk.pos);
             }
         }
         if dirty {
             self.unload_chunks();
-            let instant = std::time::Instant::now();
-            self.load_chunks(player_pos, device);
-            info!("Took {}ms to generate chunk", instant.elapsed().as_millis());
+        }
+        let mut missing = self.missing_chunks(player_pos);
+        if !missing.is_empty() {
+            missing.truncate(MAX_LOADS_PER_TICK);
+            self.generate_chunks(missing, device, uploader);
+        }
+        self.pending = self.missing_chunks(player_pos).len();
+        self.refresh_stale(player_pos);
+        self.stats.collect();
+    }
+
+    /// Flags the nearest stale chunks for remeshing
+    fn refresh_stale(&mut self, player_pos: ChunkPos) {
+        let mut stale = (0..self.chunks.len())
+            .filter(|index| self.chunks[*index].stale)
+            .collect::<Vec<_>>();
+        stale.sort_by_key(|index| {
+            let offset = self.chunks[*index].pos - player_pos;
+            offset.x * offset.x + offset.y * offset.y + offset.z * offset.z
+        });
+        for index in stale.into_iter().take(MAX_STALE_REMESHES_PER_TICK) {
+            let chunk = &mut self.chunks[index];
+            chunk.stale = false;
+            chunk.dirty = true;
         }
     }
 
+    /// Rebuilds the mesh of every loaded chunk over the next ticks, after
+    /// the blocks or their textures changed
+    pub fn remesh_all(&mut self) {
+        self.chunks.iter_mut().for_each(|chunk| chunk.stale = true);
+    }
+
     pub fn unload_chunks(&mut self) {
-        self.chunks.retain(|c| c.loaded);
+        // Edits would be lost once the chunk is dropped
+        let unsaved = self
+            .chunks
+            .iter()
+            .filter(|c| !c.loaded && c.modified)
+            .map(|c| (c.pos, c.blocks.clone()))
+            .collect::<Vec<_>>();
+        if !unsaved.is_empty() {
+            self.saver.queue_chunks(unsaved);
+        }
+        let totals = &mut self.totals;
+        let events = &mut self.events;
+        let pool = &mut self.pool;
+        let columns = &mut self.columns;
+        self.chunks.retain_mut(|c| {
+            if !c.loaded {
+                totals.remove(c);
+                columns.remove(c.pos);
+                c.free(pool);
+                events.push(WorldEvent::ChunkUnloaded(c.pos));
+            }
+            c.loaded
+        });
+        self.indices = self
+            .chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| (chunk.pos, index))
+            .collect();
     }
 
-    pub fn load_chunks(&mut self, player_pos: ChunkPos, device: &wgpu::Device) {
+    /// Chunks in range of the player and inside the border
+    /// that aren't loaded, nearest first. The range is a cube, the
+    /// chunks above and below the player are streamed like the others.
+    fn missing_chunks(&self, player_pos: ChunkPos) -> Vec<ChunkPos> {
         const DIST: i32 = RENDER_DISTANCE / 2;
-        // new boundaries
-        let start_x = player_pos.x - DIST;
-        let end_x = player_pos.x + DIST;
-        let start_z = player_pos.z - DIST;
-        let end_z = player_pos.z + DIST;
+        let mut missing = cube(DIST)
+            .filter(|offset| {
+                let pos = player_pos + *offset;
+                self.border.contains_chunk(pos) && !self.indices.contains_key(&pos)
+            })
+            .collect::<Vec<_>>();
+        missing
+            .sort_by_key(|offset| offset.x * offset.x + offset.y * offset.y + offset.z * offset.z);
+        missing
+            .into_iter()
+            .map(|offset| player_pos + offset)
+            .collect()
+    }
 
-        let chunks = (start_x..=end_x)
+    fn generate_chunks(
+        &mut self,
+        positions: Vec<ChunkPos>,
+        device: &wgpu::Device,
+        uploader: &mut Uploader,
+    ) {
+        let mut chunks = positions
             .into_par_iter()
-            .map(|x| {
-                let chunks = (start_z..=end_z)
-                    .into_par_iter()
-                    .map(|z| ChunkPos::new(x, z))
-                    .filter(|p| !self.positions.contains(p))
-                    .map(|pos| Chunk::new(device, pos))
-                    .collect::<Vec<_>>();
-                return chunks;
+            .map_with(self.stats.reporter(), |reporter, pos| {
+                match self.saver.load_chunk(pos) {
+                    Some(blocks) => Chunk::from_blocks(pos, blocks, self.lighting, reporter),
+                    None => Chunk::new(pos, self.lighting, reporter),
+                }
             })
-            .flatten()
             .collect::<Vec<_>>();
 
-        self.positions.extend(chunks.iter().map(|c| c.pos));
+        // The pool is shared, so meshes are uploaded one after the other
+        let reporter = self.stats.reporter();
+        for (index, chunk) in chunks.iter_mut().enumerate() {
+            reporter.time(ChunkMetric::Upload, || {
+                chunk.upload(&mut self.pool, device, uploader)
+            });
+            // Appended after the chunks already loaded
+            self.indices.insert(chunk.pos, self.chunks.len() + index);
+            self.columns.insert(chunk.pos);
+            self.totals.add(chunk);
+            self.events.push(WorldEvent::ChunkLoaded(chunk.pos));
+        }
         self.chunks.extend(chunks);
     }
 
+    /// Number of chunks in range of the player still to be loaded
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// How many chunks of the cube reaching `radius` chunks around
+    /// `center` are loaded, out of the chunks of the cube inside the border
+    pub fn area_progress(&self, center: ChunkPos, radius: i32) -> (usize, usize) {
+        let area = cube(radius)
+            .map(|offset| center + offset)
+            .filter(|pos| self.border.contains_chunk(*pos))
+            .collect::<Vec<_>>();
+        let loaded = area
+            .iter()
+            .filter(|pos| self.indices.contains_key(pos))
+            .count();
+        (loaded, area.len())
+    }
+
+    pub fn border(&self) -> WorldBorder {
+        self.border
+    }
+
+    /// Moves the border, unloading the chunks left outside of it
+    pub fn set_border(&mut self, border: WorldBorder) {
+        if border == self.border {
+            return;
+        }
+        self.border = border;
+        let mut dirty = false;
+        for chunk in self.chunks.iter_mut() {
+            if !border.contains_chunk(chunk.pos) {
+                dirty = true;
+                chunk.loaded = false;
+                self.indices.remove(&chunk.pos);
+            }
+        }
+        if dirty {
+            self.unload_chunks();
+        }
+    }
+
+    /// Switches how faces are lit, remeshing every chunk if it changed
+    pub fn set_lighting(&mut self, lighting: Lighting) {
+        if lighting != self.lighting {
+            self.lighting = lighting;
+            self.chunks.iter_mut().for_each(|chunk| chunk.dirty = true);
+        }
+    }
+
+    pub fn storage(&self) -> &Arc<WorldStorage> {
+        self.saver.storage()
+    }
+
+    pub fn saver(&self) -> &ChunkSaver {
+        &self.saver
+    }
+
+    /// Copies the blocks of every chunk edited since the last call
+    pub fn take_modified(&mut self) -> Vec<(ChunkPos, ChunkBlocks)> {
+        self.chunks
+            .iter_mut()
+            .filter(|c| c.modified)
+            .map(|c| {
+                c.modified = false;
+                (c.pos, c.blocks.clone())
+            })
+            .collect()
+    }
+
+    /// Bytes the blocks and metadata of the loaded chunks take in memory,
+    /// and the number of chunks storing metadata
+    pub fn block_memory(&self) -> (usize, usize) {
+        let bytes = self
+            .chunks
+            .iter()
+            .map(|chunk| chunk.blocks.memory_usage())
+            .sum();
+        let with_meta = self
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.blocks.meta().is_allocated())
+            .count();
+        (bytes, with_meta)
+    }
+
+    pub fn totals(&self) -> ChunkTotals {
+        self.totals
+    }
+
+    pub fn stats(&self) -> &ChunkStats {
+        &self.stats
+    }
+
+    pub fn pool(&self) -> &ChunkPool {
+        &self.pool
+    }
+
+    /// How full and fragmented the memory of the chunk meshes is
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
+
     pub fn chunks(&self) -> &Vec<Chunk> {
         &self.chunks
     }
+
+    pub fn chunk(&self, pos: ChunkPos) -> Option<&Chunk> {
+        self.indices.get(&pos).map(|index| &self.chunks[*index])
+    }
+
+    pub fn chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
+        self.indices.get(&pos).map(|index| &mut self.chunks[*index])
+    }
+
+    /// Returns the block at the given world position,
+    /// or `None` if the chunk containing it is not loaded.
+    pub fn get_block(&self, pos: Vec3<i32>) -> Option<BlockId> {
+        let local = chunk::to_local(pos);
+        if !Chunk::is_pos_in_bounds(local) {
+            return None;
+        }
+        self.chunk(ChunkPos::from_block(pos))
+            .map(|chunk| chunk.get_block(local))
+    }
+
+    /// Height of the highest block that isn't air in the loaded chunks of
+    /// the column, or `None` if they are all empty or none is loaded.
+    pub fn height_at_world(&self, x: i32, z: i32) -> Option<i32> {
+        self.highest_block(x, z).map(|(y, _)| y)
+    }
+
+    /// Returns the height and block of the highest block that isn't air in
+    /// the loaded chunks of the column, or `None` if they are all empty or
+    /// none is loaded. Blocks in unloaded chunks above are not known.
+    pub fn highest_block(&self, x: i32, z: i32) -> Option<(i32, BlockId)> {
+        let pos = Vec3::new(x, 0, z);
+        let local = chunk::to_local(pos);
+        self.columns
+            .top_down(ChunkPos::from_block(pos))
+            .find_map(|chunk_pos| {
+                let (y, block) = self.chunk(chunk_pos)?.highest_block(local.x, local.z)?;
+                Some((chunk_pos.to_world().y + y, block))
+            })
+    }
+
+    /// Replaces the block at the given world position and returns the old one.
+    /// The change takes effect on the meshes once the events are processed.
+    pub fn set_block(&mut self, pos: Vec3<i32>, id: BlockId) -> Option<BlockId> {
+        self.set_block_with_meta(pos, id, 0)
+    }
+
+    /// Returns the metadata of the block at the given world position,
+    /// or `None` if the chunk containing it is not loaded.
+    pub fn get_meta(&self, pos: Vec3<i32>) -> Option<u8> {
+        let local = chunk::to_local(pos);
+        if !Chunk::is_pos_in_bounds(local) {
+            return None;
+        }
+        self.chunk(ChunkPos::from_block(pos))
+            .map(|chunk| chunk.get_meta(local))
+    }
+
+    /// Replaces the block at the given world position along with its
+    /// metadata and returns the old block. Changing only the metadata
+    /// is a block change too, from and to the same block.
+    pub fn set_block_with_meta(
+        &mut self,
+        pos: Vec3<i32>,
+        id: BlockId,
+        meta: u8,
+    ) -> Option<BlockId> {
+        let local = chunk::to_local(pos);
+        if !Chunk::is_pos_in_bounds(local) {
+            return None;
+        }
+        let chunk = self.chunk_mut(ChunkPos::from_block(pos))?;
+        let old = chunk.set_block(local, id);
+        chunk.set_meta(local, meta);
+        self.events
+            .push(WorldEvent::BlockChanged { pos, old, new: id });
+        Some(old)
+    }
+
+    /// Returns the light at the given world position,
+    /// or `None` if the chunk containing it is not loaded.
+    pub fn get_light(&self, pos: Vec3<i32>, kind: LightKind) -> Option<u8> {
+        let local = chunk::to_local(pos);
+        if !Chunk::is_pos_in_bounds(local) {
+            return None;
+        }
+        self.chunk(ChunkPos::from_block(pos))
+            .map(|chunk| chunk.get_light(local, kind))
+    }
+
+    /// Sets the light at the given world position.
+    /// Returns whether the light level changed.
+    pub fn set_light(&mut self, pos: Vec3<i32>, kind: LightKind, level: u8) -> bool {
+        let local = chunk::to_local(pos);
+        if !Chunk::is_pos_in_bounds(local) {
+            return false;
+        }
+        match self.chunk_mut(ChunkPos::from_block(pos)) {
+            Some(chunk) if chunk.get_light(local, kind) != level => {
+                chunk.set_light(local, kind, level);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Drains the events emitted since the last call and applies them to the
+    /// loaded chunks. The drained events are returned for other systems,
+    /// followed by a `NeighborChanged` event for every loaded neighbor of
+    /// the changed blocks.
+    pub fn process_events(&mut self) -> Vec<WorldEvent> {
+        let mut events = std::mem::take(&mut self.events);
+        for event in &events {
+            self.relight_on(event);
+            self.remesh_on(event);
+            self.mark_unsaved_on(event);
+        }
+        let notifications = events
+            .iter()
+            .filter_map(|event| match event {
+                WorldEvent::BlockChanged { pos, .. } => Some(*pos),
+                _ => None,
+            })
+            .flat_map(|changed| Direction::ALL.map(|dir| (changed + dir.normalized(), changed)))
+            .filter(|(pos, _)| self.get_block(*pos).is_some())
+            .map(|(pos, changed)| WorldEvent::NeighborChanged { pos, changed })
+            .collect::<Vec<_>>();
+        events.extend(notifications);
+        events
+    }
+
+    /// Spreads the light of loaded chunks and around changed blocks,
+    /// remeshing every chunk that shows a face whose light changed.
+    fn relight_on(&mut self, event: &WorldEvent) {
+        let changed = match event {
+            WorldEvent::BlockChanged { pos, .. } => light::relight_block(self, *pos),
+            WorldEvent::ChunkLoaded(pos) => light::light_chunk(self, *pos),
+            WorldEvent::NeighborChanged { .. } | WorldEvent::ChunkUnloaded(_) => return,
+        };
+        for pos in changed {
+            self.mark_dirty_around(pos);
+        }
+    }
+
+    /// Flags the chunks whose mesh is affected by the event for remeshing.
+    fn remesh_on(&mut self, event: &WorldEvent) {
+        if let WorldEvent::BlockChanged { pos, .. } = event {
+            self.mark_dirty_around(*pos);
+        }
+    }
+
+    /// Flags the chunk containing the block for remeshing. A block on a
+    /// chunk border also changes the neighbors sharing its faces and corners.
+    fn mark_dirty_around(&mut self, pos: Vec3<i32>) {
+        let local = chunk::to_local(pos);
+        let border_offset = |v: i32, size: usize| {
+            if v == 0 {
+                -1
+            } else if v == size as i32 - 1 {
+                1
+            } else {
+                0
+            }
+        };
+        let dx = border_offset(local.x, CHUNK_WIDTH);
+        let dy = border_offset(local.y, CHUNK_HEIGHT);
+        let dz = border_offset(local.z, CHUNK_DEPTH);
+        let chunk_pos = ChunkPos::from_block(pos);
+        for x in [0, dx] {
+            for y in [0, dy] {
+                for z in [0, dz] {
+                    if let Some(chunk) = self.chunk_mut(chunk_pos + ChunkPos::new(x, y, z)) {
+                        chunk.dirty = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Flags the chunks edited by the event to be written on the next save
+    fn mark_unsaved_on(&mut self, event: &WorldEvent) {
+        if let WorldEvent::BlockChanged { pos, .. } = event {
+            if let Some(chunk) = self.chunk_mut(ChunkPos::from_block(*pos)) {
+                chunk.modified = true;
+            }
+        }
+    }
+
+    /// Rebuilds the meshes of every chunk that changed since the last call
+    pub fn remesh_dirty(&mut self, device: &wgpu::Device, uploader: &mut Uploader) {
+        let dirty = (0..self.chunks.len())
+            .filter(|index| self.chunks[*index].dirty)
+            .collect::<Vec<_>>();
+        let reporter = self.stats.reporter();
+        for index in dirty {
+            let chunk = &self.chunks[index];
+            // Blocks in unloaded chunks are lit by the sky
+            let border = |pos| {
+                let block = self.get_block(pos).unwrap_or(BlockId::AIR);
+                let levels = LightKind::ALL.map(|kind| match kind {
+                    LightKind::Block => self.get_light(pos, kind).unwrap_or(0),
+                    LightKind::Sky => self.get_light(pos, kind).unwrap_or(MAX_LIGHT),
+                });
+                (block, levels)
+            };
+            let blocks = chunk.blocks.to_vec();
+            let meshes = reporter.time(ChunkMetric::Meshing, || {
+                Chunk::build_mesh(
+                    &blocks,
+                    chunk.blocks.meta(),
+                    &chunk.light,
+                    chunk.pos,
+                    self.lighting,
+                    border,
+                )
+            });
+            reporter.record(
+                ChunkMetric::Vertices,
+                (meshes.0.vertices.len() + meshes.1.vertices.len()) as f64,
+            );
+            let lod = reporter.time(ChunkMetric::Meshing, || {
+                lod::build_lod_mesh(&blocks, &chunk.light, chunk.pos, |pos| border(pos).1)
+            });
+            let visibility = ChunkVisibility::from_blocks(&blocks);
+            let chunk = &mut self.chunks[index];
+            chunk.visibility = visibility;
+            self.totals.remove(chunk);
+            chunk.set_meshes(meshes);
+            chunk.lod.set_meshes(lod);
+            let pool = &mut self.pool;
+            reporter.time(ChunkMetric::Upload, || chunk.upload(pool, device, uploader));
+            self.totals.add(chunk);
+        }
+        self.stats.collect();
+    }
+}
+
+/// Offsets of the chunks of the cube reaching `radius` chunks around a chunk
+fn cube(radius: i32) -> impl Iterator<Item = ChunkPos> {
+    (-radius..=radius).flat_map(move |x| {
+        (-radius..=radius)
+            .flat_map(move |y| (-radius..=radius).map(move |z| ChunkPos::new(x, y, z)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::direction::Direction;
+
+    /// Deep underground, where replacing dirt with stone changes no light
+    const CENTER: ChunkPos = ChunkPos::new(0, 8, 0);
+
+    /// The chunk at `CENTER` and its six neighbors
+    fn loaded_manager() -> (ChunkManager, tempfile::TempDir) {
+        let positions = std::iter::once(CENTER).chain(Direction::ALL.map(|dir| {
+            let offset = dir.normalized();
+            CENTER + ChunkPos::new(offset.x, offset.y, offset.z)
+        }));
+        ChunkManager::with_chunks(positions)
+    }
+
+    fn dirty_chunks(manager: &ChunkManager) -> Vec<ChunkPos> {
+        let mut dirty = manager
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.dirty)
+            .map(|chunk| chunk.pos)
+            .collect::<Vec<_>>();
+        dirty.sort_by_key(|pos| (pos.x, pos.y, pos.z));
+        dirty
+    }
+
+    #[test]
+    fn border_edit_remeshes_both_chunks() {
+        let (mut manager, _dir) = loaded_manager();
+        let pos = CENTER.to_world() + Vec3::new(CHUNK_WIDTH as i32 - 1, 8, 8);
+        assert_eq!(manager.set_block(pos, BlockId::STONE), Some(BlockId::DIRT));
+        manager.process_events();
+        assert_eq!(
+            dirty_chunks(&manager),
+            [CENTER, CENTER + ChunkPos::new(1, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn inner_edit_remeshes_its_chunk() {
+        let (mut manager, _dir) = loaded_manager();
+        let pos = CENTER.to_world() + Vec3::new(8, 8, 8);
+        manager.set_block(pos, BlockId::STONE);
+        manager.process_events();
+        assert_eq!(dirty_chunks(&manager), [CENTER]);
+    }
+
+    #[test]
+    fn events_are_kept_until_drained_once() {
+        let (mut manager, _dir) = loaded_manager();
+        let first = CENTER.to_world() + Vec3::new(8, 8, 8);
+        manager.set_block(first, BlockId::STONE);
+        let events = manager.process_events();
+        assert_eq!(
+            events[0],
+            WorldEvent::BlockChanged {
+                pos: first,
+                old: BlockId::DIRT,
+                new: BlockId::STONE,
+            }
+        );
+        // One notification for each of the loaded neighbors
+        assert_eq!(events.len(), 1 + Direction::ALL.len());
+
+        // Queued while nobody drains them, e.g. before a system subscribes
+        let second = first + Vec3::unit_x();
+        manager.set_block(second, BlockId::STONE);
+        manager.set_block(first, BlockId::DIRT);
+        let events = manager.process_events();
+        let changed = events
+            .iter()
+            .filter_map(|event| match event {
+                WorldEvent::BlockChanged { pos, .. } => Some(*pos),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(changed, [second, first]);
+        assert!(manager.process_events().is_empty());
+    }
+
+    #[test]
+    fn blocks_at_chunk_corners_on_both_sides_of_the_origin() {
+        let below = ChunkPos::new(-1, -1, -1);
+        let (mut manager, _dir) = ChunkManager::with_chunks([below, ChunkPos::ORIGIN]);
+        // World position, the chunk holding it and its position in the chunk
+        let corners = [
+            (Vec3::new(-1, -1, -1), below, Vec3::new(15, 15, 15)),
+            (Vec3::new(-16, -16, -16), below, Vec3::zero()),
+            (Vec3::new(-16, -1, -9), below, Vec3::new(0, 15, 7)),
+            (Vec3::zero(), ChunkPos::ORIGIN, Vec3::zero()),
+            (
+                Vec3::new(15, 15, 15),
+                ChunkPos::ORIGIN,
+                Vec3::new(15, 15, 15),
+            ),
+        ];
+        for (pos, chunk, local) in corners {
+            assert!(manager.set_block(pos, BlockId::GLASS).is_some());
+            assert_eq!(manager.get_block(pos), Some(BlockId::GLASS));
+            let chunk = manager.chunk(chunk).unwrap();
+            assert_eq!(chunk.get_block(local), BlockId::GLASS);
+        }
+        // One block past the corners, in chunks that aren't loaded
+        assert_eq!(manager.get_block(Vec3::new(-17, -1, -1)), None);
+        assert_eq!(manager.get_block(Vec3::new(16, 15, 15)), None);
+    }
 }
@@ -0,0 +1,246 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    time::Instant,
+};
+
+pub const CSV_PATH: &str = "chunk_stats.csv";
+/// Number of recent samples the histograms and percentiles cover
+const WINDOW: usize = 512;
+/// Buckets of a histogram, each twice as wide as the previous one.
+/// The last one also counts everything above.
+pub const BUCKETS: usize = 16;
+
+/// What is measured for every generated or remeshed chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkMetric {
+    /// Terrain generation, in milliseconds
+    Generation,
+    /// Building the opaque and transparent meshes, in milliseconds
+    Meshing,
+    /// Staging the meshes to be copied into the chunk pool, in milliseconds
+    Upload,
+    /// Vertices of both meshes
+    Vertices,
+}
+
+impl ChunkMetric {
+    pub const ALL: [ChunkMetric; 4] = [
+        ChunkMetric::Generation,
+        ChunkMetric::Meshing,
+        ChunkMetric::Upload,
+        ChunkMetric::Vertices,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChunkMetric::Generation => "generation_ms",
+            ChunkMetric::Meshing => "meshing_ms",
+            ChunkMetric::Upload => "upload_ms",
+            ChunkMetric::Vertices => "vertices",
+        }
+    }
+
+    /// Upper bound of the first bucket
+    fn first_bound(&self) -> f64 {
+        match self {
+            ChunkMetric::Vertices => 64.0,
+            _ => 0.015625,
+        }
+    }
+}
+
+/// A measure sent by a worker thread
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    metric: ChunkMetric,
+    value: f64,
+}
+
+/// Sends measures from the threads generating and meshing chunks
+#[derive(Clone)]
+pub struct ChunkReporter(Sender<Sample>);
+
+impl ChunkReporter {
+    pub fn record(&self, metric: ChunkMetric, value: f64) {
+        // The collector lives as long as the chunk manager
+        let _ = self.0.send(Sample { metric, value });
+    }
+
+    /// Runs `f` and records how long it took, in milliseconds
+    pub fn time<T>(&self, metric: ChunkMetric, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(metric, start.elapsed().as_secs_f64() * 1000.0);
+        result
+    }
+}
+
+/// Bucketed counts of the most recent samples of a metric
+pub struct Histogram {
+    first_bound: f64,
+    counts: [usize; BUCKETS],
+    window: VecDeque<f64>,
+}
+
+impl Histogram {
+    fn new(first_bound: f64) -> Self {
+        Self {
+            first_bound,
+            counts: [0; BUCKETS],
+            window: VecDeque::with_capacity(WINDOW),
+        }
+    }
+
+    fn bucket(&self, value: f64) -> usize {
+        let mut bound = self.first_bound;
+        for bucket in 0..BUCKETS - 1 {
+            if value < bound {
+                return bucket;
+            }
+            bound *= 2.0;
+        }
+        BUCKETS - 1
+    }
+
+    /// Range of values counted by the bucket, the last one is unbounded
+    pub fn bucket_range(&self, bucket: usize) -> (f64, f64) {
+        let max = self.first_bound * 2f64.powi(bucket as i32);
+        let min = if bucket == 0 { 0.0 } else { max / 2.0 };
+        if bucket == BUCKETS - 1 {
+            (min, f64::INFINITY)
+        } else {
+            (min, max)
+        }
+    }
+
+    fn push(&mut self, value: f64) {
+        if self.window.len() == WINDOW {
+            if let Some(old) = self.window.pop_front() {
+                self.counts[self.bucket(old)] -= 1;
+            }
+        }
+        self.counts[self.bucket(value)] += 1;
+        self.window.push_back(value);
+    }
+
+    pub fn counts(&self) -> &[usize; BUCKETS] {
+        &self.counts
+    }
+
+    pub fn len(&self) -> usize {
+        self.window.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.window.is_empty()
+    }
+
+    /// The sample below which `fraction` of the samples are
+    pub fn percentile(&self, fraction: f64) -> f64 {
+        if self.window.is_empty() {
+            return 0.0;
+        }
+        let mut sorted = self.window.iter().copied().collect::<Vec<_>>();
+        sorted.sort_by(f64::total_cmp);
+        let index = ((sorted.len() as f64 * fraction) as usize).min(sorted.len() - 1);
+        sorted[index]
+    }
+
+    pub fn max(&self) -> f64 {
+        self.window.iter().copied().fold(0.0, f64::max)
+    }
+}
+
+/// Rolling histograms of the time spent on each chunk
+pub struct ChunkStats {
+    histograms: Vec<Histogram>,
+    sender: Sender<Sample>,
+    /// Behind a lock only so that the chunk manager can be shared
+    /// with the meshing threads
+    receiver: Mutex<Receiver<Sample>>,
+}
+
+impl ChunkStats {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            histograms: ChunkMetric::ALL
+                .iter()
+                .map(|metric| Histogram::new(metric.first_bound()))
+                .collect(),
+            sender,
+            receiver: Mutex::new(receiver),
+        }
+    }
+
+    /// A handle worker threads record samples with
+    pub fn reporter(&self) -> ChunkReporter {
+        ChunkReporter(self.sender.clone())
+    }
+
+    /// Adds the samples reported since the last call to the histograms
+    pub fn collect(&mut self) {
+        let receiver = match self.receiver.get_mut() {
+            Ok(receiver) => receiver,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        for sample in receiver.try_iter() {
+            self.histograms[sample.metric as usize].push(sample.value);
+        }
+    }
+
+    pub fn histogram(&self, metric: ChunkMetric) -> &Histogram {
+        &self.histograms[metric as usize]
+    }
+
+    /// One line per metric with its percentiles, for the console
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for metric in ChunkMetric::ALL {
+            let histogram = self.histogram(metric);
+            if histogram.is_empty() {
+                let _ = writeln!(summary, "{}: no samples", metric.name());
+                continue;
+            }
+            let _ = writeln!(
+                summary,
+                "{}: p50 {:.3}, p95 {:.3}, max {:.3} over {} chunks",
+                metric.name(),
+                histogram.percentile(0.5),
+                histogram.percentile(0.95),
+                histogram.max(),
+                histogram.len()
+            );
+        }
+        summary.trim_end().to_owned()
+    }
+
+    /// The bucket counts of every metric, one bucket per row
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("metric,bucket_min,bucket_max,count\n");
+        for metric in ChunkMetric::ALL {
+            let histogram = self.histogram(metric);
+            for (bucket, count) in histogram.counts().iter().enumerate() {
+                let (min, max) = histogram.bucket_range(bucket);
+                let _ = writeln!(csv, "{},{},{},{}", metric.name(), min, max, count);
+            }
+        }
+        csv
+    }
+
+    /// Writes the histograms to `CSV_PATH`
+    pub fn save_csv(&self) -> Result<(), std::io::Error> {
+        std::fs::write(CSV_PATH, self.to_csv())
+    }
+}
+
+impl Default for ChunkStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,26 @@
+use vek::Vec3;
+
+use crate::block::BlockId;
+
+use super::chunk::ChunkPos;
+
+/// Something that happened to the loaded world. Events are queued as they
+/// happen and drained once per frame, after which every system interested
+/// in them gets to see the whole batch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WorldEvent {
+    /// `old` and `new` are the same block when only its metadata changed
+    BlockChanged {
+        pos: Vec3<i32>,
+        old: BlockId,
+        new: BlockId,
+    },
+    /// A block next to `pos` changed, the one at `changed`. Sent to the
+    /// loaded neighbors of every changed block, for the blocks that react.
+    NeighborChanged {
+        pos: Vec3<i32>,
+        changed: Vec3<i32>,
+    },
+    ChunkLoaded(ChunkPos),
+    ChunkUnloaded(ChunkPos),
+}
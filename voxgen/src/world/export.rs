@@ -0,0 +1,416 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde_json::json;
+use vek::Vec3;
+
+use crate::{
+    renderer::atlas::ATLAS_PNG,
+    world::chunk::{ChunkMesh, ChunkPos},
+};
+
+/// Where the export button writes the world
+pub const OBJ_EXPORT_PATH: &str = "export/world.obj";
+/// Name of the atlas image written next to exported models
+pub const ATLAS_FILE: &str = "atlas.png";
+
+/// Writes the meshes as a single Wavefront OBJ object, moving the vertices
+/// of each chunk from its origin into world space.
+pub fn write_obj<'a>(
+    out: &mut impl Write,
+    mtl_name: &str,
+    meshes: impl Iterator<Item = (ChunkPos, &'a ChunkMesh)>,
+) -> std::io::Result<()> {
+    writeln!(out, "mtllib {}", mtl_name)?;
+    writeln!(out, "o world")?;
+    writeln!(out, "usemtl atlas")?;
+    // OBJ indices are 1-based and global to the file
+    let mut first_index = 1;
+    for (pos, mesh) in meshes {
+        let origin = pos.to_world().map(|v| v as f32);
+        for vertex in &mesh.vertices {
+            let Vec3 { x, y, z } = Vec3::<f32>::from(vertex.pos()) + origin;
+            writeln!(out, "v {} {} {}", x, y, z)?;
+        }
+        for vertex in &mesh.vertices {
+            let [u, v] = vertex.uv();
+            // OBJ texture coordinates start at the bottom of the image
+            writeln!(out, "vt {} {}", u, 1.0 - v)?;
+        }
+        for face in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [face[0], face[1], face[2]].map(|i| i + first_index);
+            writeln!(out, "f {a}/{a} {b}/{b} {c}/{c}")?;
+        }
+        first_index += mesh.vertices.len() as u32;
+    }
+    Ok(())
+}
+
+pub fn write_mtl(out: &mut impl Write) -> std::io::Result<()> {
+    writeln!(out, "newmtl atlas")?;
+    writeln!(out, "Kd 1.0 1.0 1.0")?;
+    writeln!(out, "map_Kd {}", ATLAS_FILE)
+}
+
+/// Exports the meshes to `path` along with its material and atlas image,
+/// writing each chunk as it goes instead of building the file in memory.
+pub fn export_obj<'a>(
+    path: &Path,
+    meshes: impl Iterator<Item = (ChunkPos, &'a ChunkMesh)>,
+) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    if !dir.as_os_str().is_empty() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mtl_path = path.with_extension("mtl");
+    let mtl_name = mtl_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("world.mtl");
+
+    let mut obj = BufWriter::new(File::create(path)?);
+    write_obj(&mut obj, mtl_name, meshes)?;
+    obj.flush()?;
+
+    let mut mtl = BufWriter::new(File::create(&mtl_path)?);
+    write_mtl(&mut mtl)?;
+    mtl.flush()?;
+
+    std::fs::write(dir.join(ATLAS_FILE), ATLAS_PNG)
+}
+
+/// Where the glTF export button writes the world
+pub const GLB_EXPORT_PATH: &str = "export/world.glb";
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const COMPONENT_FLOAT: u32 = 5126;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const FILTER_NEAREST: u32 = 9728;
+
+/// Builds a binary glTF with one mesh per chunk, each placed by its node
+/// translation, sharing a material that embeds the atlas. `progress` is
+/// called with the number of chunks done after each chunk.
+pub fn write_glb(
+    out: &mut impl Write,
+    chunks: &[(ChunkPos, ChunkMesh)],
+    mut progress: impl FnMut(usize),
+) -> std::io::Result<()> {
+    let mut bin: Vec<u8> = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut meshes = Vec::new();
+    let mut nodes = Vec::new();
+
+    let mut push_view = |bin: &mut Vec<u8>, data: &[u8], target: Option<u32>| {
+        let offset = bin.len();
+        bin.extend_from_slice(data);
+        // Accessors require 4 byte aligned offsets
+        bin.resize(align4(bin.len()), 0);
+        let mut view = json!({ "buffer": 0, "byteOffset": offset, "byteLength": data.len() });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        buffer_views.push(view);
+        buffer_views.len() - 1
+    };
+
+    for (i, (pos, mesh)) in chunks.iter().enumerate() {
+        // Vertices are relative to the chunk, nodes move them into place
+        let origin = pos.to_world().map(|v| v as f32);
+        let positions = mesh
+            .vertices
+            .iter()
+            .map(|vertex| Vec3::from(vertex.pos()))
+            .collect::<Vec<_>>();
+        let min = positions
+            .iter()
+            .fold(Vec3::broadcast(f32::MAX), |a, b| Vec3::partial_min(a, *b));
+        let max = positions
+            .iter()
+            .fold(Vec3::broadcast(f32::MIN), |a, b| Vec3::partial_max(a, *b));
+        let positions = positions
+            .iter()
+            .flat_map(|p| p.into_array())
+            .collect::<Vec<f32>>();
+        let uvs = mesh
+            .vertices
+            .iter()
+            .flat_map(|vertex| vertex.uv())
+            .collect::<Vec<f32>>();
+
+        let position_view = push_view(
+            &mut bin,
+            bytemuck::cast_slice(&positions),
+            Some(TARGET_ARRAY_BUFFER),
+        );
+        let uv_view = push_view(
+            &mut bin,
+            bytemuck::cast_slice(&uvs),
+            Some(TARGET_ARRAY_BUFFER),
+        );
+        let index_view = push_view(
+            &mut bin,
+            bytemuck::cast_slice(&mesh.indices),
+            Some(TARGET_ELEMENT_ARRAY_BUFFER),
+        );
+
+        let first_accessor = accessors.len();
+        accessors.push(json!({
+            "bufferView": position_view,
+            "componentType": COMPONENT_FLOAT,
+            "count": mesh.vertices.len(),
+            "type": "VEC3",
+            "min": min.into_array(),
+            "max": max.into_array(),
+        }));
+        accessors.push(json!({
+            "bufferView": uv_view,
+            "componentType": COMPONENT_FLOAT,
+            "count": mesh.vertices.len(),
+            "type": "VEC2",
+        }));
+        accessors.push(json!({
+            "bufferView": index_view,
+            "componentType": COMPONENT_UNSIGNED_INT,
+            "count": mesh.indices.len(),
+            "type": "SCALAR",
+        }));
+        meshes.push(json!({
+            "primitives": [{
+                "attributes": {
+                    "POSITION": first_accessor,
+                    "TEXCOORD_0": first_accessor + 1,
+                },
+                "indices": first_accessor + 2,
+                "material": 0,
+            }],
+        }));
+        nodes.push(json!({
+            "name": format!("chunk_{}_{}_{}", pos.x, pos.y, pos.z),
+            "mesh": i,
+            "translation": origin.into_array(),
+        }));
+        progress(i + 1);
+    }
+    let image_view = push_view(&mut bin, ATLAS_PNG, None);
+
+    let document = json!({
+        "asset": { "version": "2.0", "generator": "VoxelEngine" },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": [{
+            "name": "atlas",
+            "pbrMetallicRoughness": {
+                "baseColorTexture": { "index": 0 },
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+        }],
+        "textures": [{ "sampler": 0, "source": 0 }],
+        "samplers": [{ "magFilter": FILTER_NEAREST, "minFilter": FILTER_NEAREST }],
+        "images": [{ "bufferView": image_view, "mimeType": "image/png" }],
+        "accessors": accessors,
+        "bufferViews": buffer_views,
+        "buffers": [{ "byteLength": bin.len() }],
+    });
+    let mut json = serde_json::to_vec(&document)?;
+    // Chunks are padded to 4 bytes, JSON with spaces
+    json.resize(align4(json.len()), b' ');
+
+    let total_len = 12 + 8 + json.len() + 8 + bin.len();
+    for word in [GLB_MAGIC, 2, total_len as u32] {
+        out.write_all(&word.to_le_bytes())?;
+    }
+    out.write_all(&(json.len() as u32).to_le_bytes())?;
+    out.write_all(&GLB_CHUNK_JSON.to_le_bytes())?;
+    out.write_all(&json)?;
+    out.write_all(&(bin.len() as u32).to_le_bytes())?;
+    out.write_all(&GLB_CHUNK_BIN.to_le_bytes())?;
+    out.write_all(&bin)
+}
+
+/// Writes the chunks to a `.glb` file on a background thread
+pub fn export_glb(path: PathBuf, chunks: Vec<(ChunkPos, ChunkMesh)>) {
+    std::thread::spawn(move || {
+        log::info!("Exporting {} chunks to {}", chunks.len(), path.display());
+        match write_glb_file(&path, &chunks) {
+            Ok(()) => log::info!("Exported the world to {}", path.display()),
+            Err(e) => log::error!("Failed to export the world: {}", e),
+        }
+    });
+}
+
+fn write_glb_file(path: &Path, chunks: &[(ChunkPos, ChunkMesh)]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut out = BufWriter::new(File::create(path)?);
+    write_glb(&mut out, chunks, |done| {
+        log::info!("Exported {}/{} chunks", done, chunks.len())
+    })?;
+    out.flush()
+}
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::BlockId,
+        world::{
+            chunk::{Chunk, TOTAL_CHUNK_SIZE},
+            light::{LightMap, Lighting},
+            palette::BlockMeta,
+        },
+    };
+
+    /// A stone block at the corner of chunk (1, 0, -1), centered on (16, 0, -16)
+    const GOLDEN_OBJ: &str = "\
+mtllib world.mtl
+o world
+usemtl atlas
+v 16.5 0.5 -15.5
+v 15.5 0.5 -15.5
+v 15.5 0.5 -16.5
+v 16.5 0.5 -16.5
+v 16.5 -0.5 -16.5
+v 15.5 -0.5 -16.5
+v 15.5 -0.5 -15.5
+v 16.5 -0.5 -15.5
+v 15.5 -0.5 -16.5
+v 15.5 0.5 -16.5
+v 15.5 0.5 -15.5
+v 15.5 -0.5 -15.5
+v 16.5 -0.5 -15.5
+v 16.5 0.5 -15.5
+v 16.5 0.5 -16.5
+v 16.5 -0.5 -16.5
+v 15.5 -0.5 -16.5
+v 16.5 -0.5 -16.5
+v 16.5 0.5 -16.5
+v 15.5 0.5 -16.5
+v 15.5 0.5 -15.5
+v 16.5 0.5 -15.5
+v 16.5 -0.5 -15.5
+v 15.5 -0.5 -15.5
+vt 0.1875 0.9375
+vt 0.1875 1
+vt 0.25 1
+vt 0.25 0.9375
+vt 0.1875 0.9375
+vt 0.1875 1
+vt 0.25 1
+vt 0.25 0.9375
+vt 0.1875 0.9375
+vt 0.1875 1
+vt 0.25 1
+vt 0.25 0.9375
+vt 0.1875 0.9375
+vt 0.1875 1
+vt 0.25 1
+vt 0.25 0.9375
+vt 0.1875 0.9375
+vt 0.25 0.9375
+vt 0.25 1
+vt 0.1875 1
+vt 0.1875 1
+vt 0.25 1
+vt 0.25 0.9375
+vt 0.1875 0.9375
+f 1/1 2/2 3/3
+f 3/3 4/4 1/1
+f 5/5 6/6 7/7
+f 7/7 8/8 5/5
+f 9/9 10/10 11/11
+f 11/11 12/12 9/9
+f 13/13 14/14 15/15
+f 15/15 16/16 13/13
+f 17/17 18/18 19/19
+f 19/19 20/20 17/17
+f 21/21 22/22 23/23
+f 23/23 24/24 21/21
+";
+
+    fn single_block_mesh(pos: ChunkPos) -> ChunkMesh {
+        let mut blocks = vec![BlockId::AIR; TOTAL_CHUNK_SIZE];
+        blocks[0] = BlockId::STONE;
+        let border = |_| (BlockId::AIR, [0; 2]);
+        let meta = BlockMeta::default();
+        Chunk::build_mesh(
+            &blocks,
+            &meta,
+            &LightMap::new(),
+            pos,
+            Lighting::Flat,
+            border,
+        )
+        .0
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn glb_layout() {
+        let chunks =
+            [ChunkPos::ORIGIN, ChunkPos::new(1, 0, -1)].map(|pos| (pos, single_block_mesh(pos)));
+        let mut out = Vec::new();
+        let mut done = Vec::new();
+        write_glb(&mut out, &chunks, |count| done.push(count)).unwrap();
+        assert_eq!(done, [1, 2]);
+
+        // Header, then the JSON and binary chunks, each padded to 4 bytes
+        assert_eq!(read_u32(&out, 0), GLB_MAGIC);
+        assert_eq!(read_u32(&out, 4), 2);
+        assert_eq!(read_u32(&out, 8) as usize, out.len());
+        let json_len = read_u32(&out, 12) as usize;
+        assert_eq!(read_u32(&out, 16), GLB_CHUNK_JSON);
+        assert_eq!(json_len % 4, 0);
+        let bin_start = 20 + json_len;
+        let bin_len = read_u32(&out, bin_start) as usize;
+        assert_eq!(read_u32(&out, bin_start + 4), GLB_CHUNK_BIN);
+        assert_eq!(bin_len % 4, 0);
+        assert_eq!(bin_start + 8 + bin_len, out.len());
+
+        let json: serde_json::Value = serde_json::from_slice(&out[20..bin_start]).unwrap();
+        assert_eq!(json["buffers"][0]["byteLength"], bin_len);
+        let accessors = json["accessors"].as_array().unwrap();
+        // Positions, UVs and indices for each chunk
+        assert_eq!(accessors.len(), 3 * chunks.len());
+        for (accessors, (_, mesh)) in accessors.chunks(3).zip(&chunks) {
+            assert_eq!(accessors[0]["count"], mesh.vertices.len());
+            assert_eq!(accessors[1]["count"], mesh.vertices.len());
+            assert_eq!(accessors[2]["count"], mesh.indices.len());
+        }
+        for view in json["bufferViews"].as_array().unwrap() {
+            assert_eq!(view["byteOffset"].as_u64().unwrap() % 4, 0);
+        }
+        assert_eq!(
+            json["nodes"][1]["translation"],
+            serde_json::json!([16.0, 0.0, -16.0])
+        );
+    }
+
+    #[test]
+    fn single_block_matches_the_golden_obj() {
+        let pos = ChunkPos::new(1, 0, -1);
+        let mesh = single_block_mesh(pos);
+        let mut out = Vec::new();
+        write_obj(&mut out, "world.mtl", std::iter::once((pos, &mesh))).unwrap();
+        let obj = String::from_utf8(out).unwrap();
+        assert_eq!(obj, GOLDEN_OBJ);
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+use crate::block::BlockId;
+
+use super::chunk::{Chunk, ChunkPos};
+
+/// Where `update` writes the hashes, relative to the working directory
+pub const HASHES_PATH: &str = "assets/worldgen_hashes.ron";
+/// The hashes the generator is checked against, as of the last build
+const EXPECTED_HASHES: &str = include_str!("../../../assets/worldgen_hashes.ron");
+
+/// Chunks whose terrain is hashed, spread over every quadrant, far from
+/// the origin and at the bedrock, the surface and the sky above, so that
+/// position dependent features are covered
+pub const SAMPLE_CHUNKS: [(i32, i32, i32); 14] = [
+    (0, 0, 0),
+    (1, 0, 0),
+    (0, 0, 1),
+    (-1, 0, 0),
+    (0, 0, -1),
+    (-1, 0, -1),
+    (0, 15, 0),
+    (0, 16, 0),
+    (0, -1, 0),
+    (7, 15, -3),
+    (-12, 8, 5),
+    (31, 15, 31),
+    (-1000, 15, 250),
+    (4096, 15, -4096),
+];
+
+/// The hash of the generated blocks of a chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkHash {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub hash: u64,
+}
+
+/// FNV-1a over the block ids in index order. Unlike the standard hasher
+/// it is specified, so the hashes stay the same across Rust versions.
+pub fn hash_blocks(blocks: &[BlockId]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    blocks.iter().fold(OFFSET_BASIS, |hash, block| {
+        (hash ^ u8::from(*block) as u64).wrapping_mul(PRIME)
+    })
+}
+
+/// Generates every sample chunk and hashes its blocks
+pub fn compute() -> Vec<ChunkHash> {
+    SAMPLE_CHUNKS
+        .iter()
+        .map(|&(x, y, z)| ChunkHash {
+            x,
+            y,
+            z,
+            hash: hash_blocks(&Chunk::generate(ChunkPos::new(x, y, z))),
+        })
+        .collect()
+}
+
+/// Compares the generated terrain with the checked-in hashes, returning
+/// one line per chunk that changed
+pub fn check() -> Result<(), Vec<String>> {
+    let expected: Vec<ChunkHash> = ron::from_str(EXPECTED_HASHES)
+        .map_err(|e| vec![format!("Failed to parse {}: {}", HASHES_PATH, e)])?;
+    let mismatches = compute()
+        .into_iter()
+        .filter_map(|actual| {
+            let expected = expected
+                .iter()
+                .find(|hash| (hash.x, hash.y, hash.z) == (actual.x, actual.y, actual.z));
+            match expected {
+                Some(expected) if expected.hash == actual.hash => None,
+                Some(expected) => Some(format!(
+                    "Chunk {} {} {}: expected {:016x}, generated {:016x}",
+                    actual.x, actual.y, actual.z, expected.hash, actual.hash
+                )),
+                None => Some(format!(
+                    "Chunk {} {} {}: no expected hash",
+                    actual.x, actual.y, actual.z
+                )),
+            }
+        })
+        .collect::<Vec<_>>();
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches)
+    }
+}
+
+/// Writes the hashes of the current generator to `HASHES_PATH`, for when
+/// the terrain was changed on purpose. Takes effect on the next build.
+pub fn update() -> Result<(), Box<dyn std::error::Error>> {
+    let hashes = ron::ser::to_string_pretty(&compute(), ron::ser::PrettyConfig::default())?;
+    std::fs::write(HASHES_PATH, hashes + "\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One chunk of every layer of the terrain, which all hash differently
+    const LAYERS: [((i32, i32, i32), u64); 4] = [
+        // Bedrock under dirt
+        ((0, 0, 0), 0xbbce_7e5d_27b7_f625),
+        // Dirt only
+        ((0, 8, 0), 0x1341_1b19_e515_7325),
+        // Dirt under the grass of the surface
+        ((0, 15, 0), 0xf0e3_afb8_a64b_de25),
+        // Air above the surface
+        ((0, 16, 0), 0xb93a_0c83_ce3b_6325),
+    ];
+
+    #[test]
+    fn hash_is_fnv1a() {
+        assert_eq!(hash_blocks(&[]), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash_blocks(&[BlockId::AIR]), 0xaf63_bd4c_8601_b7df);
+    }
+
+    #[test]
+    fn layers_keep_their_hashes() {
+        for ((x, y, z), expected) in LAYERS {
+            let hash = hash_blocks(&Chunk::generate(ChunkPos::new(x, y, z)));
+            assert_eq!(hash, expected, "chunk {} {} {}", x, y, z);
+        }
+        for (i, (_, a)) in LAYERS.iter().enumerate() {
+            for (_, b) in &LAYERS[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn generator_matches_checked_in_hashes() {
+        if let Err(mismatches) = check() {
+            panic!("{}", mismatches.join("\n"));
+        }
+    }
+}
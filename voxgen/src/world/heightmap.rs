@@ -0,0 +1,183 @@
+use std::collections::{BTreeSet, HashMap};
+
+use vek::Vec3;
+
+use crate::block::BlockId;
+
+use super::chunk::{Chunk, ChunkPos, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+
+/// Height of the highest block that isn't air in every column of a chunk,
+/// kept up to date as blocks are placed and broken
+#[derive(Debug, Clone)]
+pub struct Heightmap {
+    /// One above the highest block of each column, 0 for empty columns.
+    /// Indexed by `x + z * CHUNK_WIDTH`.
+    heights: [u16; CHUNK_WIDTH * CHUNK_DEPTH],
+}
+
+impl Heightmap {
+    /// Scans every column of the unpacked blocks of a chunk
+    pub fn from_blocks(blocks: &[BlockId]) -> Self {
+        let mut heightmap = Self {
+            heights: [0; CHUNK_WIDTH * CHUNK_DEPTH],
+        };
+        for x in 0..CHUNK_WIDTH as i32 {
+            for z in 0..CHUNK_DEPTH as i32 {
+                let top = (0..CHUNK_HEIGHT as i32)
+                    .rev()
+                    .find(|y| !blocks[Chunk::index_of(Vec3::new(x, *y, z))].is_air());
+                heightmap.set(x, z, top);
+            }
+        }
+        heightmap
+    }
+
+    fn index(x: i32, z: i32) -> usize {
+        x as usize + z as usize * CHUNK_WIDTH
+    }
+
+    fn set(&mut self, x: i32, z: i32, top: Option<i32>) {
+        self.heights[Self::index(x, z)] = top.map_or(0, |y| y as u16 + 1);
+    }
+
+    /// Height of the highest block of the column that isn't air, `None` if
+    /// the whole column is air. `x` and `z` are relative to the chunk.
+    pub fn height_at(&self, x: i32, z: i32) -> Option<i32> {
+        match self.heights[Self::index(x, z)] {
+            0 => None,
+            height => Some(height as i32 - 1),
+        }
+    }
+
+    /// Updates the column after the block at the position relative to the
+    /// chunk became `block`. Breaking the highest block scans the column
+    /// down from it with `block_at`, which returns the block at a height.
+    pub fn update(&mut self, pos: Vec3<i32>, block: BlockId, block_at: impl Fn(i32) -> BlockId) {
+        let top = self.height_at(pos.x, pos.z);
+        if !block.is_air() {
+            if top.is_none_or(|top| pos.y > top) {
+                self.set(pos.x, pos.z, Some(pos.y));
+            }
+        } else if top == Some(pos.y) {
+            let top = (0..pos.y).rev().find(|y| !block_at(*y).is_air());
+            self.set(pos.x, pos.z, top);
+        }
+    }
+}
+
+/// The loaded chunks stacked in every column, so that the highest block
+/// of a column can be found from the heightmaps of its chunks
+#[derive(Debug, Default)]
+pub struct Columns {
+    /// Heights of the loaded chunks of each column, by `ChunkPos::column`
+    stacks: HashMap<ChunkPos, BTreeSet<i32>>,
+}
+
+impl Columns {
+    pub fn insert(&mut self, pos: ChunkPos) {
+        self.stacks.entry(pos.column()).or_default().insert(pos.y);
+    }
+
+    pub fn remove(&mut self, pos: ChunkPos) {
+        if let Some(stack) = self.stacks.get_mut(&pos.column()) {
+            stack.remove(&pos.y);
+            if stack.is_empty() {
+                self.stacks.remove(&pos.column());
+            }
+        }
+    }
+
+    /// The loaded chunks of the column containing the chunk, from the top down
+    pub fn top_down(&self, pos: ChunkPos) -> impl Iterator<Item = ChunkPos> + '_ {
+        let column = pos.column();
+        self.stacks
+            .get(&column)
+            .into_iter()
+            .flat_map(|stack| stack.iter().rev())
+            .map(move |y| ChunkPos { y: *y, ..column })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::TOTAL_CHUNK_SIZE;
+
+    const X: i32 = 3;
+    const Z: i32 = 5;
+
+    /// The unpacked blocks of a chunk with the given blocks in the tested column
+    struct Column {
+        blocks: Vec<BlockId>,
+        heightmap: Heightmap,
+    }
+
+    impl Column {
+        fn new(placed: &[(i32, BlockId)]) -> Self {
+            let mut blocks = vec![BlockId::AIR; TOTAL_CHUNK_SIZE];
+            for (y, block) in placed {
+                blocks[Chunk::index_of(Vec3::new(X, *y, Z))] = *block;
+            }
+            let heightmap = Heightmap::from_blocks(&blocks);
+            Self { blocks, heightmap }
+        }
+
+        /// Changes a block like `Chunk::set_block` does
+        fn set(&mut self, y: i32, block: BlockId) {
+            let pos = Vec3::new(X, y, Z);
+            self.blocks[Chunk::index_of(pos)] = block;
+            let blocks = &self.blocks;
+            self.heightmap
+                .update(pos, block, |y| blocks[Chunk::index_of(Vec3::new(X, y, Z))]);
+        }
+
+        fn height(&self) -> Option<i32> {
+            self.heightmap.height_at(X, Z)
+        }
+    }
+
+    #[test]
+    fn placing_above_the_top_raises_it() {
+        let mut column = Column::new(&[(0, BlockId::BEDROCK), (4, BlockId::DIRT)]);
+        assert_eq!(column.height(), Some(4));
+        column.set(9, BlockId::STONE);
+        assert_eq!(column.height(), Some(9));
+        // Filling the gap below changes nothing
+        column.set(6, BlockId::STONE);
+        assert_eq!(column.height(), Some(9));
+        column.set(CHUNK_HEIGHT as i32 - 1, BlockId::GLASS);
+        assert_eq!(column.height(), Some(CHUNK_HEIGHT as i32 - 1));
+        // Other columns are left alone
+        assert_eq!(column.heightmap.height_at(X + 1, Z), None);
+    }
+
+    #[test]
+    fn breaking_the_top_finds_the_block_below() {
+        let mut column = Column::new(&[(2, BlockId::DIRT), (7, BlockId::STONE)]);
+        assert_eq!(column.height(), Some(7));
+        // Breaking a block under the top changes nothing
+        column.set(2, BlockId::AIR);
+        column.set(5, BlockId::DIRT);
+        assert_eq!(column.height(), Some(7));
+        // The air between the blocks is skipped
+        column.set(7, BlockId::AIR);
+        assert_eq!(column.height(), Some(5));
+        column.set(6, BlockId::AIR);
+        assert_eq!(column.height(), Some(5));
+    }
+
+    #[test]
+    fn breaking_the_last_block_empties_the_column() {
+        let mut column = Column::new(&[(6, BlockId::STONE)]);
+        column.set(6, BlockId::AIR);
+        assert_eq!(column.height(), None);
+
+        // Down to the lowest block of the chunk
+        let mut column = Column::new(&[(0, BlockId::BEDROCK)]);
+        assert_eq!(column.height(), Some(0));
+        column.set(0, BlockId::AIR);
+        assert_eq!(column.height(), None);
+        column.set(0, BlockId::DIRT);
+        assert_eq!(column.height(), Some(0));
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+
+use vek::Vec3;
+
+use crate::block::BlockId;
+
+use super::chunk_manager::ChunkManager;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Edit {
+    pub pos: Vec3<i32>,
+    pub old: BlockId,
+    pub new: BlockId,
+}
+
+/// Records block edits so they can be undone and redone.
+///
+/// Edits recorded between two calls to [`EditHistory::commit`]
+/// form a single undo step.
+pub struct EditHistory {
+    undo: VecDeque<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    pending: Vec<Edit>,
+    capacity: usize,
+}
+
+impl EditHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            undo: VecDeque::with_capacity(capacity),
+            redo: Vec::new(),
+            pending: Vec::new(),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, edit: Edit) {
+        self.pending.push(edit);
+        self.redo.clear();
+    }
+
+    /// Groups the edits recorded since the last commit into one undo step
+    pub fn commit(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        self.undo.push_back(std::mem::take(&mut self.pending));
+        if self.undo.len() > self.capacity {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn undo(&mut self, chunks: &mut ChunkManager) {
+        self.commit();
+        if let Some(step) = self.undo.pop_back() {
+            for edit in step.iter().rev() {
+                apply(chunks, edit.pos, edit.old);
+            }
+            self.redo.push(step);
+        }
+    }
+
+    pub fn redo(&mut self, chunks: &mut ChunkManager) {
+        self.commit();
+        if let Some(step) = self.redo.pop() {
+            for edit in step.iter() {
+                apply(chunks, edit.pos, edit.new);
+            }
+            self.undo.push_back(step);
+        }
+    }
+}
+
+fn apply(chunks: &mut ChunkManager, pos: Vec3<i32>, id: BlockId) {
+    if chunks.set_block(pos, id).is_none() {
+        log::warn!("Skipping edit at {:?}: chunk is not loaded", pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{border::WorldBorder, chunk::ChunkPos};
+
+    /// Two dirt chunks deep underground, side by side along x
+    fn loaded_manager() -> (ChunkManager, tempfile::TempDir) {
+        ChunkManager::with_chunks([ChunkPos::new(0, 8, 0), ChunkPos::new(1, 8, 0)])
+    }
+
+    /// The last block of the first chunk and the first of the second
+    const LEFT: Vec3<i32> = Vec3::new(15, 130, 0);
+    const RIGHT: Vec3<i32> = Vec3::new(16, 130, 0);
+
+    fn place_stone(chunks: &mut ChunkManager, history: &mut EditHistory) {
+        for pos in [LEFT, RIGHT] {
+            let old = chunks.set_block(pos, BlockId::STONE).unwrap();
+            history.record(Edit {
+                pos,
+                old,
+                new: BlockId::STONE,
+            });
+        }
+        history.commit();
+    }
+
+    #[test]
+    fn undo_across_chunk_borders() {
+        let (mut chunks, _dir) = loaded_manager();
+        let mut history = EditHistory::new(8);
+        place_stone(&mut chunks, &mut history);
+
+        history.undo(&mut chunks);
+        assert_eq!(chunks.get_block(LEFT), Some(BlockId::DIRT));
+        assert_eq!(chunks.get_block(RIGHT), Some(BlockId::DIRT));
+
+        history.redo(&mut chunks);
+        assert_eq!(chunks.get_block(LEFT), Some(BlockId::STONE));
+        assert_eq!(chunks.get_block(RIGHT), Some(BlockId::STONE));
+    }
+
+    #[test]
+    fn undo_in_an_unloaded_chunk_is_skipped() {
+        let (mut chunks, _dir) = loaded_manager();
+        let mut history = EditHistory::new(8);
+        place_stone(&mut chunks, &mut history);
+        // Leaves only the first chunk loaded
+        chunks.set_border(WorldBorder::new(0));
+        assert_eq!(chunks.get_block(RIGHT), None);
+
+        history.undo(&mut chunks);
+        assert_eq!(chunks.get_block(LEFT), Some(BlockId::DIRT));
+        assert_eq!(chunks.get_block(RIGHT), None);
+    }
+}
@@ -0,0 +1,434 @@
+use std::collections::{HashSet, VecDeque};
+
+use vek::Vec3;
+
+use super::{
+    chunk::{Chunk, ChunkPos, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH, TOTAL_CHUNK_SIZE},
+    chunk_manager::ChunkManager,
+};
+use crate::{block::BlockId, direction::Direction};
+
+pub const MAX_LIGHT: u8 = 15;
+
+/// The kinds of light every block stores a level of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+    /// Light emitted by blocks like torches
+    Block,
+    /// Light coming down from the sky, dimmed at night by the shader
+    Sky,
+}
+
+impl LightKind {
+    pub const ALL: [LightKind; 2] = [LightKind::Block, LightKind::Sky];
+
+    fn shift(self) -> u8 {
+        match self {
+            LightKind::Block => 0,
+            LightKind::Sky => 4,
+        }
+    }
+}
+
+/// How the light of the blocks around a face is applied to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lighting {
+    /// Every face has the light of the block in front of it
+    Flat,
+    /// Every corner averages the light of the blocks around it,
+    /// so light fades across faces. Slower to mesh.
+    Smooth,
+}
+
+/// The block and sky light of every block of a chunk, packed as two nibbles per byte
+#[derive(Debug, Clone)]
+pub struct LightMap {
+    data: Vec<u8>,
+}
+
+impl LightMap {
+    pub fn new() -> Self {
+        Self {
+            data: vec![0; TOTAL_CHUNK_SIZE],
+        }
+    }
+
+    /// Fills in the sky light of every block with an unobstructed view of
+    /// the sky, as if nothing was above the chunk
+    pub fn with_sky_columns(blocks: &[BlockId]) -> Self {
+        let mut light = Self::new();
+        for x in 0..CHUNK_WIDTH as i32 {
+            for z in 0..CHUNK_DEPTH as i32 {
+                for y in (0..CHUNK_HEIGHT as i32).rev() {
+                    let index = Chunk::index_of(Vec3::new(x, y, z));
+                    if blocks[index].is_opaque() {
+                        break;
+                    }
+                    light.set(index, LightKind::Sky, MAX_LIGHT);
+                }
+            }
+        }
+        light
+    }
+
+    pub fn get(&self, index: usize, kind: LightKind) -> u8 {
+        (self.data[index] >> kind.shift()) & 0x0F
+    }
+
+    pub fn set(&mut self, index: usize, kind: LightKind, level: u8) {
+        let byte = &mut self.data[index];
+        *byte = (*byte & !(0x0F << kind.shift())) | ((level & 0x0F) << kind.shift());
+    }
+
+    /// The block and sky light of a block
+    pub fn levels(&self, index: usize) -> [u8; 2] {
+        LightKind::ALL.map(|kind| self.get(index, kind))
+    }
+}
+
+impl Default for LightMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Updates the light around a block that was placed or broken.
+/// Returns the positions whose light changed.
+pub fn relight_block(world: &mut ChunkManager, pos: Vec3<i32>) -> HashSet<Vec3<i32>> {
+    let mut changed = HashSet::new();
+    for kind in LightKind::ALL {
+        let mut queue = VecDeque::new();
+        remove_light(world, kind, pos, &mut queue, &mut changed);
+        let emission = source_level(world, kind, pos);
+        if emission > 0 {
+            set_light(world, kind, pos, emission, &mut changed);
+            queue.push_back(pos);
+        }
+        spread_light(world, kind, queue, &mut changed);
+    }
+    changed
+}
+
+/// Lights a chunk that was just loaded from its own light sources
+/// and the light shining in from the loaded chunks around it.
+/// The chunk's sky columns must already be filled in, they are cut
+/// where the chunk above is loaded and shades them, and the chunk
+/// shades the columns of the chunk below in turn.
+/// Returns the positions whose light changed.
+pub fn light_chunk(world: &mut ChunkManager, chunk_pos: ChunkPos) -> HashSet<Vec3<i32>> {
+    let mut changed = HashSet::new();
+    let origin = chunk_pos.to_world();
+    let (width, height, depth) = (CHUNK_WIDTH as i32, CHUNK_HEIGHT as i32, CHUNK_DEPTH as i32);
+    if world.chunk(chunk_pos).is_none() {
+        return changed;
+    }
+
+    // Only the sky that reaches the top of the chunk comes down its columns
+    for x in 0..width {
+        for z in 0..depth {
+            let above = origin + Vec3::new(x, height, z);
+            if matches!(
+                world.get_light(above, LightKind::Sky),
+                None | Some(MAX_LIGHT)
+            ) {
+                continue;
+            }
+            for y in (0..height).rev() {
+                let pos = origin + Vec3::new(x, y, z);
+                if world.get_light(pos, LightKind::Sky) != Some(MAX_LIGHT) {
+                    break;
+                }
+                set_light(world, LightKind::Sky, pos, 0, &mut changed);
+            }
+        }
+    }
+    // Columns of the chunk below were lit as if the sky was above them
+    let mut relight = VecDeque::new();
+    for x in 0..width {
+        for z in 0..depth {
+            let (bottom, below) = (origin + Vec3::new(x, 0, z), origin + Vec3::new(x, -1, z));
+            if world.get_light(below, LightKind::Sky) == Some(MAX_LIGHT)
+                && world.get_light(bottom, LightKind::Sky) != Some(MAX_LIGHT)
+            {
+                remove_light(world, LightKind::Sky, below, &mut relight, &mut changed);
+            }
+        }
+    }
+
+    let chunk = match world.chunk(chunk_pos) {
+        Some(chunk) => chunk,
+        None => return changed,
+    };
+
+    let emitters = chunk
+        .blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, block)| block.light_emission() > 0)
+        .map(|(index, _)| origin + Chunk::local_pos_of(index))
+        .collect::<VecDeque<_>>();
+
+    // The lowest block of each column the sky shines on
+    let mut sky_bottom = [[height; CHUNK_DEPTH]; CHUNK_WIDTH];
+    for (x, column) in sky_bottom.iter_mut().enumerate() {
+        for (z, bottom) in column.iter_mut().enumerate() {
+            while *bottom > 0
+                && chunk.get_light(Vec3::new(x as i32, *bottom - 1, z as i32), LightKind::Sky)
+                    == MAX_LIGHT
+            {
+                *bottom -= 1;
+            }
+        }
+    }
+    // Sky light spreads sideways from the columns into overhangs
+    // and into the neighbors at the chunk border
+    let mut sky = relight;
+    for x in 0..width {
+        for z in 0..depth {
+            let bottom = sky_bottom[x as usize][z as usize];
+            let on_border = x == 0 || z == 0 || x == width - 1 || z == depth - 1;
+            let top = if on_border {
+                height
+            } else {
+                [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                    .iter()
+                    .map(|(dx, dz)| sky_bottom[(x + dx) as usize][(z + dz) as usize])
+                    .max()
+                    .unwrap_or(bottom)
+            };
+            for y in bottom..top.min(height) {
+                let pos = origin + Vec3::new(x, y, z);
+                sky.push_back(pos);
+                // Faces of the neighbors towards this chunk were lit before it was loaded
+                if on_border {
+                    changed.insert(pos);
+                }
+            }
+            // So were the faces of the chunks above and below, as if it was open sky
+            changed.insert(origin + Vec3::new(x, 0, z));
+            changed.insert(origin + Vec3::new(x, height - 1, z));
+        }
+    }
+
+    for &pos in &emitters {
+        let emission = world.get_block(pos).map_or(0, |b| b.light_emission());
+        set_light(world, LightKind::Block, pos, emission, &mut changed);
+    }
+    // The blocks just outside each face of the chunk
+    let border = (0..height)
+        .flat_map(|y| {
+            (0..width)
+                .flat_map(move |x| [Vec3::new(x, y, -1), Vec3::new(x, y, depth)])
+                .chain((0..depth).flat_map(move |z| [Vec3::new(-1, y, z), Vec3::new(width, y, z)]))
+        })
+        .chain((0..width).flat_map(|x| {
+            (0..depth).flat_map(move |z| [Vec3::new(x, -1, z), Vec3::new(x, height, z)])
+        }))
+        .map(|offset| origin + offset)
+        .collect::<Vec<_>>();
+    for (kind, mut queue) in [(LightKind::Block, emitters), (LightKind::Sky, sky)] {
+        for &pos in &border {
+            if world.get_light(pos, kind).unwrap_or(0) > 1 {
+                queue.push_back(pos);
+            }
+        }
+        spread_light(world, kind, queue, &mut changed);
+    }
+    changed
+}
+
+/// The light a block is lit with on its own. For sky light these are the
+/// blocks below an unloaded chunk, as what is above them is not known.
+fn source_level(world: &ChunkManager, kind: LightKind, pos: Vec3<i32>) -> u8 {
+    let block = match world.get_block(pos) {
+        Some(block) => block,
+        None => return 0,
+    };
+    match kind {
+        LightKind::Block => block.light_emission(),
+        LightKind::Sky if !block.is_opaque() && world.get_block(pos + Vec3::unit_y()).is_none() => {
+            MAX_LIGHT
+        }
+        LightKind::Sky => 0,
+    }
+}
+
+/// The level light spreads at from a block lit at `level` in the given direction.
+/// Full sky light travels straight down without getting dimmer.
+fn spread_level(kind: LightKind, level: u8, dir: Direction) -> u8 {
+    if kind == LightKind::Sky && level == MAX_LIGHT && dir == Direction::Down {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+fn set_light(
+    world: &mut ChunkManager,
+    kind: LightKind,
+    pos: Vec3<i32>,
+    level: u8,
+    changed: &mut HashSet<Vec3<i32>>,
+) {
+    if world.set_light(pos, kind, level) {
+        changed.insert(pos);
+    }
+}
+
+/// Floods light outwards from the queued positions, losing one level per block
+fn spread_light(
+    world: &mut ChunkManager,
+    kind: LightKind,
+    mut queue: VecDeque<Vec3<i32>>,
+    changed: &mut HashSet<Vec3<i32>>,
+) {
+    while let Some(pos) = queue.pop_front() {
+        let level = world.get_light(pos, kind).unwrap_or(0);
+        if level <= 1 {
+            continue;
+        }
+        for dir in Direction::ALL {
+            let neighbor = pos + dir.normalized();
+            if world
+                .get_block(neighbor)
+                .is_none_or(|block| block.is_opaque())
+            {
+                continue;
+            }
+            let next = spread_level(kind, level, dir);
+            if world
+                .get_light(neighbor, kind)
+                .is_some_and(|light| light < next)
+            {
+                set_light(world, kind, neighbor, next, changed);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+}
+
+/// Darkens the light that reached other blocks through `pos`. Blocks lit
+/// by other sources are queued in `relight` to spread into the dark area.
+fn remove_light(
+    world: &mut ChunkManager,
+    kind: LightKind,
+    pos: Vec3<i32>,
+    relight: &mut VecDeque<Vec3<i32>>,
+    changed: &mut HashSet<Vec3<i32>>,
+) {
+    let level = match world.get_light(pos, kind) {
+        Some(level) => level,
+        None => return,
+    };
+    set_light(world, kind, pos, 0, changed);
+    let mut queue = VecDeque::from([(pos, level)]);
+    while let Some((pos, level)) = queue.pop_front() {
+        for dir in Direction::ALL {
+            let neighbor = pos + dir.normalized();
+            let neighbor_level = match world.get_light(neighbor, kind) {
+                Some(light) => light,
+                None => continue,
+            };
+            if neighbor_level != 0
+                && (neighbor_level < level || neighbor_level == spread_level(kind, level, dir))
+            {
+                set_light(world, kind, neighbor, 0, changed);
+                queue.push_back((neighbor, neighbor_level));
+                // Light sources keep shining on their own
+                let emission = source_level(world, kind, neighbor);
+                if emission > 0 {
+                    set_light(world, kind, neighbor, emission, changed);
+                    relight.push_back(neighbor);
+                }
+            } else if neighbor_level > 0 {
+                relight.push_back(neighbor);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockId;
+
+    /// Deep underground, so the rooms are dark without a torch
+    const CHUNK: ChunkPos = ChunkPos::new(0, 8, 0);
+
+    fn world_pos(local: (i32, i32, i32)) -> Vec3<i32> {
+        CHUNK.to_world() + Vec3::from(local)
+    }
+
+    /// Replaces the dirt of the box between the local corners with air
+    fn carve(world: &mut ChunkManager, min: (i32, i32, i32), max: (i32, i32, i32)) {
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    world.set_block(world_pos((x, y, z)), BlockId::AIR);
+                }
+            }
+        }
+    }
+
+    fn block_light(world: &ChunkManager, local: (i32, i32, i32)) -> u8 {
+        world.get_light(world_pos(local), LightKind::Block).unwrap()
+    }
+
+    fn place(world: &mut ChunkManager, local: (i32, i32, i32), block: BlockId) {
+        world.set_block(world_pos(local), block);
+        world.process_events();
+    }
+
+    #[test]
+    fn torch_lights_a_sealed_room() {
+        let (mut world, _dir) = ChunkManager::with_chunks([CHUNK]);
+        carve(&mut world, (2, 4, 2), (6, 6, 6));
+        // A pocket behind the wall, close enough for the light to reach it
+        carve(&mut world, (9, 5, 4), (9, 5, 4));
+        let torch = (4, 5, 4);
+        place(&mut world, torch, BlockId::TORCH);
+
+        for x in 2..=6 {
+            for y in 4..=6 {
+                for z in 2..=6 {
+                    let distance = (x - torch.0).abs() + (y - torch.1).abs() + (z - torch.2).abs();
+                    assert_eq!(block_light(&world, (x, y, z)), 14 - distance as u8);
+                }
+            }
+        }
+        assert_eq!(block_light(&world, (7, 5, 4)), 0);
+        assert_eq!(block_light(&world, (9, 5, 4)), 0);
+        assert_eq!(world.get_light(world_pos(torch), LightKind::Sky), Some(0));
+
+        place(&mut world, torch, BlockId::AIR);
+        for x in 2..=6 {
+            assert_eq!(block_light(&world, (x, 5, 4)), 0);
+        }
+    }
+
+    #[test]
+    fn light_goes_around_through_a_doorway() {
+        let (mut world, _dir) = ChunkManager::with_chunks([CHUNK]);
+        carve(&mut world, (2, 4, 2), (6, 6, 6));
+        carve(&mut world, (8, 4, 2), (12, 6, 6));
+        let door = (7, 5, 2);
+        carve(&mut world, door, door);
+        place(&mut world, (4, 5, 6), BlockId::TORCH);
+
+        // 7 blocks to the doorway, then along the far side of the wall
+        assert_eq!(block_light(&world, door), 7);
+        assert_eq!(block_light(&world, (8, 5, 2)), 6);
+        assert_eq!(block_light(&world, (8, 5, 6)), 2);
+        assert_eq!(block_light(&world, (12, 5, 2)), 2);
+        assert_eq!(block_light(&world, (12, 5, 6)), 0);
+
+        place(&mut world, door, BlockId::DIRT);
+        for z in 2..=6 {
+            assert_eq!(block_light(&world, (8, 5, z)), 0);
+        }
+        assert_eq!(block_light(&world, (6, 5, 6)), 12);
+
+        place(&mut world, door, BlockId::AIR);
+        assert_eq!(block_light(&world, (8, 5, 2)), 6);
+    }
+}
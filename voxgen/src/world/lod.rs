@@ -0,0 +1,171 @@
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use vek::Vec3;
+
+use crate::{
+    block::{BlockId, BlockShape},
+    direction::Direction,
+    renderer::{buffer::ChunkBuffer, mesh::quad::Quad},
+};
+
+use super::{
+    chunk::{Chunk, ChunkMesh, ChunkPos, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH},
+    light::{LightMap, MAX_LIGHT},
+    visibility::SECTION_HEIGHT,
+};
+
+/// Cells of a half resolution chunk along each axis
+const LOD_WIDTH: usize = CHUNK_WIDTH / 2;
+const LOD_HEIGHT: usize = CHUNK_HEIGHT / 2;
+const LOD_DEPTH: usize = CHUNK_DEPTH / 2;
+const LOD_SIZE: usize = LOD_WIDTH * LOD_HEIGHT * LOD_DEPTH;
+
+/// The half resolution meshes of a chunk, drawn instead of the full ones
+/// when the chunk is far from the camera
+#[derive(Default)]
+pub struct LodMeshes {
+    pub buffer: ChunkBuffer,
+    pub mesh: ChunkMesh,
+    pub transparent_buffer: ChunkBuffer,
+    pub transparent_mesh: ChunkMesh,
+}
+
+impl LodMeshes {
+    pub fn new(meshes: (ChunkMesh, ChunkMesh)) -> Self {
+        let mut lod = Self::default();
+        lod.set_meshes(meshes);
+        lod
+    }
+
+    /// Replaces the meshes built by [`build_lod_mesh`], keeping the
+    /// buffers for them to be uploaded in place
+    pub fn set_meshes(&mut self, meshes: (ChunkMesh, ChunkMesh)) {
+        let (mesh, transparent_mesh) = meshes;
+        self.mesh = mesh;
+        self.transparent_mesh = transparent_mesh;
+    }
+
+    /// Vertices of both meshes
+    pub fn vertices(&self) -> usize {
+        self.mesh.vertices.len() + self.transparent_mesh.vertices.len()
+    }
+}
+
+/// Merges every 2x2x2 blocks of the unpacked chunk blocks into a cell.
+/// Cells are air if most of their blocks are, otherwise they take the
+/// most common of their other blocks. Plants count as air, as they
+/// would turn into solid cubes.
+pub fn downsample(blocks: &[BlockId]) -> Vec<BlockId> {
+    (0..LOD_SIZE)
+        .into_par_iter()
+        .map(|index| {
+            let cell = cell_pos(index);
+            let mut counts: Vec<(BlockId, usize)> = Vec::with_capacity(8);
+            for offset in CELL_OFFSETS {
+                let block = blocks[Chunk::index_of(cell * 2 + offset)];
+                if block.is_air() || block.shape() == BlockShape::Cross {
+                    continue;
+                }
+                match counts.iter_mut().find(|(id, _)| *id == block) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((block, 1)),
+                }
+            }
+            let solid = counts.iter().map(|(_, count)| count).sum::<usize>();
+            if solid * 2 < CELL_OFFSETS.len() {
+                return BlockId::AIR;
+            }
+            // The first block found wins ties, which is an upper one so
+            // that the surface keeps its top block like grass
+            counts
+                .iter()
+                .rev()
+                .max_by_key(|(_, count)| *count)
+                .map_or(BlockId::AIR, |(block, _)| *block)
+        })
+        .collect()
+}
+
+/// Builds the half resolution opaque and transparent meshes of the unpacked
+/// chunk blocks. Faces are twice as large and lit evenly by the brightest
+/// block of the cell in front of them, `border` returns the block and sky
+/// light levels outside the chunk by world position.
+/// Faces on the sides of the chunk are always emitted.
+pub fn build_lod_mesh(
+    blocks: &[BlockId],
+    light: &LightMap,
+    pos: ChunkPos,
+    border: impl Fn(Vec3<i32>) -> [u8; 2] + Sync,
+) -> (ChunkMesh, ChunkMesh) {
+    let cells = downsample(blocks);
+    let world_pos = pos.to_world();
+    // The brightest block and sky light of the cell
+    let cell_light = |cell: Vec3<i32>| {
+        CELL_OFFSETS
+            .into_iter()
+            .map(|offset| {
+                let local_pos = cell * 2 + offset;
+                if Chunk::is_pos_in_bounds(local_pos) {
+                    light.levels(Chunk::index_of(local_pos))
+                } else {
+                    border(world_pos + local_pos)
+                }
+            })
+            .fold([0, 0], |max, levels| {
+                [max[0].max(levels[0]), max[1].max(levels[1])]
+            })
+    };
+    let mut quads = (0..LOD_SIZE)
+        .into_par_iter()
+        .filter(|index| !cells[*index].is_air())
+        .map(|index| {
+            let block = cells[index];
+            let cell = cell_pos(index);
+            let section = cell.y as usize * 2 / SECTION_HEIGHT;
+            let quads = Direction::ALL
+                .into_iter()
+                .filter_map(|dir| {
+                    let neighbor = cell + dir.normalized();
+                    if is_cell_in_bounds(neighbor)
+                        && !block.is_face_visible(cells[cell_index(neighbor)])
+                    {
+                        return None;
+                    }
+                    let light = cell_light(neighbor).map(|level| level as f32 / MAX_LIGHT as f32);
+                    Some(Quad::doubled(block, dir, cell).with_light([light; 4]))
+                })
+                .collect::<Vec<_>>();
+            (section, block.is_transparent(), quads)
+        })
+        .collect::<Vec<_>>();
+    Chunk::group_quads(&mut quads)
+}
+
+/// The blocks of a cell relative to its lowest corner block, upper ones first
+const CELL_OFFSETS: [Vec3<i32>; 8] = [
+    Vec3::new(0, 1, 0),
+    Vec3::new(1, 1, 0),
+    Vec3::new(0, 1, 1),
+    Vec3::new(1, 1, 1),
+    Vec3::new(0, 0, 0),
+    Vec3::new(1, 0, 0),
+    Vec3::new(0, 0, 1),
+    Vec3::new(1, 0, 1),
+];
+
+fn cell_pos(index: usize) -> Vec3<i32> {
+    Vec3::new(
+        (index % LOD_WIDTH) as i32,
+        ((index / LOD_WIDTH) % LOD_HEIGHT) as i32,
+        (index / (LOD_WIDTH * LOD_HEIGHT)) as i32,
+    )
+}
+
+fn cell_index(cell: Vec3<i32>) -> usize {
+    cell.x as usize + cell.y as usize * LOD_WIDTH + cell.z as usize * LOD_WIDTH * LOD_HEIGHT
+}
+
+fn is_cell_in_bounds(cell: Vec3<i32>) -> bool {
+    (0..LOD_WIDTH as i32).contains(&cell.x)
+        && (0..LOD_HEIGHT as i32).contains(&cell.y)
+        && (0..LOD_DEPTH as i32).contains(&cell.z)
+}
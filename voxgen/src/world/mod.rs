@@ -1,2 +1,21 @@
+pub mod border;
 pub mod chunk;
 pub mod chunk_manager;
+pub mod chunk_stats;
+pub mod events;
+pub mod export;
+pub mod gen_hashes;
+pub mod heightmap;
+pub mod history;
+pub mod light;
+pub mod lod;
+pub mod palette;
+pub mod pathfinding;
+pub mod raycast;
+pub mod region;
+pub mod saver;
+pub mod spawn;
+pub mod storage;
+pub mod structure;
+pub mod ticks;
+pub mod visibility;
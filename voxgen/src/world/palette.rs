@@ -0,0 +1,444 @@
+use serde::{Deserialize, Serialize};
+
+use crate::block::BlockId;
+
+use super::chunk::{CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH, TOTAL_CHUNK_SIZE};
+
+/// Height of the sections a chunk's blocks are stored in
+pub const SECTION_HEIGHT: usize = 16;
+pub const SECTION_SIZE: usize = CHUNK_WIDTH * SECTION_HEIGHT * CHUNK_DEPTH;
+const SECTIONS: usize = CHUNK_HEIGHT / SECTION_HEIGHT;
+
+/// Blocks stored as indices into a palette of the distinct blocks, packed
+/// with as few bits as the palette needs. A container holding a single
+/// block type takes no space besides its palette.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PalettedContainer {
+    palette: Vec<BlockId>,
+    /// Bits per index, entries never straddle two words
+    bits: u32,
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl PalettedContainer {
+    pub fn new(len: usize, fill: BlockId) -> Self {
+        Self {
+            palette: vec![fill],
+            bits: 0,
+            words: Vec::new(),
+            len,
+        }
+    }
+
+    /// Packs the blocks with a palette of exactly the blocks they contain
+    pub fn from_blocks(blocks: &[BlockId]) -> Self {
+        let mut palette = Vec::new();
+        let keys = blocks
+            .iter()
+            .map(|id| match palette.iter().position(|block| block == id) {
+                Some(key) => key,
+                None => {
+                    palette.push(*id);
+                    palette.len() - 1
+                }
+            })
+            .collect::<Vec<_>>();
+        let bits = bits_for(palette.len());
+        let mut container = Self {
+            palette,
+            bits,
+            words: vec![0; word_count(blocks.len(), bits)],
+            len: blocks.len(),
+        };
+        for (index, key) in keys.into_iter().enumerate() {
+            container.set_key(index, key);
+        }
+        container
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The distinct blocks, including ones that were since replaced
+    pub fn palette(&self) -> &[BlockId] {
+        &self.palette
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn get(&self, index: usize) -> BlockId {
+        self.palette[self.key(index)]
+    }
+
+    /// Replaces the block at `index` and returns the old one
+    pub fn set(&mut self, index: usize, id: BlockId) -> BlockId {
+        let old = self.get(index);
+        if old == id {
+            return old;
+        }
+        let key = match self.palette.iter().position(|block| *block == id) {
+            Some(key) => key,
+            None => {
+                self.palette.push(id);
+                let bits = bits_for(self.palette.len());
+                if bits > self.bits {
+                    self.repack(bits);
+                }
+                self.palette.len() - 1
+            }
+        };
+        self.set_key(index, key);
+        old
+    }
+
+    /// Bytes used on the heap and by the container itself
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.palette.capacity() * std::mem::size_of::<BlockId>()
+            + self.words.capacity() * std::mem::size_of::<u64>()
+    }
+
+    /// Whether a deserialized container is consistent
+    pub fn is_valid(&self) -> bool {
+        !self.palette.is_empty()
+            && self.bits <= 8
+            && bits_for(self.palette.len()) <= self.bits
+            && self.words.len() == word_count(self.len, self.bits)
+            && (0..self.len).all(|index| self.key(index) < self.palette.len())
+    }
+
+    fn key(&self, index: usize) -> usize {
+        debug_assert!(index < self.len);
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = 64 / self.bits as usize;
+        let shift = (index % per_word) as u32 * self.bits;
+        ((self.words[index / per_word] >> shift) & mask(self.bits)) as usize
+    }
+
+    fn set_key(&mut self, index: usize, key: usize) {
+        if self.bits == 0 {
+            return;
+        }
+        let per_word = 64 / self.bits as usize;
+        let shift = (index % per_word) as u32 * self.bits;
+        let word = &mut self.words[index / per_word];
+        *word = (*word & !(mask(self.bits) << shift)) | ((key as u64) << shift);
+    }
+
+    /// Moves every index into words of `bits` bits each
+    fn repack(&mut self, bits: u32) {
+        let keys = (0..self.len)
+            .map(|index| self.key(index))
+            .collect::<Vec<_>>();
+        self.bits = bits;
+        self.words = vec![0; word_count(self.len, bits)];
+        for (index, key) in keys.into_iter().enumerate() {
+            self.set_key(index, key);
+        }
+    }
+}
+
+/// Bits needed to index a palette of the given size
+fn bits_for(palette_len: usize) -> u32 {
+    if palette_len <= 1 {
+        0
+    } else {
+        usize::BITS - (palette_len - 1).leading_zeros()
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    (1 << bits) - 1
+}
+
+fn word_count(len: usize, bits: u32) -> usize {
+    if bits == 0 {
+        0
+    } else {
+        let per_word = 64 / bits as usize;
+        len.div_ceil(per_word)
+    }
+}
+
+/// Highest metadata value a block can have
+pub const MAX_META: u8 = 15;
+
+/// 4 bits of state per block beyond its id, like the level of water.
+/// Nothing is allocated while every block has a value of 0, so chunks
+/// without blocks that have metadata don't pay for it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockMeta {
+    /// Two blocks per byte, the even index in the lower half
+    nibbles: Option<Vec<u8>>,
+}
+
+impl BlockMeta {
+    pub fn get(&self, index: usize) -> u8 {
+        match &self.nibbles {
+            Some(nibbles) => (nibbles[index / 2] >> ((index % 2) * 4)) & MAX_META,
+            None => 0,
+        }
+    }
+
+    /// Replaces the metadata of the block at `index` and returns the old one
+    pub fn set(&mut self, index: usize, value: u8) -> u8 {
+        debug_assert!(value <= MAX_META);
+        let old = self.get(index);
+        if old == value {
+            return old;
+        }
+        let nibbles = self
+            .nibbles
+            .get_or_insert_with(|| vec![0; TOTAL_CHUNK_SIZE.div_ceil(2)]);
+        let shift = (index % 2) * 4;
+        let byte = &mut nibbles[index / 2];
+        *byte = (*byte & !(MAX_META << shift)) | ((value & MAX_META) << shift);
+        // Freed once the last block with metadata lost it
+        if value == 0 && nibbles.iter().all(|byte| *byte == 0) {
+            self.nibbles = None;
+        }
+        old
+    }
+
+    /// Whether any block has metadata, and it takes memory
+    pub fn is_allocated(&self) -> bool {
+        self.nibbles.is_some()
+    }
+
+    /// Bytes used on the heap
+    pub fn memory_usage(&self) -> usize {
+        self.nibbles.as_ref().map_or(0, Vec::capacity)
+    }
+
+    /// Whether deserialized metadata is consistent
+    pub fn is_valid(&self) -> bool {
+        match &self.nibbles {
+            Some(nibbles) => {
+                nibbles.len() == TOTAL_CHUNK_SIZE.div_ceil(2)
+                    && nibbles.iter().any(|byte| *byte != 0)
+            }
+            None => true,
+        }
+    }
+}
+
+/// The blocks of a chunk, stored in paletted sections of `SECTION_HEIGHT`
+/// layers so that mostly uniform sections like air or stone stay small.
+/// Indexed like a flat array, see `compute_1d`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkBlocks {
+    sections: Vec<PalettedContainer>,
+    meta: BlockMeta,
+}
+
+impl ChunkBlocks {
+    pub fn filled(id: BlockId) -> Self {
+        Self {
+            sections: vec![PalettedContainer::new(SECTION_SIZE, id); SECTIONS],
+            meta: BlockMeta::default(),
+        }
+    }
+
+    pub fn from_slice(blocks: &[BlockId]) -> Self {
+        debug_assert_eq!(blocks.len(), TOTAL_CHUNK_SIZE);
+        let mut sections = vec![Vec::new(); SECTIONS];
+        for (index, block) in blocks.iter().enumerate() {
+            let (section, _) = locate(index);
+            sections[section].push(*block);
+        }
+        Self {
+            sections: sections
+                .iter()
+                .map(|blocks| PalettedContainer::from_blocks(blocks))
+                .collect(),
+            meta: BlockMeta::default(),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> BlockId {
+        let (section, index) = locate(index);
+        self.sections[section].get(index)
+    }
+
+    /// Replaces the block at `index`, clearing its metadata, and returns the old one
+    pub fn set(&mut self, index: usize, id: BlockId) -> BlockId {
+        self.meta.set(index, 0);
+        let (section, index) = locate(index);
+        self.sections[section].set(index, id)
+    }
+
+    pub fn get_meta(&self, index: usize) -> u8 {
+        self.meta.get(index)
+    }
+
+    /// Replaces the metadata of the block at `index` and returns the old
+    /// one. Blocks without metadata keep a value of 0.
+    pub fn set_meta(&mut self, index: usize, value: u8) -> u8 {
+        if !self.get(index).has_metadata() {
+            return 0;
+        }
+        self.meta.set(index, value)
+    }
+
+    pub fn meta(&self) -> &BlockMeta {
+        &self.meta
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = BlockId> + '_ {
+        (0..TOTAL_CHUNK_SIZE).map(|index| self.get(index))
+    }
+
+    /// Unpacks the blocks into a flat array
+    pub fn to_vec(&self) -> Vec<BlockId> {
+        self.iter().collect()
+    }
+
+    pub fn sections(&self) -> &[PalettedContainer] {
+        &self.sections
+    }
+
+    /// Bytes used on the heap and by the storage itself
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self
+                .sections
+                .iter()
+                .map(PalettedContainer::memory_usage)
+                .sum::<usize>()
+            + self.meta.memory_usage()
+    }
+
+    /// Whether deserialized blocks are consistent
+    pub fn is_valid(&self) -> bool {
+        self.sections.len() == SECTIONS
+            && self
+                .sections
+                .iter()
+                .all(|section| section.len() == SECTION_SIZE && section.is_valid())
+            && self.meta.is_valid()
+            && (!self.meta.is_allocated()
+                || (0..TOTAL_CHUNK_SIZE)
+                    .all(|index| self.meta.get(index) == 0 || self.get(index).has_metadata()))
+    }
+}
+
+/// The section a chunk index is in and its index inside the section
+fn locate(index: usize) -> (usize, usize) {
+    let x = index % CHUNK_WIDTH;
+    let y = (index / CHUNK_WIDTH) % CHUNK_HEIGHT;
+    let z = index / (CHUNK_WIDTH * CHUNK_HEIGHT);
+    (
+        y / SECTION_HEIGHT,
+        x + (y % SECTION_HEIGHT) * CHUNK_WIDTH + z * CHUNK_WIDTH * SECTION_HEIGHT,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_grow_with_the_palette() {
+        let mut container = PalettedContainer::new(SECTION_SIZE, BlockId::from_raw(0));
+        assert_eq!(container.bits(), 0);
+        // Bits per index once the palette has 2, 3, ... 17 blocks
+        let expected_bits = [1, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 5];
+        let mut expected = vec![BlockId::from_raw(0); SECTION_SIZE];
+        for (id, bits) in (1..=16).zip(expected_bits) {
+            // Each block lands on several indices spread over the words
+            for index in (id as usize * 7..SECTION_SIZE).step_by(97) {
+                let block = BlockId::from_raw(id);
+                assert_eq!(container.set(index, block), expected[index]);
+                expected[index] = block;
+            }
+            assert_eq!(container.palette().len(), id as usize + 1);
+            assert_eq!(container.bits(), bits);
+            for (index, block) in expected.iter().enumerate() {
+                assert_eq!(container.get(index), *block, "index {}", index);
+            }
+            assert!(container.is_valid());
+        }
+    }
+
+    #[test]
+    fn packs_blocks_with_their_palette() {
+        let blocks = (0..SECTION_SIZE)
+            .map(|index| BlockId::from_raw((index * 31 % 17) as u8))
+            .collect::<Vec<_>>();
+        let container = PalettedContainer::from_blocks(&blocks);
+        assert_eq!(container.palette().len(), 17);
+        assert_eq!(container.bits(), 5);
+        for (index, block) in blocks.iter().enumerate() {
+            assert_eq!(container.get(index), *block);
+        }
+        let uniform = PalettedContainer::from_blocks(&[BlockId::STONE; SECTION_SIZE]);
+        assert_eq!(uniform.bits(), 0);
+        assert_eq!(uniform.get(SECTION_SIZE - 1), BlockId::STONE);
+    }
+
+    #[test]
+    fn meta_is_allocated_only_while_set() {
+        let mut meta = BlockMeta::default();
+        assert!(!meta.is_allocated());
+        assert_eq!(meta.set(10, 0), 0);
+        assert!(!meta.is_allocated());
+        assert_eq!(meta.set(10, 3), 0);
+        assert_eq!(meta.set(11, MAX_META), 0);
+        assert!(meta.is_allocated());
+        assert_eq!((meta.get(10), meta.get(11), meta.get(12)), (3, MAX_META, 0));
+        assert_eq!(meta.set(10, 0), 3);
+        assert!(meta.is_allocated());
+        assert_eq!(meta.set(11, 0), MAX_META);
+        assert!(!meta.is_allocated());
+        assert_eq!(meta.memory_usage(), 0);
+    }
+
+    #[test]
+    fn blocks_without_metadata_allocate_none() {
+        let mut blocks = ChunkBlocks::filled(BlockId::DIRT);
+        assert_eq!(blocks.set_meta(0, 5), 0);
+        assert_eq!(blocks.get_meta(0), 0);
+        assert!(!blocks.meta().is_allocated());
+        // Replacing a block clears its metadata
+        blocks.set(1, BlockId::WATER);
+        blocks.set_meta(1, 4);
+        assert!(blocks.meta().is_allocated());
+        blocks.set(1, BlockId::DIRT);
+        assert_eq!(blocks.get_meta(1), 0);
+        assert!(!blocks.meta().is_allocated());
+    }
+
+    #[test]
+    fn meta_survives_a_save_round_trip() {
+        let mut blocks = ChunkBlocks::filled(BlockId::AIR);
+        for (index, level) in [(0, 1), (77, 7), (TOTAL_CHUNK_SIZE - 1, 15)] {
+            blocks.set(index, BlockId::WATER);
+            blocks.set_meta(index, level);
+        }
+        let bytes = bincode::serialize(&blocks).unwrap();
+        let loaded: ChunkBlocks = bincode::deserialize(&bytes).unwrap();
+        assert!(loaded.is_valid());
+        assert!(loaded.meta().is_allocated());
+        for index in 0..TOTAL_CHUNK_SIZE {
+            assert_eq!(loaded.get(index), blocks.get(index));
+            assert_eq!(loaded.get_meta(index), blocks.get_meta(index));
+        }
+        assert_eq!(loaded.get_meta(77), 7);
+
+        let empty = ChunkBlocks::filled(BlockId::DIRT);
+        let loaded: ChunkBlocks =
+            bincode::deserialize(&bincode::serialize(&empty).unwrap()).unwrap();
+        assert!(loaded.is_valid());
+        assert!(!loaded.meta().is_allocated());
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use vek::Vec3;
+
+/// Most cells a search expands before giving up, enough to cover the
+/// ground within a few dozen blocks of the start
+pub const MAX_SEARCHED: usize = 8192;
+/// Extra cost of climbing a block, so that flat paths are preferred
+const CLIMB_COST: u32 = 1;
+
+/// Whether a body two blocks tall can stand with its feet in the cell:
+/// the block below is solid and the cell and the one above it are free.
+/// `is_solid` returns `None` for unloaded blocks, which are never walkable.
+pub fn is_walkable(pos: Vec3<i32>, is_solid: impl Fn(Vec3<i32>) -> Option<bool>) -> bool {
+    is_solid(pos - Vec3::unit_y()) == Some(true)
+        && is_solid(pos) == Some(false)
+        && is_solid(pos + Vec3::unit_y()) == Some(false)
+}
+
+/// The walkable cell in the column of `pos` closest to its height, at
+/// most `range` blocks above or below it
+pub fn floor_near(
+    pos: Vec3<i32>,
+    range: i32,
+    is_solid: impl Fn(Vec3<i32>) -> Option<bool>,
+) -> Option<Vec3<i32>> {
+    (0..=range)
+        .flat_map(|dy| [dy, -dy])
+        .map(|dy| pos + Vec3::unit_y() * dy)
+        .find(|cell| is_walkable(*cell, &is_solid))
+}
+
+/// The walkable cells next to `pos` along x and z, one step up or down
+/// at most. Climbing needs room above the head before moving over, and
+/// dropping needs room for the head above the lower cell.
+fn neighbors(
+    pos: Vec3<i32>,
+    is_solid: &impl Fn(Vec3<i32>) -> Option<bool>,
+) -> impl Iterator<Item = Vec3<i32>> + '_ {
+    let offsets = [
+        Vec3::unit_x(),
+        -Vec3::unit_x(),
+        Vec3::unit_z(),
+        -Vec3::unit_z(),
+    ];
+    offsets
+        .into_iter()
+        .flat_map(move |offset| [0, 1, -1].map(|dy| pos + offset + Vec3::unit_y() * dy))
+        .filter(move |next| {
+            let head_room = match next.y - pos.y {
+                1 => pos + Vec3::unit_y() * 2,
+                -1 => *next + Vec3::unit_y() * 2,
+                _ => *next,
+            };
+            is_solid(head_room) == Some(false) && is_walkable(*next, is_solid)
+        })
+}
+
+/// Cost of the cheapest path from `pos` to `goal` if nothing was in the
+/// way. Climbing moves along x or z too, so only those are counted.
+fn estimate(pos: Vec3<i32>, goal: Vec3<i32>) -> u32 {
+    pos.x.abs_diff(goal.x) + pos.z.abs_diff(goal.z)
+}
+
+/// Finds the shortest walk with A* between two cells the feet are in,
+/// moving along x and z and climbing or dropping a block at a time.
+/// Returns the cells to walk through after `start`, ending with `goal`,
+/// or `None` when `goal` can't be reached within [`MAX_SEARCHED`] cells.
+pub fn find_path(
+    start: Vec3<i32>,
+    goal: Vec3<i32>,
+    is_solid: impl Fn(Vec3<i32>) -> Option<bool>,
+) -> Option<Vec<Vec3<i32>>> {
+    if !is_walkable(goal, &is_solid) {
+        return None;
+    }
+    let mut costs = HashMap::from([(start, 0)]);
+    let mut came_from = HashMap::new();
+    let mut closed = HashSet::new();
+    // Ties go to the cells closer to the goal, then to the lowest
+    // coordinates so that the same grid always gives the same path
+    let mut open = BinaryHeap::from([Reverse((
+        estimate(start, goal),
+        estimate(start, goal),
+        start.into_tuple(),
+    ))]);
+    while let Some(Reverse((_, _, pos))) = open.pop() {
+        let pos = Vec3::from(pos);
+        if pos == goal {
+            let mut path = Vec::new();
+            let mut cell = goal;
+            while cell != start {
+                path.push(cell);
+                cell = came_from[&cell];
+            }
+            path.reverse();
+            return Some(path);
+        }
+        // Cells are queued again when a cheaper way to them is found
+        if !closed.insert(pos) {
+            continue;
+        }
+        if closed.len() > MAX_SEARCHED {
+            return None;
+        }
+        let cost = costs[&pos];
+        for next in neighbors(pos, &is_solid) {
+            let climb = if next.y > pos.y { CLIMB_COST } else { 0 };
+            let next_cost = cost + 1 + climb;
+            if let Some(known) = costs.get(&next) {
+                if *known <= next_cost {
+                    continue;
+                }
+            }
+            costs.insert(next, next_cost);
+            came_from.insert(next, pos);
+            let remaining = estimate(next, goal);
+            open.push(Reverse((
+                next_cost + remaining,
+                remaining,
+                next.into_tuple(),
+            )));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A hand built grid of blocks, loaded from -8 to 16 along x and z and
+    /// from -8 to 8 along y, with a floor whose top is at y = 0
+    struct Grid {
+        solid: HashSet<Vec3<i32>>,
+    }
+
+    impl Grid {
+        fn floor() -> Self {
+            let solid = (-8..=16)
+                .flat_map(|x| (-8..=16).map(move |z| Vec3::new(x, 0, z)))
+                .collect();
+            Self { solid }
+        }
+
+        fn is_solid(&self) -> impl Fn(Vec3<i32>) -> Option<bool> + '_ {
+            |pos| {
+                let loaded = (-8..=16).contains(&pos.x)
+                    && (-8..=16).contains(&pos.z)
+                    && (-8..=8).contains(&pos.y);
+                loaded.then(|| self.solid.contains(&pos))
+            }
+        }
+
+        fn path(&self, start: Vec3<i32>, goal: Vec3<i32>) -> Option<Vec<Vec3<i32>>> {
+            find_path(start, goal, self.is_solid())
+        }
+    }
+
+    /// Every step moves to a walkable cell next to the previous one
+    fn assert_walkable(grid: &Grid, start: Vec3<i32>, path: &[Vec3<i32>]) {
+        let mut from = start;
+        for &cell in path {
+            let step = cell - from;
+            assert_eq!(step.x.abs() + step.z.abs(), 1, "{:?} to {:?}", from, cell);
+            assert!(step.y.abs() <= 1, "{:?} to {:?}", from, cell);
+            assert!(is_walkable(cell, grid.is_solid()), "{:?}", cell);
+            from = cell;
+        }
+    }
+
+    #[test]
+    fn walks_straight_on_flat_ground() {
+        let grid = Grid::floor();
+        let path = grid.path(Vec3::new(0, 1, 0), Vec3::new(5, 1, 0)).unwrap();
+        let expected = (1..=5).map(|x| Vec3::new(x, 1, 0)).collect::<Vec<_>>();
+        assert_eq!(path, expected);
+        assert_eq!(
+            grid.path(Vec3::new(0, 1, 0), Vec3::new(0, 1, 0)),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn climbs_stairs() {
+        let mut grid = Grid::floor();
+        // Three steps up along +x, each as wide as the floor
+        for step in 1..=3 {
+            for z in -8..=16 {
+                for y in 1..=step {
+                    grid.solid.insert(Vec3::new(2 + step, y, z));
+                }
+            }
+        }
+        let start = Vec3::new(0, 1, 0);
+        let top = Vec3::new(5, 4, 0);
+        let path = grid.path(start, top).unwrap();
+        assert_eq!(
+            path,
+            [
+                Vec3::new(1, 1, 0),
+                Vec3::new(2, 1, 0),
+                Vec3::new(3, 2, 0),
+                Vec3::new(4, 3, 0),
+                top,
+            ]
+        );
+        // And back down
+        let back = grid.path(top, start).unwrap();
+        assert_walkable(&grid, top, &back);
+        assert_eq!(back.len(), 5);
+    }
+
+    #[test]
+    fn routes_around_a_pit() {
+        let mut grid = Grid::floor();
+        // Down to the bottom of the loaded area
+        for x in 3..=4 {
+            for z in -1..=1 {
+                grid.solid.remove(&Vec3::new(x, 0, z));
+            }
+        }
+        let start = Vec3::new(0, 1, 0);
+        let path = grid.path(start, Vec3::new(7, 1, 0)).unwrap();
+        assert_walkable(&grid, start, &path);
+        // Around either side of the pit, 2 blocks out and back
+        assert_eq!(path.len(), 7 + 4);
+        assert!(path
+            .iter()
+            .all(|cell| !((3..=4).contains(&cell.x) && (-1..=1).contains(&cell.z))));
+    }
+
+    #[test]
+    fn steps_down_into_a_shallow_pit() {
+        let mut grid = Grid::floor();
+        // A single block deep, which is walked through instead of around
+        for x in 3..=4 {
+            for z in -8..=16 {
+                grid.solid.remove(&Vec3::new(x, 0, z));
+                grid.solid.insert(Vec3::new(x, -1, z));
+            }
+        }
+        let start = Vec3::new(0, 1, 0);
+        let path = grid.path(start, Vec3::new(7, 1, 0)).unwrap();
+        assert_walkable(&grid, start, &path);
+        assert_eq!(path.len(), 7);
+        assert_eq!(path[2], Vec3::new(3, 0, 0));
+    }
+
+    #[test]
+    fn unreachable_goals_return_none() {
+        let mut grid = Grid::floor();
+        // A wall two blocks high all around the goal
+        let goal = Vec3::new(10, 1, 10);
+        for dx in -2i32..=2 {
+            for dz in -2i32..=2 {
+                if dx.abs() == 2 || dz.abs() == 2 {
+                    for y in 1..=2 {
+                        grid.solid.insert(goal + Vec3::new(dx, y - 1, dz));
+                    }
+                }
+            }
+        }
+        let start = Vec3::new(0, 1, 0);
+        assert_eq!(grid.path(start, goal), None);
+        // Inside a block, in the air and outside the loaded area
+        assert_eq!(grid.path(start, Vec3::new(5, 0, 5)), None);
+        assert_eq!(grid.path(start, Vec3::new(5, 3, 5)), None);
+        assert_eq!(grid.path(start, Vec3::new(40, 1, 0)), None);
+    }
+}
@@ -0,0 +1,132 @@
+use vek::Vec3;
+
+use crate::{block::BlockId, direction::Direction};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    /// World position of the block that was hit
+    pub block_pos: Vec3<i32>,
+    pub block: BlockId,
+    /// Face of the block the ray entered through, `None` when the ray
+    /// starts inside the block
+    pub face: Option<Direction>,
+    pub distance: f32,
+}
+
+/// Walks the voxel grid along the ray (Amanatides & Woo) and returns the
+/// first non-air block reported by `block_at`.
+///
+/// Blocks are centered on integer coordinates, so the block at `(0, 0, 0)`
+/// spans from `-0.5` to `0.5` on every axis.
+pub fn raycast(
+    origin: Vec3<f32>,
+    dir: Vec3<f32>,
+    max_distance: f32,
+    block_at: impl Fn(Vec3<i32>) -> Option<BlockId>,
+) -> Option<RaycastHit> {
+    if dir.magnitude_squared() == 0.0 {
+        return None;
+    }
+    let dir = dir.normalized();
+    // Shift into a grid where the cell boundaries lie on integers
+    let start = origin + 0.5;
+    let mut cell = start.map(|v| v.floor() as i32);
+    let step = dir.map(|v| if v > 0.0 { 1 } else { -1 });
+    let delta = dir.map(|v| {
+        if v == 0.0 {
+            f32::INFINITY
+        } else {
+            1.0 / v.abs()
+        }
+    });
+    let mut next = Vec3::new(
+        boundary_distance(start.x, dir.x),
+        boundary_distance(start.y, dir.y),
+        boundary_distance(start.z, dir.z),
+    );
+    let mut distance = 0.0;
+    // Direction the ray came from when it entered `cell`, none for the
+    // cell it starts in
+    let mut face = None;
+
+    while distance <= max_distance {
+        if let Some(block) = block_at(cell).filter(|block| !block.is_air()) {
+            return Some(RaycastHit {
+                block_pos: cell,
+                block,
+                face,
+                distance,
+            });
+        }
+        if next.x < next.y && next.x < next.z {
+            distance = next.x;
+            next.x += delta.x;
+            cell.x += step.x;
+            face = if step.x > 0 {
+                Some(Direction::Left)
+            } else {
+                Some(Direction::Right)
+            };
+        } else if next.y < next.z {
+            distance = next.y;
+            next.y += delta.y;
+            cell.y += step.y;
+            face = if step.y > 0 {
+                Some(Direction::Down)
+            } else {
+                Some(Direction::Up)
+            };
+        } else {
+            distance = next.z;
+            next.z += delta.z;
+            cell.z += step.z;
+            face = if step.z > 0 {
+                Some(Direction::Back)
+            } else {
+                Some(Direction::Front)
+            };
+        }
+    }
+    None
+}
+
+/// Distance along the ray until it crosses the next cell boundary on one axis.
+fn boundary_distance(start: f32, dir: f32) -> f32 {
+    if dir > 0.0 {
+        (start.floor() + 1.0 - start) / dir
+    } else if dir < 0.0 {
+        (start - start.floor()) / -dir
+    } else {
+        f32::INFINITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stone below y = 0, air above
+    fn ground(pos: Vec3<i32>) -> Option<BlockId> {
+        Some(if pos.y < 0 {
+            BlockId::STONE
+        } else {
+            BlockId::AIR
+        })
+    }
+
+    #[test]
+    fn hits_the_top_of_the_ground() {
+        let hit = raycast(Vec3::new(0.0, 3.0, 0.0), -Vec3::unit_y(), 8.0, ground).unwrap();
+        assert_eq!(hit.block_pos, Vec3::new(0, -1, 0));
+        assert_eq!(hit.face, Some(Direction::Up));
+        assert_eq!(hit.distance, 3.5);
+    }
+
+    #[test]
+    fn no_face_when_starting_inside_a_block() {
+        let hit = raycast(Vec3::new(0.0, -2.0, 0.0), Vec3::unit_y(), 8.0, ground).unwrap();
+        assert_eq!(hit.block_pos, Vec3::new(0, -2, 0));
+        assert_eq!(hit.face, None);
+        assert_eq!(hit.distance, 0.0);
+    }
+}
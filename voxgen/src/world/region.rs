@@ -0,0 +1,425 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use super::chunk::ChunkPos;
+
+/// Width, height and depth of a region, in chunks. Regions of columns were
+/// 32 chunks wide, but a cube that size would need a 256 KiB index of which
+/// only the few layers around the surface are ever used. 16 keeps the index
+/// at 32 KiB and a region at the same 4096 chunks as a 64 by 64 area.
+pub const REGION_SIZE: i32 = 16;
+const CHUNKS_PER_REGION: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+/// Identifies region files, the index follows it
+const MAGIC: &[u8; 4] = b"VXRG";
+/// The magic and an offset and a length per chunk, as little endian u32
+const HEADER_SIZE: u64 = MAGIC.len() as u64 + CHUNKS_PER_REGION as u64 * 8;
+/// A region is compacted once its stale blobs take more space than the
+/// live ones, and at least this many bytes
+const MIN_COMPACTED_WASTE: u64 = 1 << 20;
+
+/// Where the blob of a chunk is in its region file, a length of 0 when missing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Entry {
+    offset: u32,
+    length: u32,
+}
+
+impl Entry {
+    fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Whether the blob lies after the header and inside the file
+    fn is_valid(&self, file_len: u64) -> bool {
+        self.offset as u64 >= HEADER_SIZE && self.offset as u64 + self.length as u64 <= file_len
+    }
+}
+
+/// Chunks stored by cubes of `REGION_SIZE` chunks a side in a single file.
+/// A file starts with an index of where each chunk is, followed by the chunks
+/// compressed with LZ4. Rewriting a chunk appends it and updates the index,
+/// the space of the old copy is reclaimed when the region gets compacted.
+pub struct RegionStore {
+    dir: PathBuf,
+    /// The index of every region read so far, by path. Reads and writes may
+    /// come from several threads, they hold the lock one at a time so that
+    /// an index never points into a region compacted since.
+    indices: Mutex<HashMap<PathBuf, Vec<Entry>>>,
+}
+
+impl RegionStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            indices: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock_indices(&self) -> MutexGuard<'_, HashMap<PathBuf, Vec<Entry>>> {
+        self.indices.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn region_path(&self, pos: ChunkPos) -> PathBuf {
+        let x = pos.x.div_euclid(REGION_SIZE);
+        let y = pos.y.div_euclid(REGION_SIZE);
+        let z = pos.z.div_euclid(REGION_SIZE);
+        self.dir.join(format!("r.{}.{}.{}.region", x, y, z))
+    }
+
+    /// Index of the chunk in the index of its region
+    fn slot(pos: ChunkPos) -> usize {
+        let x = pos.x.rem_euclid(REGION_SIZE);
+        let y = pos.y.rem_euclid(REGION_SIZE);
+        let z = pos.z.rem_euclid(REGION_SIZE);
+        (x + y * REGION_SIZE + z * REGION_SIZE * REGION_SIZE) as usize
+    }
+
+    /// Returns the stored data of the chunk, or `None` if it was never written
+    /// or its entry is corrupted
+    pub fn read_chunk(&self, pos: ChunkPos) -> Option<Vec<u8>> {
+        let path = self.region_path(pos);
+        let mut indices = self.lock_indices();
+        let mut file = File::open(&path).ok()?;
+        let entry = match indices.get(&path) {
+            Some(index) => Ok(index[Self::slot(pos)]),
+            None => read_index(&mut file).map(|index| {
+                let entry = index[Self::slot(pos)];
+                indices.insert(path.clone(), index);
+                entry
+            }),
+        };
+        let result = entry
+            .and_then(|entry| read_blob(&mut file, entry))
+            .and_then(|blob| match blob {
+                Some(blob) => lz4_flex::decompress_size_prepended(&blob)
+                    .map(Some)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+                None => Ok(None),
+            });
+        result
+            .map_err(|e| {
+                log::warn!(
+                    "Failed to read chunk {:?} from {}: {}",
+                    pos,
+                    path.display(),
+                    e
+                )
+            })
+            .ok()
+            .flatten()
+    }
+
+    /// Appends the data of the chunk to its region and points the index at it,
+    /// compacting the region when it holds too many stale copies
+    pub fn write_chunk(&self, pos: ChunkPos, data: &[u8]) -> io::Result<()> {
+        let path = self.region_path(pos);
+        let mut indices = self.lock_indices();
+        // Taken out so that a failed write reads the index from the file again
+        let index = indices.remove(&path);
+        let index = self.append_chunk(&path, index, Self::slot(pos), data)?;
+        indices.insert(path, index);
+        Ok(())
+    }
+
+    /// Appends the chunk at `slot` to the region at `path`, returning the
+    /// updated index
+    fn append_chunk(
+        &self,
+        path: &Path,
+        index: Option<Vec<Entry>>,
+        slot: usize,
+        data: &[u8],
+    ) -> io::Result<Vec<Entry>> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let read = match index {
+            Some(index) => Ok(index),
+            None => read_index(&mut file),
+        };
+        let mut index = match read {
+            Ok(index) => index,
+            Err(e) => {
+                if file.metadata()?.len() > 0 {
+                    log::warn!(
+                        "Corrupted region {}, starting it over: {}",
+                        path.display(),
+                        e
+                    );
+                }
+                let index = vec![Entry::default(); CHUNKS_PER_REGION];
+                file.set_len(0)?;
+                file.seek(SeekFrom::Start(0))?;
+                file.write_all(&encode_header(&index))?;
+                index
+            }
+        };
+
+        let blob = lz4_flex::compress_prepend_size(data);
+        let offset = file.seek(SeekFrom::End(0))?;
+        let entry = Entry {
+            offset: u32::try_from(offset).map_err(|_| io::Error::other("region file is full"))?,
+            length: blob.len() as u32,
+        };
+        // The blob is written before the index so a crash leaves the old copy
+        file.write_all(&blob)?;
+        file.seek(SeekFrom::Start(MAGIC.len() as u64 + slot as u64 * 8))?;
+        file.write_all(&encode_entry(entry))?;
+        index[slot] = entry;
+
+        let file_len = offset + blob.len() as u64;
+        let live = live_bytes(&index);
+        let waste = file_len - HEADER_SIZE - live;
+        if waste > live && waste >= MIN_COMPACTED_WASTE {
+            drop(file);
+            return compact_file(path);
+        }
+        Ok(index)
+    }
+
+    /// Rewrites the region of the chunk with only the chunks its index points at
+    pub fn compact(&self, pos: ChunkPos) -> io::Result<()> {
+        let path = self.region_path(pos);
+        let mut indices = self.lock_indices();
+        indices.remove(&path);
+        let index = compact_file(&path)?;
+        indices.insert(path, index);
+        Ok(())
+    }
+
+    /// Size of the file of the chunk's region and the bytes of the chunks its
+    /// index points at, `None` if the region doesn't exist
+    pub fn region_usage(&self, pos: ChunkPos) -> Option<(u64, u64)> {
+        let mut file = File::open(self.region_path(pos)).ok()?;
+        let index = read_index(&mut file).ok()?;
+        Some((file.metadata().ok()?.len(), live_bytes(&index)))
+    }
+}
+
+fn live_bytes(index: &[Entry]) -> u64 {
+    index.iter().map(|entry| entry.length as u64).sum()
+}
+
+fn encode_entry(entry: Entry) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&entry.offset.to_le_bytes());
+    bytes[4..].copy_from_slice(&entry.length.to_le_bytes());
+    bytes
+}
+
+fn encode_header(index: &[Entry]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(MAGIC);
+    for entry in index {
+        header.extend_from_slice(&encode_entry(*entry));
+    }
+    header
+}
+
+fn read_index(file: &mut File) -> io::Result<Vec<Entry>> {
+    let mut header = vec![0; HEADER_SIZE as usize];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a region file",
+        ));
+    }
+    let index = header[MAGIC.len()..]
+        .chunks_exact(8)
+        .map(|bytes| Entry {
+            offset: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            length: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        })
+        .collect();
+    Ok(index)
+}
+
+/// Reads the compressed blob the entry points at. Entries pointing
+/// outside of the file are reported as errors.
+fn read_blob(file: &mut File, entry: Entry) -> io::Result<Option<Vec<u8>>> {
+    if entry.is_empty() {
+        return Ok(None);
+    }
+    if !entry.is_valid(file.metadata()?.len()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("bad offset {} or length {}", entry.offset, entry.length),
+        ));
+    }
+    let mut blob = vec![0; entry.length as usize];
+    file.seek(SeekFrom::Start(entry.offset as u64))?;
+    file.read_exact(&mut blob)?;
+    Ok(Some(blob))
+}
+
+/// Copies the live blobs into a new file which then replaces the region,
+/// returning its index. Corrupted entries are dropped.
+fn compact_file(path: &Path) -> io::Result<Vec<Entry>> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let old_index = read_index(&mut file)?;
+    let mut index = vec![Entry::default(); CHUNKS_PER_REGION];
+    let mut blobs = Vec::new();
+    let mut offset = HEADER_SIZE;
+    for (slot, entry) in old_index.iter().enumerate() {
+        if entry.is_empty() {
+            continue;
+        }
+        if !entry.is_valid(file_len) {
+            log::warn!("Dropping corrupted chunk {} of {}", slot, path.display());
+            continue;
+        }
+        let blob = read_blob(&mut file, *entry)?.unwrap_or_default();
+        index[slot] = Entry {
+            offset: offset as u32,
+            length: blob.len() as u32,
+        };
+        offset += blob.len() as u64;
+        blobs.push(blob);
+    }
+
+    // Written next to the region first so a crash never leaves a truncated file
+    let tmp = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    writer.write_all(&encode_header(&index))?;
+    for blob in &blobs {
+        writer.write_all(blob)?;
+    }
+    writer.flush()?;
+    drop(writer);
+    drop(file);
+    std::fs::rename(&tmp, path)?;
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes LZ4 can't shrink, different for every chunk and version
+    fn chunk_data(pos: ChunkPos, version: u32, len: usize) -> Vec<u8> {
+        let mut state = (pos.x as u32).wrapping_mul(73_856_093)
+            ^ (pos.y as u32).wrapping_mul(19_349_663)
+            ^ (pos.z as u32).wrapping_mul(83_492_791)
+            ^ version.wrapping_mul(2_654_435_761)
+            | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RegionStore::new(dir.path());
+        // Across several regions, on both sides of the origin
+        let chunks = [
+            ChunkPos::new(0, 0, 0),
+            ChunkPos::new(15, 15, 15),
+            ChunkPos::new(16, 0, 0),
+            ChunkPos::new(-1, -1, -1),
+            ChunkPos::new(-17, 3, 40),
+        ];
+        for (i, pos) in chunks.iter().enumerate() {
+            store
+                .write_chunk(*pos, &chunk_data(*pos, 0, 100 + i * 1000))
+                .unwrap();
+        }
+        for (i, pos) in chunks.iter().enumerate() {
+            assert_eq!(
+                store.read_chunk(*pos),
+                Some(chunk_data(*pos, 0, 100 + i * 1000))
+            );
+        }
+        assert_eq!(store.read_chunk(ChunkPos::new(1, 0, 0)), None);
+        assert_eq!(store.read_chunk(ChunkPos::new(100, 0, 0)), None);
+    }
+
+    #[test]
+    fn reads_the_index_once_per_region() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RegionStore::new(dir.path());
+        let first = ChunkPos::new(0, 0, 0);
+        let second = ChunkPos::new(1, 0, 0);
+        store
+            .write_chunk(first, &chunk_data(first, 0, 100))
+            .unwrap();
+        store
+            .write_chunk(second, &chunk_data(second, 0, 100))
+            .unwrap();
+
+        // Only the index on disk is broken, the blobs are still in place
+        let path = dir.path().join("r.0.0.0.region");
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.write_all(b"XXXX").unwrap();
+        drop(file);
+        assert_eq!(store.read_chunk(first), Some(chunk_data(first, 0, 100)));
+        assert_eq!(store.read_chunk(second), Some(chunk_data(second, 0, 100)));
+        assert_eq!(RegionStore::new(dir.path()).read_chunk(first), None);
+    }
+
+    #[test]
+    fn rewrites_compact_the_region() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RegionStore::new(dir.path());
+        let chunks = (0..300)
+            .map(|i| {
+                ChunkPos::new(
+                    i % REGION_SIZE,
+                    i / REGION_SIZE % REGION_SIZE,
+                    i / (REGION_SIZE * REGION_SIZE),
+                )
+            })
+            .collect::<Vec<_>>();
+        let len = 4096;
+        for pos in &chunks {
+            store.write_chunk(*pos, &chunk_data(*pos, 0, len)).unwrap();
+        }
+        let (mut previous_len, live) = store.region_usage(chunks[0]).unwrap();
+        assert_eq!(previous_len, HEADER_SIZE + live);
+
+        let mut compactions = 0;
+        for version in 1..4 {
+            for pos in &chunks {
+                store
+                    .write_chunk(*pos, &chunk_data(*pos, version, len))
+                    .unwrap();
+                let (file_len, live) = store.region_usage(*pos).unwrap();
+                assert!(file_len - HEADER_SIZE - live <= live.max(MIN_COMPACTED_WASTE));
+                if file_len < previous_len {
+                    compactions += 1;
+                    // Only the live copies are left after the rename
+                    assert_eq!(file_len, HEADER_SIZE + live);
+                }
+                previous_len = file_len;
+            }
+        }
+        assert!(compactions > 0);
+        assert!(!dir.path().join("r.0.0.0.tmp").exists());
+
+        // The entries point at the last copy of every chunk
+        for pos in &chunks {
+            assert_eq!(store.read_chunk(*pos), Some(chunk_data(*pos, 3, len)));
+        }
+        store.compact(chunks[0]).unwrap();
+        for pos in &chunks {
+            assert_eq!(store.read_chunk(*pos), Some(chunk_data(*pos, 3, len)));
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::ui::hud::HudSender;
+
+use super::{
+    chunk::ChunkPos,
+    palette::ChunkBlocks,
+    storage::{WorldMeta, WorldStorage},
+};
+
+/// Saves waiting for the IO thread past which queueing more blocks the caller
+pub const MAX_QUEUED_SAVES: usize = 256;
+/// How long exiting waits for the queued saves to be written
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+enum SaveJob {
+    /// Writes the snapshot of the chunk if it is still the one of this generation
+    Chunk(ChunkPos, u64),
+    Meta(WorldMeta),
+    /// Posts the message once the jobs queued before it are written
+    Notify(HudSender, String),
+}
+
+/// Copies of the chunks waiting to be written, with the generation of
+/// the latest copy so that older jobs for the same chunk are skipped
+#[derive(Default)]
+struct Snapshots {
+    chunks: HashMap<ChunkPos, (u64, ChunkBlocks)>,
+    next_generation: u64,
+}
+
+/// State shared with the IO thread
+struct Shared {
+    storage: Arc<WorldStorage>,
+    snapshots: Mutex<Snapshots>,
+    /// Jobs sent and not written yet
+    queued: AtomicUsize,
+}
+
+impl Shared {
+    fn snapshots(&self) -> MutexGuard<'_, Snapshots> {
+        match self.snapshots.lock() {
+            Ok(snapshots) => snapshots,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn run(&self, job: SaveJob) {
+        match job {
+            SaveJob::Chunk(pos, generation) => {
+                let blocks = match self.snapshots().chunks.get(&pos) {
+                    Some((latest, blocks)) if *latest == generation => blocks.clone(),
+                    // A newer snapshot is queued behind this job
+                    _ => return,
+                };
+                self.storage.save_chunks(vec![(pos, blocks)]);
+                let mut snapshots = self.snapshots();
+                if matches!(snapshots.chunks.get(&pos), Some((latest, _)) if *latest == generation)
+                {
+                    snapshots.chunks.remove(&pos);
+                }
+            }
+            SaveJob::Meta(meta) => self.storage.save_meta(&meta),
+            SaveJob::Notify(hud, message) => hud.post(message, log::Level::Info),
+        }
+    }
+}
+
+/// Writes chunks and the world metadata on a dedicated thread so saving
+/// never stalls a frame. Chunks are queued as snapshots of their blocks,
+/// which the thread compresses into their region. Until then loading
+/// a queued chunk returns its snapshot instead of the outdated file.
+pub struct ChunkSaver {
+    shared: Arc<Shared>,
+    sender: SyncSender<SaveJob>,
+}
+
+impl ChunkSaver {
+    pub fn new(storage: Arc<WorldStorage>) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(MAX_QUEUED_SAVES);
+        let shared = Arc::new(Shared {
+            storage,
+            snapshots: Mutex::new(Snapshots::default()),
+            queued: AtomicUsize::new(0),
+        });
+        let thread_shared = Arc::clone(&shared);
+        std::thread::Builder::new()
+            .name("chunk-saver".to_owned())
+            .spawn(move || Self::run(&thread_shared, receiver))
+            .expect("Failed to spawn the chunk saving thread");
+        Self { shared, sender }
+    }
+
+    fn run(shared: &Shared, receiver: Receiver<SaveJob>) {
+        // Ends once the saver is dropped and the queue is empty
+        for job in receiver {
+            shared.run(job);
+            shared.queued.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    pub fn storage(&self) -> &Arc<WorldStorage> {
+        &self.shared.storage
+    }
+
+    /// Queues the chunks to be written. When `MAX_QUEUED_SAVES` jobs are
+    /// already waiting this blocks until the IO thread catches up.
+    pub fn queue_chunks(&self, chunks: Vec<(ChunkPos, ChunkBlocks)>) {
+        for (pos, blocks) in chunks {
+            let generation = {
+                let mut snapshots = self.shared.snapshots();
+                let generation = snapshots.next_generation;
+                snapshots.next_generation += 1;
+                snapshots.chunks.insert(pos, (generation, blocks));
+                generation
+            };
+            self.send(SaveJob::Chunk(pos, generation));
+        }
+    }
+
+    /// Queues the metadata to be written after the chunks queued before it
+    pub fn queue_meta(&self, meta: WorldMeta) {
+        self.send(SaveJob::Meta(meta));
+    }
+
+    /// Posts the message to the feed after the saves queued before it
+    pub fn queue_notification(&self, hud: HudSender, message: impl Into<String>) {
+        self.send(SaveJob::Notify(hud, message.into()));
+    }
+
+    fn send(&self, job: SaveJob) {
+        self.shared.queued.fetch_add(1, Ordering::AcqRel);
+        if let Err(mpsc::SendError(job)) = self.sender.send(job) {
+            log::error!("The chunk saving thread stopped, saving on this thread");
+            self.shared.run(job);
+            self.shared.queued.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Returns the blocks of the chunk, from its queued snapshot if it
+    /// wasn't written yet, or `None` if it was never edited
+    pub fn load_chunk(&self, pos: ChunkPos) -> Option<ChunkBlocks> {
+        if let Some((_, blocks)) = self.shared.snapshots().chunks.get(&pos) {
+            return Some(blocks.clone());
+        }
+        self.shared.storage.load_chunk(pos)
+    }
+
+    /// Number of saves waiting to be written
+    pub fn queued(&self) -> usize {
+        self.shared.queued.load(Ordering::Acquire)
+    }
+
+    /// Waits until every queued save is written, at most `timeout`.
+    /// Returns the number of saves left on timeout.
+    pub fn flush(&self, timeout: Duration) -> Result<(), usize> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let queued = self.queued();
+            if queued == 0 {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(queued);
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use vek::Vec3;
+
+use crate::block::BlockId;
+
+use super::chunk::SURFACE_HEIGHT;
+
+/// Blocks from the origin the search for a spawn reaches along each axis
+pub const SPAWN_SEARCH_RADIUS: i32 = 128;
+/// Most blocks the surface may rise or drop to a neighboring column for
+/// the column to be spawned on, steeper ones are cliffs
+const MAX_STEP: i32 = 1;
+/// Blocks between the surface and the feet of a player at the spawn
+const SPAWN_CLEARANCE: f32 = 2.0;
+
+/// Finds where the feet of new players are placed: a couple of blocks above
+/// the grass column nearest to the origin that isn't on a cliff. `surface`
+/// returns the height and block of the top of a column, `None` if it has no
+/// ground. Columns are searched ring by ring up to `SPAWN_SEARCH_RADIUS`,
+/// if none fits, like in an ocean, the spawn is above the origin instead.
+pub fn find_spawn(surface: impl Fn(i32, i32) -> Option<(i32, BlockId)>) -> Vec3<f32> {
+    let found = (0..=SPAWN_SEARCH_RADIUS)
+        .flat_map(ring)
+        .find_map(|(x, z)| spawn_height(&surface, x, z).map(|height| (x, height, z)));
+    let (x, height, z) = match found {
+        Some(column) => column,
+        None => {
+            let height = surface(0, 0).map_or(SURFACE_HEIGHT, |(height, _)| height);
+            log::warn!(
+                "No grass to spawn on within {} blocks of the origin",
+                SPAWN_SEARCH_RADIUS
+            );
+            (0, height, 0)
+        }
+    };
+    // Blocks are centered on integer coordinates
+    Vec3::new(x as f32, height as f32 + 0.5 + SPAWN_CLEARANCE, z as f32)
+}
+
+/// Height of the surface of the column if it can be spawned on: grass, not
+/// water or sand, and no higher or lower than `MAX_STEP` from its neighbors
+fn spawn_height(
+    surface: &impl Fn(i32, i32) -> Option<(i32, BlockId)>,
+    x: i32,
+    z: i32,
+) -> Option<i32> {
+    let (height, block) = surface(x, z)?;
+    if block != BlockId::GRASS {
+        return None;
+    }
+    let flat =
+        [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .iter()
+            .all(|(dx, dz)| match surface(x + dx, z + dz) {
+                Some((neighbor, _)) => (neighbor - height).abs() <= MAX_STEP,
+                None => false,
+            });
+    flat.then_some(height)
+}
+
+/// The columns at `radius` blocks from the origin along either axis, the
+/// origin itself at a radius of 0
+fn ring(radius: i32) -> Vec<(i32, i32)> {
+    if radius == 0 {
+        return vec![(0, 0)];
+    }
+    (-radius..radius)
+        .flat_map(|i| [(i, -radius), (radius, i), (-i, radius), (-radius, -i)])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const HEIGHT: i32 = 60;
+    /// Feet above a column at `HEIGHT`
+    const FEET: f32 = HEIGHT as f32 + 0.5 + SPAWN_CLEARANCE;
+
+    fn flat(block: BlockId) -> impl Fn(i32, i32) -> Option<(i32, BlockId)> {
+        move |_, _| Some((HEIGHT, block))
+    }
+
+    #[test]
+    fn spawns_above_the_origin_on_flat_grass() {
+        assert_eq!(find_spawn(flat(BlockId::GRASS)), Vec3::new(0.0, FEET, 0.0));
+    }
+
+    #[test]
+    fn water_and_sand_are_skipped() {
+        for block in [BlockId::WATER, BlockId::SAND] {
+            // A pond around the origin, in grass from 3 blocks away
+            let spawn = find_spawn(|x: i32, z: i32| {
+                let block = if x.abs().max(z.abs()) < 3 {
+                    block
+                } else {
+                    BlockId::GRASS
+                };
+                Some((HEIGHT, block))
+            });
+            assert_eq!(spawn, Vec3::new(-3.0, FEET, -3.0));
+        }
+    }
+
+    #[test]
+    fn cliffs_are_skipped() {
+        // The ground rises by 2 blocks from x = 1
+        let spawn =
+            find_spawn(|x, _| Some((if x >= 1 { HEIGHT + 2 } else { HEIGHT }, BlockId::GRASS)));
+        assert_eq!(spawn, Vec3::new(-1.0, FEET, -1.0));
+        // A single step is walkable
+        let spawn =
+            find_spawn(|x, _| Some((if x >= 1 { HEIGHT + 1 } else { HEIGHT }, BlockId::GRASS)));
+        assert_eq!(spawn, Vec3::new(0.0, FEET, 0.0));
+    }
+
+    #[test]
+    fn columns_next_to_missing_ground_are_skipped() {
+        let spawn = find_spawn(|x, z| (x != 1 || z != 0).then_some((HEIGHT, BlockId::GRASS)));
+        assert_eq!(spawn, Vec3::new(-1.0, FEET, -1.0));
+    }
+
+    #[test]
+    fn falls_back_above_the_origin_within_the_radius() {
+        let farthest = Cell::new(0);
+        let ocean = |x: i32, z: i32| {
+            farthest.set(farthest.get().max(x.abs()).max(z.abs()));
+            Some((HEIGHT, BlockId::WATER))
+        };
+        assert_eq!(find_spawn(ocean), Vec3::new(0.0, FEET, 0.0));
+        assert_eq!(farthest.get(), SPAWN_SEARCH_RADIUS);
+
+        // Without any ground, at the height of the generated surface
+        let spawn = find_spawn(|_, _| None);
+        let feet = SURFACE_HEIGHT as f32 + 0.5 + SPAWN_CLEARANCE;
+        assert_eq!(spawn, Vec3::new(0.0, feet, 0.0));
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::BlockId;
+
+use super::{chunk::ChunkPos, palette::ChunkBlocks, region::RegionStore};
+
+/// Directory the world is saved to, relative to the working directory
+pub const WORLD_DIR: &str = "world";
+/// Written as the first byte of every file and chunk so the format can evolve
+pub const FORMAT_VERSION: u8 = 6;
+
+/// Information about the world that isn't stored in chunks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldMeta {
+    /// Picks the randomly ticked blocks, the terrain doesn't depend on it
+    pub seed: u64,
+    /// Progress through the day when the world was saved
+    pub time_of_day: f32,
+    /// Where the feet of new players are placed
+    pub spawn: [f32; 3],
+    /// Missing until the player was saved once
+    pub player: Option<PlayerRecord>,
+    /// Blocks with a scheduled tick, in the order they run
+    pub block_ticks: Vec<[i32; 3]>,
+    /// Broken blocks lying around, waiting to be collected
+    pub drops: Vec<DropRecord>,
+}
+
+/// The state of the player restored when the world is loaded again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    /// Position of the feet
+    pub feet: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub hotbar_slot: u8,
+    pub flying: bool,
+    /// The block of every hotbar slot
+    pub hotbar: Vec<BlockId>,
+    /// The collected blocks and their counts
+    pub inventory: Vec<(BlockId, u32)>,
+}
+
+/// A stack of broken blocks lying in the world
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DropRecord {
+    /// Center of the cube
+    pub pos: [f32; 3],
+    pub block: BlockId,
+    pub count: u32,
+}
+
+/// A seed for a new world, different every time
+pub fn new_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64);
+    // Spreads the few changing bits of the clock over the whole seed
+    nanos.wrapping_mul(0x9e37_79b9_7f4a_7c15).rotate_left(29)
+}
+
+/// Reads and writes the world directory. Only chunks that were edited
+/// are stored, everything else is generated again when loaded.
+pub struct WorldStorage {
+    dir: PathBuf,
+    regions: RegionStore,
+    /// Saves may run on a background thread, so writes are serialized
+    write_lock: Mutex<()>,
+}
+
+impl WorldStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        Self {
+            regions: RegionStore::new(dir.join("region")),
+            dir,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn load_meta(&self) -> Option<WorldMeta> {
+        self.read(&self.dir.join("level.dat"))
+    }
+
+    pub fn save_meta(&self, meta: &WorldMeta) {
+        let _guard = self.write_lock.lock();
+        self.write(&self.dir.join("level.dat"), meta);
+    }
+
+    /// Returns the saved blocks of the chunk, or `None` if it was never edited
+    /// or can't be read, in which case it is generated again
+    pub fn load_chunk(&self, pos: ChunkPos) -> Option<ChunkBlocks> {
+        let data = self.regions.read_chunk(pos)?;
+        let blocks = match data.split_first() {
+            Some((&FORMAT_VERSION, data)) => bincode::deserialize::<ChunkBlocks>(data)
+                .map_err(|e| log::warn!("Failed to read chunk {:?}: {}", pos, e))
+                .ok()?,
+            _ => {
+                log::warn!("Unsupported format version of chunk {:?}", pos);
+                return None;
+            }
+        };
+        if !blocks.is_valid() {
+            log::warn!("Corrupted chunk {:?}, generating it again", pos);
+            return None;
+        }
+        Some(blocks)
+    }
+
+    /// Writes the chunks into their region files
+    pub fn save_chunks(&self, chunks: Vec<(ChunkPos, ChunkBlocks)>) {
+        for (pos, blocks) in chunks {
+            let mut data = vec![FORMAT_VERSION];
+            let result = bincode::serialize_into(&mut data, &blocks)
+                .map_err(|e| e.to_string())
+                .and_then(|_| {
+                    self.regions
+                        .write_chunk(pos, &data)
+                        .map_err(|e| e.to_string())
+                });
+            if let Err(e) = result {
+                log::error!("Failed to save chunk {:?}: {}", pos, e);
+            }
+        }
+    }
+
+    fn read<T: serde::de::DeserializeOwned>(&self, path: &PathBuf) -> Option<T> {
+        let mut reader = BufReader::new(File::open(path).ok()?);
+        let mut version = [0; 1];
+        reader.read_exact(&mut version).ok()?;
+        if version[0] != FORMAT_VERSION {
+            log::warn!(
+                "Unsupported format version {} in {}",
+                version[0],
+                path.display()
+            );
+            return None;
+        }
+        bincode::deserialize_from(reader)
+            .map_err(|e| log::warn!("Failed to read {}: {}", path.display(), e))
+            .ok()
+    }
+
+    fn write<T: Serialize>(&self, path: &PathBuf, value: &T) {
+        if let Err(e) = write_file(path, value) {
+            log::error!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn write_file<T: Serialize>(path: &PathBuf, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Write to a temporary file first so a crash never leaves a truncated file
+    let tmp = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    writer.write_all(&[FORMAT_VERSION])?;
+    bincode::serialize_into(&mut writer, value)?;
+    writer.flush()?;
+    drop(writer);
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::TOTAL_CHUNK_SIZE;
+
+    fn meta() -> WorldMeta {
+        WorldMeta {
+            seed: 0x1234_5678_9abc_def0,
+            time_of_day: 0.8,
+            spawn: [0.5, 256.0, 0.5],
+            player: Some(PlayerRecord {
+                feet: [10.5, 300.0, -4.25],
+                yaw: 90.0,
+                pitch: -12.0,
+                hotbar_slot: 3,
+                flying: true,
+                hotbar: vec![BlockId::STONE, BlockId::GRASS],
+                inventory: vec![(BlockId::DIRT, 12)],
+            }),
+            block_ticks: vec![[1, 250, -3]],
+            drops: vec![DropRecord {
+                pos: [2.5, 256.25, 2.5],
+                block: BlockId::SAND,
+                count: 4,
+            }],
+        }
+    }
+
+    #[test]
+    fn meta_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = WorldStorage::new(dir.path());
+        assert_eq!(storage.load_meta(), None);
+        storage.save_meta(&meta());
+        assert_eq!(storage.load_meta(), Some(meta()));
+        // Like after a restart
+        assert_eq!(WorldStorage::new(dir.path()).load_meta(), Some(meta()));
+    }
+
+    #[test]
+    fn chunk_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut edited = ChunkBlocks::filled(BlockId::STONE);
+        edited.set(0, BlockId::AIR);
+        edited.set(TOTAL_CHUNK_SIZE - 1, BlockId::GLASS);
+        edited.set_meta(5, 3);
+        let chunks = vec![
+            (ChunkPos::new(0, 15, 0), edited),
+            (
+                ChunkPos::new(-3, -1, 40),
+                ChunkBlocks::filled(BlockId::WATER),
+            ),
+        ];
+        WorldStorage::new(dir.path()).save_chunks(chunks.clone());
+
+        let storage = WorldStorage::new(dir.path());
+        for (pos, blocks) in &chunks {
+            let loaded = storage.load_chunk(*pos).unwrap();
+            assert_eq!(loaded.to_vec(), blocks.to_vec());
+            assert_eq!(loaded.get_meta(5), blocks.get_meta(5));
+        }
+        assert!(storage.load_chunk(ChunkPos::new(1, 15, 0)).is_none());
+    }
+
+    #[test]
+    fn other_versions_are_generated_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = WorldStorage::new(dir.path());
+        storage.save_meta(&meta());
+        let path = dir.path().join("level.dat");
+        let mut data = std::fs::read(&path).unwrap();
+        data[0] = FORMAT_VERSION - 1;
+        std::fs::write(&path, data).unwrap();
+        assert_eq!(storage.load_meta(), None);
+
+        let pos = ChunkPos::new(0, 0, 0);
+        let mut data = vec![FORMAT_VERSION - 1];
+        bincode::serialize_into(&mut data, &ChunkBlocks::filled(BlockId::DIRT)).unwrap();
+        storage.regions.write_chunk(pos, &data).unwrap();
+        assert!(storage.load_chunk(pos).is_none());
+    }
+}
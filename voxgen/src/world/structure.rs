@@ -0,0 +1,442 @@
+use std::{collections::HashSet, error::Error, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use vek::Vec3;
+
+use crate::{block::BlockId, renderer::WorldRenderer};
+
+use super::{chunk::ChunkPos, chunk_manager::ChunkManager, history::Edit};
+
+/// Where structures saved from the clipboard are written
+pub const STRUCTURES_DIR: &str = "structures";
+
+/// A block model that can be pasted into the world
+#[derive(Debug, Clone)]
+pub struct Structure {
+    /// Size in blocks along the world axes
+    pub size: Vec3<usize>,
+    /// Indexed by `x + y * size.x + z * size.x * size.y`
+    pub blocks: Vec<BlockId>,
+}
+
+/// Maps MagicaVoxel palette colors to blocks. Colors without an exact
+/// entry use the block whose reference color is the closest.
+pub struct BlockMapping {
+    pub exact: Vec<([u8; 3], BlockId)>,
+    pub reference: Vec<([u8; 3], BlockId)>,
+    /// Used when the file has no palette
+    pub fallback: BlockId,
+}
+
+impl Default for BlockMapping {
+    fn default() -> Self {
+        Self {
+            exact: Vec::new(),
+            reference: vec![
+                ([134, 96, 67], BlockId::DIRT),
+                ([95, 159, 53], BlockId::GRASS),
+                ([125, 125, 125], BlockId::STONE),
+                ([40, 40, 40], BlockId::BEDROCK),
+            ],
+            fallback: BlockId::STONE,
+        }
+    }
+}
+
+impl BlockMapping {
+    pub fn block(&self, color: [u8; 3]) -> BlockId {
+        if let Some((_, block)) = self.exact.iter().find(|(c, _)| *c == color) {
+            return *block;
+        }
+        let distance = |c: &[u8; 3]| {
+            (0..3)
+                .map(|i| (c[i] as i32 - color[i] as i32).pow(2))
+                .sum::<i32>()
+        };
+        self.reference
+            .iter()
+            .min_by_key(|(c, _)| distance(c))
+            .map_or(self.fallback, |(_, block)| *block)
+    }
+}
+
+#[derive(Debug)]
+pub enum VoxError {
+    Io(std::io::Error),
+    /// The file isn't a MagicaVoxel file or is truncated
+    Invalid(&'static str),
+}
+
+impl std::fmt::Display for VoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VoxError::Io(e) => write!(f, "{}", e),
+            VoxError::Invalid(reason) => write!(f, "invalid .vox file: {}", reason),
+        }
+    }
+}
+
+impl From<std::io::Error> for VoxError {
+    fn from(e: std::io::Error) -> Self {
+        VoxError::Io(e)
+    }
+}
+
+/// Versions of the MagicaVoxel format that can be read
+const VOX_VERSIONS: [u32; 2] = [150, 200];
+
+/// Reads little endian values from the file contents
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], VoxError> {
+        if self.data.len() < len {
+            return Err(VoxError::Invalid("unexpected end of file"));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, VoxError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// How a structure is stored on disk, `vek` types can't be serialized
+#[derive(Serialize, Deserialize)]
+struct StructureFile {
+    size: [u32; 3],
+    blocks: Vec<BlockId>,
+}
+
+fn structure_file(name: &str) -> PathBuf {
+    PathBuf::from(STRUCTURES_DIR).join(format!("{}.bin", name))
+}
+
+/// Chunks overlapping the box from `min` to `max`, included, that aren't loaded
+pub fn unloaded_chunks(world: &WorldRenderer, min: Vec3<i32>, max: Vec3<i32>) -> Vec<ChunkPos> {
+    let (min, max) = (ChunkPos::from_block(min), ChunkPos::from_block(max));
+    (min.x..=max.x)
+        .flat_map(|x| (min.y..=max.y).map(move |y| (x, y)))
+        .flat_map(|(x, y)| (min.z..=max.z).map(move |z| ChunkPos::new(x, y, z)))
+        .filter(|pos| world.chunk_manager().chunk(*pos).is_none())
+        .collect()
+}
+
+impl Structure {
+    /// Copies the blocks of the box between two opposite corners, included.
+    /// Blocks outside of loaded chunks are air.
+    pub fn copy(world: &WorldRenderer, a: Vec3<i32>, b: Vec3<i32>) -> Self {
+        let (min, max) = (Vec3::partial_min(a, b), Vec3::partial_max(a, b));
+        let size = (max - min + 1).map(|v| v as usize);
+        let mut blocks = Vec::with_capacity(size.product());
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    let block = world.block_at_world(Vec3::new(x, y, z));
+                    blocks.push(block.unwrap_or(BlockId::AIR));
+                }
+            }
+        }
+        Self { size, blocks }
+    }
+
+    /// The same structure turned by `quarter_turns` quarters of a turn
+    /// clockwise, seen from above. Only the positions of the blocks change.
+    pub fn rotated(&self, quarter_turns: u32) -> Self {
+        let mut rotated = self.clone();
+        for _ in 0..quarter_turns % 4 {
+            let size = rotated.size;
+            let mut blocks = vec![BlockId::AIR; size.product()];
+            // The x axis becomes the z axis and the z axis the reversed x axis
+            let new_size = Vec3::new(size.z, size.y, size.x);
+            for (index, block) in rotated.blocks.iter().enumerate() {
+                let pos = rotated.position(index);
+                let (x, z) = (size.z - 1 - pos.z, pos.x);
+                blocks[x + pos.y * new_size.x + z * new_size.x * new_size.y] = *block;
+            }
+            rotated = Self {
+                size: new_size,
+                blocks,
+            };
+        }
+        rotated
+    }
+
+    /// Position of the block at `index` relative to the minimum corner
+    fn position(&self, index: usize) -> Vec3<usize> {
+        Vec3::new(
+            index % self.size.x,
+            (index / self.size.x) % self.size.y,
+            index / (self.size.x * self.size.y),
+        )
+    }
+
+    /// Number of blocks that aren't air
+    pub fn block_count(&self) -> usize {
+        self.blocks.iter().filter(|block| !block.is_air()).count()
+    }
+
+    /// Loads a structure saved in `STRUCTURES_DIR`
+    pub fn load(name: &str) -> Result<Self, Box<dyn Error>> {
+        let file: StructureFile = bincode::deserialize(&std::fs::read(structure_file(name))?)?;
+        let size = Vec3::<u32>::from(file.size).map(|v| v as usize);
+        if file.blocks.len() != size.product() {
+            return Err("the size doesn't match the number of blocks".into());
+        }
+        Ok(Self {
+            size,
+            blocks: file.blocks,
+        })
+    }
+
+    pub fn save(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        let file = StructureFile {
+            size: self.size.map(|v| v as u32).into_array(),
+            blocks: self.blocks.clone(),
+        };
+        std::fs::create_dir_all(STRUCTURES_DIR)?;
+        std::fs::write(structure_file(name), bincode::serialize(&file)?)?;
+        Ok(())
+    }
+
+    pub fn load_vox(path: &str, mapping: &BlockMapping) -> Result<Self, VoxError> {
+        Self::parse_vox(&std::fs::read(path)?, mapping)
+    }
+
+    /// Parses the first model of a MagicaVoxel file. MagicaVoxel is Z-up,
+    /// so its Z axis becomes the world Y axis.
+    pub fn parse_vox(data: &[u8], mapping: &BlockMapping) -> Result<Self, VoxError> {
+        let mut reader = Reader { data };
+        if reader.bytes(4)? != b"VOX " {
+            return Err(VoxError::Invalid("missing VOX header"));
+        }
+        if !VOX_VERSIONS.contains(&reader.u32()?) {
+            return Err(VoxError::Invalid("unsupported version"));
+        }
+        if reader.bytes(4)? != b"MAIN" {
+            return Err(VoxError::Invalid("missing MAIN chunk"));
+        }
+        let main_content = reader.u32()? as usize;
+        let _main_children = reader.u32()?;
+        reader.bytes(main_content)?;
+
+        let mut size = None;
+        let mut voxels = None;
+        let mut palette = None;
+        let mut skipped = HashSet::new();
+        while !reader.data.is_empty() {
+            let id = reader.bytes(4)?;
+            let content_len = reader.u32()? as usize;
+            let children_len = reader.u32()? as usize;
+            let mut content = Reader {
+                data: reader.bytes(content_len)?,
+            };
+            reader.bytes(children_len)?;
+            match id {
+                b"SIZE" if size.is_none() => {
+                    size = Some(Vec3::new(content.u32()?, content.u32()?, content.u32()?));
+                }
+                b"XYZI" if voxels.is_none() => {
+                    let count = content.u32()? as usize;
+                    voxels = Some(content.bytes(count * 4)?);
+                }
+                b"RGBA" => palette = Some(content.bytes(256 * 4)?),
+                b"SIZE" | b"XYZI" => {
+                    if skipped.insert(id) {
+                        log::warn!("Only the first model of .vox files is imported");
+                    }
+                }
+                _ => {
+                    if skipped.insert(id) {
+                        log::warn!(
+                            "Skipping unsupported .vox chunk {}",
+                            String::from_utf8_lossy(id)
+                        );
+                    }
+                }
+            }
+        }
+
+        let vox_size = size.ok_or(VoxError::Invalid("missing SIZE chunk"))?;
+        let voxels = voxels.ok_or(VoxError::Invalid("missing XYZI chunk"))?;
+        let size = Vec3::new(vox_size.x, vox_size.z, vox_size.y).map(|v| v as usize);
+        let mut blocks = vec![BlockId::AIR; size.product()];
+        for voxel in voxels.chunks_exact(4) {
+            let (x, y, z) = (voxel[0] as usize, voxel[2] as usize, voxel[1] as usize);
+            if x >= size.x || y >= size.y || z >= size.z {
+                continue;
+            }
+            // Palette entry `i` is referenced by color index `i + 1`
+            let block = match palette {
+                Some(palette) => {
+                    let i = (voxel[3] as usize).saturating_sub(1) * 4;
+                    mapping.block([palette[i], palette[i + 1], palette[i + 2]])
+                }
+                None => mapping.fallback,
+            };
+            blocks[x + y * size.x + z * size.x * size.y] = block;
+        }
+        Ok(Self { size, blocks })
+    }
+
+    /// Writes the non-air blocks into the world with the structure's minimum
+    /// corner at `origin`. The affected chunks are remeshed on the next tick.
+    /// Returns the edits that were made.
+    pub fn paste(&self, chunks: &mut ChunkManager, origin: Vec3<i32>) -> Vec<Edit> {
+        let mut edits = Vec::new();
+        for (index, block) in self.blocks.iter().enumerate() {
+            if block.is_air() {
+                continue;
+            }
+            let pos = origin + self.position(index).map(|v| v as i32);
+            if let Some(old) = chunks.set_block(pos, *block) {
+                edits.push(Edit {
+                    pos,
+                    old,
+                    new: *block,
+                });
+            }
+        }
+        edits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::ChunkPos;
+
+    /// A .vox file made of the given chunks after the MAIN chunk
+    fn vox_file(version: u32, chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut children = Vec::new();
+        for (id, content) in chunks {
+            children.extend_from_slice(*id);
+            children.extend((content.len() as u32).to_le_bytes());
+            children.extend(0u32.to_le_bytes());
+            children.extend(content);
+        }
+        let mut data = b"VOX ".to_vec();
+        data.extend(version.to_le_bytes());
+        data.extend(b"MAIN");
+        data.extend(0u32.to_le_bytes());
+        data.extend((children.len() as u32).to_le_bytes());
+        data.extend(children);
+        data
+    }
+
+    fn size(x: u32, y: u32, z: u32) -> (&'static [u8; 4], Vec<u8>) {
+        (
+            b"SIZE",
+            [x, y, z].iter().flat_map(|v| v.to_le_bytes()).collect(),
+        )
+    }
+
+    /// Voxels as x, y, z and color index, in MagicaVoxel's axes
+    fn voxels(voxels: &[[u8; 4]]) -> (&'static [u8; 4], Vec<u8>) {
+        let mut content = (voxels.len() as u32).to_le_bytes().to_vec();
+        content.extend(voxels.iter().flatten());
+        (b"XYZI", content)
+    }
+
+    fn palette(colors: &[[u8; 3]]) -> (&'static [u8; 4], Vec<u8>) {
+        let mut content = vec![0; 256 * 4];
+        for (i, color) in colors.iter().enumerate() {
+            content[i * 4..i * 4 + 3].copy_from_slice(color);
+        }
+        (b"RGBA", content)
+    }
+
+    fn parse(data: &[u8]) -> Result<Structure, VoxError> {
+        Structure::parse_vox(data, &BlockMapping::default())
+    }
+
+    #[test]
+    fn checks_the_header_and_version() {
+        let chunks = [size(1, 1, 1), voxels(&[[0, 0, 0, 1]])];
+        assert!(parse(&vox_file(150, &chunks)).is_ok());
+        assert!(parse(&vox_file(200, &chunks)).is_ok());
+        assert!(matches!(
+            parse(&vox_file(300, &chunks)),
+            Err(VoxError::Invalid("unsupported version"))
+        ));
+        let mut riff = vox_file(150, &chunks);
+        riff[..4].copy_from_slice(b"RIFF");
+        assert!(matches!(
+            parse(&riff),
+            Err(VoxError::Invalid("missing VOX header"))
+        ));
+        // Cut in the middle of the voxels
+        let truncated = vox_file(150, &chunks);
+        assert!(parse(&truncated[..truncated.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn skips_unknown_chunks() {
+        let chunks = [
+            (b"nTRN", vec![7; 12]),
+            size(1, 1, 1),
+            (b"LAYR", vec![]),
+            voxels(&[[0, 0, 0, 1]]),
+        ];
+        let structure = parse(&vox_file(150, &chunks)).unwrap();
+        assert_eq!(structure.blocks, [BlockId::STONE]);
+    }
+
+    #[test]
+    fn z_up_becomes_y_up() {
+        let chunks = [size(2, 3, 4), voxels(&[[1, 2, 3, 1]])];
+        let structure = parse(&vox_file(150, &chunks)).unwrap();
+        assert_eq!(structure.size, Vec3::new(2, 4, 3));
+        // x + y * size.x + z * size.x * size.y with y = 3 and z = 2
+        let index = 1 + 3 * 2 + 2 * 2 * 4;
+        assert_eq!(structure.blocks[index], BlockId::STONE);
+        assert_eq!(structure.blocks.iter().filter(|b| !b.is_air()).count(), 1);
+    }
+
+    #[test]
+    fn palette_colors_map_to_the_closest_block() {
+        let chunks = [
+            size(3, 1, 1),
+            voxels(&[[0, 0, 0, 1], [1, 0, 0, 2], [2, 0, 0, 3]]),
+            palette(&[[95, 159, 53], [130, 120, 120], [36, 44, 40]]),
+        ];
+        let structure = parse(&vox_file(150, &chunks)).unwrap();
+        assert_eq!(
+            structure.blocks,
+            [BlockId::GRASS, BlockId::STONE, BlockId::BEDROCK]
+        );
+
+        let mapping = BlockMapping {
+            exact: vec![([130, 120, 120], BlockId::DIRT)],
+            ..Default::default()
+        };
+        let structure = Structure::parse_vox(&vox_file(150, &chunks), &mapping).unwrap();
+        assert_eq!(structure.blocks[1], BlockId::DIRT);
+    }
+
+    #[test]
+    fn paste_splits_the_edits_across_chunks() {
+        let (mut chunks, _dir) =
+            ChunkManager::with_chunks([ChunkPos::ORIGIN, ChunkPos::new(1, 0, 0)]);
+        let structure = Structure {
+            size: Vec3::new(4, 1, 1),
+            blocks: vec![BlockId::STONE, BlockId::AIR, BlockId::STONE, BlockId::STONE],
+        };
+        // Across the border between x = 15 and x = 16
+        let edits = structure.paste(&mut chunks, Vec3::new(14, 10, 3));
+        let positions = edits.iter().map(|edit| edit.pos.x).collect::<Vec<_>>();
+        assert_eq!(positions, [14, 16, 17]);
+        assert!(edits.iter().all(|edit| edit.old == BlockId::DIRT));
+        for x in [14, 16, 17] {
+            assert_eq!(chunks.get_block(Vec3::new(x, 10, 3)), Some(BlockId::STONE));
+        }
+        assert_eq!(chunks.get_block(Vec3::new(15, 10, 3)), Some(BlockId::DIRT));
+        chunks.process_events();
+        assert!(chunks.chunks().iter().all(|chunk| chunk.dirty));
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use vek::Vec3;
+
+use super::chunk::ChunkPos;
+
+/// Most ticks kept at once, including the ones waiting for a chunk.
+/// Ticks scheduled beyond it are dropped, so that breaking a dam in
+/// front of a lake can't grow the queue without bounds.
+pub const MAX_SCHEDULED_TICKS: usize = 65536;
+
+/// Blocks scheduled to be ticked a number of simulation steps later, like
+/// flowing water. A block is scheduled at most once and ticks that are due
+/// at the same step run in the order they were scheduled, so the same
+/// edits always play out the same way.
+#[derive(Debug, Default)]
+pub struct TickQueue {
+    /// The scheduled blocks with the step they are due at, earliest first
+    queue: VecDeque<(u64, Vec3<i32>)>,
+    /// The blocks in `queue`
+    scheduled: HashSet<Vec3<i32>>,
+    /// Blocks whose tick needs a chunk that isn't loaded, by that chunk
+    waiting: HashMap<ChunkPos, Vec<Vec3<i32>>>,
+    waiting_len: usize,
+    /// Steps since the queue was created
+    step: u64,
+    /// Ticks that were dropped as the queue was full
+    dropped: usize,
+}
+
+impl TickQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A queue with the ticks returned by [`TickQueue::saved`], which
+    /// run in the same order after `delay` steps
+    pub fn restore(saved: &[[i32; 3]], delay: u64) -> Self {
+        let mut queue = Self::new();
+        for pos in saved {
+            queue.schedule(Vec3::from(*pos), delay);
+        }
+        queue
+    }
+
+    /// Moves on to the next simulation step
+    pub fn step(&mut self) {
+        self.step += 1;
+    }
+
+    /// Schedules a tick of the block `delay` steps from now, unless it
+    /// already has one or the queue is full. Returns whether it was scheduled.
+    pub fn schedule(&mut self, pos: Vec3<i32>, delay: u64) -> bool {
+        if self.scheduled.contains(&pos) {
+            return false;
+        }
+        if self.len() >= MAX_SCHEDULED_TICKS {
+            self.dropped += 1;
+            return false;
+        }
+        self.scheduled.insert(pos);
+        let due = self.step + delay;
+        let at = self.queue.partition_point(|(other, _)| *other <= due);
+        self.queue.insert(at, (due, pos));
+        true
+    }
+
+    /// Takes the next block whose tick is due, if any
+    pub fn pop_due(&mut self) -> Option<Vec3<i32>> {
+        match self.queue.front() {
+            Some((due, _)) if *due <= self.step => (),
+            _ => return None,
+        }
+        let (_, pos) = self.queue.pop_front()?;
+        self.scheduled.remove(&pos);
+        Some(pos)
+    }
+
+    /// Keeps the tick of the block until the chunk it needs is loaded
+    pub fn wait_for(&mut self, chunk: ChunkPos, pos: Vec3<i32>) {
+        if self.len() >= MAX_SCHEDULED_TICKS {
+            self.dropped += 1;
+            return;
+        }
+        let waiting = self.waiting.entry(chunk).or_default();
+        if !waiting.contains(&pos) {
+            waiting.push(pos);
+            self.waiting_len += 1;
+        }
+    }
+
+    /// Schedules the ticks that were waiting for the chunk, now loaded
+    pub fn chunk_loaded(&mut self, chunk: ChunkPos, delay: u64) {
+        if let Some(blocks) = self.waiting.remove(&chunk) {
+            self.waiting_len -= blocks.len();
+            for pos in blocks {
+                self.schedule(pos, delay);
+            }
+        }
+    }
+
+    /// Number of ticks kept, scheduled or waiting for a chunk
+    pub fn len(&self) -> usize {
+        self.queue.len() + self.waiting_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of ticks waiting for a chunk to be loaded
+    pub fn waiting(&self) -> usize {
+        self.waiting_len
+    }
+
+    /// Number of ticks dropped as the queue was full
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// The blocks of every tick in the order they run, followed by the
+    /// ones waiting for a chunk, to be saved with the world
+    pub fn saved(&self) -> Vec<[i32; 3]> {
+        let mut waiting = self.waiting.iter().collect::<Vec<_>>();
+        waiting.sort_by_key(|(chunk, _)| (chunk.x, chunk.y, chunk.z));
+        self.queue
+            .iter()
+            .map(|(_, pos)| *pos)
+            .chain(waiting.into_iter().flat_map(|(_, blocks)| blocks.clone()))
+            .map(Vec3::into_array)
+            .collect()
+    }
+}
@@ -0,0 +1,136 @@
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::{block::BlockId, direction::Direction};
+
+use super::chunk::{compute_1d, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+
+/// Height of the cubes chunks are split into for occlusion culling
+pub const SECTION_HEIGHT: usize = 16;
+pub const CHUNK_SECTIONS: usize = CHUNK_HEIGHT / SECTION_HEIGHT;
+
+const SECTION_VOLUME: usize = CHUNK_WIDTH * SECTION_HEIGHT * CHUNK_DEPTH;
+
+/// Which faces of a section can be seen from which, through blocks
+/// that aren't opaque. A bit for every pair of faces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionVisibility(u64);
+
+impl SectionVisibility {
+    /// A section of opaque blocks, no face sees another
+    pub const NONE: Self = Self(0);
+    /// A section without opaque blocks, every face sees every other
+    pub const ALL: Self = Self((1 << 36) - 1);
+
+    /// Finds the faces connected by the blocks of the section at `section`
+    /// of the unpacked chunk blocks, flood filling every group of blocks
+    /// that aren't opaque.
+    pub fn from_blocks(blocks: &[BlockId], section: usize) -> Self {
+        let start = section * SECTION_HEIGHT;
+        let mut open = vec![false; SECTION_VOLUME];
+        let mut open_count = 0;
+        for (index, open) in open.iter_mut().enumerate() {
+            let (x, y, z) = section_pos(index);
+            *open = !blocks[compute_1d(x, start + y, z)].is_opaque();
+            open_count += *open as usize;
+        }
+        match open_count {
+            0 => return Self::NONE,
+            SECTION_VOLUME => return Self::ALL,
+            _ => {}
+        }
+
+        let mut visibility = Self::NONE;
+        let mut stack = Vec::new();
+        for first in 0..SECTION_VOLUME {
+            if !open[first] {
+                continue;
+            }
+            // Fill the group, closing its blocks so they aren't visited again
+            open[first] = false;
+            stack.push(first);
+            let mut faces = 0u8;
+            while let Some(index) = stack.pop() {
+                let (x, y, z) = section_pos(index);
+                for dir in Direction::ALL {
+                    let offset = dir.normalized();
+                    let (nx, ny, nz) = (
+                        x as i32 + offset.x,
+                        y as i32 + offset.y,
+                        z as i32 + offset.z,
+                    );
+                    let outside = nx < 0
+                        || ny < 0
+                        || nz < 0
+                        || nx >= CHUNK_WIDTH as i32
+                        || ny >= SECTION_HEIGHT as i32
+                        || nz >= CHUNK_DEPTH as i32;
+                    if outside {
+                        faces |= 1 << usize::from(dir);
+                        continue;
+                    }
+                    let neighbor = section_index(nx as usize, ny as usize, nz as usize);
+                    if open[neighbor] {
+                        open[neighbor] = false;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+            visibility.connect(faces);
+        }
+        visibility
+    }
+
+    /// Connects every pair of the faces, a bit per direction
+    fn connect(&mut self, faces: u8) {
+        for from in Direction::ALL {
+            for to in Direction::ALL {
+                if faces & (1 << usize::from(from)) != 0 && faces & (1 << usize::from(to)) != 0 {
+                    self.0 |= 1 << Self::bit(from, to);
+                }
+            }
+        }
+    }
+
+    fn bit(from: Direction, to: Direction) -> usize {
+        usize::from(from) * Direction::ALL.len() + usize::from(to)
+    }
+
+    /// Whether something entering the section through `from` can leave it through `to`
+    pub fn connects(self, from: Direction, to: Direction) -> bool {
+        self.0 & (1 << Self::bit(from, to)) != 0
+    }
+}
+
+/// Position in a section of the index in it, x first, then y and z
+fn section_pos(index: usize) -> (usize, usize, usize) {
+    (
+        index % CHUNK_WIDTH,
+        (index / CHUNK_WIDTH) % SECTION_HEIGHT,
+        index / (CHUNK_WIDTH * SECTION_HEIGHT),
+    )
+}
+
+fn section_index(x: usize, y: usize, z: usize) -> usize {
+    x + y * CHUNK_WIDTH + z * CHUNK_WIDTH * SECTION_HEIGHT
+}
+
+/// The visibility of every section of a chunk, from the bottom up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkVisibility([SectionVisibility; CHUNK_SECTIONS]);
+
+impl ChunkVisibility {
+    /// Computes the visibility of every section of the unpacked chunk blocks
+    pub fn from_blocks(blocks: &[BlockId]) -> Self {
+        let sections = (0..CHUNK_SECTIONS)
+            .into_par_iter()
+            .map(|section| SectionVisibility::from_blocks(blocks, section))
+            .collect::<Vec<_>>();
+        let mut visibility = [SectionVisibility::NONE; CHUNK_SECTIONS];
+        visibility.copy_from_slice(&sections);
+        Self(visibility)
+    }
+
+    pub fn section(&self, section: usize) -> SectionVisibility {
+        self.0[section]
+    }
+}